@@ -0,0 +1,44 @@
+use bevy::prelude::{Entity, Vec3};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// The game is a binary, so there's no library to link against.
+#[path = "../src/game/spatial_hash.rs"]
+#[allow(dead_code)]
+mod spatial_hash;
+
+use spatial_hash::{overlapping_pairs_naive, Collider, SpatialHash};
+
+/// Enemies spread over a field that grows with their number, so each has
+/// roughly as many neighbours as in a crowded window.
+fn colliders(count: u32) -> Vec<Collider> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let extent = (count as f32).sqrt() * 64.;
+    (0..count)
+        .map(|index| Collider {
+            entity: Entity::from_raw(index),
+            position: Vec3::new(rng.gen_range(0.0..extent), rng.gen_range(0.0..extent), 0.),
+            size: 64.,
+        })
+        .collect()
+}
+
+fn collision(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision");
+    for count in [1_000, 2_500, 5_000] {
+        let colliders = colliders(count);
+        let mut spatial_hash = SpatialHash::default();
+        group.bench_with_input(
+            BenchmarkId::new("spatial_hash", count),
+            &colliders,
+            |b, c| b.iter(|| spatial_hash.overlapping_pairs(black_box(c))),
+        );
+        group.bench_with_input(BenchmarkId::new("naive", count), &colliders, |b, c| {
+            b.iter(|| overlapping_pairs_naive(black_box(c)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, collision);
+criterion_main!(benches);
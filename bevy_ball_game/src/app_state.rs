@@ -1,10 +1,19 @@
 use bevy::prelude::*;
+
+use crate::game::high_scores::NameEntry;
+
 pub struct AppStatePlugin;
 
 impl Plugin for AppStatePlugin {
     fn build(&self, app: &mut App) {
-        app.add_state::<AppState>()
-            .add_systems(Update, (open_menu, start_game));
+        app.add_state::<AppState>().add_systems(
+            Update,
+            (
+                open_menu,
+                // G is also a letter of someone's initials.
+                start_game.run_if(not(resource_exists::<NameEntry>())),
+            ),
+        );
     }
 }
 
@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use super::replay::Playback;
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Arena>()
+            .add_systems(
+                PreStartup,
+                fit_arena_to_window.run_if(not(resource_exists::<Playback>())),
+            )
+            .add_systems(
+                First,
+                fit_arena_to_window.run_if(not(resource_exists::<Playback>())),
+            );
+    }
+}
+
+/// The area the game is played in, with its bottom left corner at the
+/// origin. It follows the size of the window when there is one, and stays
+/// as it was set otherwise, such as in tests. A replay plays back in the
+/// arena it was recorded in, whatever the size of the window.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Arena {
+            width: 1280.,
+            height: 720.,
+        }
+    }
+}
+
+impl Arena {
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+
+    pub fn center(&self) -> Vec3 {
+        Vec3::new(self.width / 2., self.height / 2., 0.)
+    }
+}
+
+pub fn fit_arena_to_window(
+    mut arena: ResMut<Arena>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let fitted = Arena {
+        width: window.width(),
+        height: window.height(),
+    };
+    // Only touch it when it changes, so others can tell when it does.
+    if *arena != fitted {
+        *arena = fitted;
+    }
+}
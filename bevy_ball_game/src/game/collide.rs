@@ -1,13 +1,25 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use std::cmp::Ordering;
 
-use super::tick_schedule::MovementSet;
+use bevy::prelude::*;
+
+use super::arena::Arena;
+
+use super::spatial_hash::{Collider, SpatialHash};
+use super::tick_schedule::{MovementSet, TickSet};
 
 pub struct CollidePlugin;
 impl Plugin for CollidePlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<CollisionEvent>().add_systems(
-            Update,
-            (constrain_to_window, collision).in_set(MovementSet::CollisionChecking),
+        // Events usually last two frames, but a frame can go by without a
+        // tick, so these last two ticks instead.
+        app.init_resource::<Events<CollisionEvent>>().add_systems(
+            FixedUpdate,
+            (
+                Events::<CollisionEvent>::update_system.in_set(TickSet::Input),
+                (constrain_to_arena, collision)
+                    .chain()
+                    .in_set(MovementSet::CollisionChecking),
+            ),
         );
     }
 }
@@ -17,6 +29,9 @@ pub struct Collides {
     pub object_size: f32,
 }
 
+/// Either an entity running into the edge of the arena, or two entities
+/// running into each other. The latter is sent once per pair, with
+/// `direction` pointing from `entity_b` to `entity_a`.
 #[derive(Event)]
 pub struct CollisionEvent {
     pub entity_a: Entity,
@@ -24,18 +39,31 @@ pub struct CollisionEvent {
     pub direction: Vec3,
 }
 
-fn constrain_to_window(
+impl CollisionEvent {
+    /// A collision between two entities seen from each of them in turn,
+    /// as `(entity, other, direction away from other)`.
+    pub fn both_ways(&self) -> Vec<(Entity, Entity, Vec3)> {
+        match self.entity_b {
+            Some(entity_b) => vec![
+                (self.entity_a, entity_b, self.direction),
+                (entity_b, self.entity_a, -self.direction),
+            ],
+            None => Vec::new(),
+        }
+    }
+}
+
+fn constrain_to_arena(
     mut ev_constrain: EventWriter<CollisionEvent>,
     mut constraint_query: Query<(Entity, &mut Transform, &Collides)>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
 ) {
-    let window = window_query.get_single().unwrap();
     for (entity, mut transform, constrain) in constraint_query.iter_mut() {
         let half_object_size = constrain.object_size / 2.0;
         let x_min = 0.0 + half_object_size;
-        let x_max = window.width() - half_object_size;
+        let x_max = arena.width - half_object_size;
         let y_min = 0.0 + half_object_size;
-        let y_max = window.height() - half_object_size;
+        let y_max = arena.height - half_object_size;
         let mut translation = transform.translation;
 
         if translation.x < x_min {
@@ -92,21 +120,37 @@ fn constrain_to_window(
 pub fn collision(
     mut ev: EventWriter<CollisionEvent>,
     collision_query: Query<(Entity, &Transform, &Collides)>,
+    mut spatial_hash: Local<SpatialHash>,
 ) {
-    for (entity_a, transform_a, collider_a) in collision_query.iter() {
-        for (entity_b, transform_b, collider_b) in collision_query.iter() {
-            if entity_a == entity_b {
-                continue;
-            }
-            let distance = transform_a.translation.distance(transform_b.translation);
-            let direction = transform_a.translation - transform_b.translation;
-            if distance < (collider_a.object_size + collider_b.object_size) / 2.0 {
-                ev.send(CollisionEvent {
-                    entity_a,
-                    entity_b: Some(entity_b),
-                    direction,
-                });
-            }
-        }
+    let colliders: Vec<Collider> = collision_query
+        .iter()
+        .map(|(entity, transform, collides)| Collider {
+            entity,
+            position: transform.translation,
+            size: collides.object_size,
+        })
+        .collect();
+    let mut pairs = spatial_hash.overlapping_pairs(&colliders);
+    // Entities are numbered differently from one run to the next, but
+    // positions come out the same when a game is replayed, so order by them
+    // to have every collision handled in the same order too.
+    pairs.sort_by(|&(a, b), &(c, d)| {
+        let key = |i: usize, j: usize| {
+            let (p, q) = (colliders[i].position, colliders[j].position);
+            let (p, q) = if p.to_array() <= q.to_array() {
+                (p, q)
+            } else {
+                (q, p)
+            };
+            [p.x, p.y, q.x, q.y]
+        };
+        key(a, b).partial_cmp(&key(c, d)).unwrap_or(Ordering::Equal)
+    });
+    for (a, b) in pairs {
+        ev.send(CollisionEvent {
+            entity_a: colliders[a].entity,
+            entity_b: Some(colliders[b].entity),
+            direction: colliders[a].position - colliders[b].position,
+        });
     }
 }
@@ -1,13 +1,19 @@
 use bevy::audio::{Volume, VolumeLevel};
+use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
 // use bevy::ui::debug;
-use bevy::window::PrimaryWindow;
-use rand::random;
+use rand::Rng;
 
 use crate::app_state::AppState;
 
 use super::collide::{Collides, CollisionEvent};
-use super::tick_schedule::{GameStart, MovementSet, Simulation};
+use super::enemy_kinds::{EnemyKind, EnemyKinds};
+use super::power_ups::ActiveEffects;
+use super::rng::GameRng;
+use super::rng::RngStream;
+use super::spawn_placement::{SpawnPlacement, Telegraph};
+use super::tick_schedule::{GameStart, MovementSet, TickSet};
+use super::waves::{advance_waves, WaveProgress, Waves};
 use super::DespawnOnEnd;
 
 pub struct EnemyPlugin;
@@ -15,9 +21,13 @@ pub struct EnemyPlugin;
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<EnemySpawnTimer>().add_systems(
-            Update,
+            FixedUpdate,
             (
-                (enemy_spawn_timer_tick, spawn_enemy_over_time).in_set(Simulation),
+                // Ticks whichever timer the wave has just set up.
+                enemy_spawn_timer_tick
+                    .after(advance_waves)
+                    .in_set(TickSet::Timers),
+                spawn_enemy_over_time.in_set(TickSet::Spawning),
                 enemy_movement.in_set(MovementSet::Movement),
                 enemy_bounce.in_set(MovementSet::Direction),
             ),
@@ -26,16 +36,24 @@ impl Plugin for EnemyPlugin {
 }
 
 pub const ENEMY_SPEED: f32 = 500.0;
-pub const ENEMY_SIZE: f32 = 64.0;
 
 #[derive(Component)]
 pub struct Enemy {
-    direction: Vec3,
+    pub direction: Vec3,
+    pub speed: f32,
 }
 
+/// The sounds an enemy makes when it bounces, one picked at random.
+#[derive(Component)]
+pub struct BounceSounds {
+    pub sources: Vec<Handle<AudioSource>>,
+    pub pitch: f32,
+}
+
+/// Set to each wave's spawn interval as the wave starts.
 #[derive(Resource)]
 pub struct EnemySpawnTimer {
-    timer: Timer,
+    pub timer: Timer,
 }
 
 impl Default for EnemySpawnTimer {
@@ -46,51 +64,64 @@ impl Default for EnemySpawnTimer {
     }
 }
 
-pub fn enemy_movement(mut enemy_query: Query<(&mut Transform, &Enemy)>, time: Res<Time>) {
+pub fn enemy_movement(
+    mut enemy_query: Query<(&mut Transform, &Enemy), Without<Telegraph>>,
+    fixed_time: Res<FixedTime>,
+    active_effects: Res<ActiveEffects>,
+) {
+    let delta_seconds = fixed_time.period.as_secs_f32() * active_effects.enemy_time_scale();
     for (mut transform, enemy) in enemy_query.iter_mut() {
         let direction = enemy.direction;
-        transform.translation += direction * ENEMY_SPEED * time.delta_seconds();
+        transform.translation += direction * enemy.speed * delta_seconds;
     }
 }
 
-fn enemy_bounce(
+pub fn enemy_bounce(
     mut commands: Commands,
     mut ev_constrain: EventReader<CollisionEvent>,
-    mut mut_enemy_query: Query<&mut Enemy>,
+    mut mut_enemy_query: Query<(&mut Enemy, &BounceSounds)>,
     enemy_query: Query<Entity, With<Enemy>>,
-    asset_server: Res<AssetServer>,
+    mut rng: ResMut<GameRng>,
 ) {
     for ev in ev_constrain.iter() {
-        if let Ok(mut enemy) = mut_enemy_query.get_mut(ev.entity_a) {
-            if let Some(entity_b) = ev.entity_b {
-                if enemy_query.get(entity_b).is_ok() {
-                    enemy.direction = ev.direction.normalize();
-                    play_bounce(&mut commands, &asset_server);
-                }
-            } else {
+        if ev.entity_b.is_none() {
+            if let Ok((mut enemy, bounce_sounds)) = mut_enemy_query.get_mut(ev.entity_a) {
                 match ev.direction {
                     v if v.x == 0. => enemy.direction.y = -enemy.direction.y,
                     v if v.y == 0. => enemy.direction.x = -enemy.direction.x,
                     _ => {}
                 }
-                play_bounce(&mut commands, &asset_server);
+                play_bounce(
+                    &mut commands,
+                    rng.stream(RngStream::BounceSounds),
+                    bounce_sounds,
+                );
+            }
+        }
+        for (entity, other, direction) in ev.both_ways() {
+            if let Ok((mut enemy, bounce_sounds)) = mut_enemy_query.get_mut(entity) {
+                if enemy_query.get(other).is_ok() {
+                    enemy.direction = direction.normalize();
+                    play_bounce(
+                        &mut commands,
+                        rng.stream(RngStream::BounceSounds),
+                        bounce_sounds,
+                    );
+                }
             }
         }
     }
 }
 
-fn play_bounce(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    let sound_effect_1 = asset_server.load("audio/pluck_001.ogg");
-    let sound_effect_2 = asset_server.load("audio/pluck_002.ogg");
-    // Randomly play one of the two sound effects.
-    let sound_effect = if random::<f32>() > 0.5 {
-        sound_effect_1
-    } else {
-        sound_effect_2
-    };
+fn play_bounce(commands: &mut Commands, rng: &mut impl Rng, bounce_sounds: &BounceSounds) {
+    if bounce_sounds.sources.is_empty() {
+        return;
+    }
+    let sound_effect = bounce_sounds.sources[rng.gen_range(0..bounce_sounds.sources.len())].clone();
 
     let playback_settings = PlaybackSettings {
         volume: Volume::Relative(VolumeLevel::new(0.1)),
+        speed: bounce_sounds.pitch,
         ..default()
     };
 
@@ -100,37 +131,90 @@ fn play_bounce(commands: &mut Commands, asset_server: &Res<AssetServer>) {
     });
 }
 
-pub fn spawn_enemy(commands: &mut Commands, window: &Window, asset_server: &Res<AssetServer>) {
-    let random_x = random::<f32>() * window.width();
-    let random_y = random::<f32>() * window.height();
-    commands.spawn((
+/// Spawns an enemy of `kind`, going `speed` before the kind's own speed
+/// multiplier is applied.
+pub fn spawn_enemy<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    asset_server: &Res<AssetServer>,
+    kind: &EnemyKind,
+    position: Vec3,
+    direction: Vec3,
+    speed: f32,
+) -> EntityCommands<'w, 's, 'a> {
+    let speed = speed * kind.speed;
+    let mut enemy = commands.spawn((
         SpriteBundle {
-            transform: Transform::from_xyz(random_x, random_y, 0.0),
-            texture: asset_server.load("sprites/ball_red_large.png"),
+            transform: Transform::from_translation(position),
+            texture: asset_server.load(&kind.sprite),
+            sprite: Sprite {
+                color: kind.tint(),
+                custom_size: Some(Vec2::splat(kind.size)),
+                ..default()
+            },
             ..default()
         },
-        Enemy {
-            direction: Vec3::new(random::<f32>() - 0.5, random::<f32>() - 0.5, 0.0).normalize(),
-        },
+        Name::new(kind.name.clone()),
+        Enemy { direction, speed },
         Collides {
-            object_size: ENEMY_SIZE,
+            object_size: kind.size,
+        },
+        BounceSounds {
+            sources: kind
+                .bounce_sounds
+                .iter()
+                .map(|path| asset_server.load(path))
+                .collect(),
+            pitch: kind.bounce_pitch,
         },
         DespawnOnEnd,
     ));
+    kind.behaviour.insert(&mut enemy, kind, speed);
+    enemy
 }
 
-pub fn enemy_spawn_timer_tick(mut enemy_spawn_timer: ResMut<EnemySpawnTimer>, time: Res<Time>) {
-    enemy_spawn_timer.timer.tick(time.delta());
+pub fn enemy_spawn_timer_tick(
+    mut enemy_spawn_timer: ResMut<EnemySpawnTimer>,
+    fixed_time: Res<FixedTime>,
+) {
+    enemy_spawn_timer.timer.tick(fixed_time.period);
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_enemy_over_time(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    spawn_placement: SpawnPlacement,
     asset_server: Res<AssetServer>,
     enemy_spawn_timer: Res<EnemySpawnTimer>,
+    waves: Res<Waves>,
+    wave_progress: Res<WaveProgress>,
+    enemy_kinds: Res<EnemyKinds>,
+    enemy_query: Query<(), With<Enemy>>,
+    mut rng: ResMut<GameRng>,
 ) {
-    let window = window_query.get_single().unwrap();
-    if enemy_spawn_timer.timer.finished() {
-        spawn_enemy(&mut commands, window, &asset_server)
+    let Some(wave) = wave_progress.attacking(&waves) else {
+        return;
+    };
+    let rng = rng.stream(RngStream::Enemies);
+    if enemy_spawn_timer.timer.finished() && enemy_query.iter().len() < wave.max_enemies {
+        let Some(kind) = enemy_kinds.choose(wave_progress.index + 1, rng) else {
+            return;
+        };
+        // Skip this spawn if there's no room away from the player.
+        let Some(position) = spawn_placement.find(rng, kind.size) else {
+            return;
+        };
+        let direction = Vec3::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5, 0.0).normalize();
+        let speed = ENEMY_SPEED * wave.speed_multiplier;
+        // It only collides once it's done showing where it'll appear.
+        spawn_enemy(
+            &mut commands,
+            &asset_server,
+            kind,
+            position,
+            direction,
+            speed,
+        )
+        .remove::<Collides>()
+        .insert(Telegraph::new(kind.size));
     }
 }
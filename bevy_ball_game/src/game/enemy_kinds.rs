@@ -0,0 +1,434 @@
+use std::f32::consts::FRAC_PI_3;
+
+use bevy::asset::LoadState;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use rand::Rng;
+use serde::Deserialize;
+
+use super::collide::{Collides, CollisionEvent};
+use super::enemy::{enemy_bounce, spawn_enemy, Enemy};
+use super::player::Player;
+use super::power_ups::ActiveEffects;
+use super::rng::{GameRng, RngStream};
+use super::ron_asset::{RonAsset, RonAssetLoader};
+use super::spawn_placement::{Exclusion, SpawnPlacement, Telegraph, SPAWN_MARGIN};
+use super::tick_schedule::MovementSet;
+
+pub const ENEMY_KINDS_PATH: &str = "enemies/default.enemies.ron";
+
+pub struct EnemyKindsPlugin;
+
+impl Plugin for EnemyKindsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<EnemyKindList>()
+            .init_asset_loader::<RonAssetLoader<EnemyKindList>>()
+            .init_resource::<EnemyKinds>()
+            .add_systems(Startup, load_enemy_kinds)
+            .add_systems(Update, update_enemy_kinds)
+            .add_systems(
+                FixedUpdate,
+                // Going on from the way they bounced, if they did.
+                (steer_chasers, dash, split_on_bounce)
+                    .after(enemy_bounce)
+                    .in_set(MovementSet::Direction),
+            );
+    }
+}
+
+/// A kind of enemy, as described in the enemy kinds file. What sets it
+/// apart in how it moves comes from the components its `behaviour` adds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyKind {
+    pub name: String,
+    pub behaviour: Behaviour,
+    pub sprite: String,
+    pub tint: (f32, f32, f32),
+    pub size: f32,
+    /// Multiplies the speed of the current wave.
+    pub speed: f32,
+    /// One of these plays, picked at random, when it bounces.
+    pub bounce_sounds: Vec<String>,
+    /// Playback speed of the bounce sound, which also shifts its pitch.
+    pub bounce_pitch: f32,
+    /// How likely it is to be picked, relative to the other kinds.
+    pub weight: u32,
+    /// The wave it first turns up in, counting from 1.
+    pub first_wave: usize,
+}
+
+impl EnemyKind {
+    /// What spawns if the enemy kinds file can't be loaded, so the waves
+    /// still have something to send.
+    pub fn built_in() -> EnemyKind {
+        EnemyKind {
+            name: "Ball".to_string(),
+            behaviour: Behaviour::Bouncer,
+            sprite: "sprites/ball_red_large.png".to_string(),
+            tint: (1., 1., 1.),
+            size: 64.,
+            speed: 1.,
+            bounce_sounds: vec![
+                "audio/pluck_001.ogg".to_string(),
+                "audio/pluck_002.ogg".to_string(),
+            ],
+            bounce_pitch: 1.,
+            weight: 1,
+            first_wave: 1,
+        }
+    }
+
+    pub fn tint(&self) -> Color {
+        Color::rgb(self.tint.0, self.tint.1, self.tint.2)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum Behaviour {
+    /// Goes in a straight line and bounces off walls and other enemies.
+    Bouncer,
+    /// Turns toward the player by up to `turn_rate` radians a second.
+    Chaser { turn_rate: f32 },
+    /// Breaks into `pieces` smaller bouncers the first time it bounces.
+    Splitter { pieces: u32, piece_size: f32 },
+    /// Cruises for `cruise` seconds, stops and flashes for `telegraph`
+    /// seconds, then charges at the player for `charge` seconds at
+    /// `charge_speed` times its cruising speed.
+    Dasher {
+        cruise: f32,
+        telegraph: f32,
+        charge: f32,
+        charge_speed: f32,
+    },
+}
+
+impl Behaviour {
+    /// Adds the components that make an enemy of `kind` behave this way.
+    pub fn insert(&self, enemy: &mut EntityCommands, kind: &EnemyKind, speed: f32) {
+        match *self {
+            Behaviour::Bouncer => {}
+            Behaviour::Chaser { turn_rate } => {
+                enemy.insert(Chaser { turn_rate });
+            }
+            Behaviour::Splitter { pieces, piece_size } => {
+                let piece = EnemyKind {
+                    behaviour: Behaviour::Bouncer,
+                    size: piece_size,
+                    // The pieces keep the speed the splitter was going at.
+                    speed: 1.,
+                    ..kind.clone()
+                };
+                enemy.insert(Splitter { pieces, piece });
+            }
+            Behaviour::Dasher {
+                cruise,
+                telegraph,
+                charge,
+                charge_speed,
+            } => {
+                enemy.insert(Dasher {
+                    cruise,
+                    telegraph,
+                    charge,
+                    cruise_speed: speed,
+                    charge_speed: speed * charge_speed,
+                    tint: kind.tint(),
+                    state: DashState::Cruise,
+                    timer: Timer::from_seconds(cruise, TimerMode::Once),
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "3d0e5b7a-91c4-4f2e-8a6d-c2b1f04e9d53"]
+pub struct EnemyKindList {
+    pub kinds: Vec<EnemyKind>,
+}
+
+impl RonAsset for EnemyKindList {
+    const EXTENSIONS: &'static [&'static str] = &["enemies.ron"];
+}
+
+#[derive(Resource)]
+pub struct EnemyKindListHandle(Handle<EnemyKindList>);
+
+#[derive(Resource, Debug, Default)]
+pub struct EnemyKinds {
+    pub kinds: Vec<EnemyKind>,
+}
+
+impl EnemyKinds {
+    /// Picks one of the kinds that turn up by `wave`, by weight.
+    pub fn choose(&self, wave: usize, rng: &mut impl Rng) -> Option<&EnemyKind> {
+        let available = || self.kinds.iter().filter(|k| k.first_wave <= wave);
+        let total: u32 = available().map(|k| k.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        available().find(|k| {
+            if pick < k.weight {
+                return true;
+            }
+            pick -= k.weight;
+            false
+        })
+    }
+}
+
+pub fn load_enemy_kinds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(EnemyKindListHandle(asset_server.load(ENEMY_KINDS_PATH)));
+}
+
+pub fn update_enemy_kinds(
+    mut ev_asset: EventReader<AssetEvent<EnemyKindList>>,
+    asset_server: Res<AssetServer>,
+    kind_lists: Res<Assets<EnemyKindList>>,
+    kind_list_handle: Res<EnemyKindListHandle>,
+    mut enemy_kinds: ResMut<EnemyKinds>,
+) {
+    // Otherwise no enemy would ever be chosen to spawn.
+    if enemy_kinds.kinds.is_empty()
+        && asset_server.get_load_state(&kind_list_handle.0) == LoadState::Failed
+    {
+        warn!(
+            "Could not load {}, spawning only the built-in enemy",
+            ENEMY_KINDS_PATH
+        );
+        enemy_kinds.kinds = vec![EnemyKind::built_in()];
+    }
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = ev {
+            if handle != &kind_list_handle.0 {
+                continue;
+            }
+            if let Some(kind_list) = kind_lists.get(handle) {
+                enemy_kinds.kinds = kind_list.kinds.clone();
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Chaser {
+    pub turn_rate: f32,
+}
+
+#[derive(Component)]
+pub struct Splitter {
+    pub pieces: u32,
+    pub piece: EnemyKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DashState {
+    Cruise,
+    Telegraph,
+    Charge,
+}
+
+#[derive(Component)]
+pub struct Dasher {
+    pub cruise: f32,
+    pub telegraph: f32,
+    pub charge: f32,
+    pub cruise_speed: f32,
+    pub charge_speed: f32,
+    pub tint: Color,
+    pub state: DashState,
+    pub timer: Timer,
+}
+
+pub fn steer_chasers(
+    mut chaser_query: Query<(&mut Enemy, &Transform, &Chaser)>,
+    player_query: Query<&Transform, With<Player>>,
+    fixed_time: Res<FixedTime>,
+    active_effects: Res<ActiveEffects>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    for (mut enemy, transform, chaser) in chaser_query.iter_mut() {
+        let wanted = (player.translation - transform.translation).normalize_or_zero();
+        let angle = enemy.direction.angle_between(wanted);
+        if wanted == Vec3::ZERO || angle.is_nan() {
+            continue;
+        }
+        let turn = angle.min(
+            chaser.turn_rate * fixed_time.period.as_secs_f32() * active_effects.enemy_time_scale(),
+        );
+        let axis = enemy.direction.cross(wanted).z.signum();
+        enemy.direction = Quat::from_rotation_z(axis * turn) * enemy.direction;
+    }
+}
+
+pub fn dash(
+    mut dasher_query: Query<(&mut Enemy, &mut Dasher, &mut Sprite, &Transform)>,
+    player_query: Query<&Transform, With<Player>>,
+    fixed_time: Res<FixedTime>,
+    active_effects: Res<ActiveEffects>,
+) {
+    let delta = fixed_time.period.mul_f32(active_effects.enemy_time_scale());
+    for (mut enemy, mut dasher, mut sprite, transform) in dasher_query.iter_mut() {
+        let finished = dasher.timer.tick(delta).finished();
+        if dasher.state == DashState::Telegraph {
+            // Flash brighter a few times a second while winding up.
+            let flash = (dasher.timer.elapsed_secs() * 8.).fract() < 0.5;
+            sprite.color = if flash {
+                dasher.tint * 2.5
+            } else {
+                dasher.tint
+            };
+        }
+        if !finished {
+            continue;
+        }
+        let (state, seconds, speed) = match dasher.state {
+            DashState::Cruise => (DashState::Telegraph, dasher.telegraph, 0.),
+            DashState::Telegraph => {
+                if let Ok(player) = player_query.get_single() {
+                    let towards = (player.translation - transform.translation).normalize_or_zero();
+                    if towards != Vec3::ZERO {
+                        enemy.direction = towards;
+                    }
+                }
+                sprite.color = dasher.tint;
+                (DashState::Charge, dasher.charge, dasher.charge_speed)
+            }
+            DashState::Charge => (DashState::Cruise, dasher.cruise, dasher.cruise_speed),
+        };
+        dasher.state = state;
+        dasher.timer = Timer::from_seconds(seconds, TimerMode::Once);
+        enemy.speed = speed;
+    }
+}
+
+pub fn split_on_bounce(
+    mut commands: Commands,
+    mut ev_collide: EventReader<CollisionEvent>,
+    splitter_query: Query<(&Enemy, &Splitter, &Transform)>,
+    enemy_query: Query<(), With<Enemy>>,
+    asset_server: Res<AssetServer>,
+    spawn_placement: SpawnPlacement,
+    mut rng: ResMut<GameRng>,
+) {
+    let mut split = Vec::new();
+    // Pieces spawned this frame, which aren't in the placement's queries
+    // yet.
+    let mut placed = Vec::new();
+    for ev in ev_collide.iter() {
+        let bounced = match ev.entity_b {
+            None => vec![ev.entity_a],
+            Some(_) => ev
+                .both_ways()
+                .into_iter()
+                .filter(|(_, other, _)| enemy_query.get(*other).is_ok())
+                .map(|(entity, _, _)| entity)
+                .collect(),
+        };
+        for entity in bounced {
+            // An enemy can bounce more than once in a frame.
+            if split.contains(&entity) {
+                continue;
+            }
+            let Ok((enemy, splitter, transform)) = splitter_query.get(entity) else {
+                continue;
+            };
+            split.push(entity);
+            commands.entity(entity).despawn();
+            let piece = &splitter.piece;
+            let mut exclusions = spawn_placement.exclusions_except(&split);
+            exclusions.extend_from_slice(&placed);
+            let pieces = splitter.pieces.max(1);
+            for i in 0..pieces {
+                // Fan the pieces out around the way the splitter was going,
+                // far enough out that neighbouring ones don't touch.
+                let angle = (i as f32 - (pieces - 1) as f32 / 2.) * FRAC_PI_3;
+                let direction = Quat::from_rotation_z(angle) * enemy.direction;
+                let wanted = transform.translation + direction * (piece.size + 2. * SPAWN_MARGIN);
+                let Some(position) = spawn_placement.find_near(
+                    rng.stream(RngStream::Splits),
+                    wanted,
+                    piece.size,
+                    &exclusions,
+                ) else {
+                    continue;
+                };
+                let exclusion = Exclusion {
+                    position,
+                    radius: piece.size / 2. + SPAWN_MARGIN,
+                };
+                exclusions.push(exclusion);
+                placed.push(exclusion);
+                // Like any other enemy, it only collides once it's done
+                // showing where it'll appear.
+                spawn_enemy(
+                    &mut commands,
+                    &asset_server,
+                    piece,
+                    position,
+                    direction,
+                    enemy.speed,
+                )
+                .remove::<Collides>()
+                .insert(Telegraph::new(piece.size));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn default_kinds() -> EnemyKinds {
+        let kind_list: EnemyKindList =
+            ron::de::from_str(include_str!("../../assets/enemies/default.enemies.ron")).unwrap();
+        EnemyKinds {
+            kinds: kind_list.kinds,
+        }
+    }
+
+    #[test]
+    fn the_default_kinds_have_every_behaviour() {
+        let kinds = default_kinds();
+        let has = |f: fn(&Behaviour) -> bool| kinds.kinds.iter().any(|k| f(&k.behaviour));
+        assert!(has(|b| matches!(b, Behaviour::Bouncer)));
+        assert!(has(|b| matches!(b, Behaviour::Chaser { .. })));
+        assert!(has(|b| matches!(b, Behaviour::Splitter { .. })));
+        assert!(has(|b| matches!(b, Behaviour::Dasher { .. })));
+        assert!(kinds.kinds.iter().any(|k| k.first_wave <= 1));
+    }
+
+    #[test]
+    fn the_default_splitter_breaks_in_two() {
+        let kinds = default_kinds();
+        let (kind, pieces, piece_size) = kinds
+            .kinds
+            .iter()
+            .find_map(|k| match k.behaviour {
+                Behaviour::Splitter { pieces, piece_size } => Some((k, pieces, piece_size)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(pieces, 2);
+        assert!(piece_size < kind.size);
+    }
+
+    #[test]
+    fn only_kinds_that_turned_up_are_chosen() {
+        let kinds = default_kinds();
+        let mut rng = StdRng::seed_from_u64(1);
+        for wave in 1..6 {
+            for _ in 0..200 {
+                let kind = kinds.choose(wave, &mut rng).unwrap();
+                assert!(kind.first_wave <= wave);
+            }
+        }
+        assert!(EnemyKinds::default().choose(1, &mut rng).is_none());
+    }
+}
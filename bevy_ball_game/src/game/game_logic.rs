@@ -1,13 +1,18 @@
 use bevy::{
     audio::{Volume, VolumeLevel},
     prelude::*,
-    window::PrimaryWindow,
 };
 
 use crate::app_state::AppState;
 
 use super::{
-    collide::CollisionEvent, enemy::Enemy, player::Player, star::Star, tick_schedule::Simulation,
+    arena::Arena,
+    collide::CollisionEvent,
+    enemy::Enemy,
+    player::Player,
+    power_ups::{collect_power_up, ActiveEffects, PowerUpKind},
+    star::Star,
+    tick_schedule::TickSet,
 };
 
 pub struct GameLogicPlugin;
@@ -15,27 +20,30 @@ pub struct GameLogicPlugin;
 impl Plugin for GameLogicPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Score>()
-            .init_resource::<HighScores>()
-            .add_event::<GameOverEvent>()
+            .init_resource::<Events<GameOverEvent>>()
             .add_systems(Startup, spawn_camera)
+            .add_systems(OnEnter(AppState::Game), reset_score)
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
-                    death,
-                    handle_game_over,
-                    debug_high_scores_updated,
-                    collect_star,
-                    print_score,
-                )
-                    .in_set(Simulation),
+                    Events::<GameOverEvent>::update_system.in_set(TickSet::Input),
+                    (
+                        collect_star,
+                        // A shield picked up on the way into an enemy saves
+                        // the player.
+                        death.after(collect_power_up),
+                        handle_game_over.after(death),
+                        print_score.after(collect_star),
+                    )
+                        .in_set(TickSet::Outcome),
+                ),
             );
     }
 }
 
-pub fn spawn_camera(mut commands: Commands, window_query: Query<&Window, With<PrimaryWindow>>) {
-    let window = window_query.get_single().unwrap();
+pub fn spawn_camera(mut commands: Commands, arena: Res<Arena>) {
     commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0),
+        transform: Transform::from_translation(arena.center()),
         ..default()
     });
 }
@@ -44,14 +52,13 @@ pub struct Score {
     pub value: u32,
 }
 
-#[derive(Resource, Debug, Default)]
-pub struct HighScores {
-    pub scores: Vec<(String, u32)>,
-}
-
 #[derive(Event)]
 pub struct GameOverEvent {}
 
+pub fn reset_score(mut score: ResMut<Score>) {
+    score.value = 0;
+}
+
 pub fn death(
     mut command: Commands,
     asset_server: Res<AssetServer>,
@@ -59,22 +66,27 @@ pub fn death(
     player_query: Query<Entity, With<Player>>,
     enemy_query: Query<Entity, With<Enemy>>,
     mut ev_game_over: EventWriter<GameOverEvent>,
+    mut active_effects: ResMut<ActiveEffects>,
 ) {
-    for ev in ev_collide.iter() {
-        if let Ok(player) = player_query.get(ev.entity_a) {
-            if let Some(entity_b) = ev.entity_b {
-                if enemy_query.get(entity_b).is_ok() {
-                    command.entity(player).despawn();
-                    let end_sound = asset_server.load("audio/explosionCrunch_000.ogg");
-                    command.spawn(AudioBundle {
-                        source: end_sound,
-                        settings: PlaybackSettings {
-                            volume: Volume::Relative(VolumeLevel::new(0.1)),
-                            ..default()
-                        },
-                    });
-                    ev_game_over.send(GameOverEvent {});
+    for (entity, other, _) in ev_collide.iter().flat_map(CollisionEvent::both_ways) {
+        if let Ok(player) = player_query.get(entity) {
+            if enemy_query.get(other).is_ok() {
+                // The shield takes the hit, and the enemy with it.
+                if active_effects.is_active(PowerUpKind::Shield) {
+                    active_effects.end(PowerUpKind::Shield);
+                    command.entity(other).despawn();
+                    continue;
                 }
+                command.entity(player).despawn();
+                let end_sound = asset_server.load("audio/explosionCrunch_000.ogg");
+                command.spawn(AudioBundle {
+                    source: end_sound,
+                    settings: PlaybackSettings {
+                        volume: Volume::Relative(VolumeLevel::new(0.1)),
+                        ..default()
+                    },
+                });
+                ev_game_over.send(GameOverEvent {});
             }
         }
     }
@@ -88,21 +100,19 @@ pub fn collect_star(
     star_query: Query<Entity, With<Star>>,
     mut score: ResMut<Score>,
 ) {
-    for ev in ev_collide.iter() {
-        if player_query.get(ev.entity_a).is_ok() {
-            if let Some(entity_b) = ev.entity_b {
-                if let Ok(star) = star_query.get(entity_b) {
-                    command.entity(star).despawn();
-                    let star_sound = asset_server.load("audio/laserLarge_000.ogg");
-                    command.spawn(AudioBundle {
-                        source: star_sound,
-                        settings: PlaybackSettings {
-                            volume: Volume::Relative(VolumeLevel::new(0.1)),
-                            ..default()
-                        },
-                    });
-                    score.value += 1;
-                }
+    for (entity, other, _) in ev_collide.iter().flat_map(CollisionEvent::both_ways) {
+        if player_query.get(entity).is_ok() {
+            if let Ok(star) = star_query.get(other) {
+                command.entity(star).despawn();
+                let star_sound = asset_server.load("audio/laserLarge_000.ogg");
+                command.spawn(AudioBundle {
+                    source: star_sound,
+                    settings: PlaybackSettings {
+                        volume: Volume::Relative(VolumeLevel::new(0.1)),
+                        ..default()
+                    },
+                });
+                score.value += 1;
             }
         }
     }
@@ -114,20 +124,10 @@ pub fn print_score(score: Res<Score>) {
     }
 }
 
-pub fn handle_game_over(
-    mut commands: Commands,
-    mut ev_game_over: EventReader<GameOverEvent>,
-    score: Res<Score>,
-    mut high_scores: ResMut<HighScores>,
-) {
+// The score goes on the high score table once the game-over screen has
+// asked for the player's initials.
+pub fn handle_game_over(mut commands: Commands, mut ev_game_over: EventReader<GameOverEvent>) {
     for _ in ev_game_over.iter() {
-        high_scores.scores.push(("Player".to_string(), score.value));
         commands.insert_resource(NextState(Some(AppState::GameOver)));
     }
 }
-
-pub fn debug_high_scores_updated(high_scores: Res<HighScores>) {
-    if high_scores.is_changed() {
-        println!("{:?}", high_scores)
-    }
-}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+pub const MAX_HIGH_SCORES: usize = 10;
+pub const MAX_INITIALS: usize = 3;
+
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HighScores>()
+            .init_resource::<HighScoresFile>()
+            .add_systems(Startup, load_high_scores)
+            .add_systems(Update, save_high_scores);
+    }
+}
+
+/// The best scores so far, highest first. Scores that tie keep the order
+/// they were set in.
+#[derive(Resource, Debug, Default)]
+pub struct HighScores {
+    pub scores: Vec<(String, u32)>,
+}
+
+impl HighScores {
+    /// Whether `score` would make it onto the table.
+    pub fn qualifies(&self, score: u32) -> bool {
+        self.scores.len() < MAX_HIGH_SCORES || self.scores.iter().any(|(_, s)| score > *s)
+    }
+
+    pub fn insert(&mut self, name: String, score: u32) {
+        let position = self.scores.iter().position(|(_, s)| score > *s);
+        self.scores
+            .insert(position.unwrap_or(self.scores.len()), (name, score));
+        self.scores.truncate(MAX_HIGH_SCORES);
+    }
+
+    /// Reads a table written by `to_file_contents`, skipping lines that
+    /// don't parse.
+    pub fn from_file_contents(contents: &str) -> HighScores {
+        let mut high_scores = HighScores::default();
+        for line in contents.lines() {
+            if let Some((name, score)) = line.split_once('\t') {
+                if let Ok(score) = score.trim().parse() {
+                    high_scores.insert(name.to_string(), score);
+                }
+            }
+        }
+        high_scores
+    }
+
+    pub fn to_file_contents(&self) -> String {
+        self.scores
+            .iter()
+            .map(|(name, score)| format!("{}\t{}\n", name, score))
+            .collect()
+    }
+}
+
+/// The initials being typed in for a score that made the table. Only
+/// present while the game-over screen is asking for them.
+#[derive(Resource, Debug, Default)]
+pub struct NameEntry {
+    pub initials: String,
+}
+
+/// Where the table is kept between runs, in the user's data directory.
+pub fn high_scores_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("bevy_ball_game").join("high_scores.txt"))
+}
+
+/// Where the table is loaded from and saved to. It's kept only for as
+/// long as the game runs without one.
+#[derive(Resource, Debug)]
+pub struct HighScoresFile(pub Option<PathBuf>);
+
+impl Default for HighScoresFile {
+    fn default() -> Self {
+        HighScoresFile(high_scores_path())
+    }
+}
+
+pub fn load_high_scores(
+    mut high_scores: ResMut<HighScores>,
+    high_scores_file: Res<HighScoresFile>,
+) {
+    let Some(path) = &high_scores_file.0 else {
+        return;
+    };
+    // A missing file just means nobody has set a score yet.
+    if let Ok(contents) = fs::read_to_string(path) {
+        *high_scores = HighScores::from_file_contents(&contents);
+    }
+}
+
+pub fn save_high_scores(high_scores: Res<HighScores>, high_scores_file: Res<HighScoresFile>) {
+    if !high_scores.is_changed() || high_scores.is_added() {
+        return;
+    }
+    let Some(path) = &high_scores_file.0 else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, high_scores.to_file_contents()));
+    if let Err(e) = result {
+        warn!("Could not save high scores to {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(scores: &[u32]) -> HighScores {
+        let mut high_scores = HighScores::default();
+        for (i, score) in scores.iter().enumerate() {
+            high_scores.insert(format!("P{}", i), *score);
+        }
+        high_scores
+    }
+
+    #[test]
+    fn any_score_qualifies_until_the_table_is_full() {
+        assert!(HighScores::default().qualifies(0));
+        let high_scores = table(&[5; MAX_HIGH_SCORES - 1]);
+        assert!(high_scores.qualifies(0));
+    }
+
+    #[test]
+    fn a_full_table_takes_only_scores_that_beat_one_on_it() {
+        let high_scores = table(&[5; MAX_HIGH_SCORES]);
+        assert!(!high_scores.qualifies(4));
+        assert!(!high_scores.qualifies(5));
+        assert!(high_scores.qualifies(6));
+    }
+
+    #[test]
+    fn insert_keeps_the_highest_first() {
+        let high_scores = table(&[3, 9, 1, 5]);
+        let scores: Vec<u32> = high_scores.scores.iter().map(|(_, s)| *s).collect();
+        assert_eq!(scores, [9, 5, 3, 1]);
+    }
+
+    #[test]
+    fn insert_drops_the_lowest_off_a_full_table() {
+        let mut high_scores = table(&(1..=MAX_HIGH_SCORES as u32).collect::<Vec<_>>());
+        high_scores.insert("NEW".to_string(), 100);
+        assert_eq!(high_scores.scores.len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.scores[0], ("NEW".to_string(), 100));
+        assert_eq!(high_scores.scores.last().unwrap().1, 2);
+    }
+
+    #[test]
+    fn a_tie_goes_below_the_score_set_first() {
+        let mut high_scores = table(&[7, 7]);
+        high_scores.insert("NEW".to_string(), 7);
+        let names: Vec<&str> = high_scores.scores.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["P0", "P1", "NEW"]);
+        // A tie at the bottom of a full table doesn't get on at all.
+        let mut high_scores = table(&[7; MAX_HIGH_SCORES]);
+        high_scores.insert("NEW".to_string(), 7);
+        assert!(high_scores.scores.iter().all(|(n, _)| n != "NEW"));
+    }
+
+    #[test]
+    fn the_file_contents_read_back_the_same() {
+        let high_scores = table(&[3, 9, 1]);
+        let read = HighScores::from_file_contents(&high_scores.to_file_contents());
+        assert_eq!(read.scores, high_scores.scores);
+    }
+
+    #[test]
+    fn an_empty_file_is_an_empty_table() {
+        assert!(HighScores::from_file_contents("").scores.is_empty());
+        assert!(HighScores::from_file_contents("\n\n").scores.is_empty());
+    }
+
+    #[test]
+    fn a_corrupt_file_keeps_only_the_lines_that_parse() {
+        let contents = "ABC\t12\ngarbage\nDEF\tlots\n\t\nGHI\t 30 \nJKL\t-4\n";
+        let high_scores = HighScores::from_file_contents(contents);
+        assert_eq!(
+            high_scores.scores,
+            [("GHI".to_string(), 30), ("ABC".to_string(), 12)]
+        );
+    }
+}
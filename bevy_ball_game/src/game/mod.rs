@@ -3,15 +3,29 @@ use bevy::prelude::*;
 use crate::app_state::AppState;
 
 use self::{
-    collide::CollidePlugin, enemy::EnemyPlugin, game_logic::GameLogicPlugin, player::PlayerPlugin,
-    star::StarPlugin, tick_schedule::TickSchedulePlugin,
+    arena::ArenaPlugin, collide::CollidePlugin, enemy::EnemyPlugin, enemy_kinds::EnemyKindsPlugin,
+    game_logic::GameLogicPlugin, high_scores::HighScoresPlugin, player::PlayerPlugin,
+    power_ups::PowerUpsPlugin, replay::ReplayPlugin, spawn_placement::SpawnPlacementPlugin,
+    star::StarPlugin, tick_schedule::TickSchedulePlugin, waves::WavesPlugin,
 };
+pub mod arena;
 mod collide;
 pub mod enemy;
+pub mod enemy_kinds;
 pub mod game_logic;
+pub mod high_scores;
 mod player;
+pub mod power_ups;
+pub mod replay;
+mod rng;
+mod ron_asset;
+mod spatial_hash;
+pub mod spawn_placement;
 mod star;
+#[cfg(test)]
+mod tests;
 mod tick_schedule;
+pub mod waves;
 
 pub struct GamePlugin;
 
@@ -20,14 +34,24 @@ impl Plugin for GamePlugin {
         app.add_state::<SimulationState>()
             .add_plugins(TickSchedulePlugin)
             .add_plugins((
+                ArenaPlugin,
                 CollidePlugin,
                 EnemyPlugin,
+                EnemyKindsPlugin,
                 GameLogicPlugin,
+                HighScoresPlugin,
                 PlayerPlugin,
+                PowerUpsPlugin,
+                ReplayPlugin,
+                SpawnPlacementPlugin,
                 StarPlugin,
+                WavesPlugin,
             ))
             .add_systems(Update, toggle_simulation)
-            .add_systems(OnExit(AppState::Game), despawn_on_game_end);
+            .add_systems(
+                OnExit(AppState::Game),
+                (despawn_on_game_end, resume_simulation),
+            );
     }
 }
 
@@ -53,6 +77,11 @@ pub fn toggle_simulation(
     }
 }
 
+// So the next game doesn't start paused.
+pub fn resume_simulation(mut commands: Commands) {
+    commands.insert_resource(NextState(Some(SimulationState::Running)));
+}
+
 #[derive(Component)]
 pub struct DespawnOnEnd;
 
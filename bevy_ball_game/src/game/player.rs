@@ -1,10 +1,10 @@
 use crate::app_state::AppState;
 
+use super::arena::Arena;
 use super::collide::Collides;
 use super::tick_schedule::MovementSet;
 use super::DespawnOnEnd;
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
 
 pub const PLAYER_SPEED: f32 = 500.0;
 pub const PLAYER_SIZE: f32 = 64.0;
@@ -14,22 +14,17 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(AppState::Game), spawn_player)
-            .add_systems(Update, player_movement.in_set(MovementSet::Movement));
+            .add_systems(FixedUpdate, player_movement.in_set(MovementSet::Movement));
     }
 }
 
 #[derive(Component)]
 pub struct Player;
 
-pub fn spawn_player(
-    mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    asset_server: Res<AssetServer>,
-) {
-    let window = window_query.get_single().unwrap();
+pub fn spawn_player(mut commands: Commands, arena: Res<Arena>, asset_server: Res<AssetServer>) {
     commands.spawn((
         SpriteBundle {
-            transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0),
+            transform: Transform::from_translation(arena.center()),
             texture: asset_server.load("sprites/ball_blue_large.png"),
             ..default()
         },
@@ -44,7 +39,7 @@ pub fn spawn_player(
 pub fn player_movement(
     keyboard_input: Res<Input<KeyCode>>,
     mut player_query: Query<&mut Transform, With<Player>>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
 ) {
     if let Ok(mut transform) = player_query.get_single_mut() {
         let mut direction = Vec3::ZERO;
@@ -66,6 +61,6 @@ pub fn player_movement(
             direction = direction.normalize()
         }
 
-        transform.translation += direction * PLAYER_SPEED * time.delta_seconds();
+        transform.translation += direction * PLAYER_SPEED * fixed_time.period.as_secs_f32();
     }
 }
@@ -0,0 +1,306 @@
+use bevy::audio::{Volume, VolumeLevel};
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::app_state::AppState;
+
+use super::collide::{Collides, CollisionEvent};
+use super::enemy::Enemy;
+use super::player::{player_movement, Player};
+use super::rng::GameRng;
+use super::rng::RngStream;
+use super::spawn_placement::SpawnPlacement;
+use super::star::Star;
+use super::tick_schedule::{MovementSet, TickSet};
+use super::DespawnOnEnd;
+
+pub struct PowerUpsPlugin;
+
+impl Plugin for PowerUpsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PowerUpSpawnTimer>()
+            .init_resource::<ActiveEffects>()
+            .add_systems(OnEnter(AppState::Game), reset_effects)
+            .add_systems(
+                FixedUpdate,
+                (
+                    (expire_power_ups, tick_effects).in_set(TickSet::Timers),
+                    spawn_power_ups_over_time.in_set(TickSet::Spawning),
+                    collect_power_up.in_set(TickSet::Outcome),
+                    // Toward where the player is now.
+                    attract_stars
+                        .after(player_movement)
+                        .in_set(MovementSet::Movement),
+                ),
+            );
+    }
+}
+
+pub const POWER_UP_SIZE: f32 = 40.;
+pub const POWER_UP_INTERVAL: f32 = 12.;
+/// How long a power-up waits to be picked up before it goes away.
+pub const POWER_UP_LIFETIME: f32 = 8.;
+pub const SHIELD_DURATION: f32 = 15.;
+pub const SLOW_TIME_DURATION: f32 = 6.;
+pub const SLOW_TIME_SCALE: f32 = 0.4;
+pub const MAGNET_DURATION: f32 = 10.;
+pub const MAGNET_RADIUS: f32 = 250.;
+pub const MAGNET_SPEED: f32 = 300.;
+pub const BOMB_RADIUS: f32 = 250.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// Absorbs the next hit from an enemy, taking the enemy with it.
+    Shield,
+    /// Slows the enemies down.
+    SlowTime,
+    /// Pulls nearby stars toward the player.
+    Magnet,
+    /// Clears the enemies around the player straight away.
+    Bomb,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::Shield,
+        PowerUpKind::SlowTime,
+        PowerUpKind::Magnet,
+        PowerUpKind::Bomb,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::SlowTime => "Slow",
+            PowerUpKind::Magnet => "Magnet",
+            PowerUpKind::Bomb => "Bomb",
+        }
+    }
+
+    pub fn tint(self) -> Color {
+        match self {
+            PowerUpKind::Shield => Color::CYAN,
+            PowerUpKind::SlowTime => Color::PURPLE,
+            PowerUpKind::Magnet => Color::ORANGE,
+            PowerUpKind::Bomb => Color::RED,
+        }
+    }
+
+    /// How long the effect lasts, or `None` if it happens all at once.
+    pub fn duration(self) -> Option<f32> {
+        match self {
+            PowerUpKind::Shield => Some(SHIELD_DURATION),
+            PowerUpKind::SlowTime => Some(SLOW_TIME_DURATION),
+            PowerUpKind::Magnet => Some(MAGNET_DURATION),
+            PowerUpKind::Bomb => None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub timer: Timer,
+}
+
+#[derive(Resource)]
+pub struct PowerUpSpawnTimer {
+    timer: Timer,
+}
+
+impl Default for PowerUpSpawnTimer {
+    fn default() -> Self {
+        PowerUpSpawnTimer {
+            timer: Timer::from_seconds(POWER_UP_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+/// The power-ups the player has picked up that are still going.
+#[derive(Resource, Debug, Default)]
+pub struct ActiveEffects {
+    pub effects: Vec<(PowerUpKind, Timer)>,
+}
+
+impl ActiveEffects {
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.effects.iter().any(|(active, _)| *active == kind)
+    }
+
+    /// Starts `kind`, or starts it over if it's already going.
+    pub fn start(&mut self, kind: PowerUpKind, seconds: f32) {
+        self.end(kind);
+        self.effects
+            .push((kind, Timer::from_seconds(seconds, TimerMode::Once)));
+    }
+
+    pub fn end(&mut self, kind: PowerUpKind) {
+        self.effects.retain(|(active, _)| *active != kind);
+    }
+
+    /// What enemies' time passes at, compared to the player's.
+    pub fn enemy_time_scale(&self) -> f32 {
+        if self.is_active(PowerUpKind::SlowTime) {
+            SLOW_TIME_SCALE
+        } else {
+            1.
+        }
+    }
+}
+
+pub fn reset_effects(
+    mut active_effects: ResMut<ActiveEffects>,
+    mut power_up_spawn_timer: ResMut<PowerUpSpawnTimer>,
+) {
+    *active_effects = ActiveEffects::default();
+    power_up_spawn_timer.timer.reset();
+}
+
+pub fn tick_effects(mut active_effects: ResMut<ActiveEffects>, fixed_time: Res<FixedTime>) {
+    for (_, timer) in active_effects.effects.iter_mut() {
+        timer.tick(fixed_time.period);
+    }
+    active_effects
+        .effects
+        .retain(|(_, timer)| !timer.finished());
+}
+
+pub fn spawn_power_ups_over_time(
+    mut commands: Commands,
+    mut power_up_spawn_timer: ResMut<PowerUpSpawnTimer>,
+    asset_server: Res<AssetServer>,
+    spawn_placement: SpawnPlacement,
+    fixed_time: Res<FixedTime>,
+    mut rng: ResMut<GameRng>,
+) {
+    if !power_up_spawn_timer
+        .timer
+        .tick(fixed_time.period)
+        .finished()
+    {
+        return;
+    }
+    let rng = rng.stream(RngStream::PowerUps);
+    let Some(position) = spawn_placement.find(rng, POWER_UP_SIZE) else {
+        return;
+    };
+    let kind = PowerUpKind::ALL[rng.gen_range(0..PowerUpKind::ALL.len())];
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_translation(position),
+            texture: asset_server.load("sprites/star.png"),
+            sprite: Sprite {
+                color: kind.tint(),
+                custom_size: Some(Vec2::splat(POWER_UP_SIZE)),
+                ..default()
+            },
+            ..default()
+        },
+        PowerUp {
+            kind,
+            timer: Timer::from_seconds(POWER_UP_LIFETIME, TimerMode::Once),
+        },
+        Collides {
+            object_size: POWER_UP_SIZE,
+        },
+        DespawnOnEnd,
+    ));
+}
+
+pub fn expire_power_ups(
+    mut commands: Commands,
+    mut power_up_query: Query<(Entity, &mut PowerUp)>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (entity, mut power_up) in power_up_query.iter_mut() {
+        if power_up.timer.tick(fixed_time.period).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn collect_power_up(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut ev_collide: EventReader<CollisionEvent>,
+    player_query: Query<&Transform, With<Player>>,
+    power_up_query: Query<&PowerUp>,
+    enemy_query: Query<(Entity, &Transform), With<Enemy>>,
+    mut active_effects: ResMut<ActiveEffects>,
+) {
+    for (entity, other, _) in ev_collide.iter().flat_map(CollisionEvent::both_ways) {
+        let Ok(player) = player_query.get(entity) else {
+            continue;
+        };
+        let Ok(power_up) = power_up_query.get(other) else {
+            continue;
+        };
+        commands.entity(other).despawn();
+        commands.spawn(AudioBundle {
+            source: asset_server.load("audio/laserLarge_000.ogg"),
+            settings: PlaybackSettings {
+                volume: Volume::Relative(VolumeLevel::new(0.1)),
+                speed: 1.5,
+                ..default()
+            },
+        });
+        match power_up.kind.duration() {
+            Some(seconds) => active_effects.start(power_up.kind, seconds),
+            None => {
+                for (enemy, transform) in enemy_query.iter() {
+                    if transform.translation.distance(player.translation) < BOMB_RADIUS {
+                        commands.entity(enemy).despawn();
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn attract_stars(
+    mut star_query: Query<&mut Transform, (With<Star>, Without<Player>)>,
+    player_query: Query<&Transform, With<Player>>,
+    active_effects: Res<ActiveEffects>,
+    fixed_time: Res<FixedTime>,
+) {
+    if !active_effects.is_active(PowerUpKind::Magnet) {
+        return;
+    }
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    for mut transform in star_query.iter_mut() {
+        let offset = player.translation - transform.translation;
+        if offset.length() < MAGNET_RADIUS {
+            let step = (MAGNET_SPEED * fixed_time.period.as_secs_f32()).min(offset.length());
+            transform.translation += offset.normalize_or_zero() * step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starting_an_effect_again_starts_it_over() {
+        let mut active_effects = ActiveEffects::default();
+        active_effects.start(PowerUpKind::Magnet, 1.);
+        active_effects.effects[0]
+            .1
+            .tick(std::time::Duration::from_millis(900));
+        active_effects.start(PowerUpKind::Magnet, 1.);
+        assert_eq!(active_effects.effects.len(), 1);
+        assert_eq!(active_effects.effects[0].1.elapsed_secs(), 0.);
+    }
+
+    #[test]
+    fn slow_time_scales_enemy_time() {
+        let mut active_effects = ActiveEffects::default();
+        assert_eq!(active_effects.enemy_time_scale(), 1.);
+        active_effects.start(PowerUpKind::SlowTime, 1.);
+        assert_eq!(active_effects.enemy_time_scale(), SLOW_TIME_SCALE);
+        active_effects.end(PowerUpKind::SlowTime);
+        assert!(!active_effects.is_active(PowerUpKind::SlowTime));
+    }
+}
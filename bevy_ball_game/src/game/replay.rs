@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+
+use super::arena::Arena;
+use super::enemy_kinds::EnemyKinds;
+use super::game_logic::Score;
+use super::rng::GameRng;
+use super::star::spawn_stars;
+use super::tick_schedule::{Simulation, TickSet};
+use super::waves::Waves;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameRng>()
+            .init_resource::<ReplayFile>()
+            .add_systems(
+                PreStartup,
+                use_recorded_arena.run_if(resource_exists::<Playback>()),
+            )
+            .add_systems(OnEnter(AppState::Game), seed_game.before(spawn_stars))
+            .add_systems(OnExit(AppState::Game), (save_recording, finish_playback))
+            // The game shouldn't go on past the input that was recorded.
+            .configure_set(FixedUpdate, Simulation.run_if(not(playback_over)))
+            .add_systems(
+                PreUpdate,
+                ignore_keyboard
+                    .after(InputSystem)
+                    .run_if(resource_exists::<Playback>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_playback.run_if(in_state(AppState::MainMenu)),
+                    end_playback.run_if(in_state(AppState::Game).and_then(playback_over)),
+                )
+                    .run_if(resource_exists::<Playback>()),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    record_tick.run_if(resource_exists::<Recording>()),
+                    play_tick.run_if(resource_exists::<Playback>()),
+                )
+                    .in_set(TickSet::Input),
+            );
+    }
+}
+
+/// The keys a replay keeps track of, which are all the simulation reads.
+pub const RECORDED_KEYS: [KeyCode; 8] = [
+    KeyCode::W,
+    KeyCode::A,
+    KeyCode::S,
+    KeyCode::D,
+    KeyCode::Up,
+    KeyCode::Left,
+    KeyCode::Down,
+    KeyCode::Right,
+];
+
+/// Everything needed to play a game back: the seed, the size of the arena
+/// it was played in, and which of `RECORDED_KEYS` were held on each tick
+/// of the simulation, one bit per key. The score it ended with is kept to
+/// check the playback against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub arena: (f32, f32),
+    pub ticks: Vec<u8>,
+    pub score: u32,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ron::de::from_str(&contents).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = ron::ser::to_string(self).map_err(|e| e.to_string())?;
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, contents))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Where the last game played is recorded, next to the high scores.
+pub fn replay_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("bevy_ball_game").join("last_game.replay.ron"))
+}
+
+/// Where each game is saved to as it ends. Games aren't recorded at all
+/// without one.
+#[derive(Resource, Debug)]
+pub struct ReplayFile(pub Option<PathBuf>);
+
+impl Default for ReplayFile {
+    fn default() -> Self {
+        ReplayFile(replay_path())
+    }
+}
+
+pub fn keys_to_bits<'a>(keys: impl Iterator<Item = &'a KeyCode>) -> u8 {
+    keys.filter_map(|key| RECORDED_KEYS.iter().position(|k| k == key))
+        .fold(0, |bits, index| bits | 1 << index)
+}
+
+pub fn bits_to_keys(bits: u8) -> impl Iterator<Item = KeyCode> {
+    RECORDED_KEYS
+        .into_iter()
+        .enumerate()
+        .filter(move |(index, _)| bits & 1 << index != 0)
+        .map(|(_, key)| key)
+}
+
+/// The game being played, as it's recorded.
+#[derive(Resource, Debug)]
+pub struct Recording(pub Replay);
+
+/// Present when the game was started with `--replay`. Playback starts as
+/// soon as the game data has loaded, and the game quits when it's over.
+#[derive(Resource, Debug)]
+pub struct Playback {
+    pub replay: Replay,
+    /// The tick about to play, once playback has started.
+    pub tick: Option<usize>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback { replay, tick: None }
+    }
+
+    /// Whether every recorded tick has played.
+    pub fn is_over(&self) -> bool {
+        self.tick
+            .is_some_and(|tick| tick >= self.replay.ticks.len())
+    }
+}
+
+pub fn playback_over(playback: Option<Res<Playback>>) -> bool {
+    playback.is_some_and(|playback| playback.is_over())
+}
+
+// The window only needs to match to show all of it.
+pub fn use_recorded_arena(
+    playback: Res<Playback>,
+    mut arena: ResMut<Arena>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let (width, height) = playback.replay.arena;
+    *arena = Arena { width, height };
+    if let Ok(mut window) = window_query.get_single_mut() {
+        window.resolution.set(width, height);
+    }
+}
+
+pub fn seed_game(
+    mut commands: Commands,
+    playback: Option<Res<Playback>>,
+    replay_file: Res<ReplayFile>,
+    arena: Res<Arena>,
+    mut rng: ResMut<GameRng>,
+) {
+    let seed = match playback {
+        Some(playback) => playback.replay.seed,
+        None => {
+            let seed = random();
+            if replay_file.0.is_some() {
+                commands.insert_resource(Recording(Replay {
+                    seed,
+                    arena: (arena.width, arena.height),
+                    ticks: Vec::new(),
+                    score: 0,
+                }));
+            }
+            seed
+        }
+    };
+    // Set straight away, rather than through `commands`, for the stars
+    // spawned as the game starts.
+    *rng = GameRng::seeded(seed);
+}
+
+pub fn record_tick(mut recording: ResMut<Recording>, keyboard_input: Res<Input<KeyCode>>) {
+    recording
+        .0
+        .ticks
+        .push(keys_to_bits(keyboard_input.get_pressed()));
+}
+
+pub fn save_recording(
+    mut commands: Commands,
+    recording: Option<ResMut<Recording>>,
+    replay_file: Res<ReplayFile>,
+    score: Res<Score>,
+) {
+    let Some(mut recording) = recording else {
+        return;
+    };
+    recording.0.score = score.value;
+    if let Some(path) = &replay_file.0 {
+        match recording.0.save(path) {
+            Ok(()) => info!("Saved replay to {}", path.display()),
+            Err(e) => warn!("Could not save replay to {}: {}", path.display(), e),
+        }
+    }
+    commands.remove_resource::<Recording>();
+}
+
+pub fn start_playback(
+    mut commands: Commands,
+    mut playback: ResMut<Playback>,
+    waves: Res<Waves>,
+    enemy_kinds: Res<EnemyKinds>,
+) {
+    // Waves and enemy kinds load in the background, and the game can't
+    // start the same way without them.
+    if playback.tick.is_some() || waves.waves.is_empty() || enemy_kinds.kinds.is_empty() {
+        return;
+    }
+    commands.insert_resource(NextState(Some(AppState::Game)));
+    playback.tick = Some(0);
+    info!("Playing back replay");
+}
+
+// The keyboard has no say while a replay plays.
+pub fn ignore_keyboard(mut keyboard_input: ResMut<Input<KeyCode>>) {
+    keyboard_input.reset_all();
+}
+
+pub fn play_tick(mut playback: ResMut<Playback>, mut keyboard_input: ResMut<Input<KeyCode>>) {
+    let Some(tick) = playback.tick else {
+        return;
+    };
+    keyboard_input.reset_all();
+    if let Some(&pressed) = playback.replay.ticks.get(tick) {
+        for key in bits_to_keys(pressed) {
+            keyboard_input.press(key);
+        }
+    }
+    playback.tick = Some(tick + 1);
+}
+
+// All of the input has played without the game ending, so the recorded
+// game was quit rather than lost.
+pub fn end_playback(mut commands: Commands) {
+    commands.insert_resource(NextState(Some(AppState::MainMenu)));
+}
+
+pub fn finish_playback(
+    playback: Option<Res<Playback>>,
+    score: Res<Score>,
+    mut exit_event: EventWriter<AppExit>,
+) {
+    let Some(playback) = playback else {
+        return;
+    };
+    if !playback.is_over() {
+        warn!("The game ended before the replay ran out, so it didn't play back the same");
+    }
+    if score.value == playback.replay.score {
+        info!("Replay finished with a score of {}", score.value);
+    } else {
+        warn!(
+            "Replay finished with a score of {}, but the recorded game scored {}",
+            score.value, playback.replay.score
+        );
+    }
+    exit_event.send(AppExit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_survive_the_round_trip_to_bits() {
+        let keys = [KeyCode::W, KeyCode::Down, KeyCode::Right];
+        let bits = keys_to_bits(keys.iter().chain([KeyCode::G].iter()));
+        assert_eq!(bits_to_keys(bits).collect::<Vec<_>>(), keys);
+        assert_eq!(keys_to_bits([].iter()), 0);
+    }
+
+    #[test]
+    fn replays_survive_the_round_trip_to_a_file() {
+        let replay = Replay {
+            seed: u64::MAX,
+            arena: (1280., 720.),
+            ticks: vec![0b1001, 0b1001, 0, 0b1000_0000],
+            score: 42,
+        };
+        let path = std::env::temp_dir()
+            .join(format!("bevy_ball_game_test_{}", std::process::id()))
+            .join("test.replay.ron");
+        replay.save(&path).unwrap();
+        assert_eq!(Replay::load(&path).unwrap(), replay);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{random, SeedableRng};
+
+/// What a random number is drawn for. Each has a generator of its own, so
+/// systems drawing at the same time don't take numbers from each other
+/// depending on which runs first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngStream {
+    Enemies,
+    Stars,
+    PowerUps,
+    BounceSounds,
+    Splits,
+}
+
+impl RngStream {
+    pub const ALL: [RngStream; 5] = [
+        RngStream::Enemies,
+        RngStream::Stars,
+        RngStream::PowerUps,
+        RngStream::BounceSounds,
+        RngStream::Splits,
+    ];
+}
+
+/// Where all of the game's randomness comes from. It's seeded afresh at
+/// the start of every game, so a replay with the same seed and input plays
+/// out the same.
+#[derive(Resource)]
+pub struct GameRng {
+    streams: [StdRng; RngStream::ALL.len()],
+}
+
+impl GameRng {
+    pub fn seeded(seed: u64) -> Self {
+        let mut seeds = StdRng::seed_from_u64(seed);
+        GameRng {
+            streams: RngStream::ALL.map(|_| StdRng::from_rng(&mut seeds).unwrap()),
+        }
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+        &mut self.streams[stream as usize]
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::seeded(random())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn streams_are_independent_of_each_other() {
+        let mut alone = GameRng::seeded(7);
+        let expected: Vec<u32> = (0..8)
+            .map(|_| alone.stream(RngStream::Stars).gen())
+            .collect();
+
+        let mut interleaved = GameRng::seeded(7);
+        let drawn: Vec<u32> = (0..8)
+            .map(|_| {
+                let _: u64 = interleaved.stream(RngStream::Enemies).gen();
+                interleaved.stream(RngStream::Stars).gen()
+            })
+            .collect();
+        assert_eq!(drawn, expected);
+        assert_ne!(
+            GameRng::seeded(7).stream(RngStream::Enemies).gen::<u64>(),
+            GameRng::seeded(7).stream(RngStream::Stars).gen::<u64>()
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use bevy::prelude::{Entity, Vec3};
+
+/// What the broad phase needs to know about a `Collides` entity.
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub size: f32,
+}
+
+pub fn overlaps(a: &Collider, b: &Collider) -> bool {
+    a.position.distance(b.position) < (a.size + b.size) / 2.0
+}
+
+/// Buckets colliders into a uniform grid on the x/y plane, so only colliders
+/// in neighbouring cells are compared. The cells are as wide as the largest
+/// collider, which is as far apart as two overlapping colliders can be.
+/// Kept between frames to reuse its allocations.
+#[derive(Default)]
+pub struct SpatialHash {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    /// The indices of every pair of overlapping colliders, each pair once.
+    /// The lower entity comes first in a pair, and the pairs are sorted, so
+    /// the result doesn't depend on the order of `colliders`.
+    pub fn overlapping_pairs(&mut self, colliders: &[Collider]) -> Vec<(usize, usize)> {
+        self.cells.clear();
+        let cell_size = colliders.iter().map(|c| c.size).fold(0., f32::max);
+        if cell_size <= 0. {
+            return Vec::new();
+        }
+        let cell_of = |collider: &Collider| {
+            (
+                (collider.position.x / cell_size).floor() as i32,
+                (collider.position.y / cell_size).floor() as i32,
+            )
+        };
+        for (i, collider) in colliders.iter().enumerate() {
+            self.cells.entry(cell_of(collider)).or_default().push(i);
+        }
+
+        let mut pairs = Vec::new();
+        for (i, a) in colliders.iter().enumerate() {
+            let (x, y) = cell_of(a);
+            for neighbour in [
+                (x - 1, y - 1),
+                (x, y - 1),
+                (x + 1, y - 1),
+                (x - 1, y),
+                (x, y),
+                (x + 1, y),
+                (x - 1, y + 1),
+                (x, y + 1),
+                (x + 1, y + 1),
+            ] {
+                let Some(cell) = self.cells.get(&neighbour) else {
+                    continue;
+                };
+                for &j in cell {
+                    if j > i && overlaps(a, &colliders[j]) {
+                        pairs.push(ordered_pair(colliders, i, j));
+                    }
+                }
+            }
+        }
+        sort_pairs(colliders, &mut pairs);
+        pairs
+    }
+}
+
+/// Compares every collider with every other. Only there to check the grid
+/// against, in the tests and the collision benchmark.
+#[allow(dead_code)]
+pub fn overlapping_pairs_naive(colliders: &[Collider]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in 0..colliders.len() {
+        for j in i + 1..colliders.len() {
+            if overlaps(&colliders[i], &colliders[j]) {
+                pairs.push(ordered_pair(colliders, i, j));
+            }
+        }
+    }
+    sort_pairs(colliders, &mut pairs);
+    pairs
+}
+
+fn ordered_pair(colliders: &[Collider], i: usize, j: usize) -> (usize, usize) {
+    if colliders[i].entity <= colliders[j].entity {
+        (i, j)
+    } else {
+        (j, i)
+    }
+}
+
+fn sort_pairs(colliders: &[Collider], pairs: &mut [(usize, usize)]) {
+    pairs.sort_unstable_by_key(|&(i, j)| (colliders[i].entity, colliders[j].entity));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_colliders(seed: u64, count: u32, extent: f32) -> Vec<Collider> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|index| Collider {
+                entity: Entity::from_raw(index),
+                position: Vec3::new(
+                    rng.gen_range(-extent..extent),
+                    rng.gen_range(-extent..extent),
+                    0.,
+                ),
+                size: [30., 64.][rng.gen_range(0..2)],
+            })
+            .collect()
+    }
+
+    fn entity_pairs(colliders: &[Collider], pairs: &[(usize, usize)]) -> Vec<(Entity, Entity)> {
+        pairs
+            .iter()
+            .map(|&(i, j)| (colliders[i].entity, colliders[j].entity))
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_same_pairs_as_comparing_everything() {
+        let mut spatial_hash = SpatialHash::default();
+        for seed in 0..20 {
+            let colliders = random_colliders(seed, 500, 800.);
+            let naive = overlapping_pairs_naive(&colliders);
+            assert!(!naive.is_empty());
+            assert_eq!(spatial_hash.overlapping_pairs(&colliders), naive);
+        }
+    }
+
+    #[test]
+    fn reports_each_pair_once_lower_entity_first() {
+        let colliders = random_colliders(7, 300, 300.);
+        let pairs = entity_pairs(
+            &colliders,
+            &SpatialHash::default().overlapping_pairs(&colliders),
+        );
+        assert!(pairs.iter().all(|(a, b)| a < b));
+        assert!(pairs.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn order_does_not_depend_on_the_input_order() {
+        let colliders = random_colliders(3, 300, 300.);
+        let mut reversed = colliders.clone();
+        reversed.reverse();
+        let mut spatial_hash = SpatialHash::default();
+        let forwards = spatial_hash.overlapping_pairs(&colliders);
+        let backwards = spatial_hash.overlapping_pairs(&reversed);
+        assert_eq!(
+            entity_pairs(&colliders, &forwards),
+            entity_pairs(&reversed, &backwards)
+        );
+    }
+
+    #[test]
+    fn handles_cell_boundaries_and_negative_positions() {
+        let collider = |index, x, y| Collider {
+            entity: Entity::from_raw(index),
+            position: Vec3::new(x, y, 0.),
+            size: 64.,
+        };
+        let colliders = [
+            collider(0, -1., -1.),
+            collider(1, 1., 1.),
+            collider(2, 63.5, 0.),
+            collider(3, 200., 200.),
+            collider(4, 200., 264.),
+        ];
+        assert_eq!(
+            SpatialHash::default().overlapping_pairs(&colliders),
+            vec![(0, 1), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn nothing_overlaps_without_size() {
+        let colliders = [Collider {
+            entity: Entity::from_raw(0),
+            position: Vec3::ZERO,
+            size: 0.,
+        }; 2];
+        assert!(SpatialHash::default()
+            .overlapping_pairs(&colliders)
+            .is_empty());
+    }
+}
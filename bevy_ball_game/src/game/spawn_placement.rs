@@ -0,0 +1,265 @@
+use bevy::ecs::query::Has;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::Rng;
+
+use super::arena::Arena;
+use super::collide::Collides;
+use super::player::Player;
+use super::tick_schedule::TickSet;
+
+pub struct SpawnPlacementPlugin;
+
+impl Plugin for SpawnPlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, telegraph.in_set(TickSet::Timers));
+    }
+}
+
+/// How far from the player's edge anything new has to appear.
+pub const PLAYER_SAFE_DISTANCE: f32 = 200.;
+/// The gap kept between something new and the colliders already there.
+pub const SPAWN_MARGIN: f32 = 8.;
+/// Random positions tried before looking over the whole area for room.
+pub const SPAWN_ATTEMPTS: usize = 32;
+pub const TELEGRAPH_SECONDS: f32 = 0.8;
+
+/// A circle nothing may be spawned overlapping.
+#[derive(Debug, Clone, Copy)]
+pub struct Exclusion {
+    pub position: Vec3,
+    pub radius: f32,
+}
+
+pub fn is_clear(position: Vec3, object_size: f32, exclusions: &[Exclusion]) -> bool {
+    exclusions.iter().all(|exclusion| {
+        position.distance(exclusion.position) >= exclusion.radius + object_size / 2.
+    })
+}
+
+/// Finds a place for something `object_size` across inside an `area` with
+/// its corner at the origin, clear of every exclusion. Tries random
+/// positions first, then picks at random between the clear points of a grid
+/// over the area. `None` if there's no room anywhere.
+pub fn find_spawn_position(
+    rng: &mut impl Rng,
+    area: Vec2,
+    object_size: f32,
+    exclusions: &[Exclusion],
+) -> Option<Vec3> {
+    let half_size = object_size / 2.;
+    let min = Vec2::splat(half_size);
+    let max = area - half_size;
+    if max.x < min.x || max.y < min.y {
+        return None;
+    }
+    let is_clear = |position: Vec3| is_clear(position, object_size, exclusions);
+
+    for _ in 0..SPAWN_ATTEMPTS {
+        let position = Vec3::new(
+            rng.gen_range(min.x..=max.x),
+            rng.gen_range(min.y..=max.y),
+            0.,
+        );
+        if is_clear(position) {
+            return Some(position);
+        }
+    }
+
+    let step = object_size.max(1.);
+    let columns = ((max.x - min.x) / step) as usize + 1;
+    let rows = ((max.y - min.y) / step) as usize + 1;
+    let clear: Vec<Vec3> = (0..columns * rows)
+        .map(|i| {
+            Vec3::new(
+                min.x + (i % columns) as f32 * step,
+                min.y + (i / columns) as f32 * step,
+                0.,
+            )
+        })
+        .filter(|&position| is_clear(position))
+        .collect();
+    if clear.is_empty() {
+        return None;
+    }
+    Some(clear[rng.gen_range(0..clear.len())])
+}
+
+/// Finds places to spawn things in the arena away from the player and
+/// from whatever else collides.
+#[derive(SystemParam)]
+pub struct SpawnPlacement<'w, 's> {
+    arena: Res<'w, Arena>,
+    collider_query: Query<'w, 's, (Entity, &'static Transform, &'static Collides, Has<Player>)>,
+    telegraph_query: Query<'w, 's, (&'static Transform, &'static Telegraph)>,
+}
+
+impl SpawnPlacement<'_, '_> {
+    pub fn exclusions(&self) -> Vec<Exclusion> {
+        self.exclusions_except(&[])
+    }
+
+    /// The exclusions of everything but `except`, such as entities on
+    /// their way out.
+    pub fn exclusions_except(&self, except: &[Entity]) -> Vec<Exclusion> {
+        let colliders = self
+            .collider_query
+            .iter()
+            .filter(|(entity, ..)| !except.contains(entity))
+            .map(|(_, transform, collides, is_player)| Exclusion {
+                position: transform.translation,
+                radius: collides.object_size / 2.
+                    + if is_player {
+                        PLAYER_SAFE_DISTANCE
+                    } else {
+                        SPAWN_MARGIN
+                    },
+            });
+        let telegraphs = self
+            .telegraph_query
+            .iter()
+            .map(|(transform, telegraph)| Exclusion {
+                position: transform.translation,
+                radius: telegraph.object_size / 2. + SPAWN_MARGIN,
+            });
+        colliders.chain(telegraphs).collect()
+    }
+
+    pub fn find(&self, rng: &mut impl Rng, object_size: f32) -> Option<Vec3> {
+        self.find_clear_of(rng, object_size, &self.exclusions())
+    }
+
+    /// Anywhere in the arena clear of `exclusions`, which can take in
+    /// things that are about to be spawned as well as what's there now.
+    pub fn find_clear_of(
+        &self,
+        rng: &mut impl Rng,
+        object_size: f32,
+        exclusions: &[Exclusion],
+    ) -> Option<Vec3> {
+        find_spawn_position(rng, self.arena.size(), object_size, exclusions)
+    }
+
+    /// `wanted`, moved inside the arena, if that's clear of `exclusions`,
+    /// and anywhere else that is otherwise.
+    pub fn find_near(
+        &self,
+        rng: &mut impl Rng,
+        wanted: Vec3,
+        object_size: f32,
+        exclusions: &[Exclusion],
+    ) -> Option<Vec3> {
+        let half_size = Vec3::splat(object_size / 2.).truncate();
+        let max = (self.arena.size() - half_size).max(half_size);
+        let position = wanted.truncate().clamp(half_size, max).extend(wanted.z);
+        if is_clear(position, object_size, exclusions) {
+            return Some(position);
+        }
+        find_spawn_position(rng, self.arena.size(), object_size, exclusions)
+    }
+}
+
+/// Shown in place of an enemy that's about to appear. It grows and blinks,
+/// and only collides once the timer's up.
+#[derive(Component)]
+pub struct Telegraph {
+    pub object_size: f32,
+    pub timer: Timer,
+}
+
+impl Telegraph {
+    pub fn new(object_size: f32) -> Self {
+        Telegraph {
+            object_size,
+            timer: Timer::from_seconds(TELEGRAPH_SECONDS, TimerMode::Once),
+        }
+    }
+}
+
+pub fn telegraph(
+    mut commands: Commands,
+    mut telegraph_query: Query<(Entity, &mut Telegraph, &mut Transform, &mut Sprite)>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (entity, mut telegraph, mut transform, mut sprite) in telegraph_query.iter_mut() {
+        if telegraph.timer.tick(fixed_time.period).finished() {
+            transform.scale = Vec3::ONE;
+            sprite.color.set_a(1.);
+            commands
+                .entity(entity)
+                .remove::<Telegraph>()
+                .insert(Collides {
+                    object_size: telegraph.object_size,
+                });
+            continue;
+        }
+        let progress = telegraph.timer.percent();
+        transform.scale = Vec3::splat(0.3 + 0.7 * progress);
+        let blink = (telegraph.timer.elapsed_secs() * 10.).fract() < 0.5;
+        sprite.color.set_a(if blink { 0.8 } else { 0.3 });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const AREA: Vec2 = Vec2::new(800., 600.);
+
+    fn exclusion(x: f32, y: f32, radius: f32) -> Exclusion {
+        Exclusion {
+            position: Vec3::new(x, y, 0.),
+            radius,
+        }
+    }
+
+    #[test]
+    fn never_spawns_inside_an_exclusion() {
+        let exclusions = [
+            exclusion(400., 300., 264.),
+            exclusion(100., 100., 40.),
+            exclusion(700., 500., 40.),
+        ];
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let position = find_spawn_position(&mut rng, AREA, 64., &exclusions).unwrap();
+            for exclusion in exclusions {
+                assert!(position.distance(exclusion.position) >= exclusion.radius + 32.);
+            }
+            assert!((32.0..=768.).contains(&position.x));
+            assert!((32.0..=568.).contains(&position.y));
+        }
+    }
+
+    #[test]
+    fn finds_the_only_gap_left() {
+        // Everything but the top right corner is taken, which random
+        // positions are unlikely to hit.
+        let exclusions = [exclusion(0., 0., 900.)];
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let position = find_spawn_position(&mut rng, AREA, 30., &exclusions).unwrap();
+            assert!(position.length() >= 915.);
+        }
+    }
+
+    #[test]
+    fn gives_up_when_there_is_no_room() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let exclusions = [exclusion(400., 300., 1000.)];
+        assert!(find_spawn_position(&mut rng, AREA, 64., &exclusions).is_none());
+        assert!(find_spawn_position(&mut rng, Vec2::splat(50.), 64., &[]).is_none());
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_position() {
+        let exclusions = [exclusion(400., 300., 264.)];
+        let position = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            find_spawn_position(&mut rng, AREA, 64., &exclusions)
+        };
+        assert_eq!(position(42), position(42));
+        assert_ne!(position(42), position(43));
+    }
+}
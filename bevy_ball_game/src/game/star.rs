@@ -1,11 +1,13 @@
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
-use rand::random;
 
 use crate::app_state::AppState;
 
 use super::collide::Collides;
-use super::tick_schedule::Simulation;
+use super::player::spawn_player;
+use super::rng::GameRng;
+use super::rng::RngStream;
+use super::spawn_placement::{Exclusion, SpawnPlacement, SPAWN_MARGIN};
+use super::tick_schedule::TickSet;
 use super::DespawnOnEnd;
 
 pub struct StarPlugin;
@@ -13,10 +15,17 @@ pub struct StarPlugin;
 impl Plugin for StarPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<StarSpawnTimer>()
-            .add_systems(OnEnter(AppState::Game), spawn_stars)
+            // Once the player's there to keep them away from.
             .add_systems(
-                Update,
-                (star_spawn_timer_tick, spawn_stars_over_time).in_set(Simulation),
+                OnEnter(AppState::Game),
+                (apply_deferred, spawn_stars).chain().after(spawn_player),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    star_spawn_timer_tick.in_set(TickSet::Timers),
+                    spawn_stars_over_time.in_set(TickSet::Spawning),
+                ),
             );
     }
 }
@@ -42,38 +51,57 @@ pub struct Star {}
 
 pub fn spawn_stars(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    spawn_placement: SpawnPlacement,
     asset_server: Res<AssetServer>,
+    mut star_spawn_timer: ResMut<StarSpawnTimer>,
+    mut rng: ResMut<GameRng>,
 ) {
-    let window = window_query.get_single().unwrap();
+    star_spawn_timer.timer.reset();
+    // The stars aren't there to be kept clear of until the commands are
+    // applied, so each one is kept clear of those placed before it here.
+    let mut exclusions = spawn_placement.exclusions();
     for _ in 0..NUM_STARS {
-        spawn_star(&mut commands, window, &asset_server)
+        let Some(position) =
+            spawn_placement.find_clear_of(rng.stream(RngStream::Stars), STAR_SIZE, &exclusions)
+        else {
+            continue;
+        };
+        exclusions.push(Exclusion {
+            position,
+            radius: STAR_SIZE / 2. + SPAWN_MARGIN,
+        });
+        spawn_star(&mut commands, &asset_server, position);
     }
 }
 
-pub fn star_spawn_timer_tick(mut star_spawn_time: ResMut<StarSpawnTimer>, time: Res<Time>) {
-    star_spawn_time.timer.tick(time.delta());
+pub fn star_spawn_timer_tick(
+    mut star_spawn_time: ResMut<StarSpawnTimer>,
+    fixed_time: Res<FixedTime>,
+) {
+    star_spawn_time.timer.tick(fixed_time.period);
 }
 
 pub fn spawn_stars_over_time(
     mut commands: Commands,
     star_spawn_timer: Res<StarSpawnTimer>,
     asset_server: Res<AssetServer>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    spawn_placement: SpawnPlacement,
+    mut rng: ResMut<GameRng>,
 ) {
-    let window = window_query.get_single().unwrap();
     if star_spawn_timer.timer.finished() {
-        spawn_star(&mut commands, window, &asset_server);
+        // Stars stay out from under enemies, where they couldn't be
+        // collected.
+        if let Some(position) = spawn_placement.find(rng.stream(RngStream::Stars), STAR_SIZE) {
+            spawn_star(&mut commands, &asset_server, position);
+        }
     }
 }
 
-fn spawn_star(commands: &mut Commands, window: &Window, asset_server: &Res<AssetServer>) {
+fn spawn_star(commands: &mut Commands, asset_server: &Res<AssetServer>, position: Vec3) {
     let sprite = asset_server.load("sprites/star.png");
-    let x_pos = random::<f32>() * window.width();
-    let y_pos = random::<f32>() * window.height();
     commands.spawn((
         SpriteBundle {
-            transform: Transform::from_xyz(x_pos, y_pos, 0.0),
+            transform: Transform::from_translation(position),
             texture: sprite,
             ..default()
         },
@@ -0,0 +1,437 @@
+//! Runs the game without a window for a number of fixed ticks and checks
+//! what happened.
+
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::app_state::{AppState, AppStatePlugin};
+
+use super::arena::Arena;
+use super::collide::Collides;
+use super::enemy::{BounceSounds, Enemy};
+use super::enemy_kinds::{Behaviour, EnemyKind, EnemyKinds, Splitter};
+use super::game_logic::Score;
+use super::high_scores::HighScoresFile;
+use super::player::{Player, PLAYER_SIZE, PLAYER_SPEED};
+use super::power_ups::{ActiveEffects, PowerUpKind, SHIELD_DURATION};
+use super::replay::{Playback, Replay, ReplayFile};
+use super::spawn_placement::Telegraph;
+use super::star::{Star, STAR_SIZE};
+use super::tick_schedule::{TICK, TICKS_PER_SECOND};
+use super::waves::{Wave, WavePhase, WaveProgress, Waves};
+use super::{DespawnOnEnd, GamePlugin};
+
+const ARENA: Arena = Arena {
+    width: 800.,
+    height: 600.,
+};
+
+fn wave(duration: f32, max_enemies: usize, bonus: u32) -> Wave {
+    Wave {
+        name: "Test".to_string(),
+        duration,
+        spawn_interval: 0.2,
+        // Slow enough that they don't get to the player by themselves.
+        speed_multiplier: 0.01,
+        max_enemies,
+        breather: 1.,
+        bonus,
+    }
+}
+
+fn ball() -> EnemyKind {
+    EnemyKind {
+        name: "Ball".to_string(),
+        behaviour: Behaviour::Bouncer,
+        sprite: "sprites/ball_red_large.png".to_string(),
+        tint: (1., 1., 1.),
+        size: 64.,
+        speed: 1.,
+        bounce_sounds: Vec::new(),
+        bounce_pitch: 1.,
+        weight: 1,
+        first_wave: 1,
+    }
+}
+
+/// The game plugins under `MinimalPlugins`, with `waves` in place of the
+/// wave file, ticking once a frame. The player's own replay and high scores
+/// are left alone.
+fn game_app(waves: Vec<Wave>) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        // Nothing's there to load, so the waves and enemy kinds set here
+        // stay as they are.
+        AssetPlugin {
+            asset_folder: "no_assets".to_string(),
+            ..default()
+        },
+        InputPlugin,
+    ))
+    .add_plugins((GamePlugin, AppStatePlugin))
+    .insert_resource(ARENA)
+    .insert_resource(ReplayFile(None))
+    .insert_resource(HighScoresFile(None))
+    .insert_resource(Waves { waves })
+    .insert_resource(EnemyKinds {
+        kinds: vec![ball()],
+    })
+    .insert_resource(TimeUpdateStrategy::ManualDuration(TICK));
+    app
+}
+
+/// A game in the `Game` state with the stars it starts with cleared away
+/// and nothing left to go off on its own for a while.
+fn start_game(waves: Vec<Wave>) -> App {
+    let mut app = game_app(waves);
+    app.insert_resource(NextState(Some(AppState::Game)));
+    app.update();
+    assert_eq!(
+        app.world.resource::<State<AppState>>().get(),
+        &AppState::Game
+    );
+
+    let stars: Vec<Entity> = app
+        .world
+        .query_filtered::<Entity, With<Star>>()
+        .iter(&app.world)
+        .collect();
+    for star in stars {
+        app.world.despawn(star);
+    }
+    app
+}
+
+fn tick(app: &mut App, ticks: u32) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
+
+fn state(app: &App) -> AppState {
+    *app.world.resource::<State<AppState>>().get()
+}
+
+fn player_position(app: &mut App) -> Option<Vec3> {
+    app.world
+        .query_filtered::<&Transform, With<Player>>()
+        .get_single(&app.world)
+        .ok()
+        .map(|transform| transform.translation)
+}
+
+fn count<T: Component>(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<T>>()
+        .iter(&app.world)
+        .count()
+}
+
+/// The score and where everything that collides is, in order.
+fn snapshot(app: &mut App) -> (u32, Vec<(f32, f32)>) {
+    let mut positions: Vec<(f32, f32)> = app
+        .world
+        .query_filtered::<&Transform, With<Collides>>()
+        .iter(&app.world)
+        .map(|transform| (transform.translation.x, transform.translation.y))
+        .collect();
+    positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    (app.world.resource::<Score>().value, positions)
+}
+
+fn spawn_enemy_at(app: &mut App, position: Vec3, direction: Vec3, speed: f32) -> Entity {
+    app.world
+        .spawn((
+            Transform::from_translation(position),
+            Enemy { direction, speed },
+            Collides {
+                object_size: ball().size,
+            },
+            DespawnOnEnd,
+        ))
+        .id()
+}
+
+fn spawn_star_at(app: &mut App, position: Vec3) -> Entity {
+    app.world
+        .spawn((
+            Transform::from_translation(position),
+            Star {},
+            Collides {
+                object_size: STAR_SIZE,
+            },
+            DespawnOnEnd,
+        ))
+        .id()
+}
+
+#[test]
+fn the_player_starts_in_the_middle_of_the_arena() {
+    let mut app = start_game(vec![wave(60., 0, 0)]);
+    assert_eq!(player_position(&mut app), Some(ARENA.center()));
+    assert_eq!(app.world.resource::<Score>().value, 0);
+}
+
+#[test]
+fn an_enemy_that_reaches_the_player_ends_the_game() {
+    let mut app = start_game(vec![wave(60., 0, 0)]);
+    let player = player_position(&mut app).unwrap();
+    spawn_enemy_at(&mut app, player + Vec3::X * 200., Vec3::NEG_X, 400.);
+
+    tick(&mut app, TICKS_PER_SECOND / 4);
+    assert_eq!(state(&app), AppState::Game);
+    tick(&mut app, TICKS_PER_SECOND / 2);
+    assert_eq!(state(&app), AppState::GameOver);
+    assert_eq!(count::<Player>(&mut app), 0);
+}
+
+#[test]
+fn the_shield_takes_one_hit() {
+    let mut app = start_game(vec![wave(60., 0, 0)]);
+    app.world
+        .resource_mut::<ActiveEffects>()
+        .start(PowerUpKind::Shield, SHIELD_DURATION);
+    let player = player_position(&mut app).unwrap();
+    let first = spawn_enemy_at(&mut app, player + Vec3::X * 100., Vec3::NEG_X, 400.);
+
+    tick(&mut app, TICKS_PER_SECOND / 2);
+    assert_eq!(state(&app), AppState::Game);
+    assert!(app.world.get_entity(first).is_none());
+    assert!(!app
+        .world
+        .resource::<ActiveEffects>()
+        .is_active(PowerUpKind::Shield));
+
+    spawn_enemy_at(&mut app, player + Vec3::Y * 100., Vec3::NEG_Y, 400.);
+    tick(&mut app, TICKS_PER_SECOND / 2);
+    assert_eq!(state(&app), AppState::GameOver);
+}
+
+#[test]
+fn moving_over_stars_collects_them() {
+    let mut app = start_game(vec![wave(60., 0, 0)]);
+    let player = player_position(&mut app).unwrap();
+    let near = spawn_star_at(&mut app, player + Vec3::X * 150.);
+    let far = spawn_star_at(&mut app, player + Vec3::X * 300.);
+    let elsewhere = spawn_star_at(&mut app, player + Vec3::Y * 200.);
+
+    app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+    // Long enough to reach the near star but not the far one.
+    let reach = 150. - (PLAYER_SIZE + STAR_SIZE) / 2.;
+    tick(
+        &mut app,
+        (reach / PLAYER_SPEED * TICKS_PER_SECOND as f32) as u32 + 2,
+    );
+    assert!(app.world.get_entity(near).is_none());
+    assert!(app.world.get_entity(far).is_some());
+    assert_eq!(app.world.resource::<Score>().value, 1);
+
+    tick(&mut app, TICKS_PER_SECOND / 2);
+    assert!(app.world.get_entity(far).is_none());
+    assert!(app.world.get_entity(elsewhere).is_some());
+    assert_eq!(app.world.resource::<Score>().value, 2);
+    assert_eq!(state(&app), AppState::Game);
+}
+
+#[test]
+fn clearing_a_wave_removes_its_enemies_and_scores_its_bonus() {
+    let mut app = start_game(vec![wave(1., 3, 5), wave(60., 0, 0)]);
+    tick(&mut app, TICKS_PER_SECOND / 2);
+    let enemies = count::<Enemy>(&mut app);
+    assert!((1..=3).contains(&enemies), "{} enemies", enemies);
+
+    tick(&mut app, TICKS_PER_SECOND);
+    assert_eq!(count::<Enemy>(&mut app), 0);
+    assert_eq!(app.world.resource::<Score>().value, 5);
+    assert_eq!(state(&app), AppState::Game);
+}
+
+#[test]
+fn everything_stays_in_the_arena() {
+    let mut app = start_game(vec![Wave {
+        speed_multiplier: 1.,
+        ..wave(60., 8, 0)
+    }]);
+    for _ in 0..5 {
+        tick(&mut app, TICKS_PER_SECOND);
+        if state(&app) != AppState::Game {
+            break;
+        }
+        let mut colliders = app.world.query::<(&Transform, &Collides)>();
+        for (transform, collides) in colliders.iter(&app.world) {
+            let half_size = collides.object_size / 2.;
+            let position = transform.translation;
+            assert!(position.x >= half_size && position.x <= ARENA.width - half_size);
+            assert!(position.y >= half_size && position.y <= ARENA.height - half_size);
+        }
+    }
+}
+
+#[test]
+fn a_splitter_breaks_into_two_pieces_apart_from_each_other() {
+    let mut app = start_game(vec![wave(60., 0, 0)]);
+    let piece = EnemyKind {
+        size: 40.,
+        ..ball()
+    };
+    let splitter = spawn_enemy_at(&mut app, Vec3::new(700., 300., 0.), Vec3::X, 400.);
+    app.world.entity_mut(splitter).insert((
+        Splitter {
+            pieces: 2,
+            piece: piece.clone(),
+        },
+        BounceSounds {
+            sources: Vec::new(),
+            pitch: 1.,
+        },
+    ));
+
+    tick(&mut app, TICKS_PER_SECOND / 4);
+    assert!(app.world.get_entity(splitter).is_none());
+    let pieces: Vec<Vec3> = app
+        .world
+        .query_filtered::<&Transform, (With<Enemy>, With<Telegraph>)>()
+        .iter(&app.world)
+        .map(|transform| transform.translation)
+        .collect();
+    assert_eq!(pieces.len(), 2);
+    assert!(pieces[0].distance(pieces[1]) >= piece.size);
+    // Away from the wall it bounced off, rather than up against it.
+    for position in pieces {
+        assert!(position.x + piece.size / 2. < ARENA.width);
+    }
+    assert_eq!(count::<Enemy>(&mut app), 2);
+    assert_eq!(state(&app), AppState::Game);
+}
+
+#[test]
+fn the_first_stars_keep_clear_of_each_other() {
+    let mut app = game_app(vec![wave(60., 0, 0)]);
+    // Only a little room is left beside the player at either end, so
+    // stars placed without regard for each other would end up on top of
+    // each other.
+    app.insert_resource(Arena {
+        width: 600.,
+        height: 80.,
+    })
+    .insert_resource(NextState(Some(AppState::Game)));
+    app.update();
+    let stars: Vec<Vec3> = app
+        .world
+        .query_filtered::<&Transform, With<Star>>()
+        .iter(&app.world)
+        .map(|transform| transform.translation)
+        .collect();
+    assert!(stars.len() >= 2);
+    for (i, a) in stars.iter().enumerate() {
+        for b in &stars[i + 1..] {
+            assert!(a.distance(*b) >= STAR_SIZE);
+        }
+    }
+}
+
+#[test]
+fn a_new_game_starts_from_nothing() {
+    let mut app = start_game(vec![wave(0.5, 0, 5), wave(60., 0, 0)]);
+    tick(&mut app, TICKS_PER_SECOND);
+    assert_eq!(app.world.resource::<Score>().value, 5);
+    let player = player_position(&mut app).unwrap();
+    spawn_enemy_at(&mut app, player + Vec3::X * 100., Vec3::NEG_X, 400.);
+    tick(&mut app, TICKS_PER_SECOND / 2);
+    assert_eq!(state(&app), AppState::GameOver);
+
+    app.world.insert_resource(NextState(Some(AppState::Game)));
+    tick(&mut app, 1);
+    assert_eq!(state(&app), AppState::Game);
+    assert_eq!(app.world.resource::<Score>().value, 0);
+    assert_eq!(count::<Enemy>(&mut app), 0);
+    assert_eq!(player_position(&mut app), Some(ARENA.center()));
+}
+
+#[test]
+fn waves_that_fail_to_load_fall_back_to_a_built_in_wave() {
+    let mut app = game_app(Vec::new());
+    app.insert_resource(NextState(Some(AppState::Game)));
+    for _ in 0..1000 {
+        app.update();
+        if app.world.resource::<WaveProgress>().phase != WavePhase::Waiting {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let waves = &app.world.resource::<Waves>().waves;
+    assert_eq!(waves.len(), 1);
+    assert_eq!(waves[0].name, Wave::built_in().name);
+    assert_eq!(
+        app.world.resource::<WaveProgress>().phase,
+        WavePhase::Attack
+    );
+}
+
+#[test]
+fn enemy_kinds_that_fail_to_load_fall_back_to_a_built_in_kind() {
+    let mut app = game_app(vec![wave(60., 1, 0)]);
+    app.insert_resource(EnemyKinds::default())
+        .insert_resource(NextState(Some(AppState::Game)));
+    for _ in 0..1000 {
+        app.update();
+        if count::<Enemy>(&mut app) > 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let kinds = &app.world.resource::<EnemyKinds>().kinds;
+    assert_eq!(kinds.len(), 1);
+    assert_eq!(kinds[0].name, EnemyKind::built_in().name);
+    assert_eq!(count::<Enemy>(&mut app), 1);
+}
+
+#[test]
+fn a_recorded_game_plays_back_the_same() {
+    let waves = || {
+        vec![Wave {
+            speed_multiplier: 0.3,
+            ..wave(60., 3, 0)
+        }]
+    };
+    let path = std::env::temp_dir()
+        .join(format!(
+            "bevy_ball_game_playback_test_{}",
+            std::process::id()
+        ))
+        .join("test.replay.ron");
+
+    let mut recorded = game_app(waves());
+    recorded.insert_resource(ReplayFile(Some(path.clone())));
+    recorded.insert_resource(NextState(Some(AppState::Game)));
+    let mut last = None;
+    for frame in 0..TICKS_PER_SECOND * 3 {
+        let key = [KeyCode::D, KeyCode::W, KeyCode::Left][(frame / 40 % 3) as usize];
+        let mut keyboard_input = recorded.world.resource_mut::<Input<KeyCode>>();
+        keyboard_input.release_all();
+        keyboard_input.press(key);
+        recorded.update();
+        if state(&recorded) != AppState::Game {
+            break;
+        }
+        last = Some(snapshot(&mut recorded));
+    }
+    recorded.insert_resource(NextState(Some(AppState::MainMenu)));
+    recorded.update();
+    let replay = Replay::load(&path).unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    let mut played = game_app(waves());
+    played.insert_resource(Playback::new(replay.clone()));
+    for _ in 0..replay.ticks.len() + 10 {
+        played.update();
+        if played.world.resource::<Playback>().is_over() {
+            break;
+        }
+    }
+    assert!(played.world.resource::<Playback>().is_over());
+    assert_eq!(Some(snapshot(&mut played)), last);
+    assert_eq!(played.world.resource::<Score>().value, replay.score);
+}
@@ -1,9 +1,30 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 
 use crate::app_state::AppState;
 
+use super::player::Player;
 use super::SimulationState;
 
+/// How often the simulation ticks. It runs in `FixedUpdate`, however long
+/// frames take, so a game plays out the same from the same input.
+pub const TICKS_PER_SECOND: u32 = 60;
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND as u64);
+
+/// The steps of a tick, which run one after the other around the
+/// `MovementSet`s. Systems that need to see each other's changes go in
+/// different steps.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum TickSet {
+    /// Takes in the input for the tick.
+    Input,
+    Timers,
+    Spawning,
+    /// Reacts to what ran into what.
+    Outcome,
+}
+
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum MovementSet {
     Direction,
@@ -20,22 +41,30 @@ pub struct TickSchedulePlugin;
 
 impl Plugin for TickSchedulePlugin {
     fn build(&self, app: &mut App) {
-        app.configure_set(
-            Update,
-            Simulation
-                .run_if(in_state(SimulationState::Running).and_then(in_state(AppState::Game))),
-        )
-        .configure_set(
-            Update,
-            MovementSet::Direction
-                .before(MovementSet::Movement)
-                .in_set(Simulation),
-        )
-        .configure_set(
-            Update,
-            MovementSet::Movement
-                .before(MovementSet::CollisionChecking)
-                .in_set(Simulation),
-        );
+        app.insert_resource(FixedTime::new(TICK))
+            .configure_set(
+                FixedUpdate,
+                // Nothing more happens once the player's gone, even if the
+                // game doesn't end until the next frame.
+                Simulation.run_if(
+                    in_state(SimulationState::Running)
+                        .and_then(in_state(AppState::Game))
+                        .and_then(any_with_component::<Player>()),
+                ),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (
+                    TickSet::Input,
+                    TickSet::Timers,
+                    TickSet::Spawning,
+                    MovementSet::Direction,
+                    MovementSet::Movement,
+                    MovementSet::CollisionChecking,
+                    TickSet::Outcome,
+                )
+                    .chain()
+                    .in_set(Simulation),
+            );
     }
 }
@@ -0,0 +1,315 @@
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use serde::Deserialize;
+
+use crate::app_state::AppState;
+
+use super::enemy::{Enemy, EnemySpawnTimer};
+use super::game_logic::Score;
+use super::ron_asset::{RonAsset, RonAssetLoader};
+use super::spawn_placement::telegraph;
+use super::tick_schedule::TickSet;
+
+pub const WAVES_PATH: &str = "waves/default.waves.ron";
+
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WaveList>()
+            .init_asset_loader::<RonAssetLoader<WaveList>>()
+            .init_resource::<Waves>()
+            .init_resource::<WaveProgress>()
+            .add_systems(Startup, load_waves)
+            .add_systems(Update, update_waves)
+            .add_systems(OnEnter(AppState::Game), reset_waves)
+            .add_systems(
+                FixedUpdate,
+                // So enemies that finish showing up are there to clear away.
+                advance_waves.after(telegraph).in_set(TickSet::Timers),
+            );
+    }
+}
+
+/// One wave of enemies. Surviving it for `duration` seconds clears it: its
+/// enemies are removed, the player gets `bonus` points and a `breather`
+/// before the next wave.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Wave {
+    pub name: String,
+    pub duration: f32,
+    /// Seconds between enemy spawns.
+    pub spawn_interval: f32,
+    pub speed_multiplier: f32,
+    pub max_enemies: usize,
+    pub breather: f32,
+    pub bonus: u32,
+}
+
+impl Wave {
+    /// What's played if the wave file can't be loaded, so there's still
+    /// something to survive.
+    pub fn built_in() -> Wave {
+        Wave {
+            name: "Endless".to_string(),
+            duration: 30.,
+            spawn_interval: 1.5,
+            speed_multiplier: 0.8,
+            max_enemies: 6,
+            breather: 4.,
+            bonus: 10,
+        }
+    }
+
+    /// What's wrong with the wave's timings, if anything. Its timers
+    /// can't be made from negative seconds, and a spawn interval of none
+    /// at all would spawn on every tick.
+    pub fn timing_problem(&self) -> Option<&'static str> {
+        let seconds = |s: f32| s.is_finite() && s >= 0.;
+        if !seconds(self.duration) {
+            Some("duration")
+        } else if !seconds(self.spawn_interval) || self.spawn_interval == 0. {
+            Some("spawn_interval")
+        } else if !seconds(self.breather) {
+            Some("breather")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "8f4c6a3e-2b7d-4e51-9c0a-5d3f1e6b7a24"]
+pub struct WaveList {
+    pub waves: Vec<Wave>,
+}
+
+impl RonAsset for WaveList {
+    const EXTENSIONS: &'static [&'static str] = &["waves.ron"];
+}
+
+#[derive(Resource)]
+pub struct WaveListHandle(Handle<WaveList>);
+
+/// The waves of the current wave list. The last one repeats once the
+/// others are through.
+#[derive(Resource, Debug, Default)]
+pub struct Waves {
+    pub waves: Vec<Wave>,
+}
+
+impl Waves {
+    pub fn get(&self, index: usize) -> Option<&Wave> {
+        self.waves.get(index.min(self.waves.len().checked_sub(1)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavePhase {
+    /// The waves haven't loaded yet.
+    Waiting,
+    Attack,
+    Breather,
+}
+
+#[derive(Resource, Debug)]
+pub struct WaveProgress {
+    pub index: usize,
+    pub phase: WavePhase,
+    pub timer: Timer,
+}
+
+impl Default for WaveProgress {
+    fn default() -> Self {
+        WaveProgress {
+            index: 0,
+            phase: WavePhase::Waiting,
+            timer: Timer::default(),
+        }
+    }
+}
+
+impl WaveProgress {
+    /// The wave enemies should be spawning for, if it isn't a breather.
+    pub fn attacking<'a>(&self, waves: &'a Waves) -> Option<&'a Wave> {
+        match self.phase {
+            WavePhase::Attack => waves.get(self.index),
+            _ => None,
+        }
+    }
+}
+
+pub fn load_waves(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(WaveListHandle(asset_server.load(WAVES_PATH)));
+}
+
+// Also picks up changes to the file while the game runs, if the asset
+// server is watching for them.
+pub fn update_waves(
+    mut ev_asset: EventReader<AssetEvent<WaveList>>,
+    asset_server: Res<AssetServer>,
+    wave_lists: Res<Assets<WaveList>>,
+    wave_list_handle: Res<WaveListHandle>,
+    mut waves: ResMut<Waves>,
+) {
+    // Otherwise the first wave would be waited for forever.
+    if waves.waves.is_empty()
+        && asset_server.get_load_state(&wave_list_handle.0) == LoadState::Failed
+    {
+        warn!("Could not load {}, playing the built-in wave", WAVES_PATH);
+        waves.waves = vec![Wave::built_in()];
+    }
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = ev {
+            if handle != &wave_list_handle.0 {
+                continue;
+            }
+            if let Some(wave_list) = wave_lists.get(handle) {
+                waves.waves = checked_waves(&wave_list.waves);
+            }
+        }
+    }
+}
+
+/// The waves from the file that can be played, or the built-in wave if
+/// none of them can.
+fn checked_waves(waves: &[Wave]) -> Vec<Wave> {
+    let checked: Vec<Wave> = waves
+        .iter()
+        .filter(|wave| match wave.timing_problem() {
+            Some(field) => {
+                warn!(
+                    "Skipping wave {} in {}: bad {}",
+                    wave.name, WAVES_PATH, field
+                );
+                false
+            }
+            None => true,
+        })
+        .cloned()
+        .collect();
+    if checked.is_empty() {
+        warn!(
+            "No waves to play in {}, playing the built-in wave",
+            WAVES_PATH
+        );
+        return vec![Wave::built_in()];
+    }
+    checked
+}
+
+pub fn reset_waves(mut wave_progress: ResMut<WaveProgress>) {
+    *wave_progress = WaveProgress::default();
+}
+
+pub fn advance_waves(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    waves: Res<Waves>,
+    mut wave_progress: ResMut<WaveProgress>,
+    mut enemy_spawn_timer: ResMut<EnemySpawnTimer>,
+    mut score: ResMut<Score>,
+    enemy_query: Query<Entity, With<Enemy>>,
+) {
+    let index = wave_progress.index;
+    let Some(wave) = waves.get(index) else {
+        return;
+    };
+    match wave_progress.phase {
+        WavePhase::Waiting => start_wave(&mut wave_progress, &mut enemy_spawn_timer, wave, 0),
+        WavePhase::Attack => {
+            if wave_progress.timer.tick(fixed_time.period).finished() {
+                for enemy in enemy_query.iter() {
+                    commands.entity(enemy).despawn();
+                }
+                score.value += wave.bonus;
+                wave_progress.phase = WavePhase::Breather;
+                wave_progress.timer = Timer::from_seconds(wave.breather, TimerMode::Once);
+                info!("Cleared wave {}: {}", index + 1, wave.name);
+            }
+        }
+        WavePhase::Breather => {
+            if wave_progress.timer.tick(fixed_time.period).finished() {
+                if let Some(next) = waves.get(index + 1) {
+                    start_wave(&mut wave_progress, &mut enemy_spawn_timer, next, index + 1);
+                }
+            }
+        }
+    }
+}
+
+fn start_wave(
+    wave_progress: &mut WaveProgress,
+    enemy_spawn_timer: &mut EnemySpawnTimer,
+    wave: &Wave,
+    index: usize,
+) {
+    wave_progress.index = index;
+    wave_progress.phase = WavePhase::Attack;
+    wave_progress.timer = Timer::from_seconds(wave.duration, TimerMode::Once);
+    enemy_spawn_timer.timer = Timer::from_seconds(wave.spawn_interval, TimerMode::Repeating);
+    info!("Wave {}: {}", index + 1, wave.name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_waves() -> Waves {
+        let wave_list: WaveList =
+            ron::de::from_str(include_str!("../../assets/waves/default.waves.ron")).unwrap();
+        Waves {
+            waves: wave_list.waves,
+        }
+    }
+
+    #[test]
+    fn the_default_waves_get_harder() {
+        let waves = default_waves();
+        assert!(!waves.waves.is_empty());
+        for pair in waves.waves.windows(2) {
+            assert!(pair[1].speed_multiplier >= pair[0].speed_multiplier);
+            assert!(pair[1].max_enemies >= pair[0].max_enemies);
+        }
+    }
+
+    #[test]
+    fn the_default_waves_all_check_out() {
+        let waves = default_waves();
+        assert_eq!(checked_waves(&waves.waves).len(), waves.waves.len());
+        assert_eq!(Wave::built_in().timing_problem(), None);
+    }
+
+    #[test]
+    fn waves_with_bad_timings_are_skipped() {
+        let good = Wave::built_in();
+        let bad = Wave {
+            name: "Bad".to_string(),
+            spawn_interval: -1.,
+            ..Wave::built_in()
+        };
+        assert_eq!(bad.timing_problem(), Some("spawn_interval"));
+        let checked = checked_waves(&[bad.clone(), good.clone()]);
+        assert_eq!(checked.len(), 1);
+        assert_eq!(checked[0].name, good.name);
+
+        // With nothing left to play, the built-in wave is played instead.
+        let checked = checked_waves(&[bad]);
+        assert_eq!(checked.len(), 1);
+        assert_eq!(checked[0].name, Wave::built_in().name);
+        let endless = Wave {
+            breather: f32::NAN,
+            ..Wave::built_in()
+        };
+        assert_eq!(endless.timing_problem(), Some("breather"));
+    }
+
+    #[test]
+    fn the_last_wave_repeats() {
+        let waves = default_waves();
+        let last = waves.waves.last().unwrap();
+        assert_eq!(waves.get(waves.waves.len() + 5).unwrap().name, last.name);
+        assert!(Waves::default().get(0).is_none());
+    }
+}
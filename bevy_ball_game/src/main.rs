@@ -1,5 +1,8 @@
+use std::path::Path;
+
 use app_state::AppStatePlugin;
 use bevy::prelude::*;
+use game::replay::{Playback, Replay};
 use game::GamePlugin;
 use menus::MenusPlugin;
 // use bevy::ui::debug;
@@ -9,8 +12,33 @@ mod game;
 mod menus;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugins((GamePlugin, AppStatePlugin, MenusPlugin))
-        .run();
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
+        .add_plugins((GamePlugin, AppStatePlugin, MenusPlugin));
+
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        None => {}
+        Some("--replay") => {
+            let Some(path) = args.get(2) else {
+                eprintln!("usage: {} [--replay <file>]", args[0]);
+                std::process::exit(2);
+            };
+            match Replay::load(Path::new(path)) {
+                Ok(replay) => {
+                    app.insert_resource(Playback::new(replay));
+                }
+                Err(e) => {
+                    eprintln!("Could not load replay {}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(_) => {
+            eprintln!("usage: {} [--replay <file>]", args[0]);
+            std::process::exit(2);
+        }
+    }
+
+    app.run();
 }
@@ -1,6 +1,6 @@
 use bevy::{app::AppExit, prelude::*};
 
-use crate::app_state::AppState;
+use crate::{app_state::AppState, game::high_scores::HighScores};
 
 const BUTTON_BACKGROUND: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON_BACKGROUND: Color = Color::rgb(0.25, 0.25, 0.25);
@@ -138,3 +138,26 @@ pub fn generic_image(parent: &mut ChildBuilder, image: UiImage, size: Vec2) {
         ..default()
     });
 }
+
+pub fn leaderboard(parent: &mut ChildBuilder, high_scores: &HighScores) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::bottom(Val::Px(40.)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            generic_text(parent, "High Scores".to_string(), 32., ());
+            if high_scores.scores.is_empty() {
+                generic_text(parent, "No scores yet".to_string(), 24., ());
+            }
+            for (rank, (name, score)) in high_scores.scores.iter().enumerate() {
+                let line = format!("{:>2}. {:<3} {:>5}", rank + 1, name, score);
+                generic_text(parent, line, 24., ());
+            }
+        });
+}
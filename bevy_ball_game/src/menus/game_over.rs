@@ -1,17 +1,32 @@
-use super::common::{add_button, generic_text, PlayButton, QuitButton};
-use crate::{app_state::AppState, game::game_logic::Score};
+use super::common::{add_button, generic_text, leaderboard, PlayButton, QuitButton};
+use crate::{
+    app_state::AppState,
+    game::{
+        game_logic::Score,
+        high_scores::{HighScores, NameEntry, MAX_INITIALS},
+    },
+};
 use bevy::prelude::*;
 
 pub struct GameOverMenuPlugin;
 
 impl Plugin for GameOverMenuPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::GameOver), spawn_game_over)
-            .add_systems(OnExit(AppState::GameOver), despawn_game_over)
-            .add_systems(
-                Update,
-                interact_with_main_menu_button.run_if(in_state(AppState::GameOver)),
-            );
+        app.add_systems(
+            OnEnter(AppState::GameOver),
+            (spawn_game_over, clear_typed_characters),
+        )
+        .add_systems(OnExit(AppState::GameOver), despawn_game_over)
+        .add_systems(
+            Update,
+            interact_with_main_menu_button.run_if(in_state(AppState::GameOver)),
+        )
+        .add_systems(
+            Update,
+            (enter_initials, update_initials)
+                .chain()
+                .run_if(in_state(AppState::GameOver).and_then(resource_exists::<NameEntry>())),
+        );
     }
 }
 
@@ -21,6 +36,9 @@ pub struct GameOverMenu;
 #[derive(Component)]
 pub struct MainMenuButton;
 
+#[derive(Component)]
+pub struct InitialsText;
+
 pub fn interact_with_main_menu_button(
     button_query: Query<&Interaction, (Changed<Interaction>, With<MainMenuButton>)>,
     mut next_app_state: ResMut<NextState<AppState>>,
@@ -30,19 +48,83 @@ pub fn interact_with_main_menu_button(
     }
 }
 
-fn spawn_game_over(mut commands: Commands, score: Res<Score>) {
-    build_game_over(&mut commands, &score);
+fn spawn_game_over(mut commands: Commands, score: Res<Score>, high_scores: Res<HighScores>) {
+    let name_entry = high_scores.qualifies(score.value).then(NameEntry::default);
+    build_game_over(
+        &mut commands,
+        score.value,
+        name_entry.as_ref(),
+        &high_scores,
+    );
+    if let Some(name_entry) = name_entry {
+        commands.insert_resource(name_entry);
+    }
     println!("Spawning Game Over")
 }
 
 fn despawn_game_over(mut commands: Commands, menu_query: Query<Entity, With<GameOverMenu>>) {
+    commands.remove_resource::<NameEntry>();
     if let Ok(game_over_menu) = menu_query.get_single() {
         commands.entity(game_over_menu).despawn_recursive();
         println!("Despawning Game Over");
     }
 }
 
-pub fn build_game_over(commands: &mut Commands, score: &Res<Score>) -> Entity {
+/// Drops whatever was typed while playing, so moving with WASD right up
+/// to the end doesn't fill in the initials.
+fn clear_typed_characters(mut characters: ResMut<Events<ReceivedCharacter>>) {
+    characters.clear();
+}
+
+pub fn enter_initials(
+    mut commands: Commands,
+    mut ev_character: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut name_entry: ResMut<NameEntry>,
+    mut high_scores: ResMut<HighScores>,
+    score: Res<Score>,
+    menu_query: Query<Entity, With<GameOverMenu>>,
+) {
+    for ev in ev_character.iter() {
+        if ev.char.is_ascii_alphanumeric() && name_entry.initials.len() < MAX_INITIALS {
+            name_entry.initials.push(ev.char.to_ascii_uppercase());
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        name_entry.initials.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) && !name_entry.initials.is_empty() {
+        high_scores.insert(name_entry.initials.clone(), score.value);
+        commands.remove_resource::<NameEntry>();
+        // Show the table with the new score on it in place of the entry.
+        if let Ok(game_over_menu) = menu_query.get_single() {
+            commands.entity(game_over_menu).despawn_recursive();
+        }
+        build_game_over(&mut commands, score.value, None, &high_scores);
+    }
+}
+
+pub fn update_initials(
+    name_entry: Res<NameEntry>,
+    mut initials_query: Query<&mut Text, With<InitialsText>>,
+) {
+    if name_entry.is_changed() {
+        if let Ok(mut initials_text) = initials_query.get_single_mut() {
+            initials_text.sections[0].value = initials_placeholder(&name_entry.initials);
+        }
+    }
+}
+
+fn initials_placeholder(initials: &str) -> String {
+    format!("{:_<width$}", initials, width = MAX_INITIALS)
+}
+
+pub fn build_game_over(
+    commands: &mut Commands,
+    score: u32,
+    name_entry: Option<&NameEntry>,
+    high_scores: &HighScores,
+) -> Entity {
     let game_over_entity = commands
         .spawn((
             NodeBundle {
@@ -65,14 +147,40 @@ pub fn build_game_over(commands: &mut Commands, score: &Res<Score>) -> Entity {
             parent
                 .spawn(NodeBundle {
                     style: Style {
-                        padding: UiRect::bottom(Val::Px(100.)),
+                        padding: UiRect::bottom(Val::Px(40.)),
                         ..default()
                     },
                     ..default()
                 })
                 .with_children(|parent| {
-                    generic_text(parent, format!("Final score: {}", score.value), 64., ());
+                    generic_text(parent, format!("Final score: {}", score), 64., ());
                 });
+            match name_entry {
+                Some(name_entry) => {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                padding: UiRect::bottom(Val::Px(40.)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            generic_text(
+                                parent,
+                                "New high score! Enter your initials".into(),
+                                32.,
+                                (),
+                            );
+                            let initials = initials_placeholder(&name_entry.initials);
+                            generic_text(parent, initials, 64., InitialsText);
+                            generic_text(parent, "Press Enter to save".into(), 24., ());
+                        });
+                }
+                None => leaderboard(parent, high_scores),
+            }
             // PLAY BUTTON
             add_button(parent, "Main Menu".to_string(), MainMenuButton);
             add_button(parent, "Restart".to_string(), PlayButton);
@@ -2,7 +2,12 @@ use bevy::prelude::*;
 
 use crate::{
     app_state::AppState,
-    game::{enemy::Enemy, game_logic::Score},
+    game::{
+        enemy::Enemy,
+        game_logic::Score,
+        power_ups::ActiveEffects,
+        waves::{WavePhase, WaveProgress, Waves},
+    },
 };
 
 use super::common::{generic_image, generic_text};
@@ -14,7 +19,8 @@ impl Plugin for HudPlugin {
             .add_systems(OnExit(AppState::Game), despawn_hud)
             .add_systems(
                 Update,
-                (update_score, update_enemies).run_if(in_state(AppState::Game)),
+                (update_score, update_enemies, update_wave, update_effects)
+                    .run_if(in_state(AppState::Game)),
             );
     }
 }
@@ -27,6 +33,12 @@ pub struct ScoreIndicator;
 #[derive(Component)]
 pub struct EnemyIndicator;
 
+#[derive(Component)]
+pub struct WaveIndicator;
+
+#[derive(Component)]
+pub struct EffectIndicator;
+
 pub fn update_score(
     mut score_query: Query<&mut Text, With<ScoreIndicator>>,
     score_res: Res<Score>,
@@ -63,6 +75,58 @@ pub fn update_enemies(
     }
 }
 
+pub fn update_wave(
+    mut wave_indicator_query: Query<&mut Text, With<WaveIndicator>>,
+    waves: Res<Waves>,
+    wave_progress: Res<WaveProgress>,
+) {
+    let remaining = wave_progress.timer.remaining_secs().ceil();
+    let value = match (wave_progress.phase, waves.get(wave_progress.index)) {
+        (WavePhase::Attack, Some(wave)) => format!(
+            "Wave {}: {} {}s",
+            wave_progress.index + 1,
+            wave.name,
+            remaining
+        ),
+        (WavePhase::Breather, _) => format!("Next wave in {}s", remaining),
+        _ => String::new(),
+    };
+
+    if let Ok(mut wave_indicator) = wave_indicator_query.get_single_mut() {
+        wave_indicator.sections = vec![TextSection {
+            value,
+            style: TextStyle {
+                font_size: 32.,
+                color: Color::WHITE,
+                ..default()
+            },
+        }]
+    }
+}
+
+pub fn update_effects(
+    mut effect_indicator_query: Query<&mut Text, With<EffectIndicator>>,
+    active_effects: Res<ActiveEffects>,
+) {
+    let value = active_effects
+        .effects
+        .iter()
+        .map(|(kind, timer)| format!("{} {}s", kind.name(), timer.remaining_secs().ceil()))
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    if let Ok(mut effect_indicator) = effect_indicator_query.get_single_mut() {
+        effect_indicator.sections = vec![TextSection {
+            value,
+            style: TextStyle {
+                font_size: 32.,
+                color: Color::WHITE,
+                ..default()
+            },
+        }]
+    }
+}
+
 pub fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
     build_hud(&mut commands, &asset_server);
     println!("Spawning Main Menu");
@@ -109,6 +173,20 @@ pub fn build_hud(commands: &mut Commands, _asset_server: &Res<AssetServer>) {
                     );
                     generic_text(parent, "0".to_string(), 64., ScoreIndicator);
                 });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Px(80.),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        margin: UiRect::vertical(Val::Px(20.)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    generic_text(parent, String::new(), 32., EffectIndicator);
+                });
             parent.spawn(NodeBundle {
                 style: Style {
                     flex_grow: 1.,
@@ -116,6 +194,22 @@ pub fn build_hud(commands: &mut Commands, _asset_server: &Res<AssetServer>) {
                 },
                 ..default()
             });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Px(80.),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        margin: UiRect::vertical(Val::Px(20.)),
+                        padding: UiRect::horizontal(Val::Px(20.)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.15, 0.15, 0.15, 0.15).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    generic_text(parent, String::new(), 32., WaveIndicator);
+                });
             parent
                 .spawn(NodeBundle {
                     style: Style {
@@ -1,8 +1,8 @@
 use bevy::prelude::*;
 
-use crate::app_state::AppState;
+use crate::{app_state::AppState, game::high_scores::HighScores};
 
-use super::common::{add_button, generic_image, generic_text, PlayButton, QuitButton};
+use super::common::{add_button, generic_image, generic_text, leaderboard, PlayButton, QuitButton};
 
 pub struct MainMenuPlugin;
 
@@ -16,8 +16,12 @@ impl Plugin for MainMenuPlugin {
 #[derive(Component)]
 pub struct MainMenu;
 
-pub fn spawn_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
-    build_main_menu(&mut commands, &asset_server);
+pub fn spawn_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    high_scores: Res<HighScores>,
+) {
+    build_main_menu(&mut commands, &asset_server, &high_scores);
     println!("Spawning Main Menu");
 }
 
@@ -28,7 +32,11 @@ pub fn despawn_main_menu(mut commands: Commands, menu_query: Query<Entity, With<
     }
 }
 
-pub fn build_main_menu(commands: &mut Commands, _asset_server: &Res<AssetServer>) -> Entity {
+pub fn build_main_menu(
+    commands: &mut Commands,
+    _asset_server: &Res<AssetServer>,
+    high_scores: &HighScores,
+) -> Entity {
     let main_menu_entity = commands
         .spawn((
             NodeBundle {
@@ -53,7 +61,7 @@ pub fn build_main_menu(commands: &mut Commands, _asset_server: &Res<AssetServer>
                     style: Style {
                         flex_direction: FlexDirection::Row,
                         align_items: AlignItems::Center,
-                        padding: UiRect::bottom(Val::Px(100.)),
+                        padding: UiRect::bottom(Val::Px(40.)),
                         ..default()
                     },
                     ..default()
@@ -74,6 +82,7 @@ pub fn build_main_menu(commands: &mut Commands, _asset_server: &Res<AssetServer>
                         Vec2::ONE * 64.,
                     );
                 });
+            leaderboard(parent, high_scores);
             // PLAY BUTTON
             add_button(parent, "Play".to_string(), PlayButton);
             // QUIT BUTTON
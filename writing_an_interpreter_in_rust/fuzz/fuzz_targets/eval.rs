@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use writing_an_interpreter_in_rust::module::MemoryLoader;
+use writing_an_interpreter_in_rust::{Interpreter, Limits};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = Limits {
+        max_steps: Some(10_000),
+        max_memory: Some(1 << 20),
+        max_call_depth: 20,
+    };
+    let mut interpreter = Interpreter::new()
+        .with_limits(limits)
+        .with_loader(MemoryLoader::default())
+        .with_output(std::io::sink());
+    let _ = interpreter.eval_str(&String::from_utf8_lossy(data));
+});
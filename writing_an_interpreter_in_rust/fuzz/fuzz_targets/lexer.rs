@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use writing_an_interpreter_in_rust::{lexer, token};
+
+fuzz_target!(|data: &[u8]| {
+    let mut l = lexer::Lexer::new(String::from_utf8_lossy(data).into_owned());
+    for _ in 0..=data.len() {
+        if l.next_token().token_type == token::TokenType::EOF {
+            return;
+        }
+    }
+    panic!("lexer did not reach EOF");
+});
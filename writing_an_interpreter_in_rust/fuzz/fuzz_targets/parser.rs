@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use writing_an_interpreter_in_rust::{lexer, parser};
+
+fuzz_target!(|data: &[u8]| {
+    let mut p = parser::Parser::new(lexer::Lexer::new(String::from_utf8_lossy(data).into_owned()));
+    p.parse_program();
+});
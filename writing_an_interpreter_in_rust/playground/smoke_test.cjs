@@ -0,0 +1,25 @@
+// Loads the wasm build in Node and checks each export end to end. Run with
+// `make wasm-test`.
+const assert = require("node:assert");
+const { tokenize, parse_to_json, _eval: evaluate } = require("../target/wasm-node/writing_an_interpreter_in_rust.js");
+
+const tokens = JSON.parse(tokenize("let x = 1;"));
+assert.deepStrictEqual(tokens.map((t) => t.token_type), ["let", "Identifier", "=", "Integer", ";", "EOF"]);
+
+const tree = JSON.parse(parse_to_json("fn(a, b) { a + b }"));
+assert.strictEqual(tree.program.statements[0].expression.kind, "Function");
+assert.deepStrictEqual(tree.errors, []);
+
+const run = JSON.parse(evaluate('puts("hi"); let f = fn(n) { if (n < 2) { n } else { f(n - 1) + f(n - 2) } }; f(15)'));
+assert.deepStrictEqual(run, { output: "hi\n", result: "610", error: null });
+
+// Deep recursion needs the larger stack the module is linked with.
+const deep = JSON.parse(evaluate("let d = fn(n) { if (n == 0) { 0 } else { 1 + d(n - 1) } }; d(99)"));
+assert.strictEqual(deep.result, "99");
+const overflow = JSON.parse(evaluate("let d = fn(n) { 1 + d(n + 1) }; d(0)"));
+assert.match(overflow.error, /maximum call depth of 100 exceeded/);
+
+const spin = JSON.parse(evaluate("let spin = fn() { return spin(); }; spin()"));
+assert.match(spin.error, /budget of .* exceeded/);
+
+console.log("wasm smoke test passed");
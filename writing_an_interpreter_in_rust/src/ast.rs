@@ -1,7 +1,15 @@
+// Every node keeps the token it was parsed from for error reporting, even
+// where nothing reads it yet.
+#![allow(dead_code)]
+
 use std::fmt::Debug;
+use std::rc::Rc;
+
+use serde::Serialize;
 
 use crate::token;
 
+#[derive(Serialize)]
 pub struct ProgramNode {
     pub statements: Vec<StatementNode>,
 }
@@ -16,46 +24,286 @@ impl Debug for ProgramNode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
 pub enum ExpressionNode {
     Identifier(IdentfierExpr),
     Literal(LiteralExpr),
+    Prefix(PrefixExpr),
+    Infix(InfixExpr),
+    If(IfExpr),
+    Function(FunctionExpr),
+    Call(CallExpr),
+    Array(ArrayExpr),
+    Hash(HashExpr),
+    Index(IndexExpr),
+    Match(MatchExpr),
+    Try(TryExpr),
+    StructLiteral(StructLiteralExpr),
+    Field(FieldExpr),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
 pub enum StatementNode {
     Let(LetStatement),
     Return(ReturnStatement),
+    Expression(ExpressionStatement),
+    Import(ImportStatement),
+    Struct(StructStatement),
+    Impl(ImplStatement),
+    Yield(YieldStatement),
+    For(ForStatement),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct IdentfierExpr {
     pub token: token::Token,
     pub value: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct LiteralExpr {
     pub token: token::Token,
     pub value: LiteralEnum
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
 pub enum LiteralEnum {
     IntLiteral(i64),
     StringLiteral(String),
     Boolean(bool),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+pub struct PrefixExpr {
+    pub token: token::Token,
+    pub operator: token::TokenType,
+    pub right: Box<ExpressionNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InfixExpr {
+    pub token: token::Token,
+    pub left: Box<ExpressionNode>,
+    pub operator: token::TokenType,
+    pub right: Box<ExpressionNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IfExpr {
+    pub token: token::Token,
+    pub condition: Box<ExpressionNode>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FunctionExpr {
+    pub token: token::Token,
+    /// Declared with `fn*`: calling it makes a generator instead of running
+    /// the body.
+    pub is_generator: bool,
+    pub parameters: Vec<IdentfierExpr>,
+    /// The annotation on each parameter, in the same order.
+    pub parameter_types: Vec<Option<TypeExpr>>,
+    pub return_type: Option<TypeExpr>,
+    pub body: Rc<BlockStatement>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CallExpr {
+    pub token: token::Token,
+    pub function: Box<ExpressionNode>,
+    pub arguments: Vec<ExpressionNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArrayExpr {
+    pub token: token::Token,
+    pub elements: Vec<ExpressionNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HashExpr {
+    pub token: token::Token,
+    pub pairs: Vec<(ExpressionNode, ExpressionNode)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexExpr {
+    pub token: token::Token,
+    pub left: Box<ExpressionNode>,
+    pub index: Box<ExpressionNode>,
+}
+
+/// `Name { field: value, ... }`, building an instance of a struct. The
+/// name may be a path into a module, like `shapes.Rect`.
+#[derive(Debug, Serialize)]
+pub struct StructLiteralExpr {
+    pub token: token::Token,
+    pub name: Box<ExpressionNode>,
+    pub fields: Vec<(IdentfierExpr, ExpressionNode)>,
+}
+
+/// `left.field`. Called directly, as in `left.field()`, it may also name a
+/// method.
+#[derive(Debug, Serialize)]
+pub struct FieldExpr {
+    pub token: token::Token,
+    pub left: Box<ExpressionNode>,
+    pub field: IdentfierExpr,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchExpr {
+    pub token: token::Token,
+    pub value: Box<ExpressionNode>,
+    pub arms: Vec<MatchArm>,
+}
+
+/// A single `pattern if guard => body` arm. Expression bodies are stored as
+/// a one-statement block so every arm evaluates the same way.
+#[derive(Debug, Serialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<ExpressionNode>,
+    pub body: BlockStatement,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
+pub enum Pattern {
+    Wildcard(token::Token),
+    Literal(LiteralExpr),
+    Binding(IdentfierExpr),
+    Array(ArrayPattern),
+    Hash(HashPattern),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArrayPattern {
+    pub token: token::Token,
+    pub elements: Vec<Pattern>,
+    /// `None` when there is no `..`, `Some(None)` for a bare `..` and
+    /// `Some(Some(name))` for `..name`.
+    pub rest: Option<Option<IdentfierExpr>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HashPattern {
+    pub token: token::Token,
+    pub pairs: Vec<(LiteralExpr, Pattern)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TryExpr {
+    pub token: token::Token,
+    pub body: BlockStatement,
+    pub binding: IdentfierExpr,
+    pub handler: BlockStatement,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockStatement {
+    pub token: token::Token,
+    pub statements: Vec<StatementNode>,
+}
+
+#[derive(Debug, Serialize)]
 pub struct LetStatement {
     pub token: token::Token,
     pub name: IdentfierExpr,
+    pub annotation: Option<TypeExpr>,
     pub value: ExpressionNode,
 }
 
-#[derive(Debug)]
+/// A type annotation, as in `let x: [int] = ...` or `fn(a: int) -> bool`.
+/// Annotations are only read by the checker; the evaluator ignores them.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
+pub enum TypeExpr {
+    /// `int`, `bool`, `string`, `null`, `any` or the name of a struct.
+    Named(IdentfierExpr),
+    Array(ArrayType),
+    Hash(HashType),
+    Function(FunctionType),
+}
+
+/// `[element]`
+#[derive(Debug, Clone, Serialize)]
+pub struct ArrayType {
+    pub token: token::Token,
+    pub element: Box<TypeExpr>,
+}
+
+/// `{key: value}`
+#[derive(Debug, Clone, Serialize)]
+pub struct HashType {
+    pub token: token::Token,
+    pub key: Box<TypeExpr>,
+    pub value: Box<TypeExpr>,
+}
+
+/// `fn(parameters) -> result`
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionType {
+    pub token: token::Token,
+    pub parameters: Vec<TypeExpr>,
+    pub result: Box<TypeExpr>,
+}
+
+#[derive(Debug, Serialize)]
 pub struct ReturnStatement {
     pub token: token::Token,
     pub value: ExpressionNode,
 }
+
+#[derive(Debug, Serialize)]
+pub struct ExpressionStatement {
+    pub token: token::Token,
+    pub expression: ExpressionNode,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportStatement {
+    pub token: token::Token,
+    pub path: String,
+    pub alias: Option<IdentfierExpr>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StructStatement {
+    pub token: token::Token,
+    pub name: IdentfierExpr,
+    pub fields: Vec<IdentfierExpr>,
+}
+
+/// `impl Name { fn method(self, ...) { ... } ... }`, adding methods to a
+/// struct declared earlier.
+#[derive(Debug, Serialize)]
+pub struct ImplStatement {
+    pub token: token::Token,
+    pub name: IdentfierExpr,
+    pub methods: Vec<(IdentfierExpr, FunctionExpr)>,
+}
+
+/// `yield value;`, handing a value out of a generator and suspending it
+/// until the next one is asked for, or `yield* iterable;`, handing out every
+/// value of another iterator in turn.
+#[derive(Debug, Serialize)]
+pub struct YieldStatement {
+    pub token: token::Token,
+    pub delegate: bool,
+    pub value: ExpressionNode,
+}
+
+/// `for (binding in iterable) { body }`
+#[derive(Debug, Serialize)]
+pub struct ForStatement {
+    pub token: token::Token,
+    pub binding: IdentfierExpr,
+    pub iterable: ExpressionNode,
+    pub body: BlockStatement,
+}
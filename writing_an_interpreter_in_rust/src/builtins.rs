@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::evaluator::Evaluator;
+use crate::gc;
+use crate::object::{BuiltinObject, ErrorObject, HashKey, IteratorObject, IteratorState, Object};
+
+pub(crate) type NativeFn = fn(&mut Evaluator, Vec<Object>) -> Result<Object, ErrorObject>;
+
+const BUILTINS: &[(&str, NativeFn)] = &[
+    ("len", len),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+    ("puts", puts),
+    ("type", type_of),
+    ("throw", throw),
+    ("assert", assert),
+    ("assert_eq", assert_eq),
+    ("assert_throws", assert_throws),
+    ("gc_stats", gc_stats),
+    ("iter", iter),
+    ("next", next),
+    ("map", map),
+    ("filter", filter),
+    ("take", take),
+    ("collect", collect),
+];
+
+thread_local! {
+    // Built once per thread, so that every lookup of a name hands out the
+    // same object and a builtin is `===` to itself.
+    static OBJECTS: Vec<Object> = BUILTINS
+        .iter()
+        .map(|(name, func)| {
+            Object::Builtin(Rc::new(BuiltinObject {
+                name: name.to_string(),
+                func: Rc::new(*func),
+            }))
+        })
+        .collect();
+}
+
+pub fn lookup(name: &str) -> Option<Object> {
+    let index = BUILTINS.iter().position(|(builtin_name, _)| *builtin_name == name)?;
+    return Some(OBJECTS.with(|objects| objects[index].clone()));
+}
+
+fn expect_args_between(name: &str, args: &[Object], min: usize, max: usize) -> Result<(), String> {
+    if args.len() < min || args.len() > max {
+        return Err(format!(
+            "wrong number of arguments to `{}`: want={}..{}, got={}",
+            name,
+            min,
+            max,
+            args.len()
+        ));
+    }
+    return Ok(());
+}
+
+pub(crate) fn expect_args(name: &str, args: &[Object], want: usize) -> Result<(), String> {
+    if args.len() != want {
+        return Err(format!(
+            "wrong number of arguments to `{}`: want={}, got={}",
+            name,
+            want,
+            args.len()
+        ));
+    }
+    return Ok(());
+}
+
+fn len(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("len", &args, 1)?;
+    match &args[0] {
+        Object::String(s) => Ok(Object::Integer(s.chars().count() as i64)),
+        Object::Array(elements) => Ok(Object::Integer(elements.len() as i64)),
+        Object::Hash(pairs) => Ok(Object::Integer(pairs.len() as i64)),
+        other => Err(format!("argument to `len` not supported, got {}", other.type_name()).into()),
+    }
+}
+
+fn first(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("first", &args, 1)?;
+    match &args[0] {
+        Object::Array(elements) => Ok(elements.first().cloned().unwrap_or(Object::Null)),
+        other => Err(format!("argument to `first` must be ARRAY, got {}", other.type_name()).into()),
+    }
+}
+
+fn last(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("last", &args, 1)?;
+    match &args[0] {
+        Object::Array(elements) => Ok(elements.last().cloned().unwrap_or(Object::Null)),
+        other => Err(format!("argument to `last` must be ARRAY, got {}", other.type_name()).into()),
+    }
+}
+
+fn rest(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("rest", &args, 1)?;
+    match &args[0] {
+        Object::Array(elements) if elements.is_empty() => Ok(Object::Null),
+        Object::Array(elements) => Ok(Object::Array(Rc::new(elements[1..].to_vec()))),
+        other => Err(format!("argument to `rest` must be ARRAY, got {}", other.type_name()).into()),
+    }
+}
+
+fn push(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("push", &args, 2)?;
+    match &args[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.as_ref().clone();
+            elements.push(args[1].clone());
+            Ok(Object::Array(Rc::new(elements)))
+        }
+        other => Err(format!("argument to `push` must be ARRAY, got {}", other.type_name()).into()),
+    }
+}
+
+fn puts(evaluator: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    for arg in args {
+        evaluator.write_output(&arg.to_string());
+    }
+    return Ok(Object::Null);
+}
+
+fn type_of(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("type", &args, 1)?;
+    return Ok(Object::String(args[0].type_name().into()));
+}
+
+/// Raises its argument as an error. Errors caught by `try` are re-raised with
+/// their original trace, anything else becomes the message of a new error.
+fn throw(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("throw", &args, 1)?;
+    match &args[0] {
+        Object::Error(error) => Err(error.as_ref().clone()),
+        Object::String(message) => Err(message.to_string().into()),
+        other => Err(other.inspect().into()),
+    }
+}
+
+fn assert(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args_between("assert", &args, 1, 2)?;
+    if args[0].is_truthy() {
+        return Ok(Object::Null);
+    }
+    match args.get(1) {
+        Some(message) => Err(format!("assertion failed: {}", message).into()),
+        None => Err(format!("assertion failed: got {}", args[0].inspect()).into()),
+    }
+}
+
+fn assert_eq(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("assert_eq", &args, 2)?;
+    if args[0] == args[1] {
+        return Ok(Object::Null);
+    }
+    let diff = diff(&args[0], &args[1]);
+    return Err(format!("assertion failed: values are not equal\n{}", diff.trim_end()).into());
+}
+
+/// Calls its argument and fails unless it raises an error, optionally with
+/// the given message. Returns the error so it can be inspected further.
+fn assert_throws(evaluator: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args_between("assert_throws", &args, 1, 2)?;
+    let error = match evaluator.apply_function(args[0].clone(), Vec::new()) {
+        Ok(value) => {
+            return Err(format!("assertion failed: expected an error, got {}", value.inspect()).into());
+        }
+        Err(error) if evaluator.is_halted() => return Err(error),
+        Err(error) => error,
+    };
+    match args.get(1) {
+        Some(Object::String(message)) if message.as_ref() != error.message => Err(format!(
+            "assertion failed: expected error {:?}, got {:?}",
+            message, error.message
+        )
+        .into()),
+        Some(Object::String(_)) | None => Ok(Object::Error(Rc::new(error))),
+        Some(other) => Err(format!("argument to `assert_throws` must be STRING, got {}", other.type_name()).into()),
+    }
+}
+
+/// The cycle collector's counters, as a hash.
+fn gc_stats(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("gc_stats", &args, 0)?;
+    let stats = gc::stats();
+    let pairs = BTreeMap::from([
+        (HashKey::String("collections".into()), Object::Integer(stats.collections as i64)),
+        (HashKey::String("environments".into()), Object::Integer(stats.environments as i64)),
+        (HashKey::String("freed".into()), Object::Integer(stats.freed as i64)),
+    ]);
+    return Ok(Object::Hash(Rc::new(pairs)));
+}
+
+fn iterate(name: &str, value: &Object) -> Result<Rc<IteratorObject>, String> {
+    IteratorObject::from_iterable(value).map_err(|e| format!("argument to `{}`: {}", name, e))
+}
+
+fn iter(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("iter", &args, 1)?;
+    return Ok(Object::Iterator(iterate("iter", &args[0])?));
+}
+
+/// Advances an iterator by hand, returning `{"done": false, "value": v}`,
+/// or `{"done": true, "value": null}` once it has run out.
+fn next(evaluator: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("next", &args, 1)?;
+    let Object::Iterator(iterator) = &args[0] else {
+        return Err(format!("argument to `next` must be ITERATOR, got {}", args[0].type_name()).into());
+    };
+    let value = evaluator.next_value(iterator)?;
+    let pairs = BTreeMap::from([
+        (HashKey::String("done".into()), Object::Boolean(value.is_none())),
+        (HashKey::String("value".into()), value.unwrap_or(Object::Null)),
+    ]);
+    return Ok(Object::Hash(Rc::new(pairs)));
+}
+
+/// The iterator adapters are lazy: they only call their function, and
+/// pull from their source, as values are asked of them.
+fn map(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("map", &args, 2)?;
+    let source = iterate("map", &args[0])?;
+    let function = args[1].clone();
+    return Ok(Object::Iterator(IteratorObject::new(IteratorState::Map { source, function })));
+}
+
+fn filter(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("filter", &args, 2)?;
+    let source = iterate("filter", &args[0])?;
+    let predicate = args[1].clone();
+    return Ok(Object::Iterator(IteratorObject::new(IteratorState::Filter { source, predicate })));
+}
+
+fn take(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("take", &args, 2)?;
+    let source = iterate("take", &args[0])?;
+    let Object::Integer(n) = args[1] else {
+        return Err(format!("argument to `take` must be INTEGER, got {}", args[1].type_name()).into());
+    };
+    let remaining = n.max(0) as usize;
+    return Ok(Object::Iterator(IteratorObject::new(IteratorState::Take { source, remaining })));
+}
+
+/// Runs an iterator to the end, which never comes for an infinite one.
+fn collect(evaluator: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("collect", &args, 1)?;
+    let iterator = iterate("collect", &args[0])?;
+    let mut elements = Vec::new();
+    while let Some(value) = evaluator.next_value(&iterator)? {
+        elements.push(value);
+    }
+    return Ok(Object::Array(Rc::new(elements)));
+}
+
+/// Shows two values that should have been equal. Multi-line strings are
+/// compared line by line, anything else gets a caret under the first
+/// character that differs.
+fn diff(actual: &Object, expected: &Object) -> String {
+    let (actual, expected) = match (actual, expected) {
+        (Object::String(a), Object::String(e)) if a.contains('\n') || e.contains('\n') => {
+            let (actual, expected): (Vec<&str>, Vec<&str>) = (a.lines().collect(), e.lines().collect());
+            let mut result = String::from("  (- expected, + actual)\n");
+            for i in 0..actual.len().max(expected.len()) {
+                match (actual.get(i), expected.get(i)) {
+                    (Some(a), Some(e)) if a == e => result.push_str(&format!("    {}\n", a)),
+                    (a, e) => {
+                        if let Some(e) = e {
+                            result.push_str(&format!("  - {}\n", e));
+                        }
+                        if let Some(a) = a {
+                            result.push_str(&format!("  + {}\n", a));
+                        }
+                    }
+                }
+            }
+            return result;
+        }
+        _ => (actual.inspect(), expected.inspect()),
+    };
+    let column = actual
+        .chars()
+        .zip(expected.chars())
+        .take_while(|(a, e)| a == e)
+        .count();
+    return format!(
+        "  expected: {}\n  actual:   {}\n            {}^\n",
+        expected,
+        actual,
+        " ".repeat(column)
+    );
+}
@@ -0,0 +1,1066 @@
+//! Static types for the typed dialect. Annotations are optional: a `let`,
+//! parameter or return type written with one is checked against it, and
+//! everything else is inferred by unification in the style of
+//! Hindley–Milner, with `let` bindings generalized so that `let empty = [];`
+//! can be used as an array of anything.
+//!
+//! Unannotated parameters are inferred from how they're used, but one
+//! that's used in ways that disagree becomes `any`, which fits every type
+//! in both directions, rather than being reported. Code written without
+//! annotations keeps behaving dynamically that way, even in a file that has
+//! some, and is only checked where its types are certain. For the same
+//! reason, places where the language lets values of different types meet,
+//! such as the branches of an `if` or the elements of an array, are typed
+//! `any` when they disagree, unless an annotation says what they must be.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::ast;
+use crate::diagnostic::Diagnostic;
+use crate::token;
+use rstest::rstest;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    String,
+    Null,
+    /// Anything at all, left for the evaluator to check.
+    Any,
+    Array(Box<Type>),
+    Hash(Box<Type>, Box<Type>),
+    Function(Vec<Type>, Box<Type>),
+    /// An instance of the struct with this name.
+    Struct(String),
+    /// A type that hasn't been worked out yet.
+    Var(usize),
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Null => write!(f, "null"),
+            Type::Any => write!(f, "any"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Hash(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Type::Function(parameters, result) => {
+                let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) -> {}", parameters.join(", "), result)
+            }
+            Type::Struct(name) => write!(f, "{}", name),
+            Type::Var(id) if *id < 26 => write!(f, "{}", (b'a' + *id as u8) as char),
+            Type::Var(id) => write!(f, "t{}", id),
+        }
+    }
+}
+
+fn array(element: Type) -> Type {
+    Type::Array(Box::new(element))
+}
+
+fn function(parameters: Vec<Type>, result: Type) -> Type {
+    Type::Function(parameters, Box::new(result))
+}
+
+/// A binding's type, which may be used at a different type for each of
+/// `vars` every time the binding is referred to.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Type,
+}
+
+impl Scheme {
+    fn mono(ty: Type) -> Scheme {
+        Scheme { vars: Vec::new(), ty }
+    }
+}
+
+/// What's known about the function whose body is being checked.
+struct FunctionContext {
+    declared: Option<Type>,
+    returns: Vec<Type>,
+}
+
+/// Type checks a program, returning a diagnostic for every error.
+pub fn check(program: &ast::ProgramNode) -> Vec<Diagnostic> {
+    let mut checker = Checker::new();
+    checker.program(program);
+    return checker.diagnostics;
+}
+
+/// Whether a program is written in the typed dialect, meaning it has an
+/// annotation somewhere.
+pub fn has_annotations(program: &ast::ProgramNode) -> bool {
+    program.statements.iter().any(statement_has_annotations)
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Scheme>>,
+    structs: HashSet<String>,
+    /// What each type variable has been bound to, indexed by its id.
+    bindings: Vec<Option<Type>>,
+    /// The variables standing for unannotated parameters.
+    parameters: HashSet<usize>,
+    functions: Vec<FunctionContext>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn new() -> Checker {
+        let mut checker = Checker {
+            scopes: vec![HashMap::new()],
+            structs: HashSet::new(),
+            bindings: Vec::new(),
+            parameters: HashSet::new(),
+            functions: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        checker.define_builtins();
+        return checker;
+    }
+
+    /// The builtins' types. `puts` and the asserts take a varying number of
+    /// arguments, which no function type describes, so they're `any`.
+    fn define_builtins(&mut self) {
+        let simple = [
+            ("len", function(vec![Type::Any], Type::Int)),
+            ("type", function(vec![Type::Any], Type::String)),
+            ("throw", function(vec![Type::Any], Type::Any)),
+            ("assert_eq", function(vec![Type::Any, Type::Any], Type::Null)),
+            ("gc_stats", function(vec![], Type::Hash(Box::new(Type::String), Box::new(Type::Int)))),
+            ("puts", Type::Any),
+            ("assert", Type::Any),
+            ("assert_throws", Type::Any),
+            // Iterators have no type of their own, so they're `any`.
+            ("iter", function(vec![Type::Any], Type::Any)),
+            ("next", function(vec![Type::Any], Type::Hash(Box::new(Type::String), Box::new(Type::Any)))),
+            ("map", function(vec![Type::Any, Type::Any], Type::Any)),
+            ("filter", function(vec![Type::Any, Type::Any], Type::Any)),
+            ("take", function(vec![Type::Any, Type::Int], Type::Any)),
+            ("collect", function(vec![Type::Any], array(Type::Any))),
+        ];
+        for (name, ty) in simple {
+            self.define(name, Scheme::mono(ty));
+        }
+        for name in ["first", "last", "rest", "push"] {
+            let a = self.fresh_id();
+            let element = Type::Var(a);
+            let ty = match name {
+                "rest" => function(vec![array(element.clone())], array(element)),
+                "push" => function(vec![array(element.clone()), element.clone()], array(element)),
+                _ => function(vec![array(element.clone())], element),
+            };
+            self.define(name, Scheme { vars: vec![a], ty });
+        }
+    }
+
+    fn program(&mut self, program: &ast::ProgramNode) {
+        self.scopes.push(HashMap::new());
+        for statement in &program.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn define(&mut self, name: &str, scheme: Scheme) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), scheme);
+    }
+
+    fn lookup(&mut self, name: &str) -> Type {
+        let scheme = self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned();
+        // Names that aren't defined yet may be by the time the code runs.
+        return match scheme {
+            Some(scheme) => self.instantiate(&scheme),
+            None => Type::Any,
+        };
+    }
+
+    fn error(&mut self, message: String, span: (&token::Token, &token::Token)) {
+        self.diagnostics.push(Diagnostic::spanning(message, span.0, span.1));
+    }
+
+    fn fresh_id(&mut self) -> usize {
+        self.bindings.push(None);
+        return self.bindings.len() - 1;
+    }
+
+    fn fresh(&mut self) -> Type {
+        return Type::Var(self.fresh_id());
+    }
+
+    /// Follows bound variables until it reaches a type that isn't one.
+    fn resolve(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let Type::Var(id) = ty {
+            match &self.bindings[id] {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+        return ty;
+    }
+
+    /// Resolves every variable inside `ty` as well.
+    fn zonk(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Type::Array(element) => array(self.zonk(&element)),
+            Type::Hash(key, value) => Type::Hash(Box::new(self.zonk(&key)), Box::new(self.zonk(&value))),
+            Type::Function(parameters, result) => {
+                function(parameters.iter().map(|p| self.zonk(p)).collect(), self.zonk(&result))
+            }
+            ty => ty,
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Var(a), Type::Var(b)) if a == b => true,
+            (Type::Var(id), ty) | (ty, Type::Var(id)) => {
+                if free_vars(&self.zonk(&ty)).contains(&id) {
+                    return false;
+                }
+                self.bindings[id] = Some(ty);
+                true
+            }
+            (Type::Array(a), Type::Array(b)) => self.unify(&a, &b),
+            (Type::Hash(ak, av), Type::Hash(bk, bv)) => self.unify(&ak, &bk) && self.unify(&av, &bv),
+            (Type::Function(ap, ar), Type::Function(bp, br)) => {
+                ap.len() == bp.len() && ap.iter().zip(&bp).all(|(a, b)| self.unify(a, b)) && self.unify(&ar, &br)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Reports `found` at `span` unless it fits `expected`. If an
+    /// unannotated parameter is what they disagree over, it's made `any`
+    /// instead.
+    fn expect(&mut self, expected: &Type, found: &Type, span: (&token::Token, &token::Token)) {
+        let bindings = self.bindings.clone();
+        if self.unify(expected, found) {
+            return;
+        }
+        self.bindings = bindings;
+        let mut parameters = Vec::new();
+        self.collect_parameters(expected, &mut parameters);
+        self.collect_parameters(found, &mut parameters);
+        if parameters.is_empty() {
+            let names = self.show(&[expected, found]);
+            self.error(format!("expected {}, found {}", names[0], names[1]), span);
+        }
+        for id in parameters {
+            self.bindings[id] = Some(Type::Any);
+        }
+    }
+
+    /// The unannotated parameters' variables that `ty` is made up from.
+    fn collect_parameters(&self, ty: &Type, parameters: &mut Vec<usize>) {
+        match ty {
+            Type::Var(id) => {
+                if self.parameters.contains(id) {
+                    parameters.push(*id);
+                }
+                if let Some(bound) = &self.bindings[*id] {
+                    self.collect_parameters(bound, parameters);
+                }
+            }
+            Type::Array(element) => self.collect_parameters(element, parameters),
+            Type::Hash(key, value) => {
+                self.collect_parameters(key, parameters);
+                self.collect_parameters(value, parameters);
+            }
+            Type::Function(params, result) => {
+                params.iter().for_each(|p| self.collect_parameters(p, parameters));
+                self.collect_parameters(result, parameters);
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks `expression` against an annotated type. The elements of an
+    /// array or hash literal are checked one by one, so the one that doesn't
+    /// fit is the one reported, where they'd otherwise just make it `any`.
+    fn expression_against(&mut self, expression: &ast::ExpressionNode, expected: &Type) -> Type {
+        match (expression, self.resolve(expected)) {
+            (ast::ExpressionNode::Array(array_expr), Type::Array(element)) => {
+                for e in &array_expr.elements {
+                    self.expression_against(e, &element);
+                }
+                array(*element)
+            }
+            (ast::ExpressionNode::Hash(hash), Type::Hash(key, value)) if !hash.pairs.is_empty() => {
+                for (k, v) in &hash.pairs {
+                    self.expression_against(k, &key);
+                    self.expression_against(v, &value);
+                }
+                Type::Hash(key, value)
+            }
+            _ => {
+                let ty = self.expression(expression);
+                self.expect(expected, &ty, span(expression));
+                ty
+            }
+        }
+    }
+
+    /// The type two values that may meet at runtime have in common, or
+    /// `any` if they have none.
+    fn join(&mut self, a: &Type, b: &Type) -> Type {
+        if self.resolve(a) == Type::Any || self.resolve(b) == Type::Any {
+            return Type::Any;
+        }
+        let bindings = self.bindings.clone();
+        if self.unify(a, b) {
+            return a.clone();
+        }
+        self.bindings = bindings;
+        return Type::Any;
+    }
+
+    /// Names types for a message, lettering their variables in the order
+    /// they appear.
+    fn show(&self, types: &[&Type]) -> Vec<String> {
+        let types: Vec<Type> = types.iter().map(|ty| self.zonk(ty)).collect();
+        let mut names = HashMap::new();
+        for ty in &types {
+            for id in free_vars(ty) {
+                let next = names.len();
+                names.entry(id).or_insert(Type::Var(next));
+            }
+        }
+        return types.iter().map(|ty| substitute(ty, &names).to_string()).collect();
+    }
+
+    fn generalize(&self, ty: &Type) -> Scheme {
+        let ty = self.zonk(ty);
+        let mut in_scope = HashSet::new();
+        for scheme in self.scopes.iter().flat_map(|scope| scope.values()) {
+            let vars = free_vars(&self.zonk(&scheme.ty));
+            in_scope.extend(vars.into_iter().filter(|id| !scheme.vars.contains(id)));
+        }
+        let vars = free_vars(&ty).into_iter().filter(|id| !in_scope.contains(id)).collect();
+        return Scheme { vars, ty };
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        if scheme.vars.is_empty() {
+            return scheme.ty.clone();
+        }
+        let fresh: HashMap<usize, Type> = scheme.vars.iter().map(|id| (*id, self.fresh())).collect();
+        return substitute(&self.zonk(&scheme.ty), &fresh);
+    }
+
+    fn annotation(&mut self, annotation: &ast::TypeExpr) -> Type {
+        match annotation {
+            ast::TypeExpr::Named(name) => match name.value.as_str() {
+                "int" => Type::Int,
+                "bool" => Type::Bool,
+                "string" => Type::String,
+                "null" => Type::Null,
+                "any" => Type::Any,
+                struct_name if self.structs.contains(struct_name) => Type::Struct(name.value.clone()),
+                _ => {
+                    self.error(format!("unknown type `{}`", name.value), (&name.token, &name.token));
+                    Type::Any
+                }
+            },
+            ast::TypeExpr::Array(a) => array(self.annotation(&a.element)),
+            ast::TypeExpr::Hash(hash) => {
+                Type::Hash(Box::new(self.annotation(&hash.key)), Box::new(self.annotation(&hash.value)))
+            }
+            ast::TypeExpr::Function(func) => {
+                let parameters = func.parameters.iter().map(|p| self.annotation(p)).collect();
+                function(parameters, self.annotation(&func.result))
+            }
+        }
+    }
+
+    fn block(&mut self, block: &ast::BlockStatement) -> Type {
+        self.scopes.push(HashMap::new());
+        let mut ty = Type::Null;
+        for statement in &block.statements {
+            ty = self.statement(statement);
+        }
+        self.scopes.pop();
+        return ty;
+    }
+
+    /// Checks a statement and returns the type of its value, for the last
+    /// statement of a block.
+    fn statement(&mut self, statement: &ast::StatementNode) -> Type {
+        match statement {
+            ast::StatementNode::Let(stmt) => {
+                // A function may call itself, at the one type it's given.
+                let recursive = match &stmt.value {
+                    ast::ExpressionNode::Function(_) => {
+                        let ty = self.fresh();
+                        self.define(&stmt.name.value, Scheme::mono(ty.clone()));
+                        Some(ty)
+                    }
+                    _ => None,
+                };
+                let ty = match &stmt.annotation {
+                    Some(annotation) => {
+                        let declared = self.annotation(annotation);
+                        self.expression_against(&stmt.value, &declared);
+                        declared
+                    }
+                    None => self.expression(&stmt.value),
+                };
+                if let Some(recursive) = recursive {
+                    self.expect(&ty, &recursive, span(&stmt.value));
+                    self.scopes.last_mut().unwrap().remove(&stmt.name.value);
+                }
+                let scheme = self.generalize(&ty);
+                self.define(&stmt.name.value, scheme);
+                Type::Null
+            }
+            ast::StatementNode::Return(stmt) => {
+                let declared = self.functions.last().and_then(|f| f.declared.clone());
+                match declared {
+                    Some(declared) => self.expression_against(&stmt.value, &declared),
+                    None => {
+                        let ty = self.expression(&stmt.value);
+                        if let Some(function) = self.functions.last_mut() {
+                            function.returns.push(ty.clone());
+                        }
+                        ty
+                    }
+                }
+            }
+            ast::StatementNode::Expression(stmt) => self.expression(&stmt.expression),
+            ast::StatementNode::Import(stmt) => {
+                let name = match &stmt.alias {
+                    Some(alias) => alias.value.clone(),
+                    None => stmt.path.rsplit('/').next().unwrap_or_default().to_string(),
+                };
+                self.define(&name, Scheme::mono(Type::Any));
+                Type::Null
+            }
+            ast::StatementNode::Struct(stmt) => {
+                self.structs.insert(stmt.name.value.clone());
+                self.define(&stmt.name.value, Scheme::mono(Type::Any));
+                Type::Null
+            }
+            ast::StatementNode::Impl(stmt) => {
+                let receiver = match self.structs.contains(&stmt.name.value) {
+                    true => Type::Struct(stmt.name.value.clone()),
+                    false => Type::Any,
+                };
+                for (_, method) in &stmt.methods {
+                    self.function(method, Some(receiver.clone()));
+                }
+                Type::Null
+            }
+            ast::StatementNode::Yield(stmt) => {
+                self.expression(&stmt.value);
+                Type::Null
+            }
+            ast::StatementNode::For(stmt) => {
+                let iterable = self.expression(&stmt.iterable);
+                let element = match self.resolve(&iterable) {
+                    Type::Array(element) => *element,
+                    Type::String => Type::String,
+                    Type::Int | Type::Bool | Type::Null | Type::Function(_, _) | Type::Struct(_) => {
+                        let name = self.show(&[&iterable]).remove(0);
+                        self.error(format!("{} is not iterable", name), span(&stmt.iterable));
+                        Type::Any
+                    }
+                    _ => Type::Any,
+                };
+                self.scopes.push(HashMap::new());
+                self.define(&stmt.binding.value, Scheme::mono(element));
+                self.block(&stmt.body);
+                self.scopes.pop();
+                Type::Null
+            }
+        }
+    }
+
+    /// Checks a function literal. `receiver` is the type of `self` for a
+    /// method.
+    fn function(&mut self, func: &ast::FunctionExpr, receiver: Option<Type>) -> Type {
+        self.scopes.push(HashMap::new());
+        let mut parameters = Vec::new();
+        for (i, (parameter, annotation)) in func.parameters.iter().zip(&func.parameter_types).enumerate() {
+            let ty = match (annotation, &receiver) {
+                (Some(annotation), _) => self.annotation(annotation),
+                (None, Some(receiver)) if i == 0 => receiver.clone(),
+                (None, _) => {
+                    let id = self.fresh_id();
+                    self.parameters.insert(id);
+                    Type::Var(id)
+                }
+            };
+            self.define(&parameter.value, Scheme::mono(ty.clone()));
+            parameters.push(ty);
+        }
+        let declared = func.return_type.as_ref().map(|r| self.annotation(r));
+        self.functions.push(FunctionContext {
+            declared: declared.clone().filter(|_| !func.is_generator),
+            returns: Vec::new(),
+        });
+        let body = self.block(&func.body);
+        let context = self.functions.pop().unwrap();
+        let result = match declared {
+            // A call makes an iterator, and iterators aren't typed, so
+            // neither the body nor an annotation says anything about it.
+            _ if func.is_generator => Type::Any,
+            Some(declared) => {
+                // A `return` at the end has been checked already.
+                match func.body.statements.last() {
+                    Some(ast::StatementNode::Return(_)) => {}
+                    Some(last) => self.expect(&declared, &body, statement_span(last)),
+                    None => self.expect(&declared, &body, (&func.token, &func.token)),
+                }
+                declared
+            }
+            None => context.returns.iter().fold(body, |result, ty| self.join(&result, ty)),
+        };
+        self.scopes.pop();
+        return function(parameters, result);
+    }
+
+    fn expression(&mut self, expression: &ast::ExpressionNode) -> Type {
+        match expression {
+            ast::ExpressionNode::Identifier(ident) => self.lookup(&ident.value),
+            ast::ExpressionNode::Literal(literal) => match literal.value {
+                ast::LiteralEnum::IntLiteral(_) => Type::Int,
+                ast::LiteralEnum::StringLiteral(_) => Type::String,
+                ast::LiteralEnum::Boolean(_) => Type::Bool,
+            },
+            ast::ExpressionNode::Prefix(prefix) => {
+                let right = self.expression(&prefix.right);
+                match prefix.operator {
+                    token::TokenType::Minus => {
+                        self.expect(&Type::Int, &right, span(&prefix.right));
+                        Type::Int
+                    }
+                    _ => Type::Bool,
+                }
+            }
+            ast::ExpressionNode::Infix(infix) => self.infix(infix),
+            ast::ExpressionNode::If(if_expr) => {
+                self.expression(&if_expr.condition);
+                let consequence = self.block(&if_expr.consequence);
+                let alternative = match &if_expr.alternative {
+                    Some(alternative) => self.block(alternative),
+                    None => Type::Null,
+                };
+                self.join(&consequence, &alternative)
+            }
+            ast::ExpressionNode::Function(func) => self.function(func, None),
+            ast::ExpressionNode::Call(call) => self.call(call),
+            ast::ExpressionNode::Array(array_expr) => {
+                let mut element = None;
+                for e in &array_expr.elements {
+                    let ty = self.expression(e);
+                    element = Some(match element {
+                        Some(element) => self.join(&element, &ty),
+                        None => ty,
+                    });
+                }
+                match element {
+                    Some(element) => array(element),
+                    None => array(self.fresh()),
+                }
+            }
+            ast::ExpressionNode::Hash(hash) => {
+                let (mut key, mut value) = (None, None);
+                for (k, v) in &hash.pairs {
+                    let k_ty = self.expression(k);
+                    if matches!(
+                        self.resolve(&k_ty),
+                        Type::Null | Type::Array(_) | Type::Hash(_, _) | Type::Function(_, _) | Type::Struct(_)
+                    ) {
+                        let name = self.show(&[&k_ty]).remove(0);
+                        self.error(format!("unusable as hash key: {}", name), span(k));
+                    }
+                    let v_ty = self.expression(v);
+                    key = Some(match key {
+                        Some(key) => self.join(&key, &k_ty),
+                        None => k_ty,
+                    });
+                    value = Some(match value {
+                        Some(value) => self.join(&value, &v_ty),
+                        None => v_ty,
+                    });
+                }
+                let key = key.unwrap_or_else(|| self.fresh());
+                let value = value.unwrap_or_else(|| self.fresh());
+                Type::Hash(Box::new(key), Box::new(value))
+            }
+            ast::ExpressionNode::Index(index) => {
+                let left = self.expression(&index.left);
+                let i = self.expression(&index.index);
+                match self.resolve(&left) {
+                    Type::Array(element) => {
+                        self.expect(&Type::Int, &i, span(&index.index));
+                        *element
+                    }
+                    Type::Hash(key, value) => {
+                        self.expect(&key, &i, span(&index.index));
+                        *value
+                    }
+                    Type::Any | Type::Var(_) => Type::Any,
+                    _ => {
+                        let names = self.show(&[&left, &i]);
+                        let message = format!("index operator not supported: {}[{}]", names[0], names[1]);
+                        self.error(message, span(expression));
+                        Type::Any
+                    }
+                }
+            }
+            ast::ExpressionNode::Match(match_expr) => {
+                let value = self.expression(&match_expr.value);
+                let mut result = None;
+                for arm in &match_expr.arms {
+                    self.scopes.push(HashMap::new());
+                    self.bind_pattern(&arm.pattern, &value);
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    let ty = self.block(&arm.body);
+                    self.scopes.pop();
+                    result = Some(match result {
+                        Some(result) => self.join(&result, &ty),
+                        None => ty,
+                    });
+                }
+                result.unwrap_or(Type::Null)
+            }
+            ast::ExpressionNode::Try(try_expr) => {
+                let body = self.block(&try_expr.body);
+                self.scopes.push(HashMap::new());
+                self.define(&try_expr.binding.value, Scheme::mono(Type::Any));
+                let handler = self.block(&try_expr.handler);
+                self.scopes.pop();
+                self.join(&body, &handler)
+            }
+            ast::ExpressionNode::StructLiteral(literal) => {
+                for (_, value) in &literal.fields {
+                    self.expression(value);
+                }
+                match literal.name.as_ref() {
+                    ast::ExpressionNode::Identifier(name) if self.structs.contains(&name.value) => {
+                        Type::Struct(name.value.clone())
+                    }
+                    _ => Type::Any,
+                }
+            }
+            ast::ExpressionNode::Field(field) => {
+                let left = self.expression(&field.left);
+                match self.resolve(&left) {
+                    // Fields aren't annotated, so their types are unknown.
+                    Type::Struct(_) | Type::Any | Type::Var(_) => Type::Any,
+                    _ => {
+                        let name = self.show(&[&left]).remove(0);
+                        self.error(format!("{} has no field `{}`", name, field.field.value), span(expression));
+                        Type::Any
+                    }
+                }
+            }
+        }
+    }
+
+    fn infix(&mut self, infix: &ast::InfixExpr) -> Type {
+        let left = self.expression(&infix.left);
+        let right = self.expression(&infix.right);
+        let operands = (span(&infix.left), span(&infix.right));
+        match infix.operator {
+            token::TokenType::EQ | token::TokenType::NotEQ | token::TokenType::STRONGEQ => Type::Bool,
+            token::TokenType::Plus => match (self.resolve(&left), self.resolve(&right)) {
+                (Type::Any, _) | (_, Type::Any) => Type::Any,
+                (Type::String, _) | (_, Type::String) => {
+                    self.expect(&Type::String, &left, operands.0);
+                    self.expect(&Type::String, &right, operands.1);
+                    Type::String
+                }
+                (Type::Int, _) | (_, Type::Int) => {
+                    self.expect(&Type::Int, &left, operands.0);
+                    self.expect(&Type::Int, &right, operands.1);
+                    Type::Int
+                }
+                // Either ints or strings, which no one type covers.
+                (Type::Var(_), Type::Var(_)) => Type::Any,
+                _ => {
+                    let names = self.show(&[&left, &right]);
+                    let message = format!("`+` needs two ints or two strings, found {} and {}", names[0], names[1]);
+                    self.error(message, (operands.0 .0, operands.1 .1));
+                    Type::Any
+                }
+            },
+            _ => {
+                self.expect(&Type::Int, &left, operands.0);
+                self.expect(&Type::Int, &right, operands.1);
+                match infix.operator {
+                    token::TokenType::Minus | token::TokenType::Asterisk | token::TokenType::Slash => Type::Int,
+                    _ => Type::Bool,
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, call: &ast::CallExpr) -> Type {
+        let callee = self.expression(&call.function);
+        let arguments: Vec<Type> = call.arguments.iter().map(|a| self.expression(a)).collect();
+        match self.resolve(&callee) {
+            Type::Function(parameters, result) => {
+                if parameters.len() != arguments.len() {
+                    let message = format!(
+                        "wrong number of arguments: want={}, got={}",
+                        parameters.len(),
+                        arguments.len()
+                    );
+                    self.error(message, (first_token(&call.function), call_end(call)));
+                    return *result;
+                }
+                for ((parameter, argument), expr) in parameters.iter().zip(&arguments).zip(&call.arguments) {
+                    self.expect(parameter, argument, span(expr));
+                }
+                *result
+            }
+            Type::Var(_) => {
+                let result = self.fresh();
+                let ty = function(arguments, result.clone());
+                self.expect(&callee, &ty, span(&call.function));
+                result
+            }
+            Type::Any => Type::Any,
+            _ => {
+                let name = self.show(&[&callee]).remove(0);
+                self.error(format!("not a function: {}", name), span(&call.function));
+                Type::Any
+            }
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &ast::Pattern, ty: &Type) {
+        match pattern {
+            ast::Pattern::Wildcard(_) | ast::Pattern::Literal(_) => {}
+            ast::Pattern::Binding(name) => self.define(&name.value, Scheme::mono(ty.clone())),
+            ast::Pattern::Array(array_pattern) => {
+                let element = match self.resolve(ty) {
+                    Type::Array(element) => *element,
+                    _ => Type::Any,
+                };
+                for p in &array_pattern.elements {
+                    self.bind_pattern(p, &element);
+                }
+                if let Some(Some(rest)) = &array_pattern.rest {
+                    let rest_ty = match self.resolve(ty) {
+                        Type::Array(_) => array(element),
+                        _ => Type::Any,
+                    };
+                    self.define(&rest.value, Scheme::mono(rest_ty));
+                }
+            }
+            ast::Pattern::Hash(hash) => {
+                let value = match self.resolve(ty) {
+                    Type::Hash(_, value) => *value,
+                    _ => Type::Any,
+                };
+                for (_, p) in &hash.pairs {
+                    self.bind_pattern(p, &value);
+                }
+            }
+        }
+    }
+}
+
+fn free_vars(ty: &Type) -> Vec<usize> {
+    let mut vars = Vec::new();
+    collect_vars(ty, &mut vars);
+    return vars;
+}
+
+fn collect_vars(ty: &Type, vars: &mut Vec<usize>) {
+    match ty {
+        Type::Var(id) if !vars.contains(id) => vars.push(*id),
+        Type::Array(element) => collect_vars(element, vars),
+        Type::Hash(key, value) => {
+            collect_vars(key, vars);
+            collect_vars(value, vars);
+        }
+        Type::Function(parameters, result) => {
+            parameters.iter().for_each(|p| collect_vars(p, vars));
+            collect_vars(result, vars);
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the variables in `ty` that `replacements` has an entry for.
+fn substitute(ty: &Type, replacements: &HashMap<usize, Type>) -> Type {
+    match ty {
+        Type::Var(id) => replacements.get(id).cloned().unwrap_or(Type::Var(*id)),
+        Type::Array(element) => array(substitute(element, replacements)),
+        Type::Hash(key, value) => Type::Hash(
+            Box::new(substitute(key, replacements)),
+            Box::new(substitute(value, replacements)),
+        ),
+        Type::Function(parameters, result) => function(
+            parameters.iter().map(|p| substitute(p, replacements)).collect(),
+            substitute(result, replacements),
+        ),
+        ty => ty.clone(),
+    }
+}
+
+fn span(expression: &ast::ExpressionNode) -> (&token::Token, &token::Token) {
+    (first_token(expression), last_token(expression))
+}
+
+fn statement_span(statement: &ast::StatementNode) -> (&token::Token, &token::Token) {
+    match statement {
+        ast::StatementNode::Let(stmt) => (&stmt.token, last_token(&stmt.value)),
+        ast::StatementNode::Return(stmt) => (&stmt.token, last_token(&stmt.value)),
+        ast::StatementNode::Expression(stmt) => span(&stmt.expression),
+        ast::StatementNode::Import(stmt) => (&stmt.token, &stmt.token),
+        ast::StatementNode::Struct(stmt) => (&stmt.token, &stmt.name.token),
+        ast::StatementNode::Impl(stmt) => (&stmt.token, &stmt.name.token),
+        ast::StatementNode::Yield(stmt) => (&stmt.token, last_token(&stmt.value)),
+        ast::StatementNode::For(stmt) => (&stmt.token, last_token(&stmt.iterable)),
+    }
+}
+
+fn first_token(expression: &ast::ExpressionNode) -> &token::Token {
+    match expression {
+        ast::ExpressionNode::Infix(infix) => first_token(&infix.left),
+        ast::ExpressionNode::Call(call) => first_token(&call.function),
+        ast::ExpressionNode::Index(index) => first_token(&index.left),
+        ast::ExpressionNode::Field(field) => first_token(&field.left),
+        ast::ExpressionNode::StructLiteral(literal) => first_token(&literal.name),
+        _ => expression_token(expression),
+    }
+}
+
+/// The last token of an expression that the tree keeps. Closing brackets
+/// aren't kept, so spans stop just short of them.
+fn last_token(expression: &ast::ExpressionNode) -> &token::Token {
+    match expression {
+        ast::ExpressionNode::Prefix(prefix) => last_token(&prefix.right),
+        ast::ExpressionNode::Infix(infix) => last_token(&infix.right),
+        ast::ExpressionNode::Call(call) => call_end(call),
+        ast::ExpressionNode::Index(index) => last_token(&index.index),
+        ast::ExpressionNode::Field(field) => &field.field.token,
+        ast::ExpressionNode::Array(array) => array.elements.last().map_or(&array.token, last_token),
+        ast::ExpressionNode::Hash(hash) => hash.pairs.last().map_or(&hash.token, |(_, v)| last_token(v)),
+        ast::ExpressionNode::StructLiteral(literal) => match literal.fields.last() {
+            Some((_, value)) => last_token(value),
+            None => last_token(&literal.name),
+        },
+        _ => expression_token(expression),
+    }
+}
+
+fn call_end(call: &ast::CallExpr) -> &token::Token {
+    call.arguments.last().map_or(&call.token, last_token)
+}
+
+fn expression_token(expression: &ast::ExpressionNode) -> &token::Token {
+    match expression {
+        ast::ExpressionNode::Identifier(ident) => &ident.token,
+        ast::ExpressionNode::Literal(literal) => &literal.token,
+        ast::ExpressionNode::Prefix(prefix) => &prefix.token,
+        ast::ExpressionNode::Infix(infix) => &infix.token,
+        ast::ExpressionNode::If(if_expr) => &if_expr.token,
+        ast::ExpressionNode::Function(func) => &func.token,
+        ast::ExpressionNode::Call(call) => &call.token,
+        ast::ExpressionNode::Array(array) => &array.token,
+        ast::ExpressionNode::Hash(hash) => &hash.token,
+        ast::ExpressionNode::Index(index) => &index.token,
+        ast::ExpressionNode::Match(match_expr) => &match_expr.token,
+        ast::ExpressionNode::Try(try_expr) => &try_expr.token,
+        ast::ExpressionNode::StructLiteral(literal) => &literal.token,
+        ast::ExpressionNode::Field(field) => &field.token,
+    }
+}
+
+fn statement_has_annotations(statement: &ast::StatementNode) -> bool {
+    match statement {
+        ast::StatementNode::Let(stmt) => stmt.annotation.is_some() || expression_has_annotations(&stmt.value),
+        ast::StatementNode::Return(stmt) => expression_has_annotations(&stmt.value),
+        ast::StatementNode::Expression(stmt) => expression_has_annotations(&stmt.expression),
+        ast::StatementNode::Impl(stmt) => stmt.methods.iter().any(|(_, method)| function_has_annotations(method)),
+        ast::StatementNode::Yield(stmt) => expression_has_annotations(&stmt.value),
+        ast::StatementNode::For(stmt) => expression_has_annotations(&stmt.iterable) || block_has_annotations(&stmt.body),
+        ast::StatementNode::Import(_) | ast::StatementNode::Struct(_) => false,
+    }
+}
+
+fn block_has_annotations(block: &ast::BlockStatement) -> bool {
+    block.statements.iter().any(statement_has_annotations)
+}
+
+fn function_has_annotations(func: &ast::FunctionExpr) -> bool {
+    func.return_type.is_some() || func.parameter_types.iter().any(Option::is_some) || block_has_annotations(&func.body)
+}
+
+fn expression_has_annotations(expression: &ast::ExpressionNode) -> bool {
+    match expression {
+        ast::ExpressionNode::Identifier(_) | ast::ExpressionNode::Literal(_) => false,
+        ast::ExpressionNode::Prefix(prefix) => expression_has_annotations(&prefix.right),
+        ast::ExpressionNode::Infix(infix) => {
+            expression_has_annotations(&infix.left) || expression_has_annotations(&infix.right)
+        }
+        ast::ExpressionNode::If(if_expr) => {
+            expression_has_annotations(&if_expr.condition)
+                || block_has_annotations(&if_expr.consequence)
+                || if_expr.alternative.as_ref().is_some_and(block_has_annotations)
+        }
+        ast::ExpressionNode::Function(func) => function_has_annotations(func),
+        ast::ExpressionNode::Call(call) => {
+            expression_has_annotations(&call.function) || call.arguments.iter().any(expression_has_annotations)
+        }
+        ast::ExpressionNode::Array(array) => array.elements.iter().any(expression_has_annotations),
+        ast::ExpressionNode::Hash(hash) => hash
+            .pairs
+            .iter()
+            .any(|(k, v)| expression_has_annotations(k) || expression_has_annotations(v)),
+        ast::ExpressionNode::Index(index) => {
+            expression_has_annotations(&index.left) || expression_has_annotations(&index.index)
+        }
+        ast::ExpressionNode::Match(match_expr) => {
+            expression_has_annotations(&match_expr.value)
+                || match_expr.arms.iter().any(|arm| {
+                    arm.guard.as_ref().is_some_and(expression_has_annotations) || block_has_annotations(&arm.body)
+                })
+        }
+        ast::ExpressionNode::Try(try_expr) => {
+            block_has_annotations(&try_expr.body) || block_has_annotations(&try_expr.handler)
+        }
+        ast::ExpressionNode::StructLiteral(literal) => {
+            literal.fields.iter().any(|(_, v)| expression_has_annotations(v))
+        }
+        ast::ExpressionNode::Field(field) => expression_has_annotations(&field.left),
+    }
+}
+
+#[cfg(test)]
+fn checked(source: &str) -> Checker {
+    use crate::{lexer, parser};
+
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert!(!p.has_errors(), "{:?}", p.errors());
+    let mut checker = Checker::new();
+    checker.program(&program);
+    return checker;
+}
+
+#[rstest]
+#[case("let x = 5;", "x", "int")]
+#[case("let s = \"a\" + \"b\";", "s", "string")]
+#[case("let n = len(\"abc\") * 2;", "n", "int")]
+#[case("let f = fn(a: int, b: int) { a + b };", "f", "fn(int, int) -> int")]
+#[case("let f = fn(a: int, b: string) -> bool { a > len(b) };", "f", "fn(int, string) -> bool")]
+#[case("let id = fn(x) { x };", "id", "fn(a) -> a")]
+#[case("let add = fn(a, b) { a + b };", "add", "fn(a, b) -> any")]
+#[case("let show = fn(x) { if (type(x) == \"INTEGER\") { x + 1 } else { x + \"!\" } };", "show", "fn(any) -> any")]
+#[case("let xs = [];", "xs", "[a]")]
+#[case("let xs = push([], 1);", "xs", "[int]")]
+#[case("let h = {\"a\": [1], \"b\": []};", "h", "{string: [int]}")]
+#[case("let m = [1, \"a\"];", "m", "[any]")]
+#[case("let y = if (true) { 1 } else { 2 };", "y", "int")]
+#[case("let y = if (true) { 1 };", "y", "any")]
+#[case("let fact = fn(n: int) { if (n < 2) { return 1; } n * fact(n - 1) };", "fact", "fn(int) -> int")]
+#[case("let f = fn(g: fn(int) -> string) { g(1) };", "f", "fn(fn(int) -> string) -> string")]
+#[case("let apply = fn(g: fn(int) -> int, x: int) { g(x) }; let r = apply(fn(n: int) { n * 2 }, 3);", "r", "int")]
+#[case("let p = first([[1]]);", "p", "[int]")]
+#[case("struct P { x } let p: P = P { x: 1 };", "p", "P")]
+#[case("let pick = fn(a: bool) -> [int] { if (a) { return [1]; }; [] };", "pick", "fn(bool) -> [int]")]
+#[case("let empty = fn(x: int) { [] }; let a = push(empty(1), 1); let b = push(empty(1), \"s\");", "b", "[string]")]
+#[case("let x = match [1, 2] { [a, ..rest] => rest, _ => [] };", "x", "[int]")]
+#[case("let x = match {\"k\": true} { {\"k\": v} => v };", "x", "bool")]
+#[case("let e = try { 1 } catch (e) { 2 };", "e", "int")]
+#[case("let r = try { 1 } catch (e) { e };", "r", "any")]
+#[case("import \"lib/math\"; let sq = math.square;", "sq", "any")]
+#[case("let f = fn(x) { x }; let y = f(1) + 1;", "y", "int")]
+#[case("let count = fn(n) { if (n == 0) { return \"done\"; } count(n - 1) };", "count", "fn(int) -> string")]
+#[case("let g = fn*(n: int) -> int { yield n; return \"done\"; };", "g", "fn(int) -> any")]
+#[case("let xs = collect(take(iter([1]), 1));", "xs", "[any]")]
+fn test_infer(#[case] source: &str, #[case] name: &str, #[case] expected: &str) {
+    let checker = checked(source);
+    assert!(checker.diagnostics.is_empty(), "{:?}", checker.diagnostics);
+    let scheme = checker.scopes[1][name].clone();
+    assert_eq!(checker.show(&[&scheme.ty])[0], expected);
+}
+
+#[rstest]
+#[case("let x: int = \"five\";", &[(1, 14, 6, "expected int, found string")])]
+#[case("let f = fn(a: int) { a }; f(\"x\")", &[(1, 29, 3, "expected int, found string")])]
+#[case("let f = fn(a: int) { a }; f(1, 2)", &[(1, 27, 6, "wrong number of arguments: want=1, got=2")])]
+#[case("let f = fn(a: int) -> string { a }", &[(1, 32, 1, "expected string, found int")])]
+#[case("let f = fn(a: int) -> string {\n  if (a > 0) { return \"pos\"; }\n  return a;\n}", &[(3, 10, 1, "expected string, found int")])]
+#[case("let f = fn() -> int {}", &[(1, 9, 2, "expected int, found null")])]
+#[case("1 + true", &[(1, 5, 4, "expected int, found bool")])]
+#[case("true + false", &[(1, 1, 12, "`+` needs two ints or two strings, found bool and bool")])]
+#[case("\"n: \" + 1 * 2", &[(1, 9, 5, "expected string, found int")])]
+#[case("-\"a\"", &[(1, 2, 3, "expected int, found string")])]
+#[case("let x: [int] = [1, 2]; x[\"a\"]", &[(1, 26, 3, "expected int, found string")])]
+#[case("let x: {string: int} = {}; x[1]", &[(1, 30, 1, "expected string, found int")])]
+#[case("let s = \"abc\"; s[0]", &[(1, 16, 3, "index operator not supported: string[int]")])]
+#[case("let x = 5; x(1)", &[(1, 12, 1, "not a function: int")])]
+#[case("let x: foo = 1;", &[(1, 8, 3, "unknown type `foo`")])]
+#[case("let n: int = 1; n.x", &[(1, 17, 3, "int has no field `x`")])]
+#[case("let k = [1]; {k: 1}", &[(1, 15, 1, "unusable as hash key: [int]")])]
+#[case("let f: fn(int) -> int = fn(s: string) { 1 };", &[(1, 25, 2, "expected fn(int) -> int, found fn(string) -> int")])]
+#[case("let xs: [string] = push([], 1);", &[(1, 20, 10, "expected [string], found [int]")])]
+#[case("let g = fn(f: fn(int) -> int) { f(1) }; g(fn(s: string) { s })", &[(1, 43, 2, "expected fn(int) -> int, found fn(string) -> string")])]
+#[case("struct P { x } impl P { fn get(self) -> int { self + 1 } }", &[(1, 47, 4, "expected int, found P")])]
+#[case("let xs: [int] = [1]; for (x in xs) { let y: string = x; }", &[(1, 54, 1, "expected string, found int")])]
+#[case("for (c in \"ab\") { c + 1 }; for (x in 5) {}", &[(1, 23, 1, "expected string, found int"), (1, 38, 1, "int is not iterable")])]
+#[case("let arr: [int] = [1, 2, \"3\"];", &[(1, 25, 3, "expected int, found string")])]
+#[case("let h: {string: int} = {\"a\": 1, \"b\": true};", &[(1, 38, 4, "expected int, found bool")])]
+#[case("let k = fn(x){ x + 1 }; let s: string = k(1);", &[(1, 41, 3, "expected string, found int")])]
+#[case("let f = fn() -> [string] { return [\"a\", 1]; };", &[(1, 41, 1, "expected string, found int")])]
+#[case("let a: int = 1;\nlet b: string = a;\nlet c: bool = b;", &[(2, 17, 1, "expected string, found int"), (3, 15, 1, "expected bool, found string")])]
+fn test_check_errors(#[case] source: &str, #[case] expected: &[(usize, usize, usize, &str)]) {
+    let checker = checked(source);
+    let diagnostics: Vec<(usize, usize, usize, &str)> = checker
+        .diagnostics
+        .iter()
+        .map(|d| (d.line, d.column, d.length, d.message.as_str()))
+        .collect();
+    assert_eq!(diagnostics, expected);
+}
+
+/// Programs written without annotations check cleanly even where a static
+/// type system would turn them down.
+#[rstest]
+#[case("let f = fn(x) { if (x) { 1 } else { \"a\" } }; f(true) + 1")]
+#[case("let a = [1, \"two\", [3]]; a[2][0]")]
+#[case("let add = fn(a, b) { a + b }; add(1, 2) + add(\"a\", \"b\")")]
+#[case("let h = {1: \"a\", \"b\": 2}; h[1]")]
+#[case("match [1, \"a\"] { [x, y] => y, _ => 0 }")]
+#[case("let f = fn() { g() }; let g = fn() { 1 }; f()")]
+#[case("let p = fn(x) { x.y }; p(1)")]
+#[case("struct V { x } impl V { fn twice(self) { self.x * 2 } }; V { x: 2 }.twice()")]
+#[case("import \"lib/math\" as m; m.square(2) + 1")]
+#[case("let count = fn(n) { if (n == 0) { return \"done\"; } count(n - 1) }; count(3)")]
+#[case("try { throw(\"x\") } catch (e) { e[\"message\"] }")]
+#[case("let show = fn(x) { if (type(x) == \"INTEGER\") { x + 1 } else { x + \"!\" } }; show(1); show(\"a\")")]
+#[case("let limit: int = 3; let a = [1, \"two\", [3]]; puts(a[2][0]); let h = {1: \"a\", \"b\": 2}; let f = fn(x) { if (x) { 1 } else { \"a\" } }; f(true) + limit")]
+fn test_dynamic_code_checks(#[case] source: &str) {
+    let checker = checked(source);
+    assert!(checker.diagnostics.is_empty(), "{:?}", checker.diagnostics);
+}
+
+#[rstest]
+#[case("let x = 1; fn(a, b) { [a, b] }", false)]
+#[case("let x: int = 1;", true)]
+#[case("let f = fn(a) -> int { 1 };", true)]
+#[case("match 1 { _ => fn(a: int) { a } }", true)]
+#[case("struct P { x } impl P { fn get(self, n: int) { n } }", true)]
+fn test_has_annotations(#[case] source: &str, #[case] expected: bool) {
+    use crate::{lexer, parser};
+
+    let program = parser::Parser::new(lexer::Lexer::new(source.to_string())).parse_program();
+    assert_eq!(has_annotations(&program), expected);
+}
@@ -0,0 +1,45 @@
+use std::fs;
+use std::process;
+
+use writing_an_interpreter_in_rust::checker;
+use writing_an_interpreter_in_rust::lexer::Lexer;
+use writing_an_interpreter_in_rust::parser::Parser;
+
+pub fn check_command() -> seahorse::Command {
+    seahorse::Command::new("check")
+        .description("Type checks a script file without running it")
+        .usage("cli check [file]")
+        .action(|c| {
+            let Some(file) = c.args.first() else {
+                eprintln!("You need to provide a file to check");
+                process::exit(2);
+            };
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("error: could not read {}: {}", file, e);
+                    process::exit(2);
+                }
+            };
+
+            let mut parser = Parser::new(Lexer::new(source.clone()));
+            let program = parser.parse_program();
+            for w in parser.warnings().iter() {
+                eprint!("{}", w.render("warning", file, &source));
+            }
+            if parser.has_errors() {
+                for e in parser.errors().iter() {
+                    eprint!("{}", e.render("error", file, &source));
+                }
+                process::exit(1);
+            }
+
+            let errors = checker::check(&program);
+            for e in errors.iter() {
+                eprint!("{}", e.render("error", file, &source));
+            }
+            if !errors.is_empty() {
+                process::exit(1);
+            }
+        })
+}
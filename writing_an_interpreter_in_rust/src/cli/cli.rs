@@ -1,6 +1,13 @@
+use super::check::check_command;
+use super::debug::debug_command;
 use super::repl::repl_command;
+use super::run::run_command;
+use super::test::test_command;
+use super::transpile::transpile_command;
+use writing_an_interpreter_in_rust::evaluator::STACK_PER_CALL;
 use seahorse::App;
 use std::env;
+use std::thread;
 
 pub fn run() {
     let args: Vec<String> = env::args().collect();
@@ -9,7 +16,25 @@ pub fn run() {
         .author(env!("CARGO_PKG_AUTHORS"))
         .version(env!("CARGO_PKG_VERSION"))
         .usage("cli [args]")
-        .command(repl_command());
+        .command(repl_command())
+        .command(run_command())
+        .command(debug_command())
+        .command(test_command())
+        .command(transpile_command())
+        .command(check_command());
 
     app.run(args);
 }
+
+/// Runs `f` on a thread with enough stack for `max_call_depth` nested script
+/// calls, so running out is reported as a script error and not a crash.
+pub fn with_call_stack<T: Send>(max_call_depth: usize, f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(max_call_depth.saturating_mul(STACK_PER_CALL).max(8 << 20))
+            .spawn_scoped(scope, f)
+            .expect("failed to spawn evaluator thread")
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    })
+}
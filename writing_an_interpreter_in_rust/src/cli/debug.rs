@@ -0,0 +1,52 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+use super::cli::with_call_stack;
+use writing_an_interpreter_in_rust::debugger::Debugger;
+use writing_an_interpreter_in_rust::evaluator::{Evaluator, DEFAULT_MAX_CALL_DEPTH};
+use writing_an_interpreter_in_rust::lexer::Lexer;
+use writing_an_interpreter_in_rust::parser::Parser;
+
+pub fn debug_command() -> seahorse::Command {
+    seahorse::Command::new("debug")
+        .description("Runs a script file under the step debugger")
+        .usage("cli debug [file]")
+        .action(|c| {
+            let Some(file) = c.args.first() else {
+                eprintln!("You need to provide a file to debug");
+                process::exit(2);
+            };
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("error: could not read {}: {}", file, e);
+                    process::exit(2);
+                }
+            };
+
+            with_call_stack(DEFAULT_MAX_CALL_DEPTH, || {
+                let mut parser = Parser::new(Lexer::new(source.clone()));
+                let program = parser.parse_program();
+                if parser.has_errors() {
+                    for e in parser.errors().iter() {
+                        eprint!("{}", e.render("error", file, &source));
+                    }
+                    process::exit(1);
+                }
+
+                println!("debugging {}, type `help` for commands", file);
+                let debugger = Debugger::new(io::stdin().lock(), io::stdout());
+                let mut evaluator = Evaluator::new().with_debug_hook(debugger);
+                match evaluator.eval_module(Path::new(file), &program) {
+                    Ok(_) => println!("program finished"),
+                    Err(_) if evaluator.is_halted() => {}
+                    Err(e) => {
+                        eprint!("{}", evaluator.render_error(&e, &source));
+                        process::exit(1);
+                    }
+                }
+            });
+        })
+}
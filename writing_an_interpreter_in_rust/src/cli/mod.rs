@@ -1,2 +1,7 @@
 pub mod cli;
 mod repl;
+mod run;
+mod debug;
+mod test;
+mod transpile;
+mod check;
@@ -1,8 +1,11 @@
 use std::io::{self, Write};
-use crate::lexer::Lexer;
-use crate::parser::Parser;
+use writing_an_interpreter_in_rust::environment::Environment;
+use super::cli::with_call_stack;
+use writing_an_interpreter_in_rust::evaluator::{Evaluator, DEFAULT_MAX_CALL_DEPTH};
+use writing_an_interpreter_in_rust::lexer::Lexer;
+use writing_an_interpreter_in_rust::parser::Parser;
 
-pub fn repl(eval: fn(&str) -> String) {
+pub fn repl(mut eval: impl FnMut(&str) -> String) {
     loop {
         print!("> ");
         // Flush stdout to ensure the prompt is displayed
@@ -37,7 +40,7 @@ fn lex(input: &str) -> String {
         let token = lexer.next_token();
         tokens.push(token.clone());
 
-        if token.token_type == crate::token::TokenType::EOF {
+        if token.token_type == writing_an_interpreter_in_rust::token::TokenType::EOF {
             break;
         }
     }
@@ -49,7 +52,7 @@ fn parse(input: &str) -> String {
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
     if parser.has_errors() {
-        let mut s = format!("Parser errors:\n");
+        let mut s = "Parser errors:\n".to_string();
         for e in parser.errors().iter() {
             s.push_str(&format!("\t{}\n", e));
         }
@@ -60,6 +63,31 @@ fn parse(input: &str) -> String {
     format!("{:?}", program)
 }
 
+fn evaluate() -> impl FnMut(&str) -> String {
+    let mut evaluator = Evaluator::new();
+    let env = Environment::new();
+    move |input| {
+        let lexer = Lexer::new(input.to_string());
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        let mut s = String::new();
+        if parser.has_errors() {
+            for e in parser.errors().iter() {
+                s.push_str(&e.render("error", "<main>", input));
+            }
+            return s;
+        }
+        for w in parser.warnings().iter() {
+            s.push_str(&w.render("warning", "<main>", input));
+        }
+        match evaluator.eval_program(&program, &env) {
+            Ok(result) => s.push_str(&result.inspect()),
+            Err(e) => s.push_str(&evaluator.render_error(&e, input)),
+        }
+        return s;
+    }
+}
+
 pub fn repl_command() -> seahorse::Command {
     seahorse::Command::new("repl")
         .description("Starts the REPL")
@@ -75,6 +103,7 @@ pub fn repl_command() -> seahorse::Command {
                     "lex" => repl(lex),
                     "parser" => repl(parse),
                     "parse" => repl(parse),
+                    "eval" => with_call_stack(DEFAULT_MAX_CALL_DEPTH, || repl(evaluate())),
                     _ => println!("Invalid step"),
                 }
                 return;
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use super::cli::with_call_stack;
+use writing_an_interpreter_in_rust::checker;
+use writing_an_interpreter_in_rust::evaluator::{Evaluator, DEFAULT_MAX_CALL_DEPTH};
+use writing_an_interpreter_in_rust::lexer::Lexer;
+use writing_an_interpreter_in_rust::parser::Parser;
+use writing_an_interpreter_in_rust::stdlib::Capabilities;
+
+pub fn run_command() -> seahorse::Command {
+    seahorse::Command::new("run")
+        .description("Runs a script file")
+        .usage("cli run [file] [args...]")
+        .flag(
+            seahorse::Flag::new("max-depth", seahorse::FlagType::Uint)
+                .description("Maximum call depth before a stack overflow error (default 10000)"),
+        )
+        .flag(
+            seahorse::Flag::new("profile", seahorse::FlagType::String)
+                .description("Writes the time spent in each call stack to the given file, in folded format"),
+        )
+        .flag(
+            seahorse::Flag::new("coverage", seahorse::FlagType::String)
+                .description("Writes which lines and branches ran to the given file, in lcov format"),
+        )
+        .flag(
+            seahorse::Flag::new("allow-fs", seahorse::FlagType::Bool)
+                .description("Lets the script import std/fs to read, write and list files"),
+        )
+        .flag(
+            seahorse::Flag::new("allow-env", seahorse::FlagType::Bool)
+                .description("Lets the script import std/env to see environment variables and its arguments"),
+        )
+        .action(|c| {
+            let Some(file) = c.args.first() else {
+                eprintln!("You need to provide a file to run");
+                process::exit(2);
+            };
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("error: could not read {}: {}", file, e);
+                    process::exit(2);
+                }
+            };
+
+            let max_depth = c.uint_flag("max-depth").unwrap_or(DEFAULT_MAX_CALL_DEPTH);
+            let profile = c.string_flag("profile").ok();
+            let coverage = c.string_flag("coverage").ok();
+            let capabilities = Capabilities {
+                fs: c.bool_flag("allow-fs"),
+                env: c.bool_flag("allow-env"),
+                args: c.args[1..].to_vec(),
+            };
+            with_call_stack(max_depth, || {
+                let mut parser = Parser::new(Lexer::new(source.clone()));
+                let program = parser.parse_program();
+                for w in parser.warnings().iter() {
+                    eprint!("{}", w.render("warning", file, &source));
+                }
+                if parser.has_errors() {
+                    for e in parser.errors().iter() {
+                        eprint!("{}", e.render("error", file, &source));
+                    }
+                    process::exit(1);
+                }
+                // Only programs in the typed dialect are held to their types.
+                if checker::has_annotations(&program) {
+                    let errors = checker::check(&program);
+                    for e in errors.iter() {
+                        eprint!("{}", e.render("error", file, &source));
+                    }
+                    if !errors.is_empty() {
+                        process::exit(1);
+                    }
+                }
+
+                let mut evaluator = Evaluator::new()
+                    .with_max_call_depth(max_depth)
+                    .with_capabilities(capabilities);
+                if profile.is_some() {
+                    evaluator = evaluator.with_profiler();
+                }
+                if coverage.is_some() {
+                    evaluator = evaluator.with_coverage();
+                }
+                let result = evaluator.eval_module(Path::new(file), &program);
+                for warning in evaluator.take_warnings() {
+                    eprint!("{}", warning);
+                }
+                if let (Some(path), Some(profiler)) = (&profile, evaluator.take_profiler()) {
+                    eprint!("{}", profiler.report());
+                    write_report(path, &profiler.folded());
+                }
+                if let (Some(path), Some(coverage)) = (&coverage, evaluator.take_coverage()) {
+                    write_report(path, &coverage.lcov());
+                }
+                if let Err(e) = result {
+                    eprint!("{}", evaluator.render_error(&e, &source));
+                    process::exit(1);
+                }
+            });
+        })
+}
+
+fn write_report(path: &str, contents: &str) {
+    if let Err(e) = fs::write(path, contents) {
+        eprintln!("error: could not write {}: {}", path, e);
+        process::exit(2);
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use super::cli::with_call_stack;
+use writing_an_interpreter_in_rust::evaluator::{Evaluator, DEFAULT_MAX_CALL_DEPTH};
+use writing_an_interpreter_in_rust::lexer::Lexer;
+use writing_an_interpreter_in_rust::module::SOURCE_EXTENSION;
+use writing_an_interpreter_in_rust::parser::Parser;
+use writing_an_interpreter_in_rust::test_runner::{run_tests, TEST_FILE_SUFFIX};
+
+pub fn test_command() -> seahorse::Command {
+    seahorse::Command::new("test")
+        .description("Runs the tests in *_test files under the given paths")
+        .usage("cli test [paths...]")
+        .action(|c| {
+            let roots = if c.args.is_empty() { vec![".".to_string()] } else { c.args.clone() };
+            let mut files = Vec::new();
+            for root in &roots {
+                find_test_files(Path::new(root), &mut files);
+            }
+            files.sort();
+            if files.is_empty() {
+                println!("no *{}.{} files found", TEST_FILE_SUFFIX, SOURCE_EXTENSION);
+                return;
+            }
+
+            let (passed, failed) = with_call_stack(DEFAULT_MAX_CALL_DEPTH, || {
+                let (mut passed, mut failed) = (0, 0);
+                for file in &files {
+                    let (p, f) = run_file(file);
+                    passed += p;
+                    failed += f;
+                }
+                (passed, failed)
+            });
+            println!("\n{} passed, {} failed", passed, failed);
+            if failed > 0 {
+                process::exit(1);
+            }
+        })
+}
+
+fn is_test_file(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == SOURCE_EXTENSION)
+        && path
+            .file_stem()
+            .is_some_and(|s| s.to_string_lossy().ends_with(TEST_FILE_SUFFIX))
+}
+
+fn find_test_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        eprintln!("error: could not read {}", path.display());
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if path.is_dir() && !hidden && entry.file_name() != "target" {
+            find_test_files(&path, files);
+        } else if is_test_file(&path) {
+            files.push(path);
+        }
+    }
+}
+
+/// Runs one test file and prints its results. A file that doesn't parse or
+/// fails outside of a test counts as a single failure.
+fn run_file(file: &Path) -> (usize, usize) {
+    let name = file.strip_prefix(".").unwrap_or(file).display().to_string();
+    println!("{}", name);
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => {
+            println!("  error: could not read {}: {}", name, e);
+            return (0, 1);
+        }
+    };
+    let mut parser = Parser::new(Lexer::new(source.clone()));
+    let program = parser.parse_program();
+    if parser.has_errors() {
+        for e in parser.errors().iter() {
+            print!("{}", indent(&e.render("error", &name, &source)));
+        }
+        return (0, 1);
+    }
+
+    let mut evaluator = Evaluator::new();
+    let reports = match run_tests(&mut evaluator, file, &program) {
+        Ok(reports) => reports,
+        Err(e) => {
+            print!("{}", indent(&evaluator.render_error(&e, &source)));
+            return (0, 1);
+        }
+    };
+    let (mut passed, mut failed) = (0, 0);
+    for report in reports {
+        match report.error {
+            None => {
+                println!("  PASS {}", report.name);
+                passed += 1;
+            }
+            Some(e) => {
+                println!("  FAIL {}", report.name);
+                print!("{}", indent(&indent(&evaluator.render_error(&e, &source))));
+                failed += 1;
+            }
+        }
+    }
+    return (passed, failed);
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("  {}\n", line)).collect()
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::process;
+
+use writing_an_interpreter_in_rust::lexer::Lexer;
+use writing_an_interpreter_in_rust::parser::Parser;
+use writing_an_interpreter_in_rust::transpile;
+
+const TARGETS: &[&str] = &["js"];
+
+pub fn transpile_command() -> seahorse::Command {
+    seahorse::Command::new("transpile")
+        .description("Compiles a script file to another language")
+        .usage("cli transpile [file] --target js [--output path]")
+        .flag(
+            seahorse::Flag::new("target", seahorse::FlagType::String)
+                .description("The language to compile to (default js)"),
+        )
+        .flag(
+            seahorse::Flag::new("output", seahorse::FlagType::String)
+                .description("Where to write the result (default stdout)")
+                .alias("o"),
+        )
+        .action(|c| {
+            let Some(file) = c.args.first() else {
+                eprintln!("You need to provide a file to transpile");
+                process::exit(2);
+            };
+            let target = c.string_flag("target").unwrap_or_else(|_| "js".to_string());
+            if !TARGETS.contains(&target.as_str()) {
+                eprintln!("error: unknown target `{}`, expected one of: {}", target, TARGETS.join(", "));
+                process::exit(2);
+            }
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("error: could not read {}: {}", file, e);
+                    process::exit(2);
+                }
+            };
+
+            let mut parser = Parser::new(Lexer::new(source.clone()));
+            let program = parser.parse_program();
+            for w in parser.warnings().iter() {
+                eprint!("{}", w.render("warning", file, &source));
+            }
+            if parser.has_errors() {
+                for e in parser.errors().iter() {
+                    eprint!("{}", e.render("error", file, &source));
+                }
+                process::exit(1);
+            }
+
+            let output = match transpile::javascript(&program) {
+                Ok(output) => output,
+                Err(e) => {
+                    eprint!("{}", e.render("error", file, &source));
+                    process::exit(1);
+                }
+            };
+            match c.string_flag("output") {
+                Ok(path) => {
+                    if let Err(e) = fs::write(&path, output) {
+                        eprintln!("error: could not write {}: {}", path, e);
+                        process::exit(2);
+                    }
+                }
+                Err(_) => print!("{}", output),
+            }
+        })
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use crate::ast;
+use crate::token;
+use rstest::rstest;
+
+/// What ran in one file. Statements and branch points are keyed by the line
+/// and column of their token.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileCoverage {
+    pub statements: BTreeMap<(usize, usize), u64>,
+    /// How often each arm of an `if` or `match` was taken. An `if` always
+    /// has two, the second being its `else` whether it's written or not.
+    pub branches: BTreeMap<(usize, usize), Vec<u64>>,
+}
+
+impl FileCoverage {
+    /// Hit counts per line. A line with several statements counts the one
+    /// that ran most, so a line that ran once shows as run once.
+    pub fn lines(&self) -> BTreeMap<usize, u64> {
+        let mut lines = BTreeMap::new();
+        for ((line, _), hits) in &self.statements {
+            let count = lines.entry(*line).or_insert(0);
+            *count = (*count).max(*hits);
+        }
+        return lines;
+    }
+}
+
+/// Records which statements and branches of a script ran. Files are
+/// registered as they're loaded, so the report also lists the code that
+/// never ran at all.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    files: BTreeMap<String, FileCoverage>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// Adds every statement and branch point in `program` with no hits yet.
+    pub fn register(&mut self, file: &str, program: &ast::ProgramNode) {
+        let coverage = self.files.entry(file.to_string()).or_default();
+        for statement in &program.statements {
+            register_statement(coverage, statement);
+        }
+    }
+
+    pub fn statement(&mut self, file: &str, token: &token::Token) {
+        let coverage = self.files.entry(file.to_string()).or_default();
+        *coverage.statements.entry((token.line, token.column)).or_insert(0) += 1;
+    }
+
+    pub fn branch(&mut self, file: &str, token: &token::Token, arm: usize, arms: usize) {
+        let coverage = self.files.entry(file.to_string()).or_default();
+        let hits = coverage
+            .branches
+            .entry((token.line, token.column))
+            .or_insert_with(|| vec![0; arms]);
+        hits[arm] += 1;
+    }
+
+    pub fn files(&self) -> &BTreeMap<String, FileCoverage> {
+        &self.files
+    }
+
+    /// The report in the lcov tracefile format editors and `genhtml` read.
+    pub fn lcov(&self) -> String {
+        let mut result = String::new();
+        for (file, coverage) in &self.files {
+            result.push_str(&format!("TN:\nSF:{}\n", file));
+            let (mut found, mut hit) = (0, 0);
+            for (block, ((line, _), arms)) in coverage.branches.iter().enumerate() {
+                // A branch point that was never reached has no taken count.
+                let reached = arms.iter().any(|hits| *hits > 0);
+                for (branch, hits) in arms.iter().enumerate() {
+                    let taken = match reached {
+                        true => hits.to_string(),
+                        false => "-".to_string(),
+                    };
+                    result.push_str(&format!("BRDA:{},{},{},{}\n", line, block, branch, taken));
+                    found += 1;
+                    hit += (*hits > 0) as usize;
+                }
+            }
+            result.push_str(&format!("BRF:{}\nBRH:{}\n", found, hit));
+            let lines = coverage.lines();
+            for (line, hits) in &lines {
+                result.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            let lines_hit = lines.values().filter(|hits| **hits > 0).count();
+            result.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), lines_hit));
+        }
+        return result;
+    }
+}
+
+fn statement_token(statement: &ast::StatementNode) -> &token::Token {
+    match statement {
+        ast::StatementNode::Let(stmt) => &stmt.token,
+        ast::StatementNode::Return(stmt) => &stmt.token,
+        ast::StatementNode::Expression(stmt) => &stmt.token,
+        ast::StatementNode::Import(stmt) => &stmt.token,
+        ast::StatementNode::Struct(stmt) => &stmt.token,
+        ast::StatementNode::Impl(stmt) => &stmt.token,
+        ast::StatementNode::Yield(stmt) => &stmt.token,
+        ast::StatementNode::For(stmt) => &stmt.token,
+    }
+}
+
+fn register_statement(coverage: &mut FileCoverage, statement: &ast::StatementNode) {
+    let token = statement_token(statement);
+    coverage.statements.entry((token.line, token.column)).or_insert(0);
+    match statement {
+        ast::StatementNode::Let(stmt) => register_expression(coverage, &stmt.value),
+        ast::StatementNode::Return(stmt) => register_expression(coverage, &stmt.value),
+        ast::StatementNode::Expression(stmt) => register_expression(coverage, &stmt.expression),
+        ast::StatementNode::Yield(stmt) => register_expression(coverage, &stmt.value),
+        ast::StatementNode::For(stmt) => {
+            register_expression(coverage, &stmt.iterable);
+            register_block(coverage, &stmt.body);
+        }
+        ast::StatementNode::Impl(stmt) => {
+            for (_, func) in &stmt.methods {
+                register_block(coverage, &func.body);
+            }
+        }
+        ast::StatementNode::Import(_) | ast::StatementNode::Struct(_) => {}
+    }
+}
+
+fn register_block(coverage: &mut FileCoverage, block: &ast::BlockStatement) {
+    for statement in &block.statements {
+        register_statement(coverage, statement);
+    }
+}
+
+fn register_expression(coverage: &mut FileCoverage, expression: &ast::ExpressionNode) {
+    match expression {
+        ast::ExpressionNode::Identifier(_) | ast::ExpressionNode::Literal(_) => {}
+        ast::ExpressionNode::Prefix(prefix) => register_expression(coverage, &prefix.right),
+        ast::ExpressionNode::Infix(infix) => {
+            register_expression(coverage, &infix.left);
+            register_expression(coverage, &infix.right);
+        }
+        ast::ExpressionNode::If(if_expr) => {
+            let token = &if_expr.token;
+            coverage.branches.entry((token.line, token.column)).or_insert_with(|| vec![0; 2]);
+            register_expression(coverage, &if_expr.condition);
+            register_block(coverage, &if_expr.consequence);
+            if let Some(alternative) = &if_expr.alternative {
+                register_block(coverage, alternative);
+            }
+        }
+        ast::ExpressionNode::Function(func) => register_block(coverage, &func.body),
+        ast::ExpressionNode::Call(call) => {
+            register_expression(coverage, &call.function);
+            for argument in &call.arguments {
+                register_expression(coverage, argument);
+            }
+        }
+        ast::ExpressionNode::Array(array) => {
+            for element in &array.elements {
+                register_expression(coverage, element);
+            }
+        }
+        ast::ExpressionNode::Hash(hash) => {
+            for (key, value) in &hash.pairs {
+                register_expression(coverage, key);
+                register_expression(coverage, value);
+            }
+        }
+        ast::ExpressionNode::Index(index) => {
+            register_expression(coverage, &index.left);
+            register_expression(coverage, &index.index);
+        }
+        ast::ExpressionNode::Match(match_expr) => {
+            let token = &match_expr.token;
+            let arms = match_expr.arms.len();
+            coverage.branches.entry((token.line, token.column)).or_insert_with(|| vec![0; arms]);
+            register_expression(coverage, &match_expr.value);
+            for arm in &match_expr.arms {
+                if let Some(guard) = &arm.guard {
+                    register_expression(coverage, guard);
+                }
+                register_block(coverage, &arm.body);
+            }
+        }
+        ast::ExpressionNode::Try(try_expr) => {
+            register_block(coverage, &try_expr.body);
+            register_block(coverage, &try_expr.handler);
+        }
+        ast::ExpressionNode::StructLiteral(literal) => {
+            register_expression(coverage, &literal.name);
+            for (_, value) in &literal.fields {
+                register_expression(coverage, value);
+            }
+        }
+        ast::ExpressionNode::Field(field) => register_expression(coverage, &field.left),
+    }
+}
+
+#[cfg(test)]
+fn registered(source: &str) -> Coverage {
+    use crate::{lexer, parser};
+
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert!(!p.has_errors(), "{:?}", p.errors());
+    let mut coverage = Coverage::new();
+    coverage.register("main.wl", &program);
+    return coverage;
+}
+
+#[rstest]
+#[case("let a = 1;\nputs(a);", &[(1, 0), (2, 0)], &[])]
+#[case("let f = fn(x) {\n  if (x) { 1 } else { 2 }\n};", &[(1, 0), (2, 0)], &[(2, 2)])]
+#[case("match 1 {\n  1 => { puts(1) },\n  _ => 2\n}", &[(1, 0), (2, 0), (3, 0)], &[(1, 2)])]
+#[case("struct P { x }\nimpl P {\n  fn get(self) {\n    self.x\n  }\n}", &[(1, 0), (2, 0), (4, 0)], &[])]
+#[case("try {\n  1\n} catch (e) {\n  2\n}", &[(1, 0), (2, 0), (4, 0)], &[])]
+fn test_register(#[case] source: &str, #[case] lines: &[(usize, u64)], #[case] branches: &[(usize, usize)]) {
+    let coverage = registered(source);
+    let file = &coverage.files()["main.wl"];
+    assert_eq!(file.lines(), lines.iter().cloned().collect::<BTreeMap<usize, u64>>());
+    let arms: Vec<(usize, usize)> = file.branches.iter().map(|((line, _), arms)| (*line, arms.len())).collect();
+    assert_eq!(arms, branches);
+}
+
+#[test]
+fn test_lcov() {
+    let mut coverage = registered("let f = fn(x) {\n  if (x) { 1 } else { 2 }\n};\nif (true) { 1 };\nf(true);");
+    let at = |line, column| token::Token {
+        token_type: token::TokenType::If,
+        literal: "if".to_string(),
+        line,
+        column,
+    };
+    for line in [1, 4, 5] {
+        coverage.statement("main.wl", &at(line, 1));
+    }
+    coverage.statement("main.wl", &at(2, 3));
+    coverage.statement("main.wl", &at(2, 12));
+    coverage.branch("main.wl", &at(2, 3), 0, 2);
+    assert_eq!(
+        coverage.lcov(),
+        "TN:\nSF:main.wl\n\
+         BRDA:2,0,0,1\nBRDA:2,0,1,0\nBRDA:4,1,0,-\nBRDA:4,1,1,-\nBRF:4\nBRH:1\n\
+         DA:1,1\nDA:2,1\nDA:4,1\nDA:5,1\nLF:4\nLH:4\nend_of_record\n"
+    );
+}
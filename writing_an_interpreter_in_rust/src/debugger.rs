@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::environment::Env;
+use crate::evaluator::{DebugHook, Evaluator, Location};
+use rstest::rstest;
+
+const HELP: &str = "\
+commands:
+  s, step            run to the next statement, entering calls
+  n, next            run to the next statement in this frame, stepping over calls
+  o, out             run until the current function returns
+  c, continue        run until the next breakpoint
+  b, break [FILE:]N  set a breakpoint on line N
+  clear [FILE:]N     remove a breakpoint
+  p, print EXPR      evaluate EXPR in the paused frame
+  env                print the scopes visible from the paused frame
+  bt, where          print the call stack
+  l, list            print the source around the paused line
+  q, quit            stop the program";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StepMode {
+    Continue,
+    Into,
+    /// Pause at the next statement at most this deep.
+    Over(usize),
+    /// Pause at the next statement shallower than this.
+    Out(usize),
+}
+
+/// A line breakpoint. Without a file it's in the program's entry file.
+#[derive(Debug, Clone, PartialEq)]
+struct Breakpoint {
+    file: Option<PathBuf>,
+    line: usize,
+}
+
+impl Breakpoint {
+    fn parse(spec: &str) -> Option<Breakpoint> {
+        let (file, line) = match spec.rsplit_once(':') {
+            Some((file, line)) => (Some(PathBuf::from(file)), line),
+            None => (None, spec),
+        };
+        let line = line.trim().parse().ok()?;
+        return Some(Breakpoint { file, line });
+    }
+
+    fn matches(&self, location: &Location, main: Option<&Path>) -> bool {
+        if self.line != location.line {
+            return false;
+        }
+        let file = location.file.as_deref().map(|f| f.as_path());
+        match &self.file {
+            Some(wanted) => file.is_some_and(|f| f.ends_with(wanted)),
+            None => file == main,
+        }
+    }
+}
+
+/// An interactive, line based debugger driven by commands read from `input`.
+/// It pauses before the first statement so breakpoints can be set.
+pub struct Debugger {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    breakpoints: Vec<Breakpoint>,
+    mode: StepMode,
+    main: Option<Rc<PathBuf>>,
+    /// The frame depth and line last paused at, until execution moves on
+    /// from it. Keeps a breakpoint from firing for every statement on its line.
+    last_stop: Option<(usize, usize)>,
+    sources: HashMap<PathBuf, Vec<String>>,
+}
+
+impl Debugger {
+    pub fn new(input: impl BufRead + 'static, output: impl Write + 'static) -> Debugger {
+        Debugger {
+            input: Box::new(input),
+            output: Box::new(output),
+            breakpoints: Vec::new(),
+            mode: StepMode::Into,
+            main: None,
+            last_stop: None,
+            sources: HashMap::new(),
+        }
+    }
+
+    fn should_pause(&self, location: &Location) -> bool {
+        let at_breakpoint = self.last_stop != Some((location.depth, location.line))
+            && self
+                .breakpoints
+                .iter()
+                .any(|b| b.matches(location, self.main.as_deref().map(|m| m.as_path())));
+        match self.mode {
+            StepMode::Into => true,
+            StepMode::Over(depth) => location.depth <= depth || at_breakpoint,
+            StepMode::Out(depth) => location.depth < depth || at_breakpoint,
+            StepMode::Continue => at_breakpoint,
+        }
+    }
+
+    fn source_line(&mut self, evaluator: &Evaluator, file: Option<&Path>, line: usize) -> Option<String> {
+        let file = file?;
+        if !self.sources.contains_key(file) {
+            let source = evaluator.read_source(file).unwrap_or_default();
+            self.sources
+                .insert(file.to_path_buf(), source.lines().map(|l| l.to_string()).collect());
+        }
+        return self.sources[file].get(line.checked_sub(1)?).cloned();
+    }
+
+    fn print_location(&mut self, evaluator: &Evaluator, location: &Location) {
+        let file = location.file.as_deref().map(|f| f.as_path());
+        let name = file.map_or("<main>".to_string(), |f| f.display().to_string());
+        let _ = writeln!(self.output, "stopped at {}:{}:{}", name, location.line, location.column);
+        if let Some(text) = self.source_line(evaluator, file, location.line) {
+            let _ = writeln!(self.output, "{:>4} | {}", location.line, text);
+        }
+    }
+
+    fn list(&mut self, evaluator: &Evaluator, location: &Location) {
+        let file = location.file.as_deref().map(|f| f.as_path());
+        for line in location.line.saturating_sub(3).max(1)..=location.line + 3 {
+            let Some(text) = self.source_line(evaluator, file, line) else {
+                break;
+            };
+            let marker = if line == location.line { ">" } else { " " };
+            let _ = writeln!(self.output, "{}{:>4} | {}", marker, line, text);
+        }
+    }
+
+    fn print_env(&mut self, env: &Env) {
+        let mut scope = Some(env.clone());
+        let mut level = 0;
+        while let Some(current) = scope {
+            let current = current.borrow();
+            let outermost = current.outer().is_none();
+            let _ = writeln!(self.output, "#{}{}", level, if outermost { " (module)" } else { "" });
+            for (name, value) in current.bindings() {
+                let _ = writeln!(self.output, "  {} = {}", name, value.inspect());
+            }
+            scope = current.outer();
+            level += 1;
+        }
+    }
+
+    /// Reads and runs commands until one resumes the program. Returns
+    /// whether it should keep running.
+    fn prompt(&mut self, evaluator: &mut Evaluator, location: &Location, env: &Env) -> bool {
+        loop {
+            let _ = write!(self.output, "(debug) ");
+            let _ = self.output.flush();
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => return false,
+                Ok(_) => {}
+            }
+            let line = line.trim();
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let argument = argument.trim();
+            match command {
+                "" => {}
+                "s" | "step" => {
+                    self.mode = StepMode::Into;
+                    return true;
+                }
+                "n" | "next" => {
+                    self.mode = StepMode::Over(location.depth);
+                    return true;
+                }
+                "o" | "out" => {
+                    self.mode = StepMode::Out(location.depth);
+                    return true;
+                }
+                "c" | "continue" => {
+                    self.mode = StepMode::Continue;
+                    return true;
+                }
+                "q" | "quit" => return false,
+                "b" | "break" | "clear" => match Breakpoint::parse(argument) {
+                    Some(breakpoint) if command == "clear" => {
+                        self.breakpoints.retain(|b| *b != breakpoint);
+                        let _ = writeln!(self.output, "breakpoint cleared");
+                    }
+                    Some(breakpoint) => {
+                        let _ = writeln!(self.output, "breakpoint set at line {}", breakpoint.line);
+                        self.breakpoints.push(breakpoint);
+                    }
+                    None => {
+                        let _ = writeln!(self.output, "expected a line, like `{} 12` or `{} lib.wl:12`", command, command);
+                    }
+                },
+                "p" | "print" => {
+                    let text = match evaluator.eval_source(argument, env) {
+                        Ok(value) => value.inspect(),
+                        Err(error) => format!("error: {}", error.message),
+                    };
+                    let _ = writeln!(self.output, "{}", text);
+                }
+                "env" => self.print_env(env),
+                "bt" | "where" => {
+                    for frame in evaluator.backtrace((location.line, location.column)) {
+                        let _ = writeln!(self.output, "  {}", frame);
+                    }
+                }
+                "l" | "list" => self.list(evaluator, location),
+                "h" | "help" => {
+                    let _ = writeln!(self.output, "{}", HELP);
+                }
+                other => {
+                    let _ = writeln!(self.output, "unknown command `{}`, try `help`", other);
+                }
+            }
+        }
+    }
+}
+
+impl DebugHook for Debugger {
+    fn before_statement(&mut self, evaluator: &mut Evaluator, location: &Location, env: &Env) -> bool {
+        if self.main.is_none() {
+            self.main = location.file.clone();
+        }
+        if self.last_stop != Some((location.depth, location.line)) {
+            self.last_stop = None;
+        }
+        if !self.should_pause(location) {
+            return true;
+        }
+        self.last_stop = Some((location.depth, location.line));
+        self.print_location(evaluator, location);
+        return self.prompt(evaluator, location, env);
+    }
+}
+
+#[cfg(test)]
+fn debug_session(commands: &str) -> String {
+    use crate::evaluator::CapturedOutput;
+    use crate::module::{MemoryLoader, SourceLoader};
+    use crate::{lexer, parser};
+
+    let loader = MemoryLoader::default()
+        .with(
+            "main.wl",
+            "let add = fn(a, b) {\n  let sum = a + b;\n  sum\n};\nlet x = add(1, 2);\nlet y = add(x, 3);\nputs(y);\n",
+        );
+    let source = loader.read(Path::new("main.wl")).unwrap();
+    let output = CapturedOutput::default();
+    let debugger = Debugger::new(std::io::Cursor::new(commands.to_string()), output.clone());
+    let mut evaluator = Evaluator::new()
+        .with_loader(loader)
+        .with_output(output.clone())
+        .with_debug_hook(debugger);
+    let program = parser::Parser::new(lexer::Lexer::new(source)).parse_program();
+    if let Err(error) = evaluator.eval_module(Path::new("main.wl"), &program) {
+        let _ = writeln!(output.clone(), "{}", error.message);
+    }
+    return output.contents();
+}
+
+#[rstest]
+#[case(
+    "s\ns\ns\n",
+    "stopped at main.wl:1:1\n   1 | let add = fn(a, b) {\n(debug) \
+     stopped at main.wl:5:1\n   5 | let x = add(1, 2);\n(debug) \
+     stopped at main.wl:2:3\n   2 |   let sum = a + b;\n(debug) \
+     stopped at main.wl:3:3\n   3 |   sum\n(debug) \
+     program stopped by the debugger\n"
+)]
+#[case(
+    "n\nn\nn\nc\n",
+    "stopped at main.wl:1:1\n   1 | let add = fn(a, b) {\n(debug) \
+     stopped at main.wl:5:1\n   5 | let x = add(1, 2);\n(debug) \
+     stopped at main.wl:6:1\n   6 | let y = add(x, 3);\n(debug) \
+     stopped at main.wl:7:1\n   7 | puts(y);\n(debug) 6\n"
+)]
+#[case(
+    "b 3\nc\np sum * 10\nc\np a\nc\n",
+    "stopped at main.wl:1:1\n   1 | let add = fn(a, b) {\n(debug) breakpoint set at line 3\n(debug) \
+     stopped at main.wl:3:3\n   3 |   sum\n(debug) 30\n(debug) \
+     stopped at main.wl:3:3\n   3 |   sum\n(debug) 3\n(debug) 6\n"
+)]
+#[case(
+    "b 2\nc\no\nq\n",
+    "stopped at main.wl:1:1\n   1 | let add = fn(a, b) {\n(debug) breakpoint set at line 2\n(debug) \
+     stopped at main.wl:2:3\n   2 |   let sum = a + b;\n(debug) \
+     stopped at main.wl:6:1\n   6 | let y = add(x, 3);\n(debug) \
+     program stopped by the debugger\n"
+)]
+#[case(
+    "b 2\nc\nn\nenv\nbt\nq\n",
+    "stopped at main.wl:1:1\n   1 | let add = fn(a, b) {\n(debug) breakpoint set at line 2\n(debug) \
+     stopped at main.wl:2:3\n   2 |   let sum = a + b;\n(debug) \
+     stopped at main.wl:3:3\n   3 |   sum\n(debug) \
+     #0\n  a = 1\n  b = 2\n  sum = 3\n#1 (module)\n  add = fn(a, b) { ... }\n(debug) \
+     \x20 at add (main.wl:3:3)\n  at <module main> (main.wl:5:12)\n(debug) \
+     program stopped by the debugger\n"
+)]
+#[case(
+    "p nope\nwat\nb\n",
+    "stopped at main.wl:1:1\n   1 | let add = fn(a, b) {\n(debug) \
+     error: identifier not found: nope\n(debug) \
+     unknown command `wat`, try `help`\n(debug) \
+     expected a line, like `b 12` or `b lib.wl:12`\n(debug) \
+     program stopped by the debugger\n"
+)]
+fn test_debugger(#[case] commands: &str, #[case] expected: &str) {
+    assert_eq!(debug_session(commands), expected);
+}
@@ -0,0 +1,94 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::token;
+use rstest::rstest;
+
+/// A message tied to a position in the source. Parser errors, parser warnings
+/// and uncaught runtime errors all end up as one of these so they can be
+/// rendered the same way.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    /// How many columns to underline, from `column` on. A span running past
+    /// the end of the line is cut off there.
+    pub length: usize,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, line: usize, column: usize) -> Diagnostic {
+        Diagnostic {
+            message,
+            line,
+            column,
+            length: 1,
+        }
+    }
+
+    pub fn at(message: String, token: &token::Token) -> Diagnostic {
+        Diagnostic::new(message, token.line, token.column)
+    }
+
+    /// A diagnostic underlining everything from `start` to the end of `end`.
+    pub fn spanning(message: String, start: &token::Token, end: &token::Token) -> Diagnostic {
+        let length = match end.line == start.line {
+            true => (end.column + end.width()).saturating_sub(start.column),
+            false => usize::MAX,
+        };
+        return Diagnostic {
+            length: length.max(1),
+            ..Diagnostic::at(message, start)
+        };
+    }
+
+    /// Renders the diagnostic along with the line of `source` it points at,
+    /// with carets under the offending columns.
+    pub fn render(&self, severity: &str, file: &str, source: &str) -> String {
+        let gutter = " ".repeat(self.line.to_string().len());
+        let mut result = format!("{}: {}\n", severity, self.message);
+        result.push_str(&format!("{}--> {}:{}:{}\n", gutter, file, self.line, self.column));
+        if let Some(text) = source.lines().nth(self.line.wrapping_sub(1)) {
+            let indent = " ".repeat(self.column.saturating_sub(1));
+            let rest = text.chars().count().saturating_sub(self.column.saturating_sub(1));
+            let carets = "^".repeat(self.length.min(rest).max(1));
+            result.push_str(&format!("{} |\n", gutter));
+            result.push_str(&format!("{} | {}\n", self.line, text));
+            result.push_str(&format!("{} | {}{}\n", gutter, indent, carets));
+        }
+        return result;
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}. Line {:?}, col {:?}", self.message, self.line, self.column)
+    }
+}
+
+#[rstest]
+#[case(
+    Diagnostic::new("oops".to_string(), 2, 7),
+    "let a = 1;\nlet b = a + true;\n",
+    "error: oops\n --> main.wl:2:7\n  |\n2 | let b = a + true;\n  |       ^\n"
+)]
+#[case(
+    Diagnostic::new("oops".to_string(), 12, 1),
+    "let a = 1;\n",
+    "error: oops\n  --> main.wl:12:1\n"
+)]
+#[case(
+    Diagnostic { length: 8, ..Diagnostic::new("oops".to_string(), 2, 9) },
+    "let a = 1;\nlet b = a + true;\n",
+    "error: oops\n --> main.wl:2:9\n  |\n2 | let b = a + true;\n  |         ^^^^^^^^\n"
+)]
+#[case(
+    Diagnostic { length: usize::MAX, ..Diagnostic::new("oops".to_string(), 1, 9) },
+    "let a = [\n1];\n",
+    "error: oops\n --> main.wl:1:9\n  |\n1 | let a = [\n  |         ^\n"
+)]
+fn test_render(#[case] diagnostic: Diagnostic, #[case] source: &str, #[case] expected: &str) {
+    assert_eq!(diagnostic.render("error", "main.wl", source), expected);
+}
@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::gc;
+use crate::object::Object;
+
+pub type Env = Rc<RefCell<Environment>>;
+
+/// A scope. Every one is registered with the cycle collector in [`gc`],
+/// which is why they're only made through `new` and `new_enclosed`.
+#[derive(Default)]
+pub struct Environment {
+    pub(crate) store: HashMap<String, Object>,
+    pub(crate) outer: Option<Env>,
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        gc::track(&env);
+        return env;
+    }
+
+    pub fn new_enclosed(outer: Env) -> Env {
+        let env = Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            outer: Some(outer),
+        }));
+        gc::track(&env);
+        return env;
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref().and_then(|outer| outer.borrow().get(name)),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    /// The names defined directly in this scope, sorted.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<(String, Object)> = self.store.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        return bindings;
+    }
+
+    pub fn outer(&self) -> Option<Env> {
+        self.outer.clone()
+    }
+}
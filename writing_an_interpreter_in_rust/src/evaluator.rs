@@ -0,0 +1,1819 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::ast;
+use crate::builtins;
+use crate::coverage::Coverage;
+use crate::environment::{Env, Environment};
+use crate::lexer;
+use crate::module::{FsLoader, SourceLoader};
+use crate::diagnostic::Diagnostic;
+use crate::object::{
+    BlockPath, Branch, ErrorObject, FunctionObject, GeneratorFrame, GeneratorState, HashKey, IteratorObject,
+    IteratorState, ModuleObject, Object, StructObject, StructType, TailCall, TraceFrame,
+};
+use crate::parser;
+use crate::profiler::Profiler;
+use crate::stdlib::{self, Capabilities};
+use crate::token;
+use rstest::rstest;
+
+/// An output sink that can be cloned and read back, for capturing what a
+/// script prints.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct CapturedOutput(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+}
+
+#[cfg(test)]
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// One level of the call stack: either a function call or a module being
+/// evaluated by an import.
+struct CallFrame {
+    function: String,
+    file: Option<Rc<PathBuf>>,
+    /// Where the caller was when it entered this frame.
+    call_site: Option<(usize, usize)>,
+    is_function: bool,
+    /// How many `try` bodies of this frame we're inside. A call in one of
+    /// those can't replace the frame, or the `catch` would be lost with it.
+    try_depth: usize,
+}
+
+/// Where the evaluator is about to run a statement.
+pub struct Location {
+    pub file: Option<Rc<PathBuf>>,
+    pub line: usize,
+    pub column: usize,
+    /// How many calls and modules deep the statement is.
+    pub depth: usize,
+}
+
+/// Lets a debugger look at, and stop, a running program. It's handed the
+/// evaluator and the scope of the statement, so it can evaluate code in the
+/// paused frame; it isn't called again for statements it runs itself.
+pub trait DebugHook {
+    /// Called before each statement. Returning `false` stops the program.
+    fn before_statement(&mut self, evaluator: &mut Evaluator, location: &Location, env: &Env) -> bool;
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// Native stack to reserve per script call level. A debug build uses around
+/// 14KiB for a simple recursive call; the rest is for calls made from deep
+/// inside nested expressions.
+pub const STACK_PER_CALL: usize = 64 * 1024;
+
+pub struct Evaluator {
+    loader: Box<dyn SourceLoader>,
+    modules: HashMap<PathBuf, Rc<ModuleObject>>,
+    loading: Vec<Rc<PathBuf>>,
+    frames: Vec<CallFrame>,
+    /// The position of the call currently being made, so functions applied
+    /// from builtins still know where they were called from.
+    call_site: (usize, usize),
+    max_call_depth: usize,
+    step_budget: Option<u64>,
+    memory_budget: Option<usize>,
+    steps: u64,
+    allocated: usize,
+    /// Set once a budget runs out or a debugger stops the script, so a `try`
+    /// can't swallow the error and let the script carry on.
+    halted: bool,
+    debug_hook: Option<Box<dyn DebugHook>>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    /// Names the host makes visible to every module, behind its own lets.
+    globals: HashMap<String, Object>,
+    capabilities: Capabilities,
+    output: Box<dyn Write>,
+    warnings: Vec<String>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator {
+            loader: Box::new(FsLoader),
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            frames: Vec::new(),
+            call_site: (0, 0),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            step_budget: None,
+            memory_budget: None,
+            steps: 0,
+            allocated: 0,
+            halted: false,
+            debug_hook: None,
+            profiler: None,
+            coverage: None,
+            globals: HashMap::new(),
+            capabilities: Capabilities::default(),
+            output: Box::new(std::io::stdout()),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_loader(mut self, loader: impl SourceLoader + 'static) -> Evaluator {
+        self.loader = Box::new(loader);
+        return self;
+    }
+
+    /// Lets scripts import the standard library modules that reach outside
+    /// the interpreter. They can't, by default.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Evaluator {
+        self.capabilities = capabilities;
+        return self;
+    }
+
+    pub fn with_output(mut self, output: impl Write + 'static) -> Evaluator {
+        self.output = Box::new(output);
+        return self;
+    }
+
+    /// Caps how deeply non-tail calls may nest before a catchable "stack
+    /// overflow" error is raised. Tail calls don't count towards it.
+    pub fn with_max_call_depth(mut self, max_call_depth: usize) -> Evaluator {
+        self.max_call_depth = max_call_depth;
+        return self;
+    }
+
+    /// Caps how many statements and calls may be evaluated until the next
+    /// `reset_usage`, so a runaway script can't hang its host.
+    pub fn with_step_budget(mut self, steps: u64) -> Evaluator {
+        self.step_budget = Some(steps);
+        return self;
+    }
+
+    /// Caps how many bytes of strings, arrays, hashes and call scopes may be
+    /// allocated until the next `reset_usage`. Memory freed along the way
+    /// isn't given back, so this bounds the total a script can allocate
+    /// rather than what it holds at any one time.
+    pub fn with_memory_budget(mut self, bytes: usize) -> Evaluator {
+        self.memory_budget = Some(bytes);
+        return self;
+    }
+
+    /// Starts the step and memory budgets over, e.g. before each call a host
+    /// makes into a script.
+    pub fn reset_usage(&mut self) {
+        self.steps = 0;
+        self.allocated = 0;
+        self.halted = false;
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Object) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    pub fn with_debug_hook(mut self, hook: impl DebugHook + 'static) -> Evaluator {
+        self.debug_hook = Some(Box::new(hook));
+        return self;
+    }
+
+    /// Times every call the script makes, per function.
+    pub fn with_profiler(mut self) -> Evaluator {
+        self.profiler = Some(Profiler::new());
+        return self;
+    }
+
+    /// Counts how often each statement and branch runs.
+    pub fn with_coverage(mut self) -> Evaluator {
+        self.coverage = Some(Coverage::new());
+        return self;
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Parses and runs `source` in `env`, e.g. an expression typed into a
+    /// debugger while paused.
+    pub fn eval_source(&mut self, source: &str, env: &Env) -> Result<Object, ErrorObject> {
+        let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+        let program = p.parse_program();
+        if p.has_errors() {
+            let errors: Vec<String> = p.errors().iter().map(|e| e.to_string()).collect();
+            return Err(errors.join("\n").into());
+        }
+        return self.eval_program(&program, env);
+    }
+
+    /// Reads a module's source through the loader, e.g. to show the line an
+    /// error or a debugger points at.
+    pub fn read_source(&self, path: &Path) -> Option<String> {
+        self.loader.read(path).ok()
+    }
+
+    /// The current call stack, innermost first, as if an error were raised
+    /// at `position`.
+    pub fn backtrace(&self, position: (usize, usize)) -> Vec<TraceFrame> {
+        self.trace_at(position)
+    }
+
+    /// Whether the last error came from a budget running out or a debugger
+    /// stopping the program, rather than from the script itself.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    fn debug(&mut self, token: &token::Token, env: &Env) -> Result<(), ErrorObject> {
+        let Some(mut hook) = self.debug_hook.take() else {
+            return Ok(());
+        };
+        let location = Location {
+            file: self.frames.last().and_then(|frame| frame.file.clone()),
+            line: token.line,
+            column: token.column,
+            depth: self.frames.len(),
+        };
+        let resume = hook.before_statement(self, &location, env);
+        self.debug_hook = Some(hook);
+        if !resume {
+            self.halted = true;
+            return Err(self.error(String::from("program stopped by the debugger"), token));
+        }
+        return Ok(());
+    }
+
+    /// Records coverage, if it's being collected, against the file of the
+    /// code being run.
+    fn cover(&mut self, record: impl FnOnce(&mut Coverage, &str)) {
+        if let Some(coverage) = &mut self.coverage {
+            let file = match self.frames.last().and_then(|frame| frame.file.as_ref()) {
+                Some(file) => file.display().to_string(),
+                None => "<script>".to_string(),
+            };
+            record(coverage, &file);
+        }
+    }
+
+    fn step(&mut self, position: (usize, usize)) -> Result<(), ErrorObject> {
+        self.steps += 1;
+        match self.step_budget {
+            Some(budget) if self.steps > budget => {
+                self.halted = true;
+                Err(self.error_at(format!("instruction budget of {} exceeded", budget), position))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn allocate(&mut self, bytes: usize, position: (usize, usize)) -> Result<(), ErrorObject> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        match self.memory_budget {
+            Some(budget) if self.allocated > budget => {
+                self.halted = true;
+                Err(self.error_at(format!("memory budget of {} bytes exceeded", budget), position))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn write_output(&mut self, text: &str) {
+        // A closed stdout shouldn't bring the script down with it.
+        let _ = writeln!(self.output, "{}", text);
+    }
+
+    /// Rendered parser warnings from every module loaded so far. Draining
+    /// them lets callers report each one once.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Renders an uncaught error like a parser diagnostic, pointing at where
+    /// it was raised and followed by its stack trace. Frames that didn't come
+    /// from a file are shown against `unnamed_source`.
+    pub fn render_error(&self, error: &ErrorObject, unnamed_source: &str) -> String {
+        let Some(innermost) = error.trace.first() else {
+            return format!("error: {}\n", error.message);
+        };
+        let diagnostic = Diagnostic::new(error.message.clone(), innermost.line, innermost.column);
+        let mut result = match &innermost.file {
+            Some(file) => {
+                let source = self.read_source(file).unwrap_or_default();
+                diagnostic.render("error", &file.display().to_string(), &source)
+            }
+            None => diagnostic.render("error", "<main>", unnamed_source),
+        };
+        // Runaway recursion leaves thousands of identical frames behind, so
+        // runs of them are folded into a single line.
+        let lines: Vec<String> = error.trace.iter().map(|frame| frame.to_string()).collect();
+        let mut i = 0;
+        while i < lines.len() {
+            let repeats = lines[i..].iter().take_while(|line| **line == lines[i]).count();
+            result.push_str(&format!("  = {}\n", lines[i]));
+            if repeats > 1 {
+                result.push_str(&format!("  = ... repeated {} more times\n", repeats - 1));
+            }
+            i += repeats;
+        }
+        return result;
+    }
+
+    /// Runs an already parsed file as the entry point, so imports inside it
+    /// resolve relative to its directory.
+    pub fn eval_module(&mut self, path: &Path, program: &ast::ProgramNode) -> Result<Rc<ModuleObject>, ErrorObject> {
+        let path = self.loader.resolve(None, &path.to_string_lossy());
+        let module = self.run_module(&path, program, None)?;
+        self.modules.insert(path, module.clone());
+        return Ok(module);
+    }
+
+    pub fn eval_program(&mut self, program: &ast::ProgramNode, env: &Env) -> Result<Object, ErrorObject> {
+        let mut result = Object::Null;
+        for statement in &program.statements {
+            result = self.eval_statement(statement, env)?;
+            if let Object::ReturnValue(value) = result {
+                return Ok(*value);
+            }
+        }
+        return Ok(result);
+    }
+
+    fn eval_block_statement(&mut self, block: &ast::BlockStatement, env: &Env) -> Result<Object, ErrorObject> {
+        let mut result = Object::Null;
+        for statement in &block.statements {
+            result = self.eval_statement(statement, env)?;
+            if let Object::ReturnValue(_) = result {
+                return Ok(result);
+            }
+        }
+        return Ok(result);
+    }
+
+    /// Counts, reports and records a statement about to run.
+    fn enter_statement(&mut self, token: &token::Token, env: &Env) -> Result<(), ErrorObject> {
+        self.step((token.line, token.column))?;
+        self.debug(token, env)?;
+        self.cover(|coverage, file| coverage.statement(file, token));
+        return Ok(());
+    }
+
+    fn eval_statement(&mut self, statement: &ast::StatementNode, env: &Env) -> Result<Object, ErrorObject> {
+        let token = statement_token(statement);
+        self.enter_statement(token, env)?;
+        match statement {
+            ast::StatementNode::Expression(stmt) => self.eval_expression(&stmt.expression, env),
+            ast::StatementNode::Let(stmt) => {
+                let value = match &stmt.value {
+                    ast::ExpressionNode::Function(func) => self.eval_function(func, Some(&stmt.name.value), env),
+                    value => self.eval_expression(value, env)?,
+                };
+                env.borrow_mut().set(&stmt.name.value, value);
+                Ok(Object::Null)
+            }
+            ast::StatementNode::Return(stmt) => {
+                let value = match &stmt.value {
+                    ast::ExpressionNode::Call(call) => self.eval_call(call, env, self.in_tail_position())?,
+                    value => self.eval_expression(value, env)?,
+                };
+                Ok(Object::ReturnValue(Box::new(value)))
+            }
+            ast::StatementNode::Import(stmt) => self.eval_import(stmt, env),
+            ast::StatementNode::Struct(stmt) => {
+                let struct_type = StructType {
+                    name: stmt.name.value.clone(),
+                    fields: stmt.fields.iter().map(|f| f.value.clone()).collect(),
+                    methods: RefCell::default(),
+                };
+                env.borrow_mut().set(&stmt.name.value, Object::StructType(Rc::new(struct_type)));
+                Ok(Object::Null)
+            }
+            ast::StatementNode::Impl(stmt) => self.eval_impl(stmt, env),
+            ast::StatementNode::For(stmt) => self.eval_for(stmt, env),
+            // A generator runs its own statements, and those of the blocks
+            // it's suspended in, so one reached here is nested in an
+            // expression that can't be suspended halfway through.
+            ast::StatementNode::Yield(stmt) => Err(self.error(
+                String::from("`yield` can't be used inside an expression"),
+                &stmt.token,
+            )),
+        }
+    }
+
+    fn eval_for(&mut self, stmt: &ast::ForStatement, env: &Env) -> Result<Object, ErrorObject> {
+        let iterable = self.eval_expression(&stmt.iterable, env)?;
+        let iterator = IteratorObject::from_iterable(&iterable).map_err(|e| self.error(e, &stmt.token))?;
+        let position = (stmt.token.line, stmt.token.column);
+        loop {
+            self.step(position)?;
+            self.call_site = position;
+            let Some(value) = self.next_value(&iterator)? else {
+                return Ok(Object::Null);
+            };
+            let body_env = Environment::new_enclosed(env.clone());
+            body_env.borrow_mut().set(&stmt.binding.value, value);
+            let result = self.eval_block_statement(&stmt.body, &body_env)?;
+            if let Object::ReturnValue(_) = result {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Asks `iterator` for its next value, running as much of a generator as
+    /// it takes to get there. An iterator that runs out or raises an error
+    /// is finished from then on.
+    pub fn next_value(&mut self, iterator: &Rc<IteratorObject>) -> Result<Option<Object>, ErrorObject> {
+        let call_site = self.call_site;
+        let state = std::mem::replace(&mut *iterator.state.borrow_mut(), IteratorState::Running);
+        let (result, state) = match state {
+            IteratorState::Elements { elements, next } => {
+                let value = elements.get(next).cloned();
+                (Ok(value), IteratorState::Elements { elements, next: next + 1 })
+            }
+            IteratorState::Generator(mut generator) => {
+                let result = self.resume_generator(&mut generator);
+                let state = match generator.frames.as_slice() {
+                    [GeneratorFrame::Delegate { iterator }] => IteratorState::Delegate(iterator.clone()),
+                    _ => IteratorState::Generator(generator),
+                };
+                (result, state)
+            }
+            IteratorState::Delegate(mut inner) => {
+                // Skip past iterators that have delegated in turn, so that a
+                // generator recursing through `yield*` stays shallow.
+                loop {
+                    let next = match &*inner.state.borrow() {
+                        IteratorState::Delegate(next) => next.clone(),
+                        _ => break,
+                    };
+                    inner = next;
+                }
+                (self.next_value(&inner), IteratorState::Delegate(inner))
+            }
+            IteratorState::Map { source, function } => {
+                let result = match self.next_value(&source) {
+                    Ok(Some(value)) => self.apply_function_from(function.clone(), vec![value], Some(call_site)).map(Some),
+                    other => other,
+                };
+                (result, IteratorState::Map { source, function })
+            }
+            IteratorState::Filter { source, predicate } => {
+                let result = loop {
+                    match self.next_value(&source) {
+                        Ok(Some(value)) => match self.apply_function_from(predicate.clone(), vec![value.clone()], Some(call_site)) {
+                            Ok(keep) if keep.is_truthy() => break Ok(Some(value)),
+                            Ok(_) => {}
+                            Err(error) => break Err(error),
+                        },
+                        other => break other,
+                    }
+                };
+                (result, IteratorState::Filter { source, predicate })
+            }
+            IteratorState::Take { source, remaining } => match remaining {
+                0 => (Ok(None), IteratorState::Finished),
+                _ => (self.next_value(&source), IteratorState::Take { source, remaining: remaining - 1 }),
+            },
+            IteratorState::Running => (
+                Err(self.error_at(String::from("iterator is already running"), call_site)),
+                IteratorState::Running,
+            ),
+            IteratorState::Finished => (Ok(None), IteratorState::Finished),
+        };
+        // A re-entrant call leaves the state to the call that's running it.
+        *iterator.state.borrow_mut() = match (&result, state) {
+            (_, IteratorState::Running) => IteratorState::Running,
+            (Ok(Some(_)), state) => state,
+            _ => IteratorState::Finished,
+        };
+        return result;
+    }
+
+    /// Runs a generator up to its next `yield`, or to the end of its body.
+    /// Statements that may yield, and the blocks they enter, are run here
+    /// one at a time, keeping their place in `generator.frames`; everything
+    /// else is left to `eval_statement`.
+    fn resume_generator(&mut self, generator: &mut GeneratorState) -> Result<Option<Object>, ErrorObject> {
+        if generator.frames.is_empty() {
+            return Ok(None);
+        }
+        let function = generator.function.clone();
+        if self.frames.len() >= self.max_call_depth {
+            let message = format!("stack overflow: maximum call depth of {} exceeded", self.max_call_depth);
+            return Err(self.error_at(message, self.call_site));
+        }
+        let depth = self.frames.len();
+        // Not a function frame, so a `return` in the body can't become a
+        // tail call that would run outside the generator.
+        self.frames.push(CallFrame {
+            function: function.name.clone().unwrap_or_else(|| "<anonymous>".to_string()),
+            file: function.file.clone(),
+            call_site: Some(self.call_site),
+            is_function: false,
+            try_depth: 0,
+        });
+        let result = loop {
+            match self.generator_step(&function.body, &mut generator.frames) {
+                Ok(None) if !generator.frames.is_empty() => {}
+                Ok(value) => break Ok(value),
+                Err(error) if !self.halted => {
+                    self.frames.truncate(depth + 1);
+                    let Some(i) = generator.frames.iter().rposition(|f| matches!(f, GeneratorFrame::Try { .. })) else {
+                        break Err(error);
+                    };
+                    let GeneratorFrame::Try { handler, binding, env } = generator.frames.remove(i) else {
+                        unreachable!();
+                    };
+                    generator.frames.truncate(i);
+                    let handler_env = Environment::new_enclosed(env);
+                    handler_env.borrow_mut().set(&binding, Object::Error(Rc::new(error)));
+                    generator.frames.push(GeneratorFrame::Block { path: handler, next: 0, env: handler_env });
+                }
+                Err(error) => break Err(error),
+            }
+        };
+        self.frames.truncate(depth);
+        if !matches!(result, Ok(Some(_))) {
+            generator.frames.clear();
+        }
+        return result;
+    }
+
+    /// Advances the innermost frame of a generator by one statement or loop
+    /// iteration, returning the value if that was a `yield`.
+    fn generator_step(&mut self, body: &ast::BlockStatement, frames: &mut Vec<GeneratorFrame>) -> Result<Option<Object>, ErrorObject> {
+        let (path, index, env) = match frames.last_mut() {
+            Some(GeneratorFrame::Block { path, next, env }) => {
+                *next += 1;
+                (path.clone(), *next - 1, env.clone())
+            }
+            Some(GeneratorFrame::Loop { path, binding, iterator, env }) => {
+                let (path, binding, iterator, env) = (path.clone(), binding.clone(), iterator.clone(), env.clone());
+                match self.next_value(&iterator)? {
+                    Some(value) => {
+                        let body_env = Environment::new_enclosed(env);
+                        body_env.borrow_mut().set(&binding, value);
+                        frames.push(GeneratorFrame::Block { path, next: 0, env: body_env });
+                    }
+                    None => {
+                        frames.pop();
+                    }
+                }
+                return Ok(None);
+            }
+            Some(GeneratorFrame::Delegate { iterator }) => {
+                let iterator = iterator.clone();
+                let value = self.next_value(&iterator)?;
+                if value.is_none() {
+                    frames.pop();
+                }
+                return Ok(value);
+            }
+            // The body of the `try` finished without an error.
+            Some(GeneratorFrame::Try { .. }) | None => {
+                frames.pop();
+                return Ok(None);
+            }
+        };
+        let Some(statement) = block_at(body, &path).statements.get(index) else {
+            frames.pop();
+            return Ok(None);
+        };
+        let enter = |branch: Branch| {
+            let mut path = path.clone();
+            path.push((index, branch));
+            path
+        };
+        let expression = match statement {
+            ast::StatementNode::Yield(stmt) if stmt.delegate => {
+                self.enter_statement(&stmt.token, &env)?;
+                let iterable = self.eval_expression(&stmt.value, &env)?;
+                let iterator = IteratorObject::from_iterable(&iterable).map_err(|e| self.error(e, &stmt.token))?;
+                // Last in the body, there's nothing to come back to, and
+                // `next_value` can hand the generator over to the iterator.
+                if path.is_empty() && index + 1 == body.statements.len() {
+                    frames.clear();
+                }
+                frames.push(GeneratorFrame::Delegate { iterator });
+                return Ok(None);
+            }
+            ast::StatementNode::Yield(stmt) => {
+                self.enter_statement(&stmt.token, &env)?;
+                return Ok(Some(self.eval_expression(&stmt.value, &env)?));
+            }
+            ast::StatementNode::Return(stmt) => {
+                self.enter_statement(&stmt.token, &env)?;
+                self.eval_expression(&stmt.value, &env)?;
+                frames.clear();
+                return Ok(None);
+            }
+            ast::StatementNode::For(stmt) => {
+                self.enter_statement(&stmt.token, &env)?;
+                let iterable = self.eval_expression(&stmt.iterable, &env)?;
+                let iterator = IteratorObject::from_iterable(&iterable).map_err(|e| self.error(e, &stmt.token))?;
+                frames.push(GeneratorFrame::Loop {
+                    path: enter(Branch::Loop),
+                    binding: stmt.binding.value.clone(),
+                    iterator,
+                    env,
+                });
+                return Ok(None);
+            }
+            ast::StatementNode::Expression(stmt) if statement_may_yield(statement) => &stmt.expression,
+            statement => {
+                if let Object::ReturnValue(_) = self.eval_statement(statement, &env)? {
+                    frames.clear();
+                }
+                return Ok(None);
+            }
+        };
+        self.enter_statement(statement_token(statement), &env)?;
+        match expression {
+            ast::ExpressionNode::If(if_expr) => {
+                let condition = self.eval_expression(&if_expr.condition, &env)?;
+                let arm = (!condition.is_truthy()) as usize;
+                self.cover(|coverage, file| coverage.branch(file, &if_expr.token, arm, 2));
+                let branch = match condition.is_truthy() {
+                    true => Branch::Consequence,
+                    false if if_expr.alternative.is_some() => Branch::Alternative,
+                    false => return Ok(None),
+                };
+                let env = Environment::new_enclosed(env);
+                frames.push(GeneratorFrame::Block { path: enter(branch), next: 0, env });
+            }
+            ast::ExpressionNode::Match(match_expr) => {
+                let value = self.eval_expression(&match_expr.value, &env)?;
+                for (i, arm) in match_expr.arms.iter().enumerate() {
+                    let arm_env = Environment::new_enclosed(env.clone());
+                    if !match_pattern(&arm.pattern, &value, &arm_env) {
+                        continue;
+                    }
+                    if let Some(guard) = &arm.guard {
+                        if !self.eval_expression(guard, &arm_env)?.is_truthy() {
+                            continue;
+                        }
+                    }
+                    self.cover(|coverage, file| coverage.branch(file, &match_expr.token, i, match_expr.arms.len()));
+                    frames.push(GeneratorFrame::Block { path: enter(Branch::Arm(i)), next: 0, env: arm_env });
+                    return Ok(None);
+                }
+                return Err(self.error(format!("no match arm matched {}", value.inspect()), &match_expr.token));
+            }
+            ast::ExpressionNode::Try(try_expr) => {
+                frames.push(GeneratorFrame::Try {
+                    handler: enter(Branch::Handler),
+                    binding: try_expr.binding.value.clone(),
+                    env: env.clone(),
+                });
+                let env = Environment::new_enclosed(env);
+                frames.push(GeneratorFrame::Block { path: enter(Branch::TryBody), next: 0, env });
+            }
+            _ => unreachable!("only if, match and try statements may yield"),
+        }
+        return Ok(None);
+    }
+
+    fn eval_expression(&mut self, expression: &ast::ExpressionNode, env: &Env) -> Result<Object, ErrorObject> {
+        match expression {
+            ast::ExpressionNode::Literal(literal) => match &literal.value {
+                ast::LiteralEnum::IntLiteral(i) => Ok(Object::Integer(*i)),
+                ast::LiteralEnum::StringLiteral(s) => {
+                    self.allocate(s.len(), (literal.token.line, literal.token.column))?;
+                    Ok(Object::String(s.as_str().into()))
+                }
+                ast::LiteralEnum::Boolean(b) => Ok(Object::Boolean(*b)),
+            },
+            ast::ExpressionNode::Identifier(ident) => self.eval_identifier(ident, env),
+            ast::ExpressionNode::Prefix(prefix) => {
+                let right = self.eval_expression(&prefix.right, env)?;
+                eval_prefix_expression(&prefix.operator, right).map_err(|e| self.error(e, &prefix.token))
+            }
+            ast::ExpressionNode::Infix(infix) => {
+                let left = self.eval_expression(&infix.left, env)?;
+                let right = self.eval_expression(&infix.right, env)?;
+                let result = eval_infix_expression(&infix.operator, left, right).map_err(|e| self.error(e, &infix.token))?;
+                self.allocate(allocation_size(&result), (infix.token.line, infix.token.column))?;
+                Ok(result)
+            }
+            ast::ExpressionNode::If(if_expr) => {
+                let condition = self.eval_expression(&if_expr.condition, env)?;
+                let arm = (!condition.is_truthy()) as usize;
+                self.cover(|coverage, file| coverage.branch(file, &if_expr.token, arm, 2));
+                if condition.is_truthy() {
+                    self.eval_block_statement(&if_expr.consequence, &Environment::new_enclosed(env.clone()))
+                } else if let Some(alternative) = &if_expr.alternative {
+                    self.eval_block_statement(alternative, &Environment::new_enclosed(env.clone()))
+                } else {
+                    Ok(Object::Null)
+                }
+            }
+            ast::ExpressionNode::Function(func) => Ok(self.eval_function(func, None, env)),
+            ast::ExpressionNode::Call(call) => self.eval_call(call, env, false),
+            ast::ExpressionNode::Array(array) => {
+                let elements = Object::Array(Rc::new(self.eval_expressions(&array.elements, env)?));
+                self.allocate(allocation_size(&elements), (array.token.line, array.token.column))?;
+                Ok(elements)
+            }
+            ast::ExpressionNode::Hash(hash) => self.eval_hash(hash, env),
+            ast::ExpressionNode::Index(index) => {
+                let left = self.eval_expression(&index.left, env)?;
+                let idx = self.eval_expression(&index.index, env)?;
+                eval_index_expression(left, idx).map_err(|e| self.error(e, &index.token))
+            }
+            ast::ExpressionNode::Match(match_expr) => self.eval_match(match_expr, env),
+            ast::ExpressionNode::Try(try_expr) => self.eval_try(try_expr, env),
+            ast::ExpressionNode::StructLiteral(literal) => self.eval_struct_literal(literal, env),
+            ast::ExpressionNode::Field(field) => {
+                let left = self.eval_expression(&field.left, env)?;
+                self.eval_field(left, &field.field)
+            }
+        }
+    }
+
+    /// Evaluates a call. In tail position a call to a script function isn't
+    /// made here; it's handed back as a `TailCall` for `apply_function` to
+    /// run in place of the current frame.
+    fn eval_call(&mut self, call: &ast::CallExpr, env: &Env, tail: bool) -> Result<Object, ErrorObject> {
+        let (function, mut arguments) = match call.function.as_ref() {
+            ast::ExpressionNode::Field(field) => self.eval_method(field, env)?,
+            function => (self.eval_expression(function, env)?, Vec::new()),
+        };
+        arguments.extend(self.eval_expressions(&call.arguments, env)?);
+        if tail && matches!(function, Object::Function(_)) {
+            return Ok(Object::TailCall(Box::new(TailCall { function, arguments })));
+        }
+        self.call_site = (call.token.line, call.token.column);
+        return self.apply_function(function, arguments);
+    }
+
+    /// Evaluates the callee of `value.name(...)`. A method of a struct
+    /// instance comes back with the instance as its first argument, `self`;
+    /// anything else is an ordinary field access.
+    fn eval_method(&mut self, field: &ast::FieldExpr, env: &Env) -> Result<(Object, Vec<Object>), ErrorObject> {
+        let receiver = self.eval_expression(&field.left, env)?;
+        if let Object::Struct(instance) = &receiver {
+            let method = instance.struct_type.methods.borrow().get(&field.field.value).cloned();
+            if let (Some(method), None) = (method, instance.field(&field.field.value)) {
+                return Ok((method, vec![receiver]));
+            }
+        }
+        return Ok((self.eval_field(receiver, &field.field)?, Vec::new()));
+    }
+
+    fn eval_field(&self, left: Object, field: &ast::IdentfierExpr) -> Result<Object, ErrorObject> {
+        let name = &field.value;
+        match &left {
+            Object::Struct(instance) => {
+                if let Some(value) = instance.field(name) {
+                    return Ok(value);
+                }
+                let message = match instance.struct_type.methods.borrow().contains_key(name) {
+                    true => format!("method `{}` of {} must be called, as in `.{}()`", name, left.type_name(), name),
+                    false => format!("{} has no field `{}`", left.type_name(), name),
+                };
+                Err(self.error(message, &field.token))
+            }
+            // Functions declared without `self`, like constructors.
+            Object::StructType(struct_type) => struct_type.methods.borrow().get(name).cloned().ok_or_else(|| {
+                self.error(format!("struct {} has no method `{}`", struct_type.name, name), &field.token)
+            }),
+            Object::Module(_) | Object::Error(_) => eval_index_expression(left.clone(), Object::String(name.as_str().into()))
+                .map_err(|e| self.error(e, &field.token)),
+            other => Err(self.error(format!("{} has no field `{}`", other.type_name(), name), &field.token)),
+        }
+    }
+
+    fn eval_struct_literal(&mut self, literal: &ast::StructLiteralExpr, env: &Env) -> Result<Object, ErrorObject> {
+        let struct_type = match self.eval_expression(&literal.name, env)? {
+            Object::StructType(struct_type) => struct_type,
+            other => return Err(self.error(format!("not a struct: {}", other.type_name()), &literal.token)),
+        };
+        let mut values = vec![None; struct_type.fields.len()];
+        for (field, value) in &literal.fields {
+            let Some(index) = struct_type.fields.iter().position(|f| *f == field.value) else {
+                let message = format!("{} has no field `{}`", struct_type.name, field.value);
+                return Err(self.error(message, &field.token));
+            };
+            values[index] = Some(self.eval_expression(value, env)?);
+        }
+        let mut fields = Vec::with_capacity(values.len());
+        for (name, value) in struct_type.fields.iter().zip(values) {
+            let Some(value) = value else {
+                return Err(self.error(format!("missing field `{}` in {}", name, struct_type.name), &literal.token));
+            };
+            fields.push(value);
+        }
+        let instance = Object::Struct(Rc::new(StructObject { struct_type, values: fields }));
+        self.allocate(allocation_size(&instance), (literal.token.line, literal.token.column))?;
+        return Ok(instance);
+    }
+
+    fn eval_impl(&mut self, stmt: &ast::ImplStatement, env: &Env) -> Result<Object, ErrorObject> {
+        let struct_type = match self.eval_identifier(&stmt.name, env)? {
+            Object::StructType(struct_type) => struct_type,
+            other => return Err(self.error(format!("not a struct: {}", other.type_name()), &stmt.name.token)),
+        };
+        for (name, func) in &stmt.methods {
+            if struct_type.fields.contains(&name.value) {
+                let message = format!("{} already has a field named `{}`", struct_type.name, name.value);
+                return Err(self.error(message, &name.token));
+            }
+            let method = self.eval_function(func, Some(&format!("{}.{}", struct_type.name, name.value)), env);
+            struct_type.methods.borrow_mut().insert(name.value.clone(), method);
+        }
+        return Ok(Object::Null);
+    }
+
+    fn in_tail_position(&self) -> bool {
+        self.frames
+            .last()
+            .is_some_and(|frame| frame.is_function && frame.try_depth == 0)
+    }
+
+    fn eval_match(&mut self, match_expr: &ast::MatchExpr, env: &Env) -> Result<Object, ErrorObject> {
+        let value = self.eval_expression(&match_expr.value, env)?;
+        for (i, arm) in match_expr.arms.iter().enumerate() {
+            let arm_env = Environment::new_enclosed(env.clone());
+            if !match_pattern(&arm.pattern, &value, &arm_env) {
+                continue;
+            }
+            if let Some(guard) = &arm.guard {
+                if !self.eval_expression(guard, &arm_env)?.is_truthy() {
+                    continue;
+                }
+            }
+            self.cover(|coverage, file| coverage.branch(file, &match_expr.token, i, match_expr.arms.len()));
+            return self.eval_block_statement(&arm.body, &arm_env);
+        }
+        return Err(self.error(format!("no match arm matched {}", value.inspect()), &match_expr.token));
+    }
+
+    fn eval_try(&mut self, try_expr: &ast::TryExpr, env: &Env) -> Result<Object, ErrorObject> {
+        let depth = self.frames.len();
+        if let Some(frame) = self.frames.last_mut() {
+            frame.try_depth += 1;
+        }
+        let result = self.eval_block_statement(&try_expr.body, &Environment::new_enclosed(env.clone()));
+        self.frames.truncate(depth);
+        if let Some(frame) = self.frames.last_mut() {
+            frame.try_depth -= 1;
+        }
+        match result {
+            Ok(result) => Ok(result),
+            Err(error) if !self.halted => {
+                let handler_env = Environment::new_enclosed(env.clone());
+                handler_env
+                    .borrow_mut()
+                    .set(&try_expr.binding.value, Object::Error(Rc::new(error)));
+                self.eval_block_statement(&try_expr.handler, &handler_env)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn eval_expressions(&mut self, expressions: &[ast::ExpressionNode], env: &Env) -> Result<Vec<Object>, ErrorObject> {
+        let mut result = Vec::with_capacity(expressions.len());
+        for expression in expressions {
+            result.push(self.eval_expression(expression, env)?);
+        }
+        return Ok(result);
+    }
+
+    fn eval_identifier(&mut self, ident: &ast::IdentfierExpr, env: &Env) -> Result<Object, ErrorObject> {
+        if let Some(value) = env.borrow().get(&ident.value) {
+            return Ok(value);
+        }
+        if let Some(value) = self.globals.get(&ident.value) {
+            return Ok(value.clone());
+        }
+        if let Some(builtin) = builtins::lookup(&ident.value) {
+            return Ok(builtin);
+        }
+        return Err(self.error(format!("identifier not found: {}", ident.value), &ident.token));
+    }
+
+    fn eval_function(&mut self, func: &ast::FunctionExpr, name: Option<&str>, env: &Env) -> Object {
+        Object::Function(Rc::new(FunctionObject {
+            name: name.map(|n| n.to_string()),
+            file: self.loading.last().cloned(),
+            parameters: func.parameters.clone(),
+            body: func.body.clone(),
+            env: env.clone(),
+            is_generator: func.is_generator,
+        }))
+    }
+
+    fn eval_hash(&mut self, hash: &ast::HashExpr, env: &Env) -> Result<Object, ErrorObject> {
+        let mut pairs = BTreeMap::new();
+        for (key_node, value_node) in &hash.pairs {
+            let key = self.eval_expression(key_node, env)?;
+            let Some(hash_key) = key.hash_key() else {
+                return Err(self.error(format!("unusable as hash key: {}", key.type_name()), &hash.token));
+            };
+            let value = self.eval_expression(value_node, env)?;
+            pairs.insert(hash_key, value);
+        }
+        let hash_object = Object::Hash(Rc::new(pairs));
+        self.allocate(allocation_size(&hash_object), (hash.token.line, hash.token.column))?;
+        return Ok(hash_object);
+    }
+
+    pub fn apply_function(&mut self, function: Object, arguments: Vec<Object>) -> Result<Object, ErrorObject> {
+        return self.apply_function_from(function, arguments, Some(self.call_site));
+    }
+
+    /// Calls `function` on behalf of the host rather than from a script, so
+    /// its stack trace ends at the function instead of at a call site.
+    pub fn call_function(&mut self, function: Object, arguments: Vec<Object>) -> Result<Object, ErrorObject> {
+        return self.apply_function_from(function, arguments, None);
+    }
+
+    fn apply_function_from(
+        &mut self,
+        function: Object,
+        arguments: Vec<Object>,
+        call_site: Option<(usize, usize)>,
+    ) -> Result<Object, ErrorObject> {
+        let position = call_site.unwrap_or_default();
+        let (mut function, mut arguments) = (function, arguments);
+        // Tail calls loop here rather than recursing, reusing the stack
+        // space of the call they replace.
+        loop {
+            self.step(position)?;
+            let func = match function {
+                Object::Function(func) => func,
+                Object::Builtin(builtin) => {
+                    if let Some(profiler) = &mut self.profiler {
+                        profiler.enter(format!("builtin {}", builtin.name));
+                    }
+                    let result = (builtin.func)(self, arguments);
+                    if let Some(profiler) = &mut self.profiler {
+                        profiler.exit();
+                    }
+                    let result = result.map_err(|mut error| {
+                        if error.trace.is_empty() && call_site.is_some() {
+                            error.trace = self.trace_at(position);
+                        }
+                        error
+                    })?;
+                    self.allocate(allocation_size(&result), position)?;
+                    return Ok(result);
+                }
+                other => return Err(self.call_error(format!("not a function: {}", other.type_name()), call_site)),
+            };
+            if func.parameters.len() != arguments.len() {
+                let message = format!(
+                    "wrong number of arguments: want={}, got={}",
+                    func.parameters.len(),
+                    arguments.len()
+                );
+                return Err(self.call_error(message, call_site));
+            }
+            if self.frames.len() >= self.max_call_depth {
+                let message = format!("stack overflow: maximum call depth of {} exceeded", self.max_call_depth);
+                return Err(self.call_error(message, call_site));
+            }
+            let scope_size = std::mem::size_of::<Environment>()
+                + func.parameters.iter().map(|p| p.value.len() + std::mem::size_of::<Object>()).sum::<usize>();
+            self.allocate(scope_size, position)?;
+            let env = Environment::new_enclosed(func.env.clone());
+            for (param, arg) in func.parameters.iter().zip(arguments) {
+                env.borrow_mut().set(&param.value, arg);
+            }
+            if func.is_generator {
+                let frames = vec![GeneratorFrame::Block { path: Vec::new(), next: 0, env }];
+                let generator = GeneratorState { function: func, frames };
+                return Ok(Object::Iterator(IteratorObject::new(IteratorState::Generator(generator))));
+            }
+            self.frames.push(CallFrame {
+                function: func.name.clone().unwrap_or_else(|| "<anonymous>".to_string()),
+                file: func.file.clone(),
+                call_site,
+                is_function: true,
+                try_depth: 0,
+            });
+            if let Some(profiler) = &mut self.profiler {
+                let file = func.file.as_ref().map_or("<script>".to_string(), |f| f.display().to_string());
+                let name = func.name.as_deref().unwrap_or("<anonymous>");
+                profiler.enter(format!("{} ({}:{})", name, file, func.body.token.line));
+            }
+            let result = self.eval_block_statement(&func.body, &env);
+            if let Some(profiler) = &mut self.profiler {
+                profiler.exit();
+            }
+            self.frames.pop();
+            let value = match result? {
+                Object::ReturnValue(value) => *value,
+                value => value,
+            };
+            match value {
+                Object::TailCall(tail_call) => (function, arguments) = (tail_call.function, tail_call.arguments),
+                value => return Ok(value),
+            }
+        }
+    }
+
+    fn call_error(&self, message: String, call_site: Option<(usize, usize)>) -> ErrorObject {
+        match call_site {
+            Some(position) => self.error_at(message, position),
+            None => message.into(),
+        }
+    }
+
+    fn error(&self, message: String, token: &token::Token) -> ErrorObject {
+        self.error_at(message, (token.line, token.column))
+    }
+
+    fn error_at(&self, message: String, position: (usize, usize)) -> ErrorObject {
+        ErrorObject {
+            message,
+            trace: self.trace_at(position),
+        }
+    }
+
+    /// Snapshots the call stack, innermost first, as if an error were raised
+    /// at `position` in the current frame.
+    fn trace_at(&self, position: (usize, usize)) -> Vec<TraceFrame> {
+        let mut trace = Vec::new();
+        let (mut line, mut column) = position;
+        for frame in self.frames.iter().rev() {
+            trace.push(TraceFrame {
+                function: frame.function.clone(),
+                file: frame.file.as_deref().cloned(),
+                line,
+                column,
+            });
+            match frame.call_site {
+                Some(call_site) => (line, column) = call_site,
+                None => return trace,
+            }
+        }
+        trace.push(TraceFrame {
+            function: "<main>".to_string(),
+            file: None,
+            line,
+            column,
+        });
+        return trace;
+    }
+
+    fn eval_import(&mut self, stmt: &ast::ImportStatement, env: &Env) -> Result<Object, ErrorObject> {
+        if let Some(name) = stmt.path.strip_prefix(stdlib::PREFIX) {
+            let module = stdlib::module(name, &self.capabilities).map_err(|e| self.error(e, &stmt.token))?;
+            return Ok(bind_module(stmt, module, env));
+        }
+        let importer = self.loading.last().cloned();
+        let path = self.loader.resolve(importer.as_deref().map(|p| p.as_path()), &stmt.path);
+        if let Some(start) = self.loading.iter().position(|p| **p == path) {
+            let chain: Vec<String> = self.loading[start..]
+                .iter()
+                .map(|p| p.as_path())
+                .chain(std::iter::once(path.as_path()))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(self.error(format!("import cycle detected: {}", chain.join(" -> ")), &stmt.token));
+        }
+        let module = match self.modules.get(&path) {
+            Some(module) => module.clone(),
+            None => {
+                self.call_site = (stmt.token.line, stmt.token.column);
+                let module = self.load_module(&path)?;
+                self.modules.insert(path, module.clone());
+                module
+            }
+        };
+        return Ok(bind_module(stmt, module, env));
+    }
+
+    /// Reads and parses `path`, then runs it. The current `call_site` is the
+    /// import statement that asked for it.
+    fn load_module(&mut self, path: &Path) -> Result<Rc<ModuleObject>, ErrorObject> {
+        let call_site = self.call_site;
+        let source = self.loader.read(path).map_err(|e| self.error_at(e, call_site))?;
+        let mut p = parser::Parser::new(lexer::Lexer::new(source.clone()));
+        let program = p.parse_program();
+        if p.has_errors() {
+            let errors: Vec<String> = p.errors().iter().map(|e| e.to_string()).collect();
+            let message = format!("could not parse {}:\n\t{}", path.display(), errors.join("\n\t"));
+            return Err(self.error_at(message, call_site));
+        }
+        for warning in p.warnings() {
+            self.warnings.push(warning.render("warning", &path.display().to_string(), &source));
+        }
+        return self.run_module(path, &program, Some(call_site));
+    }
+
+    fn run_module(&mut self, path: &Path, program: &ast::ProgramNode, call_site: Option<(usize, usize)>) -> Result<Rc<ModuleObject>, ErrorObject> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let file = Rc::new(path.to_path_buf());
+        let env = Environment::new();
+        if let Some(coverage) = &mut self.coverage {
+            coverage.register(&path.display().to_string(), program);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(format!("<module {}>", name));
+        }
+        self.loading.push(file.clone());
+        self.frames.push(CallFrame {
+            function: format!("<module {}>", name),
+            file: Some(file),
+            call_site,
+            is_function: false,
+            try_depth: 0,
+        });
+        let result = self.eval_program(program, &env);
+        self.frames.pop();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+        self.loading.pop();
+        result?;
+
+        let mut exports = BTreeMap::new();
+        for statement in &program.statements {
+            let name = match statement {
+                ast::StatementNode::Let(stmt) => &stmt.name.value,
+                ast::StatementNode::Struct(stmt) => &stmt.name.value,
+                _ => continue,
+            };
+            if let Some(value) = env.borrow().get(name) {
+                exports.insert(name.clone(), value);
+            }
+        }
+        return Ok(Rc::new(ModuleObject { name, exports }));
+    }
+}
+
+/// Binds an imported module under its alias, or else its own name.
+fn bind_module(stmt: &ast::ImportStatement, module: Rc<ModuleObject>, env: &Env) -> Object {
+    let name = match &stmt.alias {
+        Some(alias) => alias.value.clone(),
+        None => module.name.clone(),
+    };
+    env.borrow_mut().set(&name, Object::Module(module));
+    return Object::Null;
+}
+
+fn statement_token(statement: &ast::StatementNode) -> &token::Token {
+    match statement {
+        ast::StatementNode::Expression(stmt) => &stmt.token,
+        ast::StatementNode::Let(stmt) => &stmt.token,
+        ast::StatementNode::Return(stmt) => &stmt.token,
+        ast::StatementNode::Import(stmt) => &stmt.token,
+        ast::StatementNode::Struct(stmt) => &stmt.token,
+        ast::StatementNode::Impl(stmt) => &stmt.token,
+        ast::StatementNode::Yield(stmt) => &stmt.token,
+        ast::StatementNode::For(stmt) => &stmt.token,
+    }
+}
+
+/// Whether a generator has to run `statement` itself, because a `yield`
+/// may be reached inside it. Functions defined in it don't count, as they
+/// can't yield on the generator's behalf.
+fn statement_may_yield(statement: &ast::StatementNode) -> bool {
+    let block_may_yield = |block: &ast::BlockStatement| block.statements.iter().any(statement_may_yield);
+    match statement {
+        ast::StatementNode::Yield(_) => true,
+        ast::StatementNode::For(stmt) => block_may_yield(&stmt.body),
+        ast::StatementNode::Expression(stmt) => match &stmt.expression {
+            ast::ExpressionNode::If(if_expr) => {
+                block_may_yield(&if_expr.consequence) || if_expr.alternative.as_ref().is_some_and(block_may_yield)
+            }
+            ast::ExpressionNode::Match(match_expr) => match_expr.arms.iter().any(|arm| block_may_yield(&arm.body)),
+            ast::ExpressionNode::Try(try_expr) => block_may_yield(&try_expr.body) || block_may_yield(&try_expr.handler),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Finds the block of a generator's body that `path` leads to.
+fn block_at<'a>(body: &'a ast::BlockStatement, path: &BlockPath) -> &'a ast::BlockStatement {
+    let mut block = body;
+    for (index, branch) in path {
+        let statement = &block.statements[*index];
+        block = match (statement, branch) {
+            (ast::StatementNode::For(stmt), Branch::Loop) => &stmt.body,
+            (ast::StatementNode::Expression(stmt), branch) => match (&stmt.expression, branch) {
+                (ast::ExpressionNode::If(if_expr), Branch::Consequence) => &if_expr.consequence,
+                (ast::ExpressionNode::If(if_expr), Branch::Alternative) => if_expr.alternative.as_ref().unwrap(),
+                (ast::ExpressionNode::Match(match_expr), Branch::Arm(i)) => &match_expr.arms[*i].body,
+                (ast::ExpressionNode::Try(try_expr), Branch::TryBody) => &try_expr.body,
+                (ast::ExpressionNode::Try(try_expr), Branch::Handler) => &try_expr.handler,
+                _ => unreachable!("generator path doesn't match its body"),
+            },
+            _ => unreachable!("generator path doesn't match its body"),
+        };
+    }
+    return block;
+}
+
+/// The bytes a freshly created object took to allocate, not counting the
+/// values it shares with others.
+fn allocation_size(object: &Object) -> usize {
+    match object {
+        Object::String(s) => s.len(),
+        Object::Array(elements) => elements.len() * std::mem::size_of::<Object>(),
+        Object::Hash(pairs) => pairs.len() * std::mem::size_of::<(HashKey, Object)>(),
+        Object::Struct(instance) => instance.values.len() * std::mem::size_of::<Object>(),
+        _ => 0,
+    }
+}
+
+/// Checks `value` against `pattern`, binding any names it introduces into
+/// `env`. Bindings from a failed match may be left behind, so callers give
+/// each attempt a fresh scope.
+fn match_pattern(pattern: &ast::Pattern, value: &Object, env: &Env) -> bool {
+    match pattern {
+        ast::Pattern::Wildcard(_) => true,
+        ast::Pattern::Binding(ident) => {
+            env.borrow_mut().set(&ident.value, value.clone());
+            true
+        }
+        ast::Pattern::Literal(literal) => match (&literal.value, value) {
+            (ast::LiteralEnum::IntLiteral(l), Object::Integer(v)) => l == v,
+            (ast::LiteralEnum::StringLiteral(l), Object::String(v)) => l.as_str() == v.as_ref(),
+            (ast::LiteralEnum::Boolean(l), Object::Boolean(v)) => l == v,
+            _ => false,
+        },
+        ast::Pattern::Array(array) => {
+            let Object::Array(elements) = value else {
+                return false;
+            };
+            let fits = match array.rest {
+                Some(_) => elements.len() >= array.elements.len(),
+                None => elements.len() == array.elements.len(),
+            };
+            if !fits {
+                return false;
+            }
+            for (pattern, element) in array.elements.iter().zip(elements.iter()) {
+                if !match_pattern(pattern, element, env) {
+                    return false;
+                }
+            }
+            if let Some(Some(rest)) = &array.rest {
+                let remaining = elements[array.elements.len()..].to_vec();
+                env.borrow_mut().set(&rest.value, Object::Array(Rc::new(remaining)));
+            }
+            true
+        }
+        ast::Pattern::Hash(hash) => {
+            let Object::Hash(pairs) = value else {
+                return false;
+            };
+            for (key, pattern) in &hash.pairs {
+                let key = match &key.value {
+                    ast::LiteralEnum::IntLiteral(i) => HashKey::Integer(*i),
+                    ast::LiteralEnum::StringLiteral(s) => HashKey::String(s.as_str().into()),
+                    ast::LiteralEnum::Boolean(b) => HashKey::Boolean(*b),
+                };
+                match pairs.get(&key) {
+                    Some(value) if match_pattern(pattern, value, env) => {}
+                    _ => return false,
+                }
+            }
+            true
+        }
+    }
+}
+
+fn eval_prefix_expression(operator: &token::TokenType, right: Object) -> Result<Object, String> {
+    match (operator, &right) {
+        (token::TokenType::Bang, _) => Ok(Object::Boolean(!right.is_truthy())),
+        (token::TokenType::Minus, Object::Integer(i)) => Ok(Object::Integer(i.wrapping_neg())),
+        _ => Err(format!("unknown operator: {:?}{}", operator, right.type_name())),
+    }
+}
+
+fn eval_infix_expression(operator: &token::TokenType, left: Object, right: Object) -> Result<Object, String> {
+    match operator {
+        token::TokenType::EQ => return Ok(Object::Boolean(left.loosely_equals(&right))),
+        token::TokenType::NotEQ => return Ok(Object::Boolean(!left.loosely_equals(&right))),
+        token::TokenType::STRONGEQ => return Ok(Object::Boolean(left.strictly_equals(&right))),
+        _ => {}
+    }
+    match (&left, &right) {
+        (Object::Integer(l), Object::Integer(r)) => eval_integer_infix_expression(operator, *l, *r),
+        (Object::String(l), Object::String(r)) => match operator {
+            token::TokenType::Plus => Ok(Object::String(format!("{}{}", l, r).into())),
+            _ => Err(format!("unknown operator: STRING {:?} STRING", operator)),
+        },
+        _ if left.type_name() != right.type_name() => Err(format!(
+            "type mismatch: {} {:?} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+        _ => Err(format!(
+            "unknown operator: {} {:?} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+fn eval_integer_infix_expression(operator: &token::TokenType, l: i64, r: i64) -> Result<Object, String> {
+    match operator {
+        token::TokenType::Plus => Ok(Object::Integer(l.wrapping_add(r))),
+        token::TokenType::Minus => Ok(Object::Integer(l.wrapping_sub(r))),
+        token::TokenType::Asterisk => Ok(Object::Integer(l.wrapping_mul(r))),
+        token::TokenType::Slash => {
+            if r == 0 {
+                return Err("division by zero".to_string());
+            }
+            Ok(Object::Integer(l.wrapping_div(r)))
+        }
+        token::TokenType::LT => Ok(Object::Boolean(l < r)),
+        token::TokenType::GT => Ok(Object::Boolean(l > r)),
+        token::TokenType::LTE => Ok(Object::Boolean(l <= r)),
+        token::TokenType::GTE => Ok(Object::Boolean(l >= r)),
+        _ => Err(format!("unknown operator: INTEGER {:?} INTEGER", operator)),
+    }
+}
+
+fn eval_index_expression(left: Object, index: Object) -> Result<Object, String> {
+    match (&left, &index) {
+        (Object::Array(elements), Object::Integer(i)) => {
+            if *i < 0 || *i as usize >= elements.len() {
+                return Ok(Object::Null);
+            }
+            Ok(elements[*i as usize].clone())
+        }
+        (Object::Hash(pairs), _) => {
+            let key = index
+                .hash_key()
+                .ok_or_else(|| format!("unusable as hash key: {}", index.type_name()))?;
+            Ok(pairs.get(&key).cloned().unwrap_or(Object::Null))
+        }
+        (Object::Error(error), Object::String(field)) => match field.as_ref() {
+            "message" => Ok(Object::String(error.message.as_str().into())),
+            "trace" => {
+                let frames = error.trace.iter().map(|f| Object::String(f.to_string().into())).collect();
+                Ok(Object::Array(Rc::new(frames)))
+            }
+            _ => Err(format!("error has no field {}", field)),
+        },
+        (Object::Module(module), Object::String(name)) => module
+            .exports
+            .get(name.as_ref())
+            .cloned()
+            .ok_or_else(|| format!("module {} has no export {}", module.name, name)),
+        _ => Err(format!(
+            "index operator not supported: {}[{}]",
+            left.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+#[cfg(test)]
+fn eval_with_loader(input: &str, loader: crate::module::MemoryLoader) -> Result<Object, ErrorObject> {
+    let mut p = parser::Parser::new(lexer::Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(!p.has_errors(), "parser errors: {:?}", p.errors());
+    let mut evaluator = Evaluator::new().with_loader(loader);
+    return evaluator.eval_program(&program, &Environment::new());
+}
+
+#[rstest]
+#[case("5", "5")]
+#[case("-5 + 10 * 2", "15")]
+#[case("(5 + 10) * 2 / 3", "10")]
+#[case("1 < 2 == true", "true")]
+#[case("!true", "false")]
+#[case("!!5", "true")]
+#[case("3 >= 3", "true")]
+#[case("\"Hello\" + \" \" + \"World!\"", "Hello World!")]
+#[case("if (1 > 2) { 10 } else { 20 }", "20")]
+#[case("if (false) { 10 }", "null")]
+#[case("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", "10")]
+#[case("let a = 5; let b = a * 2; b", "10")]
+#[case("let add = fn(a, b) { a + b }; add(2, add(3, 4))", "9")]
+#[case("let adder = fn(x) { fn(y) { x + y } }; adder(2)(3)", "5")]
+#[case("let fact = fn(n) { if (n < 2) { return 1; } n * fact(n - 1) }; fact(10)", "3628800")]
+#[case("[1, 2 * 2, 3][1]", "4")]
+#[case("[1, 2, 3][3]", "null")]
+#[case("{\"one\": 1, true: 2, 3: 3}[true]", "2")]
+#[case("len(\"four\") + len([1, 2])", "6")]
+#[case("rest(push([1, 2], 3))", "[2, 3]")]
+#[case("{\"a\": [\"b\"]}", "{\"a\": [\"b\"]}")]
+fn test_eval(#[case] input: &str, #[case] expected: &str) {
+    let result = eval_with_loader(input, Default::default()).unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[rstest]
+#[case("match 2 { 1 => \"one\", 2 => \"two\", _ => \"many\" }", "two")]
+#[case("match 7 { 1 => \"one\", _ => \"many\" }", "many")]
+#[case("match -1 { -1 => true, _ => false }", "true")]
+#[case("match \"hi\" { \"hi\" => { let x = 1; x + 1 } _ => 0 }", "2")]
+#[case("match 5 { n if n > 10 => \"big\", n => n * 2 }", "10")]
+#[case("match [1, 2, 3] { [] => 0, [head, ..rest] => rest }", "[2, 3]")]
+#[case("match [1, 2, 3] { [a, b] => 0, [a, _, c] => a + c }", "4")]
+#[case("match [1] { [a, ..] => a }", "1")]
+#[case("match [] { [a, ..rest] => a, [] => \"empty\" }", "empty")]
+#[case("match [[1, 2], 3] { [[a, b], c] => a + b + c }", "6")]
+#[case("match {\"x\": 1, \"y\": 2} { {\"x\": 0} => \"origin\", {\"x\": x, \"y\": y} => x + y }", "3")]
+#[case("match {\"kind\": \"circle\"} { {\"kind\": \"square\"} => 1, {\"missing\": _} => 2, _ => 3 }", "3")]
+#[case("match true { true => 1, false => 0 }", "1")]
+#[case("let sum = fn(xs) { match xs { [] => 0, [x, ..rest] => x + sum(rest) } }; sum([1, 2, 3, 4])", "10")]
+fn test_eval_match(#[case] input: &str, #[case] expected: &str) {
+    let result = eval_with_loader(input, Default::default()).unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[rstest]
+#[case("struct Point { x, y }; let p = Point { y: 2, x: 1 }; p.x * 10 + p.y", "12")]
+#[case("struct Point { x, y }; Point { x: 1, y: [2] }", "Point { x: 1, y: [2] }")]
+#[case("struct Unit {}; [Unit {}, type(Unit {}), type(Unit)]", "[Unit {}, \"Unit\", \"STRUCT\"]")]
+#[case("struct Point { x, y }; let x = 3; let y = 4; Point { x, y }.y", "4")]
+#[case("struct V { x, y } impl V { fn add(self, o) { V { x: self.x + o.x, y: self.y + o.y } } }; V { x: 1, y: 2 }.add(V { x: 3, y: 4 })", "V { x: 4, y: 6 }")]
+#[case("struct C { n } impl C { fn new(n) { C { n } } fn get(self) { self.n } }; C.new(7).get()", "7")]
+#[case("struct C { f }; C { f: fn(x) { x * 2 } }.f(4)", "8")]
+#[case("struct P { x }; struct Q { x }; [P { x: 1 } == P { x: 1 }, P { x: 1 } == P { x: 2 }, P { x: 1 } != Q { x: 1 }]", "[true, false, true]")]
+#[case("struct P { x }; match (P { x: 1 }).x { 1 => \"one\", _ => \"other\" }", "one")]
+#[case("struct P { x }; let p = P { x: 2 }; match p.x { 1 => \"one\", _ => \"other\" }", "other")]
+#[case("struct N { v } impl N { fn count(self, n) { if (n == 0) { return self.v; } return self.count(n - 1); } }; N { v: 5 }.count(3)", "5")]
+#[case("try { 1 + \"a\" } catch (e) { e.message }", "type mismatch: INTEGER \"+\" STRING")]
+fn test_eval_structs(#[case] input: &str, #[case] expected: &str) {
+    let result = eval_with_loader(input, Default::default()).unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[rstest]
+#[case("1", "1", true, true)]
+#[case("1", "2", false, false)]
+#[case("\"a\"", "\"a\"", true, true)]
+#[case("true", "false", false, false)]
+#[case("if (false) { 1 }", "if (false) { 1 }", true, true)]
+#[case("1", "\"1\"", true, false)]
+#[case("-4", "\"-4\"", true, false)]
+#[case("4", "\"+4\"", true, false)]
+#[case("1", "\"1.0\"", false, false)]
+#[case("1", "\" 1\"", false, false)]
+#[case("true", "1", true, false)]
+#[case("false", "0", true, false)]
+#[case("true", "2", false, false)]
+#[case("true", "\"1\"", true, false)]
+#[case("false", "\"false\"", false, false)]
+#[case("0", "if (false) { 1 }", false, false)]
+#[case("[1, [true]]", "[\"1\", [1]]", true, false)]
+#[case("[1]", "[1, 2]", false, false)]
+#[case("{\"a\": 1}", "{\"a\": \"1\"}", true, false)]
+#[case("{\"a\": 1}", "{\"b\": 1}", false, false)]
+#[case("{1: 1}", "{\"1\": 1}", false, false)]
+#[case("fn() { 1 }", "fn() { 1 }", false, false)]
+#[case("len", "len", true, true)]
+#[case("len", "puts", false, false)]
+#[case("try { throw(\"x\") } catch (e) { e }", "try { throw(\"x\") } catch (e) { e }", true, false)]
+fn test_eval_equality(#[case] left: &str, #[case] right: &str, #[case] loose: bool, #[case] strict: bool) {
+    let input = format!("let l = {}; let r = {}; [l == r, r == l, l != r, l === r, r === l]", left, right);
+    let result = eval_with_loader(&input, Default::default()).unwrap();
+    let expected = format!("[{}, {}, {}, {}, {}]", loose, loose, !loose, strict, strict);
+    assert_eq!(result.to_string(), expected);
+}
+
+#[test]
+fn test_eval_equality_across_kinds() {
+    // One value of every kind, picked so that the conversions `==` makes
+    // between integers, strings and booleans all succeed.
+    let values = [
+        ("INTEGER", "1"),
+        ("STRING", "\"1\""),
+        ("BOOLEAN", "true"),
+        ("NULL", "if (false) { 1 }"),
+        ("ARRAY", "[1]"),
+        ("HASH", "{\"a\": 1}"),
+        ("FUNCTION", "fn() { 1 }"),
+        ("BUILTIN", "len"),
+        ("MODULE", "m"),
+        ("STRUCT", "P"),
+        ("P", "P { x: 1 }"),
+        ("ERROR", "try { throw(\"x\") } catch (e) { e }"),
+    ];
+    let converts = ["INTEGER", "STRING", "BOOLEAN"];
+    for (left_kind, left) in &values {
+        for (right_kind, right) in &values {
+            let loader = crate::module::MemoryLoader::default().with("m.wl", "let x = 1;");
+            let same = left_kind == right_kind;
+            // Two values of the same kind are compared to themselves, since
+            // apart from scalars only identical values are `===`.
+            let right = if same { "l" } else { right };
+            let input = format!(
+                "import \"m\"; struct P {{ x }}; let l = {}; let r = {}; [type(l), type(r), l == r, l === r]",
+                left, right
+            );
+            let result = eval_with_loader(&input, loader).unwrap();
+            let loose = same || (converts.contains(left_kind) && converts.contains(right_kind));
+            let expected = format!("[\"{}\", \"{}\", {}, {}]", left_kind, right_kind, loose, same);
+            assert_eq!(result.to_string(), expected, "{} and {}", left, right);
+        }
+    }
+}
+
+#[test]
+fn test_eval_strict_equality_is_identity() {
+    let input = "struct P { x }; let p = P { x: 1 }; let a = [1]; let h = {}; let f = fn() { 1 }; \
+                 [p === p, p === P { x: 1 }, p == P { x: 1 }, a === a, a === [1], h === h, h === {}, f === f]";
+    let result = eval_with_loader(input, Default::default()).unwrap();
+    assert_eq!(result.to_string(), "[true, false, true, true, false, true, false, true]");
+}
+
+#[rstest]
+#[case("5 + true", "type mismatch: INTEGER \"+\" BOOLEAN")]
+#[case("-true", "unknown operator: \"-\"BOOLEAN")]
+#[case("true + false", "unknown operator: BOOLEAN \"+\" BOOLEAN")]
+#[case("foobar", "identifier not found: foobar")]
+#[case("1 / 0", "division by zero")]
+#[case("5(1)", "not a function: INTEGER")]
+#[case("fn(x) { x }()", "wrong number of arguments: want=1, got=0")]
+#[case("{fn(x) { x }: 1}", "unusable as hash key: FUNCTION")]
+#[case("match 3 { 1 => 1, 2 => 2 }", "no match arm matched 3")]
+#[case("throw(\"custom\")", "custom")]
+#[case("throw([1])", "[1]")]
+#[case("try { 1 + true } catch (e) { throw(e) }", "type mismatch: INTEGER \"+\" BOOLEAN")]
+#[case("struct P { x }; P { x: 1 } + 1", "type mismatch: P \"+\" INTEGER")]
+#[case("struct P { x }; len(P { x: 1 })", "argument to `len` not supported, got P")]
+#[case("struct P { x }; P { x: 1, y: 2 }", "P has no field `y`")]
+#[case("struct P { x, y }; P { x: 1 }", "missing field `y` in P")]
+#[case("struct P { x }; P { x: 1 }.y", "P has no field `y`")]
+#[case("struct P { x }; impl P { fn get(self) { self.x } }; P { x: 1 }.get", "method `get` of P must be called, as in `.get()`")]
+#[case("struct P { x }; P.new()", "struct P has no method `new`")]
+#[case("struct P { x }; impl P { fn x(self) { 1 } }", "P already has a field named `x`")]
+#[case("let P = 1; P { x: 1 }", "not a struct: INTEGER")]
+#[case("let P = 1; impl P {}", "not a struct: INTEGER")]
+#[case("5.x", "INTEGER has no field `x`")]
+fn test_eval_errors(#[case] input: &str, #[case] expected: &str) {
+    let err = eval_with_loader(input, Default::default()).unwrap_err();
+    assert_eq!(err.message, expected);
+}
+
+#[rstest]
+#[case("import \"lib/math\"; math[\"square\"](4)", "16")]
+#[case("import \"lib/math\" as m; m[\"twice\"](m[\"square\"])(3)", "81")]
+#[case("import \"lib/math\"; import \"lib/counter\"; counter[\"count\"]", "1")]
+#[case("import \"lib/uses_sibling\" as u; u[\"value\"]", "25")]
+#[case("import \"lib/shapes\" as s; let r = s.Rect { w: 2, h: 3 }; r.area()", "6")]
+#[case("import \"lib/math\"; math.square(4)", "16")]
+fn test_eval_imports(#[case] input: &str, #[case] expected: &str) {
+    let loader = crate::module::MemoryLoader::default()
+        .with("lib/math.wl", "let square = fn(x) { x * x }; let twice = fn(f) { fn(x) { f(f(x)) } };")
+        .with("lib/counter.wl", "puts(\"loading counter\"); let count = len([0]);")
+        .with("lib/uses_sibling.wl", "import \"./math\"; let value = math[\"square\"](5);")
+        .with("lib/shapes.wl", "struct Rect { w, h } impl Rect { fn area(self) { self.w * self.h } }");
+    let result = eval_with_loader(input, loader).unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[test]
+fn test_eval_imports_once() {
+    let loader = crate::module::MemoryLoader::default()
+        .with("counter.wl", "puts(\"loading counter\"); let count = 1;")
+        .with("user.wl", "import \"counter\"; let count = counter[\"count\"];");
+    let output = CapturedOutput::default();
+    let mut evaluator = Evaluator::new().with_loader(loader).with_output(output.clone());
+    let mut p = parser::Parser::new(lexer::Lexer::new(
+        "import \"counter\"; import \"user\"; import \"counter\" as again; again[\"count\"] + user[\"count\"]".to_string(),
+    ));
+    let program = p.parse_program();
+    let result = evaluator.eval_program(&program, &Environment::new()).unwrap();
+    assert_eq!(result.to_string(), "2");
+    assert_eq!(output.contents(), "loading counter\n");
+}
+
+#[rstest]
+#[case("import \"a\";", "import cycle detected: a.wl -> b.wl -> a.wl")]
+#[case("import \"missing\";", "could not read missing.wl: no such module")]
+#[case("import \"bad\";", "could not parse bad.wl:\n\texpected next token to be \"Identifier\", got \"=\" instead. Line 1, col 5\n\tno prefix parse function for \"=\" found. Line 1, col 5")]
+#[case("import \"other\"; other[\"nope\"]", "module other has no export nope")]
+fn test_eval_import_errors(#[case] input: &str, #[case] expected: &str) {
+    let loader = crate::module::MemoryLoader::default()
+        .with("a.wl", "import \"b\"; let x = 1;")
+        .with("b.wl", "import \"a\"; let y = 2;")
+        .with("other.wl", "let y = 2;")
+        .with("bad.wl", "let = 1;");
+    let err = eval_with_loader(input, loader).unwrap_err();
+    assert_eq!(err.message, expected);
+}
+
+#[rstest]
+#[case("try { 5 } catch (e) { 0 }", "5")]
+#[case("try { 1 + true } catch (e) { e[\"message\"] }", "type mismatch: INTEGER \"+\" BOOLEAN")]
+#[case("try { 5(1) } catch (e) { e[\"message\"] }", "not a function: INTEGER")]
+#[case("try { [1][\"x\"] } catch (e) { e[\"message\"] }", "index operator not supported: ARRAY[STRING]")]
+#[case("try { throw(\"boom\") } catch (e) { e }", "error: boom")]
+#[case("type(try { throw(1) } catch (e) { e })", "ERROR")]
+#[case("try { try { throw(\"a\") } catch (e) { throw(e[\"message\"] + \"b\") } } catch (e) { e[\"message\"] }", "ab")]
+#[case("let f = fn() { try { return 1; } catch (e) { 2 }; 3 }; f()", "1")]
+#[case("let f = fn() { 1 + true }; let g = fn() { try { f() } catch (e) { e[\"trace\"] } }; g()", "[\"at f (line 1, col 18)\", \"at g (line 1, col 50)\", \"at <main> (line 1, col 84)\"]")]
+fn test_eval_try(#[case] input: &str, #[case] expected: &str) {
+    let result = eval_with_loader(input, Default::default()).unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[test]
+fn test_uncaught_error_trace() {
+    let loader = crate::module::MemoryLoader::default()
+        .with("lib/shapes.wl", "let area = fn(shape) {\n  shape[\"w\"] * shape[\"h\"]\n};\n")
+        .with("main.wl", "import \"lib/shapes\";\nlet report = fn(s) { shapes[\"area\"](s) };\nreport({\"w\": 2, \"h\": \"3\"});\n");
+    let source = loader.sources[Path::new("main.wl")].clone();
+    let mut evaluator = Evaluator::new().with_loader(loader);
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.clone()));
+    let program = p.parse_program();
+    let Err(err) = evaluator.eval_module(Path::new("main.wl"), &program) else {
+        panic!("expected an error");
+    };
+    let trace: Vec<String> = err.trace.iter().map(|f| f.to_string()).collect();
+    assert_eq!(
+        trace,
+        vec![
+            "at area (lib/shapes.wl:2:14)",
+            "at report (main.wl:2:36)",
+            "at <module main> (main.wl:3:7)",
+        ]
+    );
+    assert_eq!(
+        evaluator.render_error(&err, &source),
+        "error: type mismatch: INTEGER \"*\" STRING
+ --> lib/shapes.wl:2:14
+  |
+2 |   shape[\"w\"] * shape[\"h\"]
+  |              ^
+  = at area (lib/shapes.wl:2:14)
+  = at report (main.wl:2:36)
+  = at <module main> (main.wl:3:7)
+"
+    );
+}
+
+#[rstest]
+#[case("let count = fn(n, acc) { if (n == 0) { return acc; } return count(n - 1, acc + 1); }; count(100000, 0)", "100000")]
+#[case("let even = fn(n) { if (n == 0) { return true; } return odd(n - 1); }; let odd = fn(n) { if (n == 0) { return false; } return even(n - 1); }; even(100001)", "false")]
+#[case("let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; try { f(100) } catch (e) { e[\"message\"] }", "stack overflow: maximum call depth of 50 exceeded")]
+#[case("let f = fn(n) { if (n == 0) { return 0; } try { return f(n - 1); } catch (e) { throw(e) } }; try { f(100) } catch (e) { e[\"message\"] }", "stack overflow: maximum call depth of 50 exceeded")]
+#[case("let f = fn(n) { if (n == 0) { return 0; } return f(n - 1); }; let g = fn() { return f(1000); }; g()", "0")]
+#[case("let f = fn() { return len(\"abc\"); }; f()", "3")]
+#[case("let f = fn(n) { return n + true; }; let g = fn() { return f(1); }; try { g() } catch (e) { e[\"trace\"] }", "[\"at f (line 1, col 26)\", \"at <main> (line 1, col 75)\"]")]
+fn test_eval_tail_calls(#[case] input: &str, #[case] expected: &str) {
+    let mut p = parser::Parser::new(lexer::Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    let mut evaluator = Evaluator::new().with_max_call_depth(50);
+    let result = evaluator.eval_program(&program, &Environment::new()).unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[test]
+fn test_profile_and_coverage() {
+    let source = "import \"lib\"\nlet f = fn(n) {\n    if (n < 1) { return 0; }\n    f(n - 1)\n};\nf(3);\nlib.twice(len(\"ab\"));";
+    let loader = crate::module::MemoryLoader::default().with("lib.wl", "let twice = fn(x) {\n    x * 2\n};");
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    let mut evaluator = Evaluator::new().with_loader(loader).with_profiler().with_coverage();
+    evaluator.eval_module(Path::new("main.wl"), &program).unwrap();
+
+    let profiler = evaluator.take_profiler().unwrap();
+    let calls: Vec<(&str, u64)> = profiler.functions().iter().map(|(l, p)| (l.as_str(), p.calls)).collect();
+    assert_eq!(
+        calls,
+        [("<module lib>", 1), ("<module main>", 1), ("builtin len", 1), ("f (main.wl:2)", 4), ("twice (lib.wl:1)", 1)]
+    );
+    let folded = profiler.folded();
+    let stacks: Vec<&str> = folded.lines().map(|l| l.rsplit_once(' ').unwrap().0).collect();
+    assert_eq!(
+        stacks,
+        [
+            "<module main>",
+            "<module main>;<module lib>",
+            "<module main>;builtin len",
+            "<module main>;f (main.wl:2)",
+            "<module main>;f (main.wl:2);f (main.wl:2)",
+            "<module main>;f (main.wl:2);f (main.wl:2);f (main.wl:2)",
+            "<module main>;f (main.wl:2);f (main.wl:2);f (main.wl:2);f (main.wl:2)",
+            "<module main>;twice (lib.wl:1)",
+        ]
+    );
+
+    let coverage = evaluator.take_coverage().unwrap();
+    let main = &coverage.files()["main.wl"];
+    assert_eq!(main.lines(), BTreeMap::from([(1, 1), (2, 1), (3, 4), (4, 3), (6, 1), (7, 1)]));
+    assert_eq!(main.branches.values().collect::<Vec<_>>(), [&vec![1, 3]]);
+    assert_eq!(coverage.files()["lib.wl"].lines(), BTreeMap::from([(1, 1), (2, 1)]));
+}
+
+#[rstest]
+#[case("let count = fn*(n) { yield n; yield n + 1; }; collect(count(5))", "[5, 6]")]
+#[case("let count = fn*() { yield 1; }; [type(count), type(count()), count()]", "[\"FUNCTION\", \"ITERATOR\", <iterator>]")]
+#[case("let from = fn*(n) { yield n; for (x in from(n + 1)) { yield x; } }; collect(take(from(1), 5))", "[1, 2, 3, 4, 5]")]
+#[case("let from = fn*(n) { yield n; yield* from(n + 1); }; collect(take(filter(map(from(0), fn(x) { x * x }), fn(x) { x / 2 * 2 == x }), 4))", "[0, 4, 16, 36]")]
+#[case("let g = fn*() { yield* [1, 2]; yield* \"ab\"; yield 3; }; collect(g())", "[1, 2, \"a\", \"b\", 3]")]
+#[case("let g = fn*(xs) { if (len(xs) > 0) { yield* xs; } }; let h = fn*() { yield* g([1]); yield* g([]); yield* g([2]); }; collect(h())", "[1, 2]")]
+#[case("let f = fn(s) { for (c in s) { if (c != \"h\") { return c + c; } } }; [f(\"héy\"), collect(map(\"hé\", fn(c) { c + c }))]", "[\"éé\", [\"hh\", \"éé\"]]")]
+#[case("let f = fn(xs) { for (x in xs) { if (x > 1) { return x; } } }; [f([1, 2, 3]), f([0])]", "[2, null]")]
+#[case("collect({\"b\": 1, \"a\": 2, 3: 0})", "[3, \"a\", \"b\"]")]
+#[case("let it = iter([1, 2]); [next(it), next(it), next(it), next(it)]", "[{\"done\": false, \"value\": 1}, {\"done\": false, \"value\": 2}, {\"done\": true, \"value\": null}, {\"done\": true, \"value\": null}]")]
+#[case("let it = iter([1, 2, 3, 4]); let firsts = collect(take(it, 2)); [firsts, collect(it)]", "[[1, 2], [3, 4]]")]
+#[case("let g = fn*(x) { if (x > 0) { yield \"pos\"; } else { yield \"neg\"; }; match x { 0 => { yield \"zero\"; } _ => { yield \"other\"; } }; yield \"end\"; }; [collect(g(1)), collect(g(0))]", "[[\"pos\", \"other\", \"end\"], [\"neg\", \"zero\", \"end\"]]")]
+#[case("let g = fn*() { yield 1; return 0; yield 2; }; collect(g())", "[1]")]
+#[case("let g = fn*() { yield 1; if (true) { return 0; } yield 2; }; collect(g())", "[1]")]
+#[case("let g = fn*() { try { yield 1; 1 + true; yield 2; } catch (e) { yield e.message; }; yield 3; }; collect(g())", "[1, \"type mismatch: INTEGER \\\"+\\\" BOOLEAN\", 3]")]
+#[case("let bad = fn*() { yield 1; throw(\"boom\"); }; let g = fn*() { try { for (x in bad()) { yield x; } } catch (e) { yield e.message; } }; collect(g())", "[1, \"boom\"]")]
+#[case("let g = fn*() { yield 1; throw(\"boom\"); }; let it = g(); next(it); [try { next(it) } catch (e) { e.message }, next(it)]", "[\"boom\", {\"done\": true, \"value\": null}]")]
+#[case("let g = fn*(n) { let f = fn() { n * 2 }; yield f(); }; collect(g(4))", "[8]")]
+#[case("let g = fn*() { for (x in [1, 2, 3]) { yield x; } }; let m = map(g(), fn(x) { if (x > 1) { throw(\"too far\") }; x * 10 }); next(m)[\"value\"]", "10")]
+#[case("struct R { lo, hi } impl R { fn* each(self) { for (x in take(iter([0, 1, 2, 3, 4, 5]), self.hi)) { if (x >= self.lo) { yield x; } } } }; collect(R { lo: 2, hi: 4 }.each())", "[2, 3]")]
+fn test_eval_generators(#[case] input: &str, #[case] expected: &str) {
+    let result = eval_with_loader(input, Default::default()).unwrap();
+    assert_eq!(result.to_string(), expected);
+}
+
+#[test]
+fn test_interleaved_generators() {
+    let source = "
+        let from = fn*(n, step) { yield n; yield* from(n + step, step); };
+        let evens = from(0, 2);
+        let odds = from(1, 2);
+        let squares = map(from(1, 1), fn(x) { x * x });
+        let round = fn(log, n) {
+            if (n == 0) { return log; }
+            round(push(push(push(log, next(evens)[\"value\"]), next(odds)[\"value\"]), next(squares)[\"value\"]), n - 1)
+        };
+        let first = round([], 3);
+        let e = next(evens)[\"value\"];
+        [first, e, collect(take(odds, 2))]
+    ";
+    let result = eval_with_loader(source, Default::default()).unwrap();
+    assert_eq!(result.to_string(), "[[0, 1, 1, 2, 3, 4, 4, 5, 9], 6, [7, 9]]");
+}
+
+#[rstest]
+#[case("let g = fn*() { let x = if (true) { yield 1; 2 }; x }; collect(g())", "`yield` can't be used inside an expression")]
+#[case("for (x in 5) { x }", "INTEGER is not iterable")]
+#[case("collect(3)", "argument to `collect`: INTEGER is not iterable")]
+#[case("next([1])", "argument to `next` must be ITERATOR, got ARRAY")]
+#[case("take([1], \"2\")", "argument to `take` must be INTEGER, got STRING")]
+#[case("let g = fn*() { for (x in it) { yield x; } }; let it = g(); collect(it)", "iterator is already running")]
+#[case("let g = fn*() { yield* it; }; let it = g(); collect(it)", "iterator is already running")]
+#[case("let g = fn*() { yield* 1; }; collect(g())", "INTEGER is not iterable")]
+#[case("let g = fn*() { yield 1; }; g(1)", "wrong number of arguments: want=0, got=1")]
+#[case("collect(map([1], fn(a, b) { a }))", "wrong number of arguments: want=2, got=1")]
+fn test_eval_generator_errors(#[case] input: &str, #[case] expected: &str) {
+    let err = eval_with_loader(input, Default::default()).unwrap_err();
+    assert_eq!(err.message, expected);
+}
+
+#[test]
+fn test_tail_delegation_stays_shallow() {
+    let source = "
+        let naturals = fn*() { let from = fn*(n) { yield n; yield* from(n + 1); }; yield* from(0); };
+        let thousands = filter(naturals(), fn(n) { if (n > 0) { n / 1000 * 1000 == n } else { false } });
+        collect(take(thousands, 3))
+    ";
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    let mut evaluator = Evaluator::new().with_max_call_depth(20);
+    let result = evaluator.eval_program(&program, &Environment::new()).unwrap();
+    assert_eq!(result.to_string(), "[1000, 2000, 3000]");
+}
+
+#[test]
+fn test_generators_share_the_budgets() {
+    let source = "let forever = fn*() { for (x in iter([0])) { yield x; } for (x in forever()) { yield x; } }; collect(forever())";
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    let mut evaluator = Evaluator::new().with_step_budget(10_000).with_max_call_depth(50);
+    let err = evaluator.eval_program(&program, &Environment::new()).unwrap_err();
+    assert_eq!(err.message, "stack overflow: maximum call depth of 50 exceeded");
+    assert!(!evaluator.is_halted());
+
+    let source = "let ones = fn*() { for (x in iter([0])) { yield 1; } for (x in ones()) { yield x; } }; let n = 0; for (x in take(ones(), 40)) { x }";
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    let mut evaluator = Evaluator::new().with_step_budget(100).with_max_call_depth(1000);
+    let err = evaluator.eval_program(&program, &Environment::new()).unwrap_err();
+    assert_eq!(err.message, "instruction budget of 100 exceeded");
+}
@@ -0,0 +1,356 @@
+//! A cycle collector for environments. Values are reference counted, which
+//! frees almost everything as soon as it's unused, but a closure stored in
+//! the scope it captures keeps that scope alive through its own `Rc`, and
+//! so does a struct whose methods were defined next to it, or a generator
+//! stored in the scope its suspended body runs in. Every cycle runs through
+//! an environment, since those, struct methods and iterators are the only
+//! values that change after they're made, and an iterator only ever holds
+//! values that existed before it did or environments. Breaking cycles at
+//! environments is enough.
+//!
+//! Environments register themselves when they're created. Once enough have
+//! been created since the last collection, the collector finds the ones
+//! nothing outside the heap can reach and clears them, which lets reference
+//! counting free the rest. It works out what's reachable from outside by
+//! trial deletion: a value whose strong count is higher than the number of
+//! references other heap values hold to it must also be held by something
+//! else, such as the evaluator, a host or a REPL.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+use crate::environment::{Env, Environment};
+use crate::object::{FunctionObject, GeneratorFrame, IteratorObject, IteratorState, Object, StructType};
+use rstest::rstest;
+
+/// How many environments may be created before the first collection.
+const MIN_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GcStats {
+    pub collections: u64,
+    /// Environments alive right now.
+    pub environments: usize,
+    /// Environments freed by collections, having been kept alive only by
+    /// cycles.
+    pub freed: u64,
+}
+
+struct Heap {
+    environments: Vec<Weak<RefCell<Environment>>>,
+    /// Collect once `environments` reaches this many entries.
+    next_collection: usize,
+    collections: u64,
+    freed: u64,
+}
+
+thread_local! {
+    // Values are `Rc`s, so a heap never spans threads.
+    static HEAP: RefCell<Heap> = const {
+        RefCell::new(Heap {
+            environments: Vec::new(),
+            next_collection: MIN_THRESHOLD,
+            collections: 0,
+            freed: 0,
+        })
+    };
+}
+
+/// Registers a new environment, collecting first if enough have been made
+/// since the last collection.
+pub(crate) fn track(env: &Env) {
+    let due = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.environments.push(Rc::downgrade(env));
+        heap.environments.len() >= heap.next_collection
+    });
+    if due {
+        collect();
+    }
+}
+
+/// Statistics for the heap of the current thread.
+pub fn stats() -> GcStats {
+    HEAP.with(|heap| {
+        let heap = heap.borrow();
+        GcStats {
+            collections: heap.collections,
+            environments: heap.environments.iter().filter(|env| env.strong_count() > 0).count(),
+            freed: heap.freed,
+        }
+    })
+}
+
+/// Frees every environment only cycles keep alive, returning how many that
+/// was.
+pub fn collect() -> usize {
+    let environments: Vec<Env> = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.environments.retain(|env| env.strong_count() > 0);
+        heap.environments.iter().filter_map(Weak::upgrade).collect()
+    });
+    let garbage = find_garbage(&environments);
+    for env in &garbage {
+        if let Ok(mut env) = env.try_borrow_mut() {
+            env.store.clear();
+            env.outer = None;
+        }
+    }
+    let freed = garbage.len();
+    drop(garbage);
+    drop(environments);
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.environments.retain(|env| env.strong_count() > 0);
+        heap.collections += 1;
+        heap.freed += freed as u64;
+        heap.next_collection = (heap.environments.len() * 2).max(MIN_THRESHOLD);
+    });
+    return freed;
+}
+
+/// A heap value, keyed by its address in the graph.
+struct Node {
+    strong: usize,
+    /// References to this value from other values in the graph.
+    internal: usize,
+    children: Vec<usize>,
+}
+
+fn address<T: ?Sized>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const () as usize
+}
+
+fn find_garbage(environments: &[Env]) -> Vec<Env> {
+    let mut nodes: HashMap<usize, Node> = HashMap::new();
+    for env in environments {
+        let mut children = Vec::new();
+        // Our own `Rc` from upgrading doesn't count.
+        let mut strong = Rc::strong_count(env) - 1;
+        match env.try_borrow() {
+            Ok(env) => {
+                if let Some(outer) = &env.outer {
+                    children.push(address(outer));
+                }
+                for value in env.store.values() {
+                    scan(value, &mut children, &mut nodes);
+                }
+            }
+            // It's being changed, so it's certainly in use.
+            Err(_) => strong = usize::MAX,
+        }
+        nodes.insert(address(env), Node { strong, internal: 0, children });
+    }
+
+    let edges: Vec<usize> = nodes.values().flat_map(|node| node.children.iter().copied()).collect();
+    for child in edges {
+        if let Some(node) = nodes.get_mut(&child) {
+            node.internal += 1;
+        }
+    }
+    let mut pending: Vec<usize> = nodes
+        .iter()
+        .filter(|(_, node)| node.strong > node.internal)
+        .map(|(id, _)| *id)
+        .collect();
+    let mut reachable: HashSet<usize> = pending.iter().copied().collect();
+    while let Some(id) = pending.pop() {
+        for child in &nodes[&id].children {
+            if nodes.contains_key(child) && reachable.insert(*child) {
+                pending.push(*child);
+            }
+        }
+    }
+    return environments
+        .iter()
+        .filter(|env| !reachable.contains(&address(env)))
+        .cloned()
+        .collect();
+}
+
+/// Adds the edge from a value's holder to `value`, and the value itself to
+/// the graph if it holds references of its own. Only environments can be
+/// part of a cycle, so this never recurses back into a value it's scanning.
+fn scan(value: &Object, edges: &mut Vec<usize>, nodes: &mut HashMap<usize, Node>) {
+    let (id, strong) = match value {
+        Object::Array(elements) => (address(elements), Rc::strong_count(elements)),
+        Object::Hash(pairs) => (address(pairs), Rc::strong_count(pairs)),
+        Object::Struct(instance) => (address(instance), Rc::strong_count(instance)),
+        Object::Module(module) => (address(module), Rc::strong_count(module)),
+        Object::Function(func) => return scan_function(func, edges, nodes),
+        Object::StructType(struct_type) => return scan_struct_type(struct_type, edges, nodes),
+        Object::Iterator(iterator) => return scan_iterator(iterator, edges, nodes),
+        _ => return,
+    };
+    edges.push(id);
+    if nodes.contains_key(&id) {
+        return;
+    }
+    let mut children = Vec::new();
+    match value {
+        Object::Array(elements) => elements.iter().for_each(|e| scan(e, &mut children, nodes)),
+        Object::Hash(pairs) => pairs.values().for_each(|v| scan(v, &mut children, nodes)),
+        Object::Struct(instance) => {
+            scan_struct_type(&instance.struct_type, &mut children, nodes);
+            instance.values.iter().for_each(|v| scan(v, &mut children, nodes));
+        }
+        Object::Module(module) => module.exports.values().for_each(|v| scan(v, &mut children, nodes)),
+        _ => {}
+    }
+    nodes.insert(id, Node { strong, internal: 0, children });
+}
+
+fn scan_function(func: &Rc<FunctionObject>, edges: &mut Vec<usize>, nodes: &mut HashMap<usize, Node>) {
+    let id = address(func);
+    edges.push(id);
+    nodes.entry(id).or_insert_with(|| Node {
+        strong: Rc::strong_count(func),
+        internal: 0,
+        children: vec![address(&func.env)],
+    });
+}
+
+fn scan_struct_type(struct_type: &Rc<StructType>, edges: &mut Vec<usize>, nodes: &mut HashMap<usize, Node>) {
+    let id = address(struct_type);
+    edges.push(id);
+    if nodes.contains_key(&id) {
+        return;
+    }
+    let mut children = Vec::new();
+    let mut strong = Rc::strong_count(struct_type);
+    match struct_type.methods.try_borrow() {
+        Ok(methods) => methods.values().for_each(|m| scan(m, &mut children, nodes)),
+        Err(_) => strong = usize::MAX,
+    }
+    nodes.insert(id, Node { strong, internal: 0, children });
+}
+
+fn scan_iterator(iterator: &Rc<IteratorObject>, edges: &mut Vec<usize>, nodes: &mut HashMap<usize, Node>) {
+    let id = address(iterator);
+    edges.push(id);
+    if nodes.contains_key(&id) {
+        return;
+    }
+    let mut children = Vec::new();
+    let mut strong = Rc::strong_count(iterator);
+    match iterator.state.try_borrow() {
+        Ok(state) => match &*state {
+            IteratorState::Elements { elements, .. } => elements.iter().for_each(|e| scan(e, &mut children, nodes)),
+            IteratorState::Generator(generator) => {
+                scan_function(&generator.function, &mut children, nodes);
+                for frame in &generator.frames {
+                    match frame {
+                        GeneratorFrame::Block { env, .. } | GeneratorFrame::Try { env, .. } => children.push(address(env)),
+                        GeneratorFrame::Loop { iterator, env, .. } => {
+                            children.push(address(env));
+                            scan_iterator(iterator, &mut children, nodes);
+                        }
+                        GeneratorFrame::Delegate { iterator } => scan_iterator(iterator, &mut children, nodes),
+                    }
+                }
+            }
+            IteratorState::Map { source, function: f } | IteratorState::Filter { source, predicate: f } => {
+                scan_iterator(source, &mut children, nodes);
+                scan(f, &mut children, nodes);
+            }
+            IteratorState::Take { source, .. } | IteratorState::Delegate(source) => {
+                scan_iterator(source, &mut children, nodes)
+            }
+            // What a running iterator holds is on the native stack, so it's
+            // in use anyway.
+            IteratorState::Running | IteratorState::Finished => {}
+        },
+        Err(_) => strong = usize::MAX,
+    }
+    nodes.insert(id, Node { strong, internal: 0, children });
+}
+
+/// Runs `source` in a scope of its own and hands the scope back.
+#[cfg(test)]
+fn run(source: &str) -> Env {
+    use crate::evaluator::Evaluator;
+    use crate::{lexer, parser};
+
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    let env = Environment::new();
+    Evaluator::new().with_output(std::io::sink()).eval_program(&program, &env).unwrap();
+    return env;
+}
+
+#[rstest]
+#[case("let a = [1, {\"k\": 2}];", 0)]
+#[case("let f = fn() { 1 };", 1)]
+#[case("let f = fn() { g }; let g = fn() { f };", 1)]
+#[case("struct P { x } impl P { fn get(self) { self.x } }", 1)]
+#[case("let make = fn() { let g = fn() { g }; g }; let a = [make()]; let b = {\"k\": make()};", 3)]
+#[case("let make = fn() { let g = fn() { g }; g }; make(); make();", 3)]
+#[case("let count = fn*() { yield 1; yield 2; }; let it = count(); next(it);", 2)]
+#[case("let each = fn*(xs) { for (x in xs) { yield x; } }; let it = map(each([1, 2]), fn(x) { it }); next(it);", 3)]
+fn test_collect(#[case] source: &str, #[case] garbage: usize) {
+    let env = run(source);
+    let kept = match source.contains("make();") {
+        // Cycles made and dropped while running are garbage right away.
+        true => 2,
+        false => 0,
+    };
+    assert_eq!(collect(), kept);
+    drop(env);
+    assert_eq!(collect(), garbage - kept);
+    assert_eq!(stats().environments, 0);
+    assert_eq!(stats().freed, garbage as u64);
+}
+
+#[test]
+fn test_values_held_outside_the_heap_survive() {
+    let env = run("let f = fn() { f }; let x = 1;");
+    let f = env.borrow().get("f").unwrap();
+    drop(env);
+    assert_eq!(collect(), 0);
+    let Object::Function(func) = &f else {
+        panic!("not a function: {}", f);
+    };
+    assert_eq!(func.env.borrow().get("x"), Some(Object::Integer(1)));
+    drop(f);
+    assert_eq!(collect(), 1);
+}
+
+#[test]
+fn test_cyclic_closures_stress() {
+    use crate::ast;
+    use crate::object::FunctionObject;
+    use crate::token;
+
+    let body = Rc::new(ast::BlockStatement {
+        token: token::Token::new(token::TokenType::LBrace, "{".to_string()),
+        statements: Vec::new(),
+    });
+    for _ in 0..2_000_000 {
+        let env = Environment::new();
+        let func = Object::Function(Rc::new(FunctionObject {
+            name: None,
+            file: None,
+            parameters: Vec::new(),
+            body: body.clone(),
+            env: env.clone(),
+            is_generator: false,
+        }));
+        env.borrow_mut().set("f", func);
+    }
+    let stats = stats();
+    assert!(stats.environments < MIN_THRESHOLD, "{:?}", stats);
+    assert!(stats.freed > 2_000_000 - MIN_THRESHOLD as u64, "{:?}", stats);
+}
+
+#[test]
+fn test_gc_stats_builtin() {
+    let source = "let churn = fn(n) { if (n == 0) { return gc_stats(); } let f = fn() { f }; return churn(n - 1); };\
+                  let stats = churn(100000);\
+                  puts(stats[\"collections\"] > 0, stats[\"environments\"] < 30000, stats[\"freed\"] > 70000);";
+    let output = crate::evaluator::CapturedOutput::default();
+    let mut interpreter = crate::Interpreter::new().with_output(output.clone());
+    interpreter.eval_str(source).unwrap();
+    assert_eq!(output.contents(), "true\ntrue\ntrue\n");
+}
@@ -0,0 +1,148 @@
+//! A grammar based generator of valid programs, for property tests. It
+//! produces source text rather than trees so that the parser is exercised
+//! too, and spells things the way people do, with as few parentheses as
+//! precedence allows.
+
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::strategy::BoxedStrategy;
+
+const NAMES: &[&str] = &["a", "b", "xs", "f", "count", "_"];
+const INFIX_OPERATORS: &[&str] = &["+", "-", "*", "/", "<", ">", "<=", ">=", "==", "===", "!="];
+const BUILTINS: &[&str] = &["len", "first", "last", "rest", "push", "puts"];
+
+fn name() -> impl Strategy<Value = String> {
+    select(NAMES).prop_map(|n| n.to_string())
+}
+
+fn string_literal() -> impl Strategy<Value = String> {
+    "[a-z \\\\\"\n\t]{0,6}".prop_map(|s| {
+        let escaped = s
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+            .replace('\t', "\\t");
+        format!("\"{}\"", escaped)
+    })
+}
+
+fn literal() -> impl Strategy<Value = String> {
+    prop_oneof![
+        (0..1000i64).prop_map(|i| i.to_string()),
+        any::<bool>().prop_map(|b| b.to_string()),
+        string_literal(),
+    ]
+}
+
+fn comma_separated(items: Vec<String>) -> String {
+    items.join(", ")
+}
+
+fn block(statements: impl Strategy<Value = Vec<String>>) -> impl Strategy<Value = String> {
+    statements.prop_map(|statements| {
+        if statements.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{ {} }}", statements.join(" "))
+        }
+    })
+}
+
+fn pattern() -> BoxedStrategy<String> {
+    let leaf = prop_oneof![
+        Just("_".to_string()),
+        name(),
+        literal(),
+        (1..100i64).prop_map(|i| format!("-{}", i)),
+    ];
+    leaf.prop_recursive(3, 12, 3, |inner| {
+        prop_oneof![
+            (prop::collection::vec(inner.clone(), 0..3), prop::option::of(prop::option::of(name()))).prop_map(
+                |(elements, rest)| {
+                    let mut elements = elements;
+                    match rest {
+                        Some(Some(rest)) => elements.push(format!("..{}", rest)),
+                        Some(None) => elements.push("..".to_string()),
+                        None => {}
+                    }
+                    format!("[{}]", comma_separated(elements))
+                }
+            ),
+            prop::collection::vec((string_literal(), inner), 0..3).prop_map(|pairs| {
+                let pairs = pairs.into_iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                format!("{{{}}}", comma_separated(pairs))
+            }),
+        ]
+    })
+    .boxed()
+}
+
+/// Expressions up to a few levels deep, built from every construct the
+/// parser knows.
+pub fn expression() -> BoxedStrategy<String> {
+    let leaf = prop_oneof![name(), literal(), select(BUILTINS).prop_map(|b| b.to_string())];
+    leaf.prop_recursive(4, 48, 4, |inner| {
+        let statements = prop::collection::vec(statement_from(inner.clone()), 0..3).boxed();
+        prop_oneof![
+            // A space keeps `- -a` from lexing as `--`.
+            (select(&["-", "!"][..]), inner.clone()).prop_map(|(op, e)| match e.starts_with('-') {
+                true => format!("{} {}", op, e),
+                false => format!("{}{}", op, e),
+            }),
+            (inner.clone(), select(INFIX_OPERATORS), inner.clone())
+                .prop_map(|(l, op, r)| format!("{} {} {}", l, op, r)),
+            inner.clone().prop_map(|e| format!("({})", e)),
+            (inner.clone(), prop::collection::vec(inner.clone(), 0..3))
+                .prop_map(|(f, args)| format!("{}({})", f, comma_separated(args))),
+            prop::collection::vec(inner.clone(), 0..4).prop_map(|e| format!("[{}]", comma_separated(e))),
+            prop::collection::vec((inner.clone(), inner.clone()), 0..3).prop_map(|pairs| {
+                let pairs = pairs.into_iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                format!("{{{}}}", comma_separated(pairs))
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(l, i)| format!("{}[{}]", l, i)),
+            (inner.clone(), name()).prop_map(|(l, field)| format!("{}.{}", l, field)),
+            (inner.clone(), block(statements.clone()), prop::option::of(block(statements.clone())))
+                .prop_map(|(c, then, otherwise)| match otherwise {
+                    Some(otherwise) => format!("if ({}) {} else {}", c, then, otherwise),
+                    None => format!("if ({}) {}", c, then),
+                }),
+            (prop::collection::vec(name(), 0..3), block(statements.clone()))
+                .prop_map(|(params, body)| format!("fn({}) {}", comma_separated(params), body)),
+            (
+                inner.clone(),
+                prop::collection::vec((pattern(), prop::option::of(inner.clone()), inner.clone()), 1..4)
+            )
+                .prop_map(|(value, arms)| {
+                    let arms: Vec<String> = arms
+                        .into_iter()
+                        // After `=>` a brace opens a block, so hash bodies need parentheses.
+                        .map(|(p, guard, body)| (p, guard, match body.starts_with('{') {
+                            true => format!("({})", body),
+                            false => body,
+                        }))
+                        .map(|(p, guard, body)| match guard {
+                            Some(guard) => format!("{} if {} => {}", p, guard, body),
+                            None => format!("{} => {}", p, body),
+                        })
+                        .collect();
+                    format!("match {} {{ {}, _ => 0 }}", value, arms.join(", "))
+                }),
+            (block(statements.clone()), name(), block(statements))
+                .prop_map(|(body, e, handler)| format!("try {} catch ({}) {}", body, e, handler)),
+        ]
+    })
+    .boxed()
+}
+
+fn statement_from(expression: BoxedStrategy<String>) -> impl Strategy<Value = String> {
+    prop_oneof![
+        (name(), expression.clone()).prop_map(|(n, e)| format!("let {} = {};", n, e)),
+        expression.clone().prop_map(|e| format!("return {};", e)),
+        expression.prop_map(|e| format!("{};", e)),
+    ]
+}
+
+/// A whole program of a few top-level statements.
+pub fn program() -> impl Strategy<Value = String> {
+    prop::collection::vec(statement_from(expression()), 1..6).prop_map(|statements| statements.join("\n"))
+}
@@ -0,0 +1,291 @@
+#[cfg(test)]
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::diagnostic::Diagnostic;
+use crate::environment::{Env, Environment};
+use crate::evaluator::Evaluator;
+use crate::lexer;
+use crate::module::SourceLoader;
+use crate::object::{BuiltinObject, ErrorObject, Object};
+use crate::parser;
+use crate::stdlib::Capabilities;
+use rstest::rstest;
+
+/// Resource limits for running untrusted scripts. The step and memory
+/// budgets apply to each `eval_str` or `call` separately.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// How many statements and calls a script may evaluate.
+    pub max_steps: Option<u64>,
+    /// How many bytes of strings, arrays, hashes and call scopes a script may
+    /// allocate, whether or not they're freed again.
+    pub max_memory: Option<usize>,
+    /// How deeply calls may nest. Each level can use up to
+    /// [`crate::evaluator::STACK_PER_CALL`] bytes of the host thread's stack,
+    /// so only raise this on threads that have room for it.
+    pub max_call_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            max_memory: None,
+            max_call_depth: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EvalError {
+    Parse(Vec<Diagnostic>),
+    Runtime(ErrorObject),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::Parse(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+            EvalError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// A script environment for a Rust host. Globals set by the host and
+/// top-level `let`s from every `eval_str` live in the same scope, so later
+/// snippets and host calls can see what earlier ones defined.
+pub struct Interpreter {
+    evaluator: Evaluator,
+    globals: Env,
+    limits: Limits,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let limits = Limits::default();
+        Interpreter {
+            evaluator: Evaluator::new().with_max_call_depth(limits.max_call_depth),
+            globals: Environment::new(),
+            limits,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Interpreter {
+        let mut evaluator = std::mem::take(&mut self.evaluator).with_max_call_depth(limits.max_call_depth);
+        if let Some(steps) = limits.max_steps {
+            evaluator = evaluator.with_step_budget(steps);
+        }
+        if let Some(bytes) = limits.max_memory {
+            evaluator = evaluator.with_memory_budget(bytes);
+        }
+        self.evaluator = evaluator;
+        self.limits = limits;
+        return self;
+    }
+
+    /// Where `import` finds modules. Defaults to the file system; hosts
+    /// running untrusted scripts will usually want to restrict this.
+    pub fn with_loader(mut self, loader: impl SourceLoader + 'static) -> Interpreter {
+        self.evaluator = std::mem::take(&mut self.evaluator).with_loader(loader);
+        return self;
+    }
+
+    /// Which standard library modules that reach outside the interpreter
+    /// scripts may import. None, by default.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Interpreter {
+        self.evaluator = std::mem::take(&mut self.evaluator).with_capabilities(capabilities);
+        return self;
+    }
+
+    /// Where `puts` writes to. Defaults to stdout.
+    pub fn with_output(mut self, output: impl Write + 'static) -> Interpreter {
+        self.evaluator = std::mem::take(&mut self.evaluator).with_output(output);
+        return self;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn eval_str(&mut self, source: &str) -> Result<Object, EvalError> {
+        let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+        let program = p.parse_program();
+        if p.has_errors() {
+            return Err(EvalError::Parse(p.errors()));
+        }
+        self.evaluator.reset_usage();
+        return self
+            .evaluator
+            .eval_program(&program, &self.globals)
+            .map_err(EvalError::Runtime);
+    }
+
+    /// Calls the global function `name`, e.g. a hook the script defined.
+    pub fn call(&mut self, name: &str, arguments: Vec<Object>) -> Result<Object, EvalError> {
+        let Some(function) = self.get_global(name) else {
+            return Err(EvalError::Runtime(format!("identifier not found: {}", name).into()));
+        };
+        self.evaluator.reset_usage();
+        return self
+            .evaluator
+            .call_function(function, arguments)
+            .map_err(EvalError::Runtime);
+    }
+
+    pub fn set_global(&mut self, name: &str, value: impl Into<Object>) {
+        self.globals.borrow_mut().set(name, value.into());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        self.globals.borrow().get(name)
+    }
+
+    /// Exposes a Rust closure to scripts as the global function `name`.
+    /// Errors it returns are raised in the script like any other, so `try`
+    /// can catch them.
+    pub fn register_fn<R: Into<Object>>(
+        &mut self,
+        name: &str,
+        func: impl Fn(Vec<Object>) -> Result<R, ErrorObject> + 'static,
+    ) {
+        let builtin = BuiltinObject {
+            name: name.to_string(),
+            func: Rc::new(move |_: &mut Evaluator, arguments| func(arguments).map(Into::into)),
+        };
+        self.set_global(name, Object::Builtin(Rc::new(builtin)));
+    }
+
+    /// Renders an error from `eval_str` against the source it was given,
+    /// the way the command line reports it.
+    pub fn render_error(&self, error: &EvalError, source: &str) -> String {
+        match error {
+            EvalError::Parse(errors) => errors
+                .iter()
+                .map(|e| e.render("error", "<main>", source))
+                .collect(),
+            EvalError::Runtime(error) => self.evaluator.render_error(error, source),
+        }
+    }
+}
+
+#[test]
+fn test_globals() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("width", 3);
+    interpreter.set_global("name", "box");
+    interpreter.eval_str("let area = width * 4;").unwrap();
+    assert_eq!(i64::try_from(interpreter.get_global("area").unwrap()).unwrap(), 12);
+    let result = interpreter.eval_str("name + \"!\"").unwrap();
+    assert_eq!(String::try_from(result).unwrap(), "box!");
+    assert!(interpreter.get_global("missing").is_none());
+}
+
+#[test]
+fn test_register_fn() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    let log = calls.clone();
+    interpreter.register_fn("record", move |args| {
+        let value = i64::try_from(args.into_iter().next().unwrap_or(Object::Null))?;
+        log.borrow_mut().push(value);
+        Ok(value * 2)
+    });
+    let result = interpreter.eval_str("record(1) + record(2)").unwrap();
+    assert_eq!(result.to_string(), "6");
+    assert_eq!(*calls.borrow(), vec![1, 2]);
+
+    let result = interpreter.eval_str("try { record(\"x\") } catch (e) { e[\"message\"] }").unwrap();
+    assert_eq!(result.to_string(), "expected INTEGER, got STRING");
+}
+
+#[test]
+fn test_call() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("let update = fn(xs) { push(xs, len(xs)) };").unwrap();
+    let result = interpreter.call("update", vec![vec![7, 8].into()]).unwrap();
+    assert_eq!(Vec::<i64>::try_from(result).unwrap(), vec![7, 8, 2]);
+    let Err(EvalError::Runtime(error)) = interpreter.call("nope", vec![]) else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.message, "identifier not found: nope");
+}
+
+#[rstest]
+#[case(Object::from(5), "5")]
+#[case(Object::from(true), "true")]
+#[case(Object::from(()), "null")]
+#[case(Object::from(None::<i64>), "null")]
+#[case(Object::from(vec![Some("a"), None]), "[\"a\", null]")]
+#[case(Object::from(std::collections::BTreeMap::from([("k".to_string(), 1)])), "{\"k\": 1}")]
+fn test_into_object(#[case] object: Object, #[case] expected: &str) {
+    assert_eq!(object.inspect(), expected);
+}
+
+#[test]
+fn test_from_object() {
+    let mut interpreter = Interpreter::new();
+    let hash = interpreter.eval_str("{\"a\": [1, 2], \"b\": []}").unwrap();
+    let hash = std::collections::BTreeMap::<String, Vec<i64>>::try_from(hash).unwrap();
+    assert_eq!(hash["a"], vec![1, 2]);
+    assert_eq!(Option::<bool>::try_from(Object::Null).unwrap(), None);
+    let error = Vec::<i64>::try_from(Object::from(vec!["x"])).unwrap_err();
+    assert_eq!(error.message, "expected INTEGER, got STRING");
+}
+
+#[rstest]
+#[case(
+    Limits { max_steps: Some(1000), ..Limits::default() },
+    "let spin = fn(n) { return spin(n + 1); }; try { spin(0) } catch (e) { 0 }",
+    "instruction budget of 1000 exceeded"
+)]
+#[case(
+    Limits { max_memory: Some(4096), ..Limits::default() },
+    "let grow = fn(s) { return grow(s + s); }; try { grow(\"ab\") } catch (e) { 0 }",
+    "memory budget of 4096 bytes exceeded"
+)]
+#[case(
+    Limits { max_call_depth: 20, ..Limits::default() },
+    "let deep = fn(n) { 1 + deep(n + 1) }; try { deep(0) } catch (e) { e[\"message\"] }",
+    "stack overflow: maximum call depth of 20 exceeded"
+)]
+fn test_limits(#[case] limits: Limits, #[case] source: &str, #[case] expected: &str) {
+    let mut interpreter = Interpreter::new().with_limits(limits);
+    // Running out of a budget can't be caught by the script, while a stack
+    // overflow can.
+    let result = match interpreter.eval_str(source) {
+        Ok(value) => value.to_string(),
+        Err(error) => error.to_string(),
+    };
+    assert_eq!(result, expected);
+    // Each evaluation gets a fresh budget.
+    assert_eq!(interpreter.eval_str("1 + 1").unwrap().to_string(), "2");
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_generated_programs_never_crash(source in crate::generator::program()) {
+        let limits = Limits {
+            max_steps: Some(10_000),
+            max_memory: Some(1 << 20),
+            max_call_depth: 20,
+        };
+        let mut interpreter = Interpreter::new().with_limits(limits).with_output(std::io::sink());
+        let result = interpreter.eval_str(&source);
+        proptest::prop_assert!(!matches!(result, Err(EvalError::Parse(_))), "{:?} in:\n{}", result, source);
+    }
+}
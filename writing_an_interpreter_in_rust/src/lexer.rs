@@ -2,7 +2,8 @@ use crate::token;
 use rstest::rstest;
 
 pub struct Lexer {
-    input: String,
+    // Indexed by character, so positions never land inside a multi-byte one.
+    input: Vec<char>,
     position: usize,
     read_position: usize,
     line: usize,
@@ -13,7 +14,7 @@ pub struct Lexer {
 impl Lexer {
     pub fn new(input: String) -> Lexer {
         let mut l = Lexer {
-            input,
+            input: input.chars().collect(),
             position: 0,
             line: 1,
             column: 0,
@@ -30,7 +31,7 @@ impl Lexer {
         if self.read_position >= self.input.len() {
             self.ch = '\0';
         } else {
-            self.ch = self.input.chars().nth(self.read_position).unwrap();
+            self.ch = self.input[self.read_position];
         }
         self.position = self.read_position;
         self.read_position += 1;
@@ -44,20 +45,21 @@ impl Lexer {
     fn jump_ahead(&mut self, n: usize) {
         self.position += n;
         self.read_position += n;
+        self.column += n;
         if self.position >= self.input.len() {
             self.ch = '\0';
         } else {
-            self.ch = self.input.chars().nth(self.position).unwrap();
+            self.ch = self.input[self.position];
         }
     }
 
+    fn at_end(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
     fn try_lookahead(&self, literal: &str) -> bool {
-        let length = literal.len();
-        if self.position + length > self.input.len() {
-            return false;
-        }
-        let lookahead = self.input[self.position..self.position + length].to_string();
-        return lookahead == literal;
+        let mut lookahead = self.input[self.position.min(self.input.len())..].iter();
+        return literal.chars().all(|c| lookahead.next() == Some(&c));
     }
 
     fn skip_whitespace(&mut self) {
@@ -83,6 +85,12 @@ impl Lexer {
             return tok;
         }
 
+        if let Some(mut tok) = self.try_string() {
+            tok.line = line;
+            tok.column = col;
+            return tok;
+        }
+
         if let Some(mut tok) = self.try_ident_or_keyword() {
             tok.line = line;
             tok.column = col;
@@ -101,7 +109,11 @@ impl Lexer {
             return tok;
         }
 
-        return token::Token::new_illegal(&self.ch.to_string());
+        let mut tok = token::Token::new_illegal(&self.ch.to_string());
+        tok.line = line;
+        tok.column = col;
+        self.read_char();
+        return tok;
     }
 
     fn try_multi_char_token(&mut self) -> Option<token::Token> {
@@ -134,6 +146,38 @@ impl Lexer {
         return None;
     }
 
+    fn try_string(&mut self) -> Option<token::Token> {
+        if self.ch != '"' {
+            return None;
+        }
+        let mut value = String::new();
+        self.read_char();
+        while self.ch != '"' {
+            if self.at_end() {
+                return Some(token::Token::new_illegal(&format!("\"{}", value)));
+            }
+            if self.ch == '\\' {
+                self.read_char();
+                match self.ch {
+                    _ if self.at_end() => continue,
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    '"' => value.push('"'),
+                    '\\' => value.push('\\'),
+                    c => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                }
+            } else {
+                value.push(self.ch);
+            }
+            self.read_char();
+        }
+        self.read_char();
+        return Some(token::Token::new_string(&value));
+    }
+
     fn try_ident_or_keyword(&mut self) -> Option<token::Token> {
         if is_identifier_char(self.ch) {
             let start = self.position;
@@ -142,7 +186,7 @@ impl Lexer {
                 end = self.position;
                 self.read_char();
             }
-            let literal = self.input[start..=end].to_string();
+            let literal: String = self.input[start..=end].iter().collect();
             if let Some(token_type) = token::lookup_keyword(&literal) {
                 return Some(token::Token::new_symbol(token_type));
             }
@@ -152,21 +196,21 @@ impl Lexer {
     }
 
     fn try_number(&mut self) -> Option<token::Token> {
-        if self.ch.is_numeric() {
+        if self.ch.is_ascii_digit() {
             let start = self.position;
             let mut end = start;
-            while self.ch.is_numeric() {
+            while self.ch.is_ascii_digit() {
                 end = self.position;
                 self.read_char();
             }
-            let literal = self.input[start..=end].to_string();
+            let literal: String = self.input[start..=end].iter().collect();
             return Some(token::Token::new_number(&literal));
         }
         return None;
     }
 
     fn try_eof(&self) -> Option<token::Token> {
-        if self.ch == '\0' {
+        if self.at_end() {
             return Some(token::Token::new_symbol(token::TokenType::EOF));
         }
         return None;
@@ -334,6 +378,116 @@ vec![
         token::Token::new_symbol(token::TokenType::EOF),
 ],
 )]
+#[case(
+"fn(a: int) -> bool".to_string(),
+vec![
+        token::Token::new_symbol(token::TokenType::Function),
+        token::Token::new_symbol(token::TokenType::LParen),
+        token::Token::new_ident("a"),
+        token::Token::new_symbol(token::TokenType::Colon),
+        token::Token::new_ident("int"),
+        token::Token::new_symbol(token::TokenType::RParen),
+        token::Token::new_symbol(token::TokenType::Arrow),
+        token::Token::new_ident("bool"),
+        token::Token::new_symbol(token::TokenType::EOF),
+],
+)]
+#[case(
+"import \"lib/math\" as m;
+[1, 2]; {\"a\": \"b\\n\"};".to_string(),
+vec![
+        token::Token::new_symbol(token::TokenType::Import),
+        token::Token::new_string("lib/math"),
+        token::Token::new_symbol(token::TokenType::As),
+        token::Token::new_ident("m"),
+        token::Token::new_symbol(token::TokenType::Semicolon),
+        token::Token::new_symbol(token::TokenType::LBracket),
+        token::Token::new_number("1"),
+        token::Token::new_symbol(token::TokenType::Comma),
+        token::Token::new_number("2"),
+        token::Token::new_symbol(token::TokenType::RBracket),
+        token::Token::new_symbol(token::TokenType::Semicolon),
+        token::Token::new_symbol(token::TokenType::LBrace),
+        token::Token::new_string("a"),
+        token::Token::new_symbol(token::TokenType::Colon),
+        token::Token::new_string("b\n"),
+        token::Token::new_symbol(token::TokenType::RBrace),
+        token::Token::new_symbol(token::TokenType::Semicolon),
+        token::Token::new_symbol(token::TokenType::EOF),
+],
+)]
+#[case(
+"@ \"open".to_string(),
+vec![
+        token::Token::new_illegal("@"),
+        token::Token::new_illegal("\"open"),
+        token::Token::new_symbol(token::TokenType::EOF),
+],
+)]
+#[case(
+"match x { [a, ..rest] => a, _ => 0 }".to_string(),
+vec![
+        token::Token::new_symbol(token::TokenType::Match),
+        token::Token::new_ident("x"),
+        token::Token::new_symbol(token::TokenType::LBrace),
+        token::Token::new_symbol(token::TokenType::LBracket),
+        token::Token::new_ident("a"),
+        token::Token::new_symbol(token::TokenType::Comma),
+        token::Token::new_symbol(token::TokenType::DotDot),
+        token::Token::new_ident("rest"),
+        token::Token::new_symbol(token::TokenType::RBracket),
+        token::Token::new_symbol(token::TokenType::FatArrow),
+        token::Token::new_ident("a"),
+        token::Token::new_symbol(token::TokenType::Comma),
+        token::Token::new_ident("_"),
+        token::Token::new_symbol(token::TokenType::FatArrow),
+        token::Token::new_number("0"),
+        token::Token::new_symbol(token::TokenType::RBrace),
+        token::Token::new_symbol(token::TokenType::EOF),
+],
+)]
+#[case(
+"struct P { x } impl P {} p.x..".to_string(),
+vec![
+        token::Token::new_symbol(token::TokenType::Struct),
+        token::Token::new_ident("P"),
+        token::Token::new_symbol(token::TokenType::LBrace),
+        token::Token::new_ident("x"),
+        token::Token::new_symbol(token::TokenType::RBrace),
+        token::Token::new_symbol(token::TokenType::Impl),
+        token::Token::new_ident("P"),
+        token::Token::new_symbol(token::TokenType::LBrace),
+        token::Token::new_symbol(token::TokenType::RBrace),
+        token::Token::new_ident("p"),
+        token::Token::new_symbol(token::TokenType::Dot),
+        token::Token::new_ident("x"),
+        token::Token::new_symbol(token::TokenType::DotDot),
+        token::Token::new_symbol(token::TokenType::EOF),
+],
+)]
+#[case(
+"fn*() { yield 1; } for (x in xs) {}".to_string(),
+vec![
+        token::Token::new_symbol(token::TokenType::Function),
+        token::Token::new_symbol(token::TokenType::Asterisk),
+        token::Token::new_symbol(token::TokenType::LParen),
+        token::Token::new_symbol(token::TokenType::RParen),
+        token::Token::new_symbol(token::TokenType::LBrace),
+        token::Token::new_symbol(token::TokenType::Yield),
+        token::Token::new_number("1"),
+        token::Token::new_symbol(token::TokenType::Semicolon),
+        token::Token::new_symbol(token::TokenType::RBrace),
+        token::Token::new_symbol(token::TokenType::For),
+        token::Token::new_symbol(token::TokenType::LParen),
+        token::Token::new_ident("x"),
+        token::Token::new_symbol(token::TokenType::In),
+        token::Token::new_ident("xs"),
+        token::Token::new_symbol(token::TokenType::RParen),
+        token::Token::new_symbol(token::TokenType::LBrace),
+        token::Token::new_symbol(token::TokenType::RBrace),
+        token::Token::new_symbol(token::TokenType::EOF),
+],
+)]
 fn test_lexer(#[case] input: String, #[case] expected: Vec<token::Token>) {
     let mut l = Lexer::new(input);
     for expected_token in expected {
@@ -350,3 +504,34 @@ fn test_lexer(#[case] input: String, #[case] expected: Vec<token::Token>) {
         );
     }
 }
+
+#[rstest]
+#[case("a == b", &[(1, 1), (1, 3), (1, 6)])]
+#[case("fn(x) -> int", &[(1, 1), (1, 3), (1, 4), (1, 5), (1, 7), (1, 10)])]
+#[case("[1, ..r]\n  x", &[(1, 1), (1, 2), (1, 3), (1, 5), (1, 7), (1, 8), (2, 3)])]
+fn test_lexer_positions(#[case] input: &str, #[case] expected: &[(usize, usize)]) {
+    let mut l = Lexer::new(input.to_string());
+    let positions: Vec<(usize, usize)> = expected
+        .iter()
+        .map(|_| {
+            let tok = l.next_token();
+            (tok.line, tok.column)
+        })
+        .collect();
+    assert_eq!(positions, expected);
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_lexer_never_panics(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..256)) {
+        let mut l = Lexer::new(String::from_utf8_lossy(&bytes).into_owned());
+        // Every call consumes at least one character, so this must terminate.
+        for _ in 0..=bytes.len() {
+            if l.next_token().token_type == token::TokenType::EOF {
+                break;
+            }
+        }
+        proptest::prop_assert_eq!(l.next_token().token_type, token::TokenType::EOF);
+    }
+}
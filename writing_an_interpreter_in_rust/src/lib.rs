@@ -0,0 +1,38 @@
+//! A tree-walking interpreter for the Monkey language, usable as a scripting
+//! layer from Rust. [`Interpreter`] is the entry point for embedding; the
+//! individual stages are public for tools that need them on their own.
+
+// Explicit `return`s and the upper case token names are the house style.
+#![allow(
+    clippy::needless_return,
+    clippy::upper_case_acronyms,
+    clippy::inherent_to_string,
+    clippy::module_inception
+)]
+
+pub mod token;
+pub mod lexer;
+pub mod ast;
+pub mod parser;
+pub mod object;
+pub mod environment;
+pub mod gc;
+mod builtins;
+pub mod stdlib;
+pub mod module;
+pub mod diagnostic;
+pub mod checker;
+pub mod evaluator;
+pub mod debugger;
+pub mod test_runner;
+pub mod interpreter;
+pub mod pretty;
+pub mod profiler;
+pub mod coverage;
+pub mod transpile;
+pub mod wasm;
+#[cfg(test)]
+mod generator;
+
+pub use interpreter::{EvalError, Interpreter, Limits};
+pub use object::{ErrorObject, Object};
@@ -1,10 +1,13 @@
-use cli::cli::run;
+// Explicit `return`s and the upper case token names are the house style.
+#![allow(
+    clippy::needless_return,
+    clippy::upper_case_acronyms,
+    clippy::inherent_to_string,
+    clippy::module_inception
+)]
 
+use cli::cli::run;
 
-mod token;
-mod lexer;
-mod ast;
-mod parser;
 mod cli;
 
 fn main() {
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+pub const SOURCE_EXTENSION: &str = "wl";
+
+/// Finds and reads the source of imported modules. The evaluator only ever
+/// talks to the file system through this trait, so tests (and hosts without a
+/// file system) can provide their own sources.
+pub trait SourceLoader {
+    /// Turns the path written in an `import` into the key the module is cached
+    /// under. Relative paths are resolved against the importing file.
+    fn resolve(&self, importer: Option<&Path>, path: &str) -> PathBuf {
+        let base = importer
+            .and_then(|p| p.parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        let mut resolved = normalize(&base.join(path));
+        if resolved.extension().is_none() {
+            resolved.set_extension(SOURCE_EXTENSION);
+        }
+        return resolved;
+    }
+
+    fn read(&self, path: &Path) -> Result<String, String>;
+}
+
+pub struct FsLoader;
+
+impl SourceLoader for FsLoader {
+    fn read(&self, path: &Path) -> Result<String, String> {
+        std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))
+    }
+}
+
+/// Serves module sources from memory, keyed by their resolved path. Hosts can
+/// use it to limit what scripts are able to import.
+#[derive(Default)]
+pub struct MemoryLoader {
+    pub sources: HashMap<PathBuf, String>,
+}
+
+impl MemoryLoader {
+    pub fn with(mut self, path: &str, source: &str) -> MemoryLoader {
+        self.sources.insert(PathBuf::from(path), source.to_string());
+        return self;
+    }
+}
+
+impl SourceLoader for MemoryLoader {
+    fn read(&self, path: &Path) -> Result<String, String> {
+        self.sources
+            .get(path)
+            .cloned()
+            .ok_or_else(|| format!("could not read {}: no such module", path.display()))
+    }
+}
+
+/// Lexically removes `.` and `..` components so that the same file imported
+/// through different relative paths is only evaluated once.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(result.components().next_back(), Some(Component::Normal(_))) {
+                    result.pop();
+                } else {
+                    result.push("..");
+                }
+            }
+            c => result.push(c.as_os_str()),
+        }
+    }
+    return result;
+}
@@ -0,0 +1,548 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::ast;
+use crate::environment::Env;
+use crate::evaluator::Evaluator;
+
+/// A function implemented in Rust. Closures are allowed so that host
+/// applications can expose their own state to scripts.
+pub type BuiltinFn = Rc<dyn Fn(&mut Evaluator, Vec<Object>) -> Result<Object, ErrorObject>>;
+
+#[derive(Clone)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+    Null,
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+    Function(Rc<FunctionObject>),
+    Builtin(Rc<BuiltinObject>),
+    Module(Rc<ModuleObject>),
+    StructType(Rc<StructType>),
+    Struct(Rc<StructObject>),
+    Error(Rc<ErrorObject>),
+    Iterator(Rc<IteratorObject>),
+    ReturnValue(Box<Object>),
+    TailCall(Box<TailCall>),
+}
+
+pub struct FunctionObject {
+    /// The name of the `let` the literal was bound by, for stack traces.
+    pub name: Option<String>,
+    /// The module the function was defined in, if it came from a file.
+    pub file: Option<Rc<PathBuf>>,
+    pub parameters: Vec<ast::IdentfierExpr>,
+    pub body: Rc<ast::BlockStatement>,
+    pub env: Env,
+    /// Declared with `fn*`, so a call returns a generator over its body.
+    pub is_generator: bool,
+}
+
+/// A call in tail position that hasn't been made yet. It only ever travels
+/// from a `return` back up to the `apply_function` that runs it.
+#[derive(Clone)]
+pub struct TailCall {
+    pub function: Object,
+    pub arguments: Vec<Object>,
+}
+
+pub struct BuiltinObject {
+    pub name: String,
+    pub func: BuiltinFn,
+}
+
+/// A type declared with `struct`. Methods are added by `impl` blocks as
+/// they're evaluated, so they live behind a `RefCell`.
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: RefCell<BTreeMap<String, Object>>,
+}
+
+/// An instance of a struct, with a value for every field in declaration
+/// order.
+pub struct StructObject {
+    pub struct_type: Rc<StructType>,
+    pub values: Vec<Object>,
+}
+
+impl StructObject {
+    pub fn field(&self, name: &str) -> Option<Object> {
+        let index = self.struct_type.fields.iter().position(|f| f == name)?;
+        return Some(self.values[index].clone());
+    }
+}
+
+/// A sequence handed out one value at a time, for `for ... in` and the
+/// iterator builtins. Generators and the `map`, `filter` and `take`
+/// adapters only do work when asked for their next value, so they may go on
+/// forever.
+pub struct IteratorObject {
+    pub state: RefCell<IteratorState>,
+}
+
+impl IteratorObject {
+    pub fn new(state: IteratorState) -> Rc<IteratorObject> {
+        Rc::new(IteratorObject { state: RefCell::new(state) })
+    }
+
+    /// Iterates over the elements of an array, the characters of a string
+    /// or the keys of a hash. An iterator is its own iterator.
+    pub fn from_iterable(value: &Object) -> Result<Rc<IteratorObject>, String> {
+        let elements = match value {
+            Object::Iterator(iterator) => return Ok(iterator.clone()),
+            Object::Array(elements) => elements.clone(),
+            Object::String(s) => Rc::new(s.chars().map(|c| Object::String(c.to_string().into())).collect()),
+            Object::Hash(pairs) => Rc::new(pairs.keys().map(HashKey::to_object).collect()),
+            other => return Err(format!("{} is not iterable", other.type_name())),
+        };
+        return Ok(IteratorObject::new(IteratorState::Elements { elements, next: 0 }));
+    }
+}
+
+pub enum IteratorState {
+    Elements { elements: Rc<Vec<Object>>, next: usize },
+    Generator(GeneratorState),
+    Map { source: Rc<IteratorObject>, function: Object },
+    Filter { source: Rc<IteratorObject>, predicate: Object },
+    Take { source: Rc<IteratorObject>, remaining: usize },
+    /// A generator whose last statement was `yield* iterable`, which has
+    /// nothing left to do but hand out the values of that iterable.
+    Delegate(Rc<IteratorObject>),
+    /// Being advanced right now, so it can't be asked for a value from
+    /// inside, as by a generator iterating over itself.
+    Running,
+    Finished,
+}
+
+/// The way from a generator's body to a block nested in it: for each level,
+/// the index of a statement and which of its blocks to go into.
+pub type BlockPath = Vec<(usize, Branch)>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Branch {
+    Consequence,
+    Alternative,
+    Arm(usize),
+    TryBody,
+    Handler,
+    Loop,
+}
+
+/// A generator between values: the stack of blocks, loops and `try`s its
+/// body is inside, innermost last. It's empty once the body has finished.
+pub struct GeneratorState {
+    pub function: Rc<FunctionObject>,
+    pub frames: Vec<GeneratorFrame>,
+}
+
+pub enum GeneratorFrame {
+    /// Running a block, of which `next` is the first statement not yet run.
+    Block { path: BlockPath, next: usize, env: Env },
+    /// A `for` loop over `iterator`, running its body once per value.
+    Loop {
+        path: BlockPath,
+        binding: String,
+        iterator: Rc<IteratorObject>,
+        env: Env,
+    },
+    /// A `yield*`, handing out each value of `iterator`.
+    Delegate { iterator: Rc<IteratorObject> },
+    /// A `try` whose body is in the frames above. If the body raises an
+    /// error, the frames are unwound to here and the handler run instead.
+    Try { handler: BlockPath, binding: String, env: Env },
+}
+
+pub struct ModuleObject {
+    pub name: String,
+    pub exports: BTreeMap<String, Object>,
+}
+
+/// A runtime error. It travels up through the evaluator as the `Err` side of
+/// a result until a `try` catches it, at which point it becomes an ordinary
+/// value.
+#[derive(Debug, Clone)]
+pub struct ErrorObject {
+    pub message: String,
+    /// The call stack when the error was raised, innermost frame first.
+    pub trace: Vec<TraceFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for ErrorObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ErrorObject {}
+
+impl From<String> for ErrorObject {
+    /// Builtins raise errors without a trace; the evaluator fills it in from
+    /// the call site.
+    fn from(message: String) -> Self {
+        ErrorObject {
+            message,
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl Display for TraceFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "at {} ({}:{}:{})", self.function, file.display(), self.line, self.column),
+            None => write!(f, "at {} (line {}, col {})", self.function, self.line, self.column),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+}
+
+impl Object {
+    /// The name `type()` returns. Struct instances go by their struct's name.
+    pub fn type_name(&self) -> String {
+        let name = match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Null => "NULL",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Module(_) => "MODULE",
+            Object::StructType(_) => "STRUCT",
+            Object::Struct(instance) => return instance.struct_type.name.clone(),
+            Object::Error(_) => "ERROR",
+            Object::Iterator(_) => "ITERATOR",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::TailCall(_) => "TAIL_CALL",
+        };
+        return name.to_string();
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+
+    /// Equality as `==` and `!=` see it. Values of the same kind compare by
+    /// value, collections element by element, and a few kinds convert to
+    /// one another first:
+    ///
+    /// | left     | right    | equal when                                          |
+    /// |----------|----------|-----------------------------------------------------|
+    /// | INTEGER  | STRING   | the string is the integer in decimal, as in `"-4"`  |
+    /// | BOOLEAN  | INTEGER  | `true` is `1` and `false` is `0`                    |
+    /// | BOOLEAN  | STRING   | `true` is `"1"` and `false` is `"0"`, as above      |
+    /// | ARRAY    | ARRAY    | same length and every element is `==`               |
+    /// | HASH     | HASH     | same keys and every value is `==`                   |
+    /// | struct   | struct   | same struct and every field is `==`                 |
+    /// | ERROR    | ERROR    | same message                                        |
+    /// | FUNCTION | FUNCTION | the same function; likewise BUILTIN, MODULE, STRUCT |
+    ///
+    /// The table is symmetric, scalars of one kind compare by value, `null`
+    /// only equals `null`, and any other pair isn't equal. There's no float
+    /// type, so there are no float conversions either; `1.5` doesn't parse.
+    pub fn loosely_equals(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Integer(i), Object::String(s)) | (Object::String(s), Object::Integer(i)) => {
+                s.parse::<i64>().is_ok_and(|parsed| parsed == *i)
+            }
+            (Object::Boolean(b), _) if matches!(other, Object::Integer(_) | Object::String(_)) => {
+                Object::Integer(*b as i64).loosely_equals(other)
+            }
+            (_, Object::Boolean(b)) if matches!(self, Object::Integer(_) | Object::String(_)) => {
+                self.loosely_equals(&Object::Integer(*b as i64))
+            }
+            (Object::Array(l), Object::Array(r)) => {
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.loosely_equals(r))
+            }
+            (Object::Hash(l), Object::Hash(r)) => {
+                l.len() == r.len() && l.iter().all(|(key, l)| r.get(key).is_some_and(|r| l.loosely_equals(r)))
+            }
+            (Object::Struct(l), Object::Struct(r)) => {
+                Rc::ptr_eq(&l.struct_type, &r.struct_type)
+                    && l.values.iter().zip(r.values.iter()).all(|(l, r)| l.loosely_equals(r))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Equality as `===` sees it: the same kind of value, and either the
+    /// same scalar or the very same array, hash, struct, function or error.
+    /// Nothing is converted, so `1 === "1"` and `[1] === [1]` are false.
+    pub fn strictly_equals(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Integer(l), Object::Integer(r)) => l == r,
+            (Object::Boolean(l), Object::Boolean(r)) => l == r,
+            (Object::String(l), Object::String(r)) => l == r,
+            (Object::Null, Object::Null) => true,
+            (Object::Array(l), Object::Array(r)) => Rc::ptr_eq(l, r),
+            (Object::Hash(l), Object::Hash(r)) => Rc::ptr_eq(l, r),
+            (Object::Struct(l), Object::Struct(r)) => Rc::ptr_eq(l, r),
+            (Object::Error(l), Object::Error(r)) => Rc::ptr_eq(l, r),
+            (Object::Function(_), Object::Function(_))
+            | (Object::Builtin(_), Object::Builtin(_))
+            | (Object::Module(_), Object::Module(_))
+            | (Object::StructType(_), Object::StructType(_))
+            | (Object::Iterator(_), Object::Iterator(_)) => self == other,
+            _ => false,
+        }
+    }
+
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(i) => Some(HashKey::Integer(*i)),
+            Object::Boolean(b) => Some(HashKey::Boolean(*b)),
+            Object::String(s) => Some(HashKey::String(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl HashKey {
+    pub fn to_object(&self) -> Object {
+        match self {
+            HashKey::Integer(i) => Object::Integer(*i),
+            HashKey::Boolean(b) => Object::Boolean(*b),
+            HashKey::String(s) => Object::String(s.clone()),
+        }
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::Integer(i) => write!(f, "{}", i),
+            Object::Boolean(b) => write!(f, "{}", b),
+            Object::String(s) => write!(f, "{}", s),
+            Object::Null => write!(f, "null"),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.inspect()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.to_object().inspect(), v.inspect()))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Function(func) => {
+                let params: Vec<String> = func.parameters.iter().map(|p| p.value.clone()).collect();
+                let keyword = if func.is_generator { "fn*" } else { "fn" };
+                write!(f, "{}({}) {{ ... }}", keyword, params.join(", "))
+            }
+            Object::Builtin(builtin) => write!(f, "builtin {}", builtin.name),
+            Object::Module(module) => write!(f, "<module {}>", module.name),
+            Object::StructType(struct_type) => write!(f, "struct {}", struct_type.name),
+            Object::Struct(instance) => {
+                let fields: Vec<String> = instance
+                    .struct_type
+                    .fields
+                    .iter()
+                    .zip(instance.values.iter())
+                    .map(|(name, value)| format!("{}: {}", name, value.inspect()))
+                    .collect();
+                match fields.is_empty() {
+                    true => write!(f, "{} {{}}", instance.struct_type.name),
+                    false => write!(f, "{} {{ {} }}", instance.struct_type.name, fields.join(", ")),
+                }
+            }
+            Object::Error(error) => write!(f, "error: {}", error.message),
+            Object::Iterator(_) => write!(f, "<iterator>"),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::TailCall(tail_call) => write!(f, "tail call to {}", tail_call.function),
+        }
+    }
+}
+
+/// Structural equality: values compare by content, functions, modules and
+/// struct types by identity.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Integer(l), Object::Integer(r)) => l == r,
+            (Object::Boolean(l), Object::Boolean(r)) => l == r,
+            (Object::String(l), Object::String(r)) => l == r,
+            (Object::Null, Object::Null) => true,
+            (Object::Array(l), Object::Array(r)) => l == r,
+            (Object::Hash(l), Object::Hash(r)) => l == r,
+            (Object::Function(l), Object::Function(r)) => Rc::ptr_eq(l, r),
+            (Object::Builtin(l), Object::Builtin(r)) => Rc::ptr_eq(l, r),
+            (Object::Module(l), Object::Module(r)) => Rc::ptr_eq(l, r),
+            (Object::StructType(l), Object::StructType(r)) => Rc::ptr_eq(l, r),
+            (Object::Struct(l), Object::Struct(r)) => Rc::ptr_eq(&l.struct_type, &r.struct_type) && l.values == r.values,
+            (Object::Error(l), Object::Error(r)) => l.message == r.message,
+            (Object::Iterator(l), Object::Iterator(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl Debug for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.inspect())
+    }
+}
+
+impl Object {
+    /// Like `Display`, but strings are quoted so they can be told apart from
+    /// identifiers when nested inside arrays and hashes.
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::String(s) => format!("{:?}", s),
+            _ => self.to_string(),
+        }
+    }
+}
+
+// Conversions between objects and plain Rust values, for host applications
+// passing data in and out of scripts.
+
+impl From<i64> for Object {
+    fn from(value: i64) -> Self {
+        Object::Integer(value)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        Object::Boolean(value)
+    }
+}
+
+impl From<&str> for Object {
+    fn from(value: &str) -> Self {
+        Object::String(value.into())
+    }
+}
+
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        Object::String(value.into())
+    }
+}
+
+impl From<()> for Object {
+    fn from(_: ()) -> Self {
+        Object::Null
+    }
+}
+
+impl<T: Into<Object>> From<Option<T>> for Object {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Object::Null, Into::into)
+    }
+}
+
+impl<T: Into<Object>> From<Vec<T>> for Object {
+    fn from(value: Vec<T>) -> Self {
+        Object::Array(Rc::new(value.into_iter().map(Into::into).collect()))
+    }
+}
+
+impl<T: Into<Object>> From<BTreeMap<String, T>> for Object {
+    fn from(value: BTreeMap<String, T>) -> Self {
+        let pairs = value
+            .into_iter()
+            .map(|(k, v)| (HashKey::String(k.into()), v.into()))
+            .collect();
+        Object::Hash(Rc::new(pairs))
+    }
+}
+
+fn expected(want: &str, got: &Object) -> ErrorObject {
+    format!("expected {}, got {}", want, got.type_name()).into()
+}
+
+impl TryFrom<Object> for i64 {
+    type Error = ErrorObject;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Integer(i) => Ok(i),
+            other => Err(expected("INTEGER", &other)),
+        }
+    }
+}
+
+impl TryFrom<Object> for bool {
+    type Error = ErrorObject;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Boolean(b) => Ok(b),
+            other => Err(expected("BOOLEAN", &other)),
+        }
+    }
+}
+
+impl TryFrom<Object> for String {
+    type Error = ErrorObject;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::String(s) => Ok(s.to_string()),
+            other => Err(expected("STRING", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<Object, Error = ErrorObject>> TryFrom<Object> for Option<T> {
+    type Error = ErrorObject;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Null => Ok(None),
+            other => T::try_from(other).map(Some),
+        }
+    }
+}
+
+impl<T: TryFrom<Object, Error = ErrorObject>> TryFrom<Object> for Vec<T> {
+    type Error = ErrorObject;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Array(elements) => elements.iter().cloned().map(T::try_from).collect(),
+            other => Err(expected("ARRAY", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<Object, Error = ErrorObject>> TryFrom<Object> for BTreeMap<String, T> {
+    type Error = ErrorObject;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        let Object::Hash(pairs) = value else {
+            return Err(expected("HASH", &value));
+        };
+        let mut result = BTreeMap::new();
+        for (key, value) in pairs.iter() {
+            let HashKey::String(key) = key else {
+                return Err(expected("STRING", &key.to_object()));
+            };
+            result.insert(key.to_string(), T::try_from(value.clone())?);
+        }
+        return Ok(result);
+    }
+}
@@ -1,13 +1,51 @@
 use crate::ast;
+use crate::diagnostic::Diagnostic;
 use crate::lexer;
 use crate::token;
 use rstest::rstest;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub enum Precedence {
+    Lowest,
+    Equals,
+    LessGreater,
+    Sum,
+    Product,
+    Prefix,
+    Call,
+}
+
+fn precedence_of(t: &token::TokenType) -> Precedence {
+    match t {
+        token::TokenType::EQ | token::TokenType::STRONGEQ | token::TokenType::NotEQ => Precedence::Equals,
+        token::TokenType::LT | token::TokenType::GT => Precedence::LessGreater,
+        token::TokenType::LTE | token::TokenType::GTE => Precedence::LessGreater,
+        token::TokenType::Plus | token::TokenType::Minus => Precedence::Sum,
+        token::TokenType::Asterisk | token::TokenType::Slash => Precedence::Product,
+        token::TokenType::LParen | token::TokenType::LBracket | token::TokenType::Dot => Precedence::Call,
+        _ => Precedence::Lowest,
+    }
+}
+
+/// How deeply expressions and patterns may nest. Everything downstream walks
+/// the tree recursively, so this keeps hostile input from overflowing the
+/// stack.
+pub const MAX_NESTING: usize = 128;
 
 pub struct Parser {
     l: lexer::Lexer,
     cur_token: token::Token,
     peek_token: token::Token,
-    errors: Vec<String>,
+    errors: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+    depth: usize,
+    /// Off while parsing a `match` value, where `name {` opens the arms
+    /// rather than a struct literal. Brackets and blocks turn it back on.
+    struct_literals: bool,
+    /// Whether each function being parsed, innermost last, is a generator.
+    /// `yield` is only allowed directly inside one.
+    generators: Vec<bool>,
 }
 
 impl Parser {
@@ -17,6 +55,10 @@ impl Parser {
             cur_token: token::Token::new(token::TokenType::EOF, "".to_string()),
             peek_token: token::Token::new(token::TokenType::EOF, "".to_string()),
             errors: Vec::new(),
+            warnings: Vec::new(),
+            depth: 0,
+            struct_literals: true,
+            generators: Vec::new(),
         };
         p.next_token();
         p.next_token();
@@ -46,7 +88,12 @@ impl Parser {
         match self.cur_token.token_type {
             token::TokenType::Let => self.parse_let_statement(),
             token::TokenType::Return => self.parse_return_statement(),
-            _ => None
+            token::TokenType::Import => self.parse_import_statement(),
+            token::TokenType::Struct => self.parse_struct_statement(),
+            token::TokenType::Impl => self.parse_impl_statement(),
+            token::TokenType::Yield => self.parse_yield_statement(),
+            token::TokenType::For => self.parse_for_statement(),
+            _ => self.parse_expression_statement(),
         }
     }
 
@@ -59,45 +106,874 @@ impl Parser {
             token: self.cur_token.clone(),
             value: self.cur_token.literal.clone(),
         };
+        let annotation = self.parse_annotation()?;
         if !self.expect_peek(token::TokenType::Assign) {
             return None;
         }
-        // TODO: We're skipping the expressions until we
-        // encounter a semicolon
-        while !self.cur_token_is(&token::TokenType::Semicolon) {
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(&token::TokenType::Semicolon) {
             self.next_token();
-            if self.cur_token_is(&token::TokenType::EOF) {
-                self.expect_peek(token::TokenType::Semicolon);
-                return None;
-            }
         }
         return Some(ast::StatementNode::Let(ast::LetStatement {
             token,
             name,
-            value: ast::ExpressionNode::Literal(ast::LiteralExpr {
-                token: token::Token::new(token::TokenType::Int(0), "0".to_string()),
-                value: ast::LiteralEnum::IntLiteral(0),
-            })
+            annotation,
+            value,
         }));
     }
 
     pub fn parse_return_statement(&mut self) -> Option<ast::StatementNode> {
         let token = self.cur_token.clone();
         self.next_token();
-        // TODO: We're skipping the expressions until we
-        // encounter a semicolon
-        while !(self.cur_token_is(&token::TokenType::Semicolon) || self.cur_token_is(&token::TokenType::EOF)) {
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(&token::TokenType::Semicolon) {
+            self.next_token();
+        }
+        return Some(ast::StatementNode::Return(ast::ReturnStatement { token, value }));
+    }
+
+    pub fn parse_yield_statement(&mut self) -> Option<ast::StatementNode> {
+        let token = self.cur_token.clone();
+        if self.generators.last() != Some(&true) {
+            self.errors.push(Diagnostic::at("`yield` outside of a generator, declared with `fn*`".to_string(), &token));
+        }
+        let delegate = self.parse_generator_marker();
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(&token::TokenType::Semicolon) {
+            self.next_token();
+        }
+        return Some(ast::StatementNode::Yield(ast::YieldStatement { token, delegate, value }));
+    }
+
+    pub fn parse_for_statement(&mut self) -> Option<ast::StatementNode> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(token::TokenType::LParen) {
+            return None;
+        }
+        let binding = self.parse_identifier()?;
+        if !self.expect_peek(token::TokenType::In) {
+            return None;
+        }
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(token::TokenType::RParen) || !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        if self.peek_token_is(&token::TokenType::Semicolon) {
+            self.next_token();
+        }
+        return Some(ast::StatementNode::For(ast::ForStatement {
+            token,
+            binding,
+            iterable,
+            body,
+        }));
+    }
+
+    pub fn parse_import_statement(&mut self) -> Option<ast::StatementNode> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(token::TokenType::String("".to_string())) {
+            return None;
+        }
+        let path = self.cur_token.literal.clone();
+        let mut alias = None;
+        if self.peek_token_is(&token::TokenType::As) {
+            self.next_token();
+            if !self.expect_peek(token::TokenType::Ident("".to_string())) {
+                return None;
+            }
+            alias = Some(ast::IdentfierExpr {
+                token: self.cur_token.clone(),
+                value: self.cur_token.literal.clone(),
+            });
+        }
+        if self.peek_token_is(&token::TokenType::Semicolon) {
+            self.next_token();
+        }
+        return Some(ast::StatementNode::Import(ast::ImportStatement { token, path, alias }));
+    }
+
+    pub fn parse_struct_statement(&mut self) -> Option<ast::StatementNode> {
+        let token = self.cur_token.clone();
+        let name = self.parse_identifier()?;
+        if !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let mut fields: Vec<ast::IdentfierExpr> = Vec::new();
+        while !self.peek_token_is(&token::TokenType::RBrace) {
+            let field = self.parse_identifier()?;
+            if fields.iter().any(|f| f.value == field.value) {
+                let msg = format!("duplicate field `{}` in struct {}", field.value, name.value);
+                self.errors.push(Diagnostic::at(msg, &field.token));
+            }
+            fields.push(field);
+            if !self.peek_token_is(&token::TokenType::RBrace) && !self.expect_peek(token::TokenType::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+        if self.peek_token_is(&token::TokenType::Semicolon) {
+            self.next_token();
+        }
+        return Some(ast::StatementNode::Struct(ast::StructStatement { token, name, fields }));
+    }
+
+    pub fn parse_impl_statement(&mut self) -> Option<ast::StatementNode> {
+        let token = self.cur_token.clone();
+        let name = self.parse_identifier()?;
+        if !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let mut methods = Vec::new();
+        while !self.peek_token_is(&token::TokenType::RBrace) {
+            if !self.expect_peek(token::TokenType::Function) {
+                return None;
+            }
+            let fn_token = self.cur_token.clone();
+            let is_generator = self.parse_generator_marker();
+            let method = self.parse_identifier()?;
+            if !self.expect_peek(token::TokenType::LParen) {
+                return None;
+            }
+            let (parameters, parameter_types) = self.parse_function_parameters()?;
+            let return_type = self.parse_return_type()?;
+            if !self.expect_peek(token::TokenType::LBrace) {
+                return None;
+            }
+            let body = self.parse_function_body(is_generator);
+            let function = ast::FunctionExpr {
+                token: fn_token,
+                is_generator,
+                parameters,
+                parameter_types,
+                return_type,
+                body: Rc::new(body),
+            };
+            methods.push((method, function));
+            if self.peek_token_is(&token::TokenType::Semicolon) {
+                self.next_token();
+            }
+        }
+        self.next_token();
+        if self.peek_token_is(&token::TokenType::Semicolon) {
+            self.next_token();
+        }
+        return Some(ast::StatementNode::Impl(ast::ImplStatement { token, name, methods }));
+    }
+
+    /// Moves on to the next token, which must be an identifier.
+    fn parse_identifier(&mut self) -> Option<ast::IdentfierExpr> {
+        if !self.expect_peek(token::TokenType::Ident("".to_string())) {
+            return None;
+        }
+        return Some(ast::IdentfierExpr {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        });
+    }
+
+    pub fn parse_expression_statement(&mut self) -> Option<ast::StatementNode> {
+        let token = self.cur_token.clone();
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(&token::TokenType::Semicolon) {
+            self.next_token();
+        }
+        return Some(ast::StatementNode::Expression(ast::ExpressionStatement {
+            token,
+            expression,
+        }));
+    }
+
+    pub fn parse_block_statement(&mut self) -> ast::BlockStatement {
+        let token = self.cur_token.clone();
+        let mut statements = Vec::new();
+        let struct_literals = std::mem::replace(&mut self.struct_literals, true);
+        self.next_token();
+        while !self.cur_token_is(&token::TokenType::RBrace) && !self.cur_token_is(&token::TokenType::EOF) {
+            if let Some(stmt) = self.parse_statement() {
+                statements.push(stmt);
+            }
+            self.next_token();
+        }
+        if self.cur_token_is(&token::TokenType::EOF) {
+            let msg = format!("expected {:?} to close block, got end of input", token::TokenType::RBrace);
+            self.errors.push(Diagnostic::at(msg, &token));
+        }
+        self.struct_literals = struct_literals;
+        return ast::BlockStatement { token, statements };
+    }
+
+    pub fn parse_expression(&mut self, precedence: Precedence) -> Option<ast::ExpressionNode> {
+        let depth = self.depth;
+        let expression = self.parse_nested_expression(precedence);
+        self.depth = depth;
+        return expression;
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<ast::ExpressionNode> {
+        self.enter_nesting()?;
+        let mut left = self.parse_prefix()?;
+        while !self.peek_token_is(&token::TokenType::Semicolon) && precedence < self.peek_precedence() {
+            // Each operator wraps `left` one level deeper.
+            self.enter_nesting()?;
+            self.next_token();
+            left = self.parse_infix(left)?;
+        }
+        return Some(left);
+    }
+
+    fn enter_nesting(&mut self) -> Option<()> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            let msg = format!("expression nested too deeply, the limit is {} levels", MAX_NESTING);
+            self.errors.push(Diagnostic::at(msg, &self.cur_token));
+            return None;
+        }
+        return Some(());
+    }
+
+    fn parse_prefix(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        match &token.token_type {
+            token::TokenType::Ident(name) if self.struct_literals && self.peek_token_is(&token::TokenType::LBrace) => {
+                let name = ast::ExpressionNode::Identifier(ast::IdentfierExpr {
+                    value: name.clone(),
+                    token: token.clone(),
+                });
+                self.parse_struct_literal(name)
+            }
+            token::TokenType::Ident(name) => Some(ast::ExpressionNode::Identifier(ast::IdentfierExpr {
+                value: name.clone(),
+                token,
+            })),
+            token::TokenType::Int(value) => Some(ast::ExpressionNode::Literal(ast::LiteralExpr {
+                value: ast::LiteralEnum::IntLiteral(*value),
+                token,
+            })),
+            token::TokenType::String(value) => Some(ast::ExpressionNode::Literal(ast::LiteralExpr {
+                value: ast::LiteralEnum::StringLiteral(value.clone()),
+                token,
+            })),
+            token::TokenType::True | token::TokenType::False => {
+                Some(ast::ExpressionNode::Literal(ast::LiteralExpr {
+                    value: ast::LiteralEnum::Boolean(token.token_type == token::TokenType::True),
+                    token,
+                }))
+            }
+            token::TokenType::Bang | token::TokenType::Minus => self.parse_prefix_expression(),
+            token::TokenType::LParen => self.parse_grouped_expression(),
+            token::TokenType::If => self.parse_if_expression(),
+            token::TokenType::Function => self.parse_function_expression(),
+            token::TokenType::LBracket => self.parse_array_expression(),
+            token::TokenType::LBrace => self.parse_hash_expression(),
+            token::TokenType::Match => self.parse_match_expression(),
+            token::TokenType::Try => self.parse_try_expression(),
+            token::TokenType::ILLEGAL if token.literal.bytes().all(|b| b.is_ascii_digit()) => {
+                let msg = format!("integer literal {} is too large", token.literal);
+                self.errors.push(Diagnostic::at(msg, &token));
+                None
+            }
+            _ => {
+                self.no_prefix_parse_error();
+                None
+            }
+        }
+    }
+
+    fn parse_infix(&mut self, left: ast::ExpressionNode) -> Option<ast::ExpressionNode> {
+        match self.cur_token.token_type {
+            token::TokenType::LParen => self.parse_call_expression(left),
+            token::TokenType::LBracket => self.parse_index_expression(left),
+            token::TokenType::Dot => self.parse_field_expression(left),
+            _ => self.parse_infix_expression(left),
+        }
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
+        let right = self.parse_expression(Precedence::Prefix)?;
+        return Some(ast::ExpressionNode::Prefix(ast::PrefixExpr {
+            operator: token.token_type.clone(),
+            token,
+            right: Box::new(right),
+        }));
+    }
+
+    fn parse_infix_expression(&mut self, left: ast::ExpressionNode) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        let precedence = self.cur_precedence();
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+        return Some(ast::ExpressionNode::Infix(ast::InfixExpr {
+            operator: token.token_type.clone(),
+            token,
+            left: Box::new(left),
+            right: Box::new(right),
+        }));
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<ast::ExpressionNode> {
+        self.next_token();
+        let struct_literals = std::mem::replace(&mut self.struct_literals, true);
+        let expression = self.parse_expression(Precedence::Lowest);
+        self.struct_literals = struct_literals;
+        if !self.expect_peek(token::TokenType::RParen) {
+            return None;
+        }
+        return expression;
+    }
+
+    /// Parses the braces of a struct literal, with the name already parsed.
+    fn parse_struct_literal(&mut self, name: ast::ExpressionNode) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
+        let mut fields: Vec<(ast::IdentfierExpr, ast::ExpressionNode)> = Vec::new();
+        while !self.peek_token_is(&token::TokenType::RBrace) {
+            let field = self.parse_identifier()?;
+            if fields.iter().any(|(f, _)| f.value == field.value) {
+                let msg = format!("field `{}` given twice", field.value);
+                self.errors.push(Diagnostic::at(msg, &field.token));
+            }
+            // `Point { x, y }` is short for `Point { x: x, y: y }`.
+            let value = if self.peek_token_is(&token::TokenType::Colon) {
+                self.next_token();
+                self.next_token();
+                self.parse_expression(Precedence::Lowest)?
+            } else {
+                ast::ExpressionNode::Identifier(field.clone())
+            };
+            fields.push((field, value));
+            if !self.peek_token_is(&token::TokenType::RBrace) && !self.expect_peek(token::TokenType::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+        return Some(ast::ExpressionNode::StructLiteral(ast::StructLiteralExpr {
+            token,
+            name: Box::new(name),
+            fields,
+        }));
+    }
+
+    fn parse_field_expression(&mut self, left: ast::ExpressionNode) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        let field = self.parse_identifier()?;
+        let expression = ast::ExpressionNode::Field(ast::FieldExpr {
+            token,
+            left: Box::new(left),
+            field,
+        });
+        if self.struct_literals && self.peek_token_is(&token::TokenType::LBrace) {
+            return self.parse_struct_literal(expression);
+        }
+        return Some(expression);
+    }
+
+    fn parse_if_expression(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(token::TokenType::LParen) {
+            return None;
+        }
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(token::TokenType::RParen) {
+            return None;
+        }
+        if !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let consequence = self.parse_block_statement();
+        let mut alternative = None;
+        if self.peek_token_is(&token::TokenType::Else) {
+            self.next_token();
+            if !self.expect_peek(token::TokenType::LBrace) {
+                return None;
+            }
+            alternative = Some(self.parse_block_statement());
+        }
+        return Some(ast::ExpressionNode::If(ast::IfExpr {
+            token,
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }));
+    }
+
+    fn parse_function_expression(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        let is_generator = self.parse_generator_marker();
+        if !self.expect_peek(token::TokenType::LParen) {
+            return None;
+        }
+        let (parameters, parameter_types) = self.parse_function_parameters()?;
+        let return_type = self.parse_return_type()?;
+        if !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let body = self.parse_function_body(is_generator);
+        return Some(ast::ExpressionNode::Function(ast::FunctionExpr {
+            token,
+            is_generator,
+            parameters,
+            parameter_types,
+            return_type,
+            body: Rc::new(body),
+        }));
+    }
+
+    /// Skips the `*` of `fn*` or `yield*`, returning whether it was there.
+    fn parse_generator_marker(&mut self) -> bool {
+        if !self.peek_token_is(&token::TokenType::Asterisk) {
+            return false;
+        }
+        self.next_token();
+        return true;
+    }
+
+    fn parse_function_body(&mut self, is_generator: bool) -> ast::BlockStatement {
+        self.generators.push(is_generator);
+        let body = self.parse_block_statement();
+        self.generators.pop();
+        return body;
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn parse_function_parameters(&mut self) -> Option<(Vec<ast::IdentfierExpr>, Vec<Option<ast::TypeExpr>>)> {
+        let mut parameters = Vec::new();
+        let mut parameter_types = Vec::new();
+        if self.peek_token_is(&token::TokenType::RParen) {
+            self.next_token();
+            return Some((parameters, parameter_types));
+        }
+        loop {
+            if !self.expect_peek(token::TokenType::Ident("".to_string())) {
+                return None;
+            }
+            parameters.push(ast::IdentfierExpr {
+                token: self.cur_token.clone(),
+                value: self.cur_token.literal.clone(),
+            });
+            parameter_types.push(self.parse_annotation()?);
+            if !self.peek_token_is(&token::TokenType::Comma) {
+                break;
+            }
+            self.next_token();
+        }
+        if !self.expect_peek(token::TokenType::RParen) {
+            return None;
+        }
+        return Some((parameters, parameter_types));
+    }
+
+    /// An optional `: type` after a name. Fails only if the colon is there
+    /// and the type after it isn't.
+    fn parse_annotation(&mut self) -> Option<Option<ast::TypeExpr>> {
+        if !self.peek_token_is(&token::TokenType::Colon) {
+            return Some(None);
+        }
+        self.next_token();
+        self.next_token();
+        return self.parse_type().map(Some);
+    }
+
+    /// An optional `-> type` after a function's parameters.
+    fn parse_return_type(&mut self) -> Option<Option<ast::TypeExpr>> {
+        if !self.peek_token_is(&token::TokenType::Arrow) {
+            return Some(None);
+        }
+        self.next_token();
+        self.next_token();
+        return self.parse_type().map(Some);
+    }
+
+    /// Parses the type starting at the current token.
+    fn parse_type(&mut self) -> Option<ast::TypeExpr> {
+        let depth = self.depth;
+        let result = self.parse_nested_type();
+        self.depth = depth;
+        return result;
+    }
+
+    fn parse_nested_type(&mut self) -> Option<ast::TypeExpr> {
+        self.enter_nesting()?;
+        let token = self.cur_token.clone();
+        match &token.token_type {
+            token::TokenType::Ident(name) => Some(ast::TypeExpr::Named(ast::IdentfierExpr {
+                value: name.clone(),
+                token,
+            })),
+            token::TokenType::LBracket => {
+                self.next_token();
+                let element = self.parse_nested_type()?;
+                if !self.expect_peek(token::TokenType::RBracket) {
+                    return None;
+                }
+                Some(ast::TypeExpr::Array(ast::ArrayType {
+                    token,
+                    element: Box::new(element),
+                }))
+            }
+            token::TokenType::LBrace => {
+                self.next_token();
+                let key = self.parse_nested_type()?;
+                if !self.expect_peek(token::TokenType::Colon) {
+                    return None;
+                }
+                self.next_token();
+                let value = self.parse_nested_type()?;
+                if !self.expect_peek(token::TokenType::RBrace) {
+                    return None;
+                }
+                Some(ast::TypeExpr::Hash(ast::HashType {
+                    token,
+                    key: Box::new(key),
+                    value: Box::new(value),
+                }))
+            }
+            token::TokenType::Function => {
+                if !self.expect_peek(token::TokenType::LParen) {
+                    return None;
+                }
+                let mut parameters = Vec::new();
+                while !self.peek_token_is(&token::TokenType::RParen) {
+                    self.next_token();
+                    parameters.push(self.parse_nested_type()?);
+                    if !self.peek_token_is(&token::TokenType::RParen) && !self.expect_peek(token::TokenType::Comma) {
+                        return None;
+                    }
+                }
+                self.next_token();
+                if !self.expect_peek(token::TokenType::Arrow) {
+                    return None;
+                }
+                self.next_token();
+                let result = self.parse_nested_type()?;
+                Some(ast::TypeExpr::Function(ast::FunctionType {
+                    token,
+                    parameters,
+                    result: Box::new(result),
+                }))
+            }
+            _ => {
+                let msg = format!("expected a type, got {:?}", token.literal);
+                self.errors.push(Diagnostic::at(msg, &token));
+                None
+            }
+        }
+    }
+
+    fn parse_call_expression(&mut self, function: ast::ExpressionNode) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        let arguments = self.parse_expression_list(token::TokenType::RParen)?;
+        return Some(ast::ExpressionNode::Call(ast::CallExpr {
+            token,
+            function: Box::new(function),
+            arguments,
+        }));
+    }
+
+    fn parse_index_expression(&mut self, left: ast::ExpressionNode) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(token::TokenType::RBracket) {
+            return None;
+        }
+        return Some(ast::ExpressionNode::Index(ast::IndexExpr {
+            token,
+            left: Box::new(left),
+            index: Box::new(index),
+        }));
+    }
+
+    fn parse_array_expression(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        let elements = self.parse_expression_list(token::TokenType::RBracket)?;
+        return Some(ast::ExpressionNode::Array(ast::ArrayExpr { token, elements }));
+    }
+
+    fn parse_hash_expression(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        let mut pairs = Vec::new();
+        while !self.peek_token_is(&token::TokenType::RBrace) {
             self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+            if !self.expect_peek(token::TokenType::Colon) {
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+            if !self.peek_token_is(&token::TokenType::RBrace) && !self.expect_peek(token::TokenType::Comma) {
+                return None;
+            }
         }
-        return Some(ast::StatementNode::Return(ast::ReturnStatement {
+        self.next_token();
+        return Some(ast::ExpressionNode::Hash(ast::HashExpr { token, pairs }));
+    }
+
+    fn parse_try_expression(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        if !self.expect_peek(token::TokenType::Catch) {
+            return None;
+        }
+        if !self.expect_peek(token::TokenType::LParen) {
+            return None;
+        }
+        if !self.expect_peek(token::TokenType::Ident("".to_string())) {
+            return None;
+        }
+        let binding = ast::IdentfierExpr {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        };
+        if !self.expect_peek(token::TokenType::RParen) {
+            return None;
+        }
+        if !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let handler = self.parse_block_statement();
+        return Some(ast::ExpressionNode::Try(ast::TryExpr {
             token,
-            value: ast::ExpressionNode::Literal(ast::LiteralExpr {
-                token: token::Token::new(token::TokenType::Int(0), "0".to_string()),
-                value: ast::LiteralEnum::IntLiteral(0),
-            })
+            body,
+            binding,
+            handler,
         }));
     }
 
+    fn parse_match_expression(&mut self) -> Option<ast::ExpressionNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
+        let struct_literals = std::mem::replace(&mut self.struct_literals, false);
+        let value = self.parse_expression(Precedence::Lowest);
+        self.struct_literals = struct_literals;
+        let value = value?;
+        if !self.expect_peek(token::TokenType::LBrace) {
+            return None;
+        }
+        let mut arms = Vec::new();
+        while !self.peek_token_is(&token::TokenType::RBrace) {
+            self.next_token();
+            let pattern = self.parse_pattern()?;
+            let mut guard = None;
+            if self.peek_token_is(&token::TokenType::If) {
+                self.next_token();
+                self.next_token();
+                guard = Some(self.parse_expression(Precedence::Lowest)?);
+            }
+            if !self.expect_peek(token::TokenType::FatArrow) {
+                return None;
+            }
+            self.next_token();
+            let is_block = self.cur_token_is(&token::TokenType::LBrace);
+            let body = if is_block {
+                self.parse_block_statement()
+            } else {
+                let body_token = self.cur_token.clone();
+                let expression = self.parse_expression(Precedence::Lowest)?;
+                ast::BlockStatement {
+                    token: body_token.clone(),
+                    statements: vec![ast::StatementNode::Expression(ast::ExpressionStatement {
+                        token: body_token,
+                        expression,
+                    })],
+                }
+            };
+            arms.push(ast::MatchArm { pattern, guard, body });
+            if self.peek_token_is(&token::TokenType::Comma) {
+                self.next_token();
+            } else if !is_block && !self.peek_token_is(&token::TokenType::RBrace) {
+                self.peek_error(&token::TokenType::Comma);
+                return None;
+            }
+        }
+        self.next_token();
+        self.check_boolean_exhaustiveness(&token, &arms);
+        return Some(ast::ExpressionNode::Match(ast::MatchExpr {
+            token,
+            value: Box::new(value),
+            arms,
+        }));
+    }
+
+    fn parse_pattern(&mut self) -> Option<ast::Pattern> {
+        let depth = self.depth;
+        let pattern = self.enter_nesting().and_then(|_| self.parse_nested_pattern());
+        self.depth = depth;
+        return pattern;
+    }
+
+    fn parse_nested_pattern(&mut self) -> Option<ast::Pattern> {
+        let token = self.cur_token.clone();
+        match &token.token_type {
+            token::TokenType::Ident(name) if name == "_" => Some(ast::Pattern::Wildcard(token)),
+            token::TokenType::Ident(name) => Some(ast::Pattern::Binding(ast::IdentfierExpr {
+                value: name.clone(),
+                token,
+            })),
+            token::TokenType::Minus => {
+                if !self.expect_peek(token::TokenType::Int(0)) {
+                    return None;
+                }
+                let value = match self.cur_token.token_type {
+                    token::TokenType::Int(value) => value.wrapping_neg(),
+                    _ => unreachable!(),
+                };
+                Some(ast::Pattern::Literal(ast::LiteralExpr {
+                    token,
+                    value: ast::LiteralEnum::IntLiteral(value),
+                }))
+            }
+            token::TokenType::LBracket => self.parse_array_pattern(),
+            token::TokenType::LBrace => self.parse_hash_pattern(),
+            _ => self.parse_literal_pattern().map(ast::Pattern::Literal),
+        }
+    }
+
+    fn parse_literal_pattern(&mut self) -> Option<ast::LiteralExpr> {
+        let token = self.cur_token.clone();
+        let value = match &token.token_type {
+            token::TokenType::Int(value) => ast::LiteralEnum::IntLiteral(*value),
+            token::TokenType::String(value) => ast::LiteralEnum::StringLiteral(value.clone()),
+            token::TokenType::True => ast::LiteralEnum::Boolean(true),
+            token::TokenType::False => ast::LiteralEnum::Boolean(false),
+            _ => {
+                let msg = format!("expected a pattern, got {:?} instead", token.literal);
+                self.errors.push(Diagnostic::at(msg, &token));
+                return None;
+            }
+        };
+        return Some(ast::LiteralExpr { token, value });
+    }
+
+    fn parse_array_pattern(&mut self) -> Option<ast::Pattern> {
+        let token = self.cur_token.clone();
+        let mut elements = Vec::new();
+        let mut rest = None;
+        while !self.peek_token_is(&token::TokenType::RBracket) {
+            self.next_token();
+            if self.cur_token_is(&token::TokenType::DotDot) {
+                let mut name = None;
+                if self.peek_token_is(&token::TokenType::Ident("".to_string())) {
+                    self.next_token();
+                    name = Some(ast::IdentfierExpr {
+                        token: self.cur_token.clone(),
+                        value: self.cur_token.literal.clone(),
+                    });
+                }
+                rest = Some(name);
+                break;
+            }
+            elements.push(self.parse_pattern()?);
+            if !self.peek_token_is(&token::TokenType::RBracket) && !self.expect_peek(token::TokenType::Comma) {
+                return None;
+            }
+        }
+        if !self.expect_peek(token::TokenType::RBracket) {
+            return None;
+        }
+        return Some(ast::Pattern::Array(ast::ArrayPattern { token, elements, rest }));
+    }
+
+    fn parse_hash_pattern(&mut self) -> Option<ast::Pattern> {
+        let token = self.cur_token.clone();
+        let mut pairs = Vec::new();
+        while !self.peek_token_is(&token::TokenType::RBrace) {
+            self.next_token();
+            let key = self.parse_literal_pattern()?;
+            if !self.expect_peek(token::TokenType::Colon) {
+                return None;
+            }
+            self.next_token();
+            pairs.push((key, self.parse_pattern()?));
+            if !self.peek_token_is(&token::TokenType::RBrace) && !self.expect_peek(token::TokenType::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+        return Some(ast::Pattern::Hash(ast::HashPattern { token, pairs }));
+    }
+
+    /// Warns about a `match` whose arms are all boolean literals but which
+    /// leaves `true` or `false` uncovered. Guarded arms don't count towards
+    /// coverage since the guard may fail.
+    fn check_boolean_exhaustiveness(&mut self, token: &token::Token, arms: &[ast::MatchArm]) {
+        let mut covered = [false, false];
+        for arm in arms {
+            match &arm.pattern {
+                ast::Pattern::Literal(ast::LiteralExpr { value: ast::LiteralEnum::Boolean(b), .. }) => {
+                    if arm.guard.is_none() {
+                        covered[*b as usize] = true;
+                    }
+                }
+                ast::Pattern::Wildcard(_) | ast::Pattern::Binding(_) if arm.guard.is_none() => return,
+                ast::Pattern::Wildcard(_) | ast::Pattern::Binding(_) => {}
+                _ => return,
+            }
+        }
+        let missing: Vec<&str> = ["false", "true"]
+            .iter()
+            .zip(covered)
+            .filter(|(_, covered)| !covered)
+            .map(|(name, _)| *name)
+            .collect();
+        // Only boolean literals get this far, so any literal arm means we're
+        // matching over a boolean.
+        let is_boolean_match = arms.iter().any(|arm| matches!(arm.pattern, ast::Pattern::Literal(_)));
+        if is_boolean_match && !missing.is_empty() {
+            let msg = format!("match over booleans is not exhaustive: `{}` not covered", missing.join("` and `"));
+            self.warnings.push(Diagnostic::at(msg, token));
+        }
+    }
+
+    fn parse_expression_list(&mut self, end: token::TokenType) -> Option<Vec<ast::ExpressionNode>> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, true);
+        let list = self.parse_expression_items(end);
+        self.struct_literals = struct_literals;
+        return list;
+    }
+
+    fn parse_expression_items(&mut self, end: token::TokenType) -> Option<Vec<ast::ExpressionNode>> {
+        let mut list = Vec::new();
+        if self.peek_token_is(&end) {
+            self.next_token();
+            return Some(list);
+        }
+        self.next_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+        while self.peek_token_is(&token::TokenType::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+        if !self.expect_peek(end) {
+            return None;
+        }
+        return Some(list);
+    }
+
+    fn peek_precedence(&self) -> Precedence {
+        precedence_of(&self.peek_token.token_type)
+    }
+
+    fn cur_precedence(&self) -> Precedence {
+        precedence_of(&self.cur_token.token_type)
+    }
+
+    fn no_prefix_parse_error(&mut self) {
+        let msg = format!("no prefix parse function for {:?} found", self.cur_token.token_type);
+        self.errors.push(Diagnostic::at(msg, &self.cur_token));
+    }
+
     pub fn cur_token_is(&self, t: &token::TokenType) -> bool {
         std::mem::discriminant(&self.cur_token.token_type) == std::mem::discriminant(t)
     }
@@ -117,17 +993,21 @@ impl Parser {
     }
 
     pub fn peek_error(&mut self, t: &token::TokenType) {
-        let msg = format!("expected next token to be {:?}, got {:?} instead. Line {:?}, col {:?}", t, self.peek_token.literal, self.peek_token.line, self.peek_token.column);
-        self.errors.push(msg);
+        let msg = format!("expected next token to be {:?}, got {:?} instead", t, self.peek_token.literal);
+        self.errors.push(Diagnostic::at(msg, &self.peek_token));
     }
 
     pub fn has_errors(&self) -> bool {
-        return self.errors.len() > 0;
+        return !self.errors.is_empty();
     }
 
-    pub fn errors(&self) -> Vec<String> {
+    pub fn errors(&self) -> Vec<Diagnostic> {
         return self.errors.clone();
     }
+
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        return self.warnings.clone();
+    }
 }
 
 #[rstest]
@@ -139,7 +1019,7 @@ fn test_parse_let(#[case] input: &str, #[case] expected_identifier: &str, #[case
     let l = lexer::Lexer::new(input.to_string());
     let mut p = Parser::new(l);
     let program = p.parse_program();
-    assert_eq!(p.errors.len() == 0, valid);
+    assert_eq!(p.errors.is_empty(), valid);
     if !valid {
         return;
     }
@@ -153,3 +1033,101 @@ fn test_parse_let(#[case] input: &str, #[case] expected_identifier: &str, #[case
         _ => panic!("Expected Let statement"),
     }
 }
+
+#[rstest]
+#[case("import \"lib/math\";", "lib/math", None)]
+#[case("import \"../shared/util\" as util;", "../shared/util", Some("util"))]
+fn test_parse_import(#[case] input: &str, #[case] expected_path: &str, #[case] expected_alias: Option<&str>) {
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    assert!(!p.has_errors(), "{:?}", p.errors());
+    assert_eq!(program.statements.len(), 1);
+    match program.statements.first().unwrap() {
+        ast::StatementNode::Import(stmt) => {
+            assert_eq!(stmt.path, expected_path);
+            assert_eq!(stmt.alias.as_ref().map(|a| a.value.as_str()), expected_alias);
+        },
+        _ => panic!("Expected Import statement"),
+    }
+}
+
+#[rstest]
+#[case("import lib;")]
+#[case("import \"lib\" as;")]
+#[case("let x = (1 + 2;")]
+#[case("fn(x, ) { x }")]
+#[case("if (x) { x")]
+#[case("match x { [a, ..rest, b] => a }")]
+#[case("match x { 1 => 1 2 => 2 }")]
+#[case("match x { y + 1 => 1 }")]
+#[case("try { 1 }")]
+#[case("try { 1 } catch e { 2 }")]
+#[case("struct P { x y }")]
+#[case("struct P { x, x }")]
+#[case("impl P { len(self) { 1 } }")]
+#[case("P { x: 1, x: 2 }")]
+#[case("p.1")]
+#[case("yield 1;")]
+#[case("fn() { yield 1; }")]
+#[case("fn*() { fn() { yield 1; } }")]
+#[case("for x in xs { x }")]
+#[case("for (x of xs) { x }")]
+#[case("for (x in xs) x")]
+#[case("match P { x: 1 } { _ => 0 }")]
+#[case("99999999999999999999")]
+#[case(&"(".repeat(100_000))]
+#[case(&"-".repeat(100_000))]
+#[case(&format!("x{}", "[0]".repeat(10_000)))]
+#[case(&format!("match x {{ {} => 1 }}", "[".repeat(100_000)))]
+fn test_parse_errors(#[case] input: &str) {
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+    p.parse_program();
+    assert!(p.has_errors());
+}
+
+#[rstest]
+#[case("99999999999999999999", "integer literal 99999999999999999999 is too large. Line 1, col 1")]
+#[case(&format!("{}1", "!".repeat(300)), "expression nested too deeply, the limit is 128 levels. Line 1, col 129")]
+#[case(&"1 + ".repeat(300), "expression nested too deeply, the limit is 128 levels. Line 1, col 509")]
+fn test_parse_first_error(#[case] input: &str, #[case] expected: &str) {
+    let mut p = Parser::new(lexer::Lexer::new(input.to_string()));
+    p.parse_program();
+    assert_eq!(p.errors()[0].to_string(), expected);
+}
+
+#[rstest]
+#[case("match x { true => 1 }", vec!["match over booleans is not exhaustive: `false` not covered. Line 1, col 1"])]
+#[case("match x { false => 0, true if y => 1 }", vec!["match over booleans is not exhaustive: `true` not covered. Line 1, col 1"])]
+#[case("match x { true => 1, false => 0 }", vec![])]
+#[case("match x { true => 1, _ => 0 }", vec![])]
+#[case("match x { 1 => 1, 2 => 0 }", vec![])]
+fn test_parse_match_warnings(#[case] input: &str, #[case] expected: Vec<&str>) {
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+    p.parse_program();
+    assert!(!p.has_errors(), "{:?}", p.errors());
+    let warnings: Vec<String> = p.warnings().iter().map(|w| w.to_string()).collect();
+    assert_eq!(warnings, expected);
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_parser_never_panics(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..256)) {
+        let mut p = Parser::new(lexer::Lexer::new(String::from_utf8_lossy(&bytes).into_owned()));
+        p.parse_program();
+    }
+
+    #[test]
+    fn test_parser_never_panics_on_tokens(
+        tokens in proptest::collection::vec(proptest::sample::select(&[
+            "let", "x", "=", "1", "\"s\"", "+", "-", "!", "*", "<", "==", "(", ")", "{", "}", "[", "]", ",", ";",
+            ":", "fn", "if", "else", "return", "match", "=>", "..", "_", "try", "catch", "import", "as",
+        ][..]), 0..64)
+    ) {
+        let mut p = Parser::new(lexer::Lexer::new(tokens.join(" ")));
+        p.parse_program();
+    }
+}
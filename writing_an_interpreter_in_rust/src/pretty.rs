@@ -0,0 +1,390 @@
+use crate::ast;
+use rstest::rstest;
+
+const INDENT: &str = "    ";
+
+/// Prints a program back out as source. Every prefix and infix expression
+/// is parenthesized, so the output parses back into the same tree whatever
+/// the precedence rules, and printing that tree again gives the same text.
+pub fn print_program(program: &ast::ProgramNode) -> String {
+    let mut printer = Printer::default();
+    for statement in &program.statements {
+        printer.statement(statement);
+        printer.out.push('\n');
+    }
+    return printer.out;
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+    /// Set while printing a `match` value, where a struct literal needs
+    /// parentheses to not be read as the start of the arms.
+    scrutinee: bool,
+}
+
+impl Printer {
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn statement(&mut self, statement: &ast::StatementNode) {
+        match statement {
+            ast::StatementNode::Let(stmt) => {
+                self.out.push_str(&format!("let {}", stmt.name.value));
+                if let Some(annotation) = &stmt.annotation {
+                    self.out.push_str(&format!(": {}", type_source(annotation)));
+                }
+                self.out.push_str(" = ");
+                self.expression(&stmt.value);
+            }
+            ast::StatementNode::Return(stmt) => {
+                self.out.push_str("return ");
+                self.expression(&stmt.value);
+            }
+            ast::StatementNode::Expression(stmt) => self.expression(&stmt.expression),
+            ast::StatementNode::Import(stmt) => {
+                self.out.push_str(&format!("import {}", quote(&stmt.path)));
+                if let Some(alias) = &stmt.alias {
+                    self.out.push_str(&format!(" as {}", alias.value));
+                }
+            }
+            ast::StatementNode::Struct(stmt) => {
+                self.out.push_str(&format!("struct {} {{", stmt.name.value));
+                if !stmt.fields.is_empty() {
+                    let fields: Vec<&str> = stmt.fields.iter().map(|f| f.value.as_str()).collect();
+                    self.out.push_str(&format!(" {} ", fields.join(", ")));
+                }
+                self.out.push('}');
+            }
+            ast::StatementNode::Impl(stmt) => {
+                self.out.push_str(&format!("impl {} {{", stmt.name.value));
+                if !stmt.methods.is_empty() {
+                    self.depth += 1;
+                    for (name, func) in &stmt.methods {
+                        self.newline();
+                        self.out.push_str(&format!("{} {}", fn_keyword(func), name.value));
+                        self.signature(func);
+                        self.block(&func.body);
+                    }
+                    self.depth -= 1;
+                    self.newline();
+                }
+                self.out.push('}');
+            }
+            ast::StatementNode::Yield(stmt) => {
+                self.out.push_str(if stmt.delegate { "yield* " } else { "yield " });
+                self.expression(&stmt.value);
+            }
+            ast::StatementNode::For(stmt) => {
+                self.out.push_str(&format!("for ({} in ", stmt.binding.value));
+                self.expression(&stmt.iterable);
+                self.out.push_str(") ");
+                self.block(&stmt.body);
+            }
+        }
+        self.out.push(';');
+    }
+
+    fn block(&mut self, block: &ast::BlockStatement) {
+        if block.statements.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push('{');
+        self.depth += 1;
+        for statement in &block.statements {
+            self.newline();
+            self.statement(statement);
+        }
+        self.depth -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    /// The parameters and return type of a function, up to its body.
+    fn signature(&mut self, func: &ast::FunctionExpr) {
+        self.out.push('(');
+        let parameters: Vec<_> = func.parameters.iter().zip(&func.parameter_types).collect();
+        self.list(&parameters, |p, (param, annotation)| {
+            p.out.push_str(&param.value);
+            if let Some(annotation) = annotation {
+                p.out.push_str(&format!(": {}", type_source(annotation)));
+            }
+        });
+        self.out.push_str(") ");
+        if let Some(return_type) = &func.return_type {
+            self.out.push_str(&format!("-> {} ", type_source(return_type)));
+        }
+    }
+
+    fn list<T>(&mut self, items: &[T], mut item: impl FnMut(&mut Printer, &T)) {
+        for (i, value) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            item(self, value);
+        }
+    }
+
+    fn expression(&mut self, expression: &ast::ExpressionNode) {
+        match expression {
+            ast::ExpressionNode::Identifier(ident) => self.out.push_str(&ident.value),
+            ast::ExpressionNode::Literal(literal) => self.out.push_str(&literal_source(&literal.value)),
+            ast::ExpressionNode::Prefix(prefix) => {
+                self.out.push_str(&format!("({}", prefix.token.literal));
+                self.expression(&prefix.right);
+                self.out.push(')');
+            }
+            ast::ExpressionNode::Infix(infix) => {
+                self.out.push('(');
+                self.expression(&infix.left);
+                self.out.push_str(&format!(" {} ", infix.token.literal));
+                self.expression(&infix.right);
+                self.out.push(')');
+            }
+            ast::ExpressionNode::If(if_expr) => {
+                self.out.push_str("if (");
+                self.expression(&if_expr.condition);
+                self.out.push_str(") ");
+                self.block(&if_expr.consequence);
+                if let Some(alternative) = &if_expr.alternative {
+                    self.out.push_str(" else ");
+                    self.block(alternative);
+                }
+            }
+            ast::ExpressionNode::Function(func) => {
+                self.out.push_str(fn_keyword(func));
+                self.signature(func);
+                self.block(&func.body);
+            }
+            ast::ExpressionNode::Call(call) => {
+                self.expression(&call.function);
+                self.out.push('(');
+                self.list(&call.arguments, |p, argument| p.expression(argument));
+                self.out.push(')');
+            }
+            ast::ExpressionNode::Array(array) => {
+                self.out.push('[');
+                self.list(&array.elements, |p, element| p.expression(element));
+                self.out.push(']');
+            }
+            ast::ExpressionNode::Hash(hash) => {
+                self.out.push('{');
+                self.list(&hash.pairs, |p, (key, value)| {
+                    p.expression(key);
+                    p.out.push_str(": ");
+                    p.expression(value);
+                });
+                self.out.push('}');
+            }
+            ast::ExpressionNode::Index(index) => {
+                self.expression(&index.left);
+                self.out.push('[');
+                self.expression(&index.index);
+                self.out.push(']');
+            }
+            ast::ExpressionNode::Match(match_expr) => {
+                self.out.push_str("match ");
+                let scrutinee = std::mem::replace(&mut self.scrutinee, true);
+                self.expression(&match_expr.value);
+                self.scrutinee = scrutinee;
+                self.out.push_str(" {");
+                self.depth += 1;
+                for arm in &match_expr.arms {
+                    self.newline();
+                    self.pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.out.push_str(" if ");
+                        self.expression(guard);
+                    }
+                    self.out.push_str(" => ");
+                    self.block(&arm.body);
+                    self.out.push(',');
+                }
+                self.depth -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            ast::ExpressionNode::Try(try_expr) => {
+                self.out.push_str("try ");
+                self.block(&try_expr.body);
+                self.out.push_str(&format!(" catch ({}) ", try_expr.binding.value));
+                self.block(&try_expr.handler);
+            }
+            ast::ExpressionNode::StructLiteral(literal) => {
+                if self.scrutinee {
+                    self.out.push('(');
+                }
+                self.expression(&literal.name);
+                self.out.push_str(" {");
+                if !literal.fields.is_empty() {
+                    self.out.push(' ');
+                    self.list(&literal.fields, |p, (field, value)| {
+                        p.out.push_str(&format!("{}: ", field.value));
+                        p.expression(value);
+                    });
+                    self.out.push(' ');
+                }
+                self.out.push('}');
+                if self.scrutinee {
+                    self.out.push(')');
+                }
+            }
+            ast::ExpressionNode::Field(field) => {
+                self.expression(&field.left);
+                self.out.push_str(&format!(".{}", field.field.value));
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &ast::Pattern) {
+        match pattern {
+            ast::Pattern::Wildcard(_) => self.out.push('_'),
+            ast::Pattern::Literal(literal) => self.out.push_str(&literal_source(&literal.value)),
+            ast::Pattern::Binding(ident) => self.out.push_str(&ident.value),
+            ast::Pattern::Array(array) => {
+                self.out.push('[');
+                self.list(&array.elements, |p, element| p.pattern(element));
+                if let Some(rest) = &array.rest {
+                    if !array.elements.is_empty() {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str("..");
+                    if let Some(name) = rest {
+                        self.out.push_str(&name.value);
+                    }
+                }
+                self.out.push(']');
+            }
+            ast::Pattern::Hash(hash) => {
+                self.out.push('{');
+                self.list(&hash.pairs, |p, (key, value)| {
+                    p.out.push_str(&literal_source(&key.value));
+                    p.out.push_str(": ");
+                    p.pattern(value);
+                });
+                self.out.push('}');
+            }
+        }
+    }
+}
+
+pub fn type_source(annotation: &ast::TypeExpr) -> String {
+    match annotation {
+        ast::TypeExpr::Named(name) => name.value.clone(),
+        ast::TypeExpr::Array(array) => format!("[{}]", type_source(&array.element)),
+        ast::TypeExpr::Hash(hash) => format!("{{{}: {}}}", type_source(&hash.key), type_source(&hash.value)),
+        ast::TypeExpr::Function(func) => {
+            let parameters: Vec<String> = func.parameters.iter().map(type_source).collect();
+            format!("fn({}) -> {}", parameters.join(", "), type_source(&func.result))
+        }
+    }
+}
+
+fn fn_keyword(func: &ast::FunctionExpr) -> &'static str {
+    match func.is_generator {
+        true => "fn*",
+        false => "fn",
+    }
+}
+
+fn literal_source(literal: &ast::LiteralEnum) -> String {
+    match literal {
+        ast::LiteralEnum::IntLiteral(i) => i.to_string(),
+        ast::LiteralEnum::StringLiteral(s) => quote(s),
+        ast::LiteralEnum::Boolean(b) => b.to_string(),
+    }
+}
+
+/// Quotes a string using only the escapes the lexer understands.
+fn quote(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    return result;
+}
+
+#[cfg(test)]
+fn reprint(source: &str) -> String {
+    use crate::{lexer, parser};
+
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert!(!p.has_errors(), "{:?} in:\n{}", p.errors(), source);
+    return print_program(&program);
+}
+
+#[rstest]
+#[case("let x = 1 + 2 * 3", "let x = (1 + (2 * 3));\n")]
+#[case("-a * !b", "((-a) * (!b));\n")]
+#[case("import \"lib/m\" as m", "import \"lib/m\" as m;\n")]
+#[case("\"a\\\"b\\\\c\\nd\\q\"", "\"a\\\"b\\\\c\\nd\\\\q\";\n")]
+#[case("let f = fn(a, b) { return a(b)[0]; }", "let f = fn(a, b) {\n    return a(b)[0];\n};\n")]
+#[case("if (x) { 1 } else { {\"k\": [1, true]} }", "if (x) {\n    1;\n} else {\n    {\"k\": [1, true]};\n};\n")]
+#[case("fn() {}()", "fn() {}();\n")]
+#[case("fn*(n) { for (x in [n]) { yield x * 2; } }", "fn*(n) {\n    for (x in [n]) {\n        yield (x * 2);\n    };\n};\n")]
+#[case("fn*() { yield* [1]; }", "fn*() {\n    yield* [1];\n};\n")]
+#[case("impl P { fn* items(self) { yield self.x; } }", "impl P {\n    fn* items(self) {\n        yield self.x;\n    }\n};\n")]
+#[case(
+    "match x { [a, ..] if a > 1 => a, {\"k\": -1} => { 0 } _ => 2 }",
+    "match x {\n    [a, ..] if (a > 1) => {\n        a;\n    },\n    {\"k\": -1} => {\n        0;\n    },\n    _ => {\n        2;\n    },\n};\n"
+)]
+#[case("try { f() } catch (e) { e }", "try {\n    f();\n} catch (e) {\n    e;\n};\n")]
+#[case("struct Point { x, y }", "struct Point { x, y };\n")]
+#[case("struct Unit {}; Unit {}", "struct Unit {};\nUnit {};\n")]
+#[case(
+    "impl Point { fn len(self) { self.x * self.y } fn zero() { Point { x: 0, y } } }",
+    "impl Point {\n    fn len(self) {\n        (self.x * self.y);\n    }\n    fn zero() {\n        Point { x: 0, y: y };\n    }\n};\n"
+)]
+#[case("-p.x.y(1)", "(-p.x.y(1));\n")]
+#[case("s.Rect { w: 1 }.w", "s.Rect { w: 1 }.w;\n")]
+#[case(
+    "let f: fn([int], {string: bool}) -> null = fn(a: [int], b: {string: bool}) -> null { puts(a) }",
+    "let f: fn([int], {string: bool}) -> null = fn(a: [int], b: {string: bool}) -> null {\n    puts(a);\n};\n"
+)]
+#[case("impl P { fn get(self, n: int) -> int { n } }", "impl P {\n    fn get(self, n: int) -> int {\n        n;\n    }\n};\n")]
+#[case("match (Point { x: 1 }).x { _ => 0 }", "match (Point { x: 1 }).x {\n    _ => {\n        0;\n    },\n};\n")]
+fn test_print_program(#[case] source: &str, #[case] expected: &str) {
+    let printed = reprint(source);
+    assert_eq!(printed, expected);
+    assert_eq!(reprint(&printed), printed);
+}
+
+#[cfg(test)]
+fn eval_result(source: &str) -> String {
+    use crate::{Interpreter, Limits};
+
+    let limits = Limits {
+        max_steps: Some(10_000),
+        max_memory: Some(1 << 20),
+        max_call_depth: 20,
+    };
+    let mut interpreter = Interpreter::new().with_limits(limits).with_output(std::io::sink());
+    match interpreter.eval_str(source) {
+        Ok(value) => value.inspect(),
+        Err(error) => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_print_program_round_trips(source in crate::generator::program()) {
+        let printed = reprint(&source);
+        proptest::prop_assert_eq!(reprint(&printed), printed.clone());
+        proptest::prop_assert_eq!(eval_result(&source), eval_result(&printed));
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use rstest::rstest;
+
+/// Call counts and timings for one function literal, builtin or module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// Time from entering to leaving the function, counted once however
+    /// deeply it recurses.
+    pub inclusive: Duration,
+    /// Time spent in the function's own body, not in what it called.
+    pub exclusive: Duration,
+}
+
+struct OpenCall {
+    label: String,
+    stack: String,
+    started: Instant,
+    in_children: Duration,
+}
+
+/// Records the calls a script makes. The evaluator reports each call as it
+/// enters and leaves it; functions are told apart by a label with their name
+/// and where they were defined.
+#[derive(Default)]
+pub struct Profiler {
+    functions: BTreeMap<String, FunctionProfile>,
+    /// Exclusive time for each distinct call stack, outermost frame first.
+    stacks: BTreeMap<String, Duration>,
+    open: Vec<OpenCall>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    pub fn enter(&mut self, label: String) {
+        self.enter_at(label, Instant::now());
+    }
+
+    pub fn exit(&mut self) {
+        self.exit_at(Instant::now());
+    }
+
+    fn enter_at(&mut self, label: String, now: Instant) {
+        let stack = match self.open.last() {
+            Some(caller) => format!("{};{}", caller.stack, label),
+            None => label.clone(),
+        };
+        self.functions.entry(label.clone()).or_default().calls += 1;
+        self.open.push(OpenCall {
+            label,
+            stack,
+            started: now,
+            in_children: Duration::ZERO,
+        });
+    }
+
+    fn exit_at(&mut self, now: Instant) {
+        let Some(call) = self.open.pop() else {
+            return;
+        };
+        let elapsed = now.saturating_duration_since(call.started);
+        let exclusive = elapsed.saturating_sub(call.in_children);
+        let recursive = self.open.iter().any(|open| open.label == call.label);
+        let profile = self.functions.entry(call.label).or_default();
+        profile.exclusive += exclusive;
+        if !recursive {
+            profile.inclusive += elapsed;
+        }
+        *self.stacks.entry(call.stack).or_default() += exclusive;
+        if let Some(caller) = self.open.last_mut() {
+            caller.in_children += elapsed;
+        }
+    }
+
+    pub fn functions(&self) -> &BTreeMap<String, FunctionProfile> {
+        &self.functions
+    }
+
+    /// The stacks in the folded format flame graph tools read: one line per
+    /// stack, frames separated by `;`, then the microseconds spent in it.
+    pub fn folded(&self) -> String {
+        let mut result = String::new();
+        for (stack, time) in &self.stacks {
+            result.push_str(&format!("{} {}\n", stack, time.as_micros()));
+        }
+        return result;
+    }
+
+    /// A table of every function, the ones with the most exclusive time
+    /// first.
+    pub fn report(&self) -> String {
+        let mut functions: Vec<(&String, &FunctionProfile)> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then_with(|| a.0.cmp(b.0)));
+        let mut result = format!("{:>8} {:>12} {:>12}  {}\n", "calls", "inclusive", "exclusive", "function");
+        for (label, profile) in functions {
+            result.push_str(&format!(
+                "{:>8} {:>12} {:>12}  {}\n",
+                profile.calls,
+                format!("{:.3}ms", profile.inclusive.as_secs_f64() * 1000.0),
+                format!("{:.3}ms", profile.exclusive.as_secs_f64() * 1000.0),
+                label
+            ));
+        }
+        return result;
+    }
+}
+
+#[cfg(test)]
+fn replay(events: &[(&str, u64)]) -> Profiler {
+    let start = Instant::now();
+    let mut profiler = Profiler::new();
+    for (event, millis) in events {
+        let now = start + Duration::from_millis(*millis);
+        match *event {
+            "exit" => profiler.exit_at(now),
+            label => profiler.enter_at(label.to_string(), now),
+        }
+    }
+    return profiler;
+}
+
+#[rstest]
+#[case(&[("main", 0), ("f", 1), ("exit", 4), ("f", 5), ("exit", 6), ("exit", 10)], "main 6000\nmain;f 4000\n")]
+#[case(&[("f", 0), ("f", 1), ("f", 2), ("exit", 3), ("exit", 5), ("exit", 9)], "f 5000\nf;f 3000\nf;f;f 1000\n")]
+#[case(&[("exit", 0), ("main", 0), ("exit", 1)], "main 1000\n")]
+fn test_folded(#[case] events: &[(&str, u64)], #[case] expected: &str) {
+    assert_eq!(replay(events).folded(), expected);
+}
+
+#[test]
+fn test_function_profiles() {
+    let profiler = replay(&[("main", 0), ("f", 1), ("f", 2), ("g", 3), ("exit", 7), ("exit", 8), ("exit", 9), ("exit", 10)]);
+    let ms = Duration::from_millis;
+    let expected = BTreeMap::from([
+        ("main".to_string(), FunctionProfile { calls: 1, inclusive: ms(10), exclusive: ms(2) }),
+        ("f".to_string(), FunctionProfile { calls: 2, inclusive: ms(8), exclusive: ms(4) }),
+        ("g".to_string(), FunctionProfile { calls: 1, inclusive: ms(4), exclusive: ms(4) }),
+    ]);
+    assert_eq!(profiler.functions(), &expected);
+    let report = profiler.report();
+    let labels: Vec<&str> = report.lines().skip(1).map(|l| l.split_whitespace().last().unwrap()).collect();
+    assert_eq!(labels, ["f", "g", "main"]);
+}
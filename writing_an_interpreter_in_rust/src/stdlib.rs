@@ -0,0 +1,250 @@
+//! Modules built into the interpreter, imported with `import "std/<name>"`.
+//! `json` only turns values into text and back, so every script gets it;
+//! `fs` and `env` reach outside the interpreter and have to be granted by
+//! the host through [`Capabilities`].
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use serde_json::Value;
+
+use crate::builtins::{expect_args, NativeFn};
+use crate::evaluator::Evaluator;
+use crate::object::{BuiltinObject, ErrorObject, HashKey, ModuleObject, Object};
+use rstest::rstest;
+
+/// What an import path has to start with to name a standard library module.
+pub const PREFIX: &str = "std/";
+
+/// What the host lets scripts reach beyond their own values. Nothing, by
+/// default.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    /// Lets scripts import `std/fs` to read, write and list files.
+    pub fs: bool,
+    /// Lets scripts import `std/env` to see the process's environment
+    /// variables and `args`.
+    pub env: bool,
+    /// The arguments the script was started with, as `env.args`.
+    pub args: Vec<String>,
+}
+
+/// Builds the module `std/<name>`, if there is one and `capabilities`
+/// allow it.
+pub fn module(name: &str, capabilities: &Capabilities) -> Result<Rc<ModuleObject>, String> {
+    let exports = match name {
+        "json" => functions("json", &[("parse", json_parse as NativeFn), ("stringify", json_stringify)]),
+        "fs" if capabilities.fs => functions(
+            "fs",
+            &[("read", fs_read as NativeFn), ("write", fs_write), ("list", fs_list)],
+        ),
+        "env" if capabilities.env => {
+            let mut exports = functions("env", &[("var", env_var)]);
+            let args = capabilities.args.iter().map(|a| Object::String(a.as_str().into())).collect();
+            exports.insert("args".to_string(), Object::Array(Rc::new(args)));
+            exports
+        }
+        "fs" => return Err(format!("`{}fs` needs file-system access, granted with --allow-fs", PREFIX)),
+        "env" => return Err(format!("`{}env` needs environment access, granted with --allow-env", PREFIX)),
+        _ => return Err(format!("no standard library module named `{}{}`", PREFIX, name)),
+    };
+    return Ok(Rc::new(ModuleObject { name: name.to_string(), exports }));
+}
+
+fn functions(module: &str, functions: &[(&str, NativeFn)]) -> BTreeMap<String, Object> {
+    functions
+        .iter()
+        .map(|(name, func)| {
+            let builtin = BuiltinObject {
+                name: format!("{}.{}", module, name),
+                func: Rc::new(*func),
+            };
+            (name.to_string(), Object::Builtin(Rc::new(builtin)))
+        })
+        .collect()
+}
+
+fn expect_string(name: &str, arg: &Object) -> Result<Rc<str>, ErrorObject> {
+    match arg {
+        Object::String(s) => Ok(s.clone()),
+        other => Err(format!("argument to `{}` must be STRING, got {}", name, other.type_name()).into()),
+    }
+}
+
+/// Parses JSON text. Objects become hashes with string keys; numbers have
+/// to be integers, as there are no others.
+fn json_parse(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("json.parse", &args, 1)?;
+    let text = expect_string("json.parse", &args[0])?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| format!("json.parse: {}", e))?;
+    return Ok(from_json(value)?);
+}
+
+fn from_json(value: Value) -> Result<Object, String> {
+    let object = match value {
+        Value::Null => Object::Null,
+        Value::Bool(b) => Object::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Object::Integer(i),
+            None => return Err(format!("json.parse: {} is not a 64-bit integer", n)),
+        },
+        Value::String(s) => Object::String(s.into()),
+        Value::Array(values) => {
+            let elements = values.into_iter().map(from_json).collect::<Result<Vec<_>, _>>()?;
+            Object::Array(Rc::new(elements))
+        }
+        Value::Object(fields) => {
+            let mut pairs = BTreeMap::new();
+            for (key, value) in fields {
+                pairs.insert(HashKey::String(key.into()), from_json(value)?);
+            }
+            Object::Hash(Rc::new(pairs))
+        }
+    };
+    return Ok(object);
+}
+
+/// Turns a value into compact JSON text. Struct instances become objects
+/// of their fields; hash keys have to be strings.
+fn json_stringify(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("json.stringify", &args, 1)?;
+    let value = to_json(&args[0])?;
+    return Ok(Object::String(value.to_string().into()));
+}
+
+fn to_json(object: &Object) -> Result<Value, String> {
+    let value = match object {
+        Object::Null => Value::Null,
+        Object::Boolean(b) => Value::Bool(*b),
+        Object::Integer(i) => Value::from(*i),
+        Object::String(s) => Value::String(s.to_string()),
+        Object::Array(elements) => Value::Array(elements.iter().map(to_json).collect::<Result<_, _>>()?),
+        Object::Hash(pairs) => {
+            let mut fields = serde_json::Map::new();
+            for (key, value) in pairs.iter() {
+                let HashKey::String(key) = key else {
+                    let key = key.to_object();
+                    return Err(format!("json.stringify: hash keys must be STRING, got {}", key.type_name()));
+                };
+                fields.insert(key.to_string(), to_json(value)?);
+            }
+            Value::Object(fields)
+        }
+        Object::Struct(instance) => {
+            let mut fields = serde_json::Map::new();
+            for (name, value) in instance.struct_type.fields.iter().zip(&instance.values) {
+                fields.insert(name.clone(), to_json(value)?);
+            }
+            Value::Object(fields)
+        }
+        other => return Err(format!("json.stringify: {} can't be represented as JSON", other.type_name())),
+    };
+    return Ok(value);
+}
+
+fn fs_read(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("fs.read", &args, 1)?;
+    let path = expect_string("fs.read", &args[0])?;
+    let contents = std::fs::read_to_string(&*path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    return Ok(Object::String(contents.into()));
+}
+
+/// Writes `contents` to a file, replacing whatever was there.
+fn fs_write(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("fs.write", &args, 2)?;
+    let path = expect_string("fs.write", &args[0])?;
+    let contents = expect_string("fs.write", &args[1])?;
+    std::fs::write(&*path, contents.as_bytes()).map_err(|e| format!("could not write {}: {}", path, e))?;
+    return Ok(Object::Null);
+}
+
+/// The names of the entries in a directory, sorted.
+fn fs_list(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("fs.list", &args, 1)?;
+    let path = expect_string("fs.list", &args[0])?;
+    let error = |e: std::io::Error| format!("could not list {}: {}", path, e);
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(&*path).map_err(error)? {
+        names.push(entry.map_err(error)?.file_name().to_string_lossy().to_string());
+    }
+    names.sort();
+    return Ok(Object::Array(Rc::new(names.into_iter().map(|n| Object::String(n.into())).collect())));
+}
+
+/// An environment variable's value, or null if it isn't set.
+fn env_var(_: &mut Evaluator, args: Vec<Object>) -> Result<Object, ErrorObject> {
+    expect_args("env.var", &args, 1)?;
+    let name = expect_string("env.var", &args[0])?;
+    match std::env::var(&*name) {
+        Ok(value) => Ok(Object::String(value.into())),
+        Err(_) => Ok(Object::Null),
+    }
+}
+
+#[cfg(test)]
+fn eval(input: &str, capabilities: Capabilities) -> Result<String, String> {
+    let mut interpreter = crate::Interpreter::new().with_capabilities(capabilities);
+    return interpreter.eval_str(input).map(|o| o.inspect()).map_err(|e| e.to_string());
+}
+
+#[rstest]
+#[case(r#"json.parse("[1, true, null, \"a\"]")"#, r#"[1, true, null, "a"]"#)]
+#[case(r#"json.parse("{\"b\": {\"c\": []}, \"a\": -2}")"#, r#"{"a": -2, "b": {"c": []}}"#)]
+#[case(r#"json.stringify({"b": [1, "x\"y"], "a": json.parse("null")})"#, r#""{\"a\":null,\"b\":[1,\"x\\\"y\"]}""#)]
+#[case(r#"struct P { x, y }; json.stringify(P { x: 1, y: false })"#, r#""{\"x\":1,\"y\":false}""#)]
+#[case(r#"let v = {"k": [1, {"n": true}]}; json.parse(json.stringify(v)) == v"#, "true")]
+#[case(r#"import "std/json" as j; j.stringify("hi")"#, r#""\"hi\"""#)]
+fn test_json(#[case] input: &str, #[case] expected: &str) {
+    let input = format!("import \"std/json\";\n{}", input);
+    assert_eq!(eval(&input, Capabilities::default()), Ok(expected.to_string()));
+}
+
+#[rstest]
+#[case(r#"json.parse("[1,")"#, "json.parse: EOF while parsing a value at line 1 column 3")]
+#[case(r#"json.parse("1.5")"#, "json.parse: 1.5 is not a 64-bit integer")]
+#[case(r#"json.parse(1)"#, "argument to `json.parse` must be STRING, got INTEGER")]
+#[case(r#"json.stringify({1: 2})"#, "json.stringify: hash keys must be STRING, got INTEGER")]
+#[case(r#"json.stringify([fn() {}])"#, "json.stringify: FUNCTION can't be represented as JSON")]
+fn test_json_errors(#[case] input: &str, #[case] expected: &str) {
+    let input = format!("import \"std/json\";\n{}", input);
+    assert_eq!(eval(&input, Capabilities::default()), Err(expected.to_string()));
+}
+
+#[rstest]
+#[case("import \"std/fs\";", "`std/fs` needs file-system access, granted with --allow-fs")]
+#[case("import \"std/env\";", "`std/env` needs environment access, granted with --allow-env")]
+#[case("import \"std/net\";", "no standard library module named `std/net`")]
+fn test_capabilities_are_required(#[case] input: &str, #[case] expected: &str) {
+    assert_eq!(eval(input, Capabilities::default()), Err(expected.to_string()));
+}
+
+#[test]
+fn test_fs() {
+    let dir = std::env::temp_dir().join(format!("stdlib-fs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir_name = dir.display().to_string();
+    let input = format!(
+        r#"import "std/fs";
+let dir = "{dir}";
+fs.write(dir + "/b.txt", "hello");
+fs.write(dir + "/a.txt", "");
+let missing = try {{ fs.read(dir + "/c.txt") }} catch (e) {{ "missing" }};
+[fs.read(dir + "/b.txt"), fs.list(dir), missing]"#,
+        dir = dir_name
+    );
+    let capabilities = Capabilities { fs: true, ..Default::default() };
+    let result = eval(&input, capabilities);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(result, Ok(r#"["hello", ["a.txt", "b.txt"], "missing"]"#.to_string()));
+}
+
+#[test]
+fn test_env() {
+    let capabilities = Capabilities {
+        env: true,
+        args: vec!["--verbose".to_string(), "x".to_string()],
+        ..Default::default()
+    };
+    let input = r#"import "std/env"; [env.args, type(env.var("PATH")), env.var("STDLIB_TEST_UNSET")]"#;
+    assert_eq!(eval(input, capabilities), Ok(r#"[["--verbose", "x"], "STRING", null]"#.to_string()));
+}
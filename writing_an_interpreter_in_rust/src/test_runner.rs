@@ -0,0 +1,120 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::ast;
+use crate::evaluator::Evaluator;
+use crate::object::{BuiltinObject, ErrorObject, FunctionObject, Object};
+use rstest::rstest;
+
+/// What a test file is named: `*_test.wl`.
+pub const TEST_FILE_SUFFIX: &str = "_test";
+
+pub struct TestReport {
+    pub name: String,
+    pub error: Option<ErrorObject>,
+}
+
+/// Runs a test file: its top level registers tests with `test(name, fn)`,
+/// then each one is called on its own so a failure can't stop the rest.
+/// Values can't be mutated, so tests can't leak state into each other
+/// through the module they share.
+pub fn run_tests(evaluator: &mut Evaluator, path: &Path, program: &ast::ProgramNode) -> Result<Vec<TestReport>, ErrorObject> {
+    let registered: Rc<RefCell<Vec<(String, Object)>>> = Rc::default();
+    let tests = registered.clone();
+    let test = move |_: &mut Evaluator, args: Vec<Object>| -> Result<Object, ErrorObject> {
+        match args.as_slice() {
+            [Object::String(name), Object::Function(function)] => {
+                // Named after the test, so its frame in a stack trace is too.
+                let function = FunctionObject {
+                    name: Some(format!("test {:?}", name)),
+                    file: function.file.clone(),
+                    parameters: function.parameters.clone(),
+                    body: function.body.clone(),
+                    env: function.env.clone(),
+                    is_generator: function.is_generator,
+                };
+                tests
+                    .borrow_mut()
+                    .push((name.to_string(), Object::Function(Rc::new(function))));
+                Ok(Object::Null)
+            }
+            _ => Err(String::from("usage: test(\"name\", fn() { ... })").into()),
+        }
+    };
+    evaluator.set_global(
+        "test",
+        Object::Builtin(Rc::new(BuiltinObject {
+            name: "test".to_string(),
+            func: Rc::new(test),
+        })),
+    );
+    evaluator.eval_module(path, program)?;
+
+    let tests = registered.take();
+    let mut reports = Vec::with_capacity(tests.len());
+    for (name, function) in tests {
+        evaluator.reset_usage();
+        let error = evaluator.call_function(function, Vec::new()).err();
+        reports.push(TestReport { name, error });
+    }
+    return Ok(reports);
+}
+
+#[cfg(test)]
+fn run_source(source: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    use crate::{lexer, parser};
+
+    let program = parser::Parser::new(lexer::Lexer::new(source.to_string())).parse_program();
+    let mut evaluator = Evaluator::new().with_loader(crate::module::MemoryLoader::default().with("math_test.wl", source));
+    let reports = run_tests(&mut evaluator, Path::new("math_test.wl"), &program).map_err(|e| e.message)?;
+    return Ok(reports
+        .into_iter()
+        .map(|r| (r.name, r.error.map(|e| e.message)))
+        .collect());
+}
+
+#[rstest]
+#[case("test(\"adds\", fn() { assert_eq(1 + 1, 2) });", vec![("adds", None)])]
+#[case(
+    "test(\"a\", fn() { assert(false) }); test(\"b\", fn() { assert(true) });",
+    vec![("a", Some("assertion failed: got false")), ("b", None)]
+)]
+#[case("test(\"a\", fn() { assert(1 > 2, \"too small\") });", vec![("a", Some("assertion failed: too small"))])]
+#[case(
+    "test(\"a\", fn() { assert_eq([1, 2, 3], [1, 2, 4]) });",
+    vec![("a", Some("assertion failed: values are not equal\n  expected: [1, 2, 4]\n  actual:   [1, 2, 3]\n                   ^"))]
+)]
+#[case(
+    "test(\"a\", fn() { assert_eq(\"x\\ny\\nz\", \"x\\nb\\nz\") });",
+    vec![("a", Some("assertion failed: values are not equal\n  (- expected, + actual)\n    x\n  - b\n  + y\n    z"))]
+)]
+#[case("test(\"a\", fn() { assert_eq({\"k\": [1]}, {\"k\": [1]}) });", vec![("a", None)])]
+#[case(
+    "test(\"a\", fn() { assert_throws(fn() { 1 / 0 }, \"division by zero\") }); \
+     test(\"b\", fn() { assert_throws(fn() { 1 }) }); \
+     test(\"c\", fn() { assert_throws(fn() { throw(\"x\") }, \"y\") }); \
+     test(\"d\", fn() { assert_eq(assert_throws(fn() { throw(\"x\") })[\"message\"], \"x\") });",
+    vec![
+        ("a", None),
+        ("b", Some("assertion failed: expected an error, got 1")),
+        ("c", Some("assertion failed: expected error \"y\", got \"x\"")),
+        ("d", None),
+    ]
+)]
+#[case("let helper = fn(x) { x * 2 }; test(\"uses helper\", fn() { assert_eq(helper(2), 5) });", vec![("uses helper", Some("assertion failed: values are not equal\n  expected: 5\n  actual:   4\n            ^"))])]
+fn test_run_tests(#[case] source: &str, #[case] expected: Vec<(&str, Option<&str>)>) {
+    let reports = run_source(source).unwrap();
+    let expected: Vec<(String, Option<String>)> = expected
+        .into_iter()
+        .map(|(name, error)| (name.to_string(), error.map(|e| e.to_string())))
+        .collect();
+    assert_eq!(reports, expected);
+}
+
+#[rstest]
+#[case("test(1, 2);", "usage: test(\"name\", fn() { ... })")]
+#[case("test(\"a\", fn() { 1 }); 1 + true;", "type mismatch: INTEGER \"+\" BOOLEAN")]
+fn test_run_tests_errors(#[case] source: &str, #[case] expected: &str) {
+    assert_eq!(run_source(source).err().unwrap(), expected);
+}
@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use serde::{Serialize, Serializer};
+
 #[derive(PartialEq, Clone)]
 pub enum TokenType {
     ILLEGAL,
@@ -8,6 +10,7 @@ pub enum TokenType {
     // Identifiers + literals
     Ident(String),
     Int(i64),
+    String(String),
 
     // Operators
     Assign,
@@ -18,6 +21,10 @@ pub enum TokenType {
     Slash,
     PlusPlus,
     MinusMinus,
+    FatArrow,
+    Arrow,
+    Dot,
+    DotDot,
 
     LT,
     GT,
@@ -31,11 +38,14 @@ pub enum TokenType {
     // Delimiters
     Comma,
     Semicolon,
+    Colon,
 
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
 
     // Keywords
     Function,
@@ -45,6 +55,16 @@ pub enum TokenType {
     If,
     Else,
     Return,
+    Import,
+    As,
+    Match,
+    Try,
+    Catch,
+    Struct,
+    Impl,
+    Yield,
+    For,
+    In,
 }
 
 impl Debug for TokenType {
@@ -53,6 +73,13 @@ impl Debug for TokenType {
     }
 }
 
+/// Token types serialize as the name they're shown with in messages.
+impl Serialize for TokenType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
 impl TokenType {
     fn to_string(&self) -> String {
         match self {
@@ -60,6 +87,7 @@ impl TokenType {
             TokenType::EOF => "EOF".to_string(),
             TokenType::Ident(_) => "Identifier".to_string(),
             TokenType::Int(_) => "Integer".to_string(),
+            TokenType::String(_) => "String".to_string(),
             TokenType::Assign => "=".to_string(),
             TokenType::Plus => "+".to_string(),
             TokenType::Minus => "-".to_string(),
@@ -68,6 +96,10 @@ impl TokenType {
             TokenType::Slash => "/".to_string(),
             TokenType::PlusPlus => "++".to_string(),
             TokenType::MinusMinus => "--".to_string(),
+            TokenType::FatArrow => "=>".to_string(),
+            TokenType::Arrow => "->".to_string(),
+            TokenType::Dot => ".".to_string(),
+            TokenType::DotDot => "..".to_string(),
             TokenType::LT => "<".to_string(),
             TokenType::GT => ">".to_string(),
             TokenType::LTE => "<=".to_string(),
@@ -77,10 +109,13 @@ impl TokenType {
             TokenType::NotEQ => "!=".to_string(),
             TokenType::Comma => ",".to_string(),
             TokenType::Semicolon => ";".to_string(),
+            TokenType::Colon => ":".to_string(),
             TokenType::LParen => "(".to_string(),
             TokenType::RParen => ")".to_string(),
             TokenType::LBrace => "{".to_string(),
             TokenType::RBrace => "}".to_string(),
+            TokenType::LBracket => "[".to_string(),
+            TokenType::RBracket => "]".to_string(),
             TokenType::Function => "fn".to_string(),
             TokenType::Let => "let".to_string(),
             TokenType::True => "true".to_string(),
@@ -88,12 +123,22 @@ impl TokenType {
             TokenType::If => "if".to_string(),
             TokenType::Else => "else".to_string(),
             TokenType::Return => "return".to_string(),
+            TokenType::Import => "import".to_string(),
+            TokenType::As => "as".to_string(),
+            TokenType::Match => "match".to_string(),
+            TokenType::Try => "try".to_string(),
+            TokenType::Catch => "catch".to_string(),
+            TokenType::Struct => "struct".to_string(),
+            TokenType::Impl => "impl".to_string(),
+            TokenType::Yield => "yield".to_string(),
+            TokenType::For => "for".to_string(),
+            TokenType::In => "in".to_string(),
         }
     }
 
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
@@ -122,10 +167,13 @@ impl Token {
             TokenType::GT => Token::new(token_type, ">".to_string()),
             TokenType::Comma => Token::new(token_type, ",".to_string()),
             TokenType::Semicolon => Token::new(token_type, ";".to_string()),
+            TokenType::Colon => Token::new(token_type, ":".to_string()),
             TokenType::LParen => Token::new(token_type, "(".to_string()),
             TokenType::RParen => Token::new(token_type, ")".to_string()),
             TokenType::LBrace => Token::new(token_type, "{".to_string()),
             TokenType::RBrace => Token::new(token_type, "}".to_string()),
+            TokenType::LBracket => Token::new(token_type, "[".to_string()),
+            TokenType::RBracket => Token::new(token_type, "]".to_string()),
             TokenType::Function => Token::new(token_type, "fn".to_string()),
             TokenType::Let => Token::new(token_type, "let".to_string()),
             TokenType::True => Token::new(token_type, "true".to_string()),
@@ -133,11 +181,26 @@ impl Token {
             TokenType::If => Token::new(token_type, "if".to_string()),
             TokenType::Else => Token::new(token_type, "else".to_string()),
             TokenType::Return => Token::new(token_type, "return".to_string()),
+            TokenType::Import => Token::new(token_type, "import".to_string()),
+            TokenType::As => Token::new(token_type, "as".to_string()),
+            TokenType::Match => Token::new(token_type, "match".to_string()),
+            TokenType::Try => Token::new(token_type, "try".to_string()),
+            TokenType::Catch => Token::new(token_type, "catch".to_string()),
+            TokenType::Struct => Token::new(token_type, "struct".to_string()),
+            TokenType::Impl => Token::new(token_type, "impl".to_string()),
+            TokenType::Yield => Token::new(token_type, "yield".to_string()),
+            TokenType::For => Token::new(token_type, "for".to_string()),
+            TokenType::In => Token::new(token_type, "in".to_string()),
             TokenType::EOF => Token::new(token_type, "".to_string()),
             TokenType::Ident(_) => Token::new(TokenType::ILLEGAL, "".to_string()),
             TokenType::Int(_) => Token::new(TokenType::ILLEGAL, "".to_string()),
+            TokenType::String(_) => Token::new(TokenType::ILLEGAL, "".to_string()),
             TokenType::PlusPlus => Token::new(TokenType::PlusPlus, "++".to_string()),
             TokenType::MinusMinus => Token::new(TokenType::MinusMinus, "--".to_string()),
+            TokenType::FatArrow => Token::new(TokenType::FatArrow, "=>".to_string()),
+            TokenType::Arrow => Token::new(TokenType::Arrow, "->".to_string()),
+            TokenType::Dot => Token::new(TokenType::Dot, ".".to_string()),
+            TokenType::DotDot => Token::new(TokenType::DotDot, "..".to_string()),
             TokenType::LTE => Token::new(TokenType::LTE, "<=".to_string()),
             TokenType::GTE => Token::new(TokenType::GTE, ">=".to_string()),
             TokenType::EQ => Token::new(TokenType::EQ, "==".to_string()),
@@ -147,6 +210,16 @@ impl Token {
         }
     }
 
+    /// How many columns the token takes up in the source. Strings are
+    /// measured after unescaping, so one with escapes comes out short.
+    pub fn width(&self) -> usize {
+        let quotes = match self.token_type {
+            TokenType::String(_) => 2,
+            _ => 0,
+        };
+        return self.literal.chars().count() + quotes;
+    }
+
     pub fn new_illegal(literal: &str) -> Token {
         Token::new(TokenType::ILLEGAL, literal.to_string())
     }
@@ -155,11 +228,17 @@ impl Token {
         Token::new(TokenType::Ident(literal.to_string()), literal.to_string())
     }
 
+    pub fn new_string(literal: &str) -> Token {
+        Token::new(TokenType::String(literal.to_string()), literal.to_string())
+    }
+
+    /// Numbers too big for an `Int` come out `ILLEGAL`, for the parser to
+    /// report.
     pub fn new_number(literal: &str) -> Token {
-        Token::new(
-            TokenType::Int(literal.parse().unwrap()),
-            literal.to_string(),
-        )
+        match literal.parse() {
+            Ok(value) => Token::new(TokenType::Int(value), literal.to_string()),
+            Err(_) => Token::new_illegal(literal),
+        }
     }
 }
 
@@ -175,10 +254,14 @@ pub fn lookup_single_char_token(c: char) -> Option<TokenType> {
         '>' => Some(TokenType::GT),
         ',' => Some(TokenType::Comma),
         ';' => Some(TokenType::Semicolon),
+        ':' => Some(TokenType::Colon),
+        '.' => Some(TokenType::Dot),
         '(' => Some(TokenType::LParen),
         ')' => Some(TokenType::RParen),
         '{' => Some(TokenType::LBrace),
         '}' => Some(TokenType::RBrace),
+        '[' => Some(TokenType::LBracket),
+        ']' => Some(TokenType::RBracket),
 
         _ => None,
     }
@@ -186,12 +269,13 @@ pub fn lookup_single_char_token(c: char) -> Option<TokenType> {
 
 pub fn lookup_multi_char_token(c: char) -> Vec<TokenType> {
     match c {
-        '=' => vec![TokenType::STRONGEQ, TokenType::EQ],
+        '=' => vec![TokenType::STRONGEQ, TokenType::EQ, TokenType::FatArrow],
         '!' => vec![TokenType::NotEQ],
         '<' => vec![TokenType::LTE],
         '>' => vec![TokenType::GTE],
-        '-' => vec![TokenType::MinusMinus],
+        '-' => vec![TokenType::MinusMinus, TokenType::Arrow],
         '+' => vec![TokenType::PlusPlus],
+        '.' => vec![TokenType::DotDot],
         _ => vec![],
     }
 }
@@ -205,6 +289,16 @@ pub fn lookup_keyword(ident: &str) -> Option<TokenType> {
         "if" => Some(TokenType::If),
         "else" => Some(TokenType::Else),
         "return" => Some(TokenType::Return),
+        "import" => Some(TokenType::Import),
+        "as" => Some(TokenType::As),
+        "match" => Some(TokenType::Match),
+        "try" => Some(TokenType::Try),
+        "catch" => Some(TokenType::Catch),
+        "struct" => Some(TokenType::Struct),
+        "impl" => Some(TokenType::Impl),
+        "yield" => Some(TokenType::Yield),
+        "for" => Some(TokenType::For),
+        "in" => Some(TokenType::In),
         _ => None,
     }
 }
@@ -0,0 +1,818 @@
+//! Compiles programs to JavaScript. The output is a single script with a
+//! small runtime in front of it, so it runs under any JavaScript engine
+//! without further setup.
+//!
+//! Expressions become JavaScript expressions where they can. `if`, `match`
+//! and `try` are statements in JavaScript, so when one of them is used as a
+//! value it is compiled to statements that leave the result in a temporary.
+
+use std::collections::HashMap;
+
+use crate::ast;
+use crate::diagnostic::Diagnostic;
+use crate::token::TokenType;
+use rstest::rstest;
+
+const RUNTIME: &str = include_str!("runtime.js");
+const INDENT: &str = "    ";
+
+/// Words JavaScript doesn't allow as variable names.
+const RESERVED: &[&str] = &[
+    "arguments", "await", "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete",
+    "do", "else", "enum", "eval", "export", "extends", "false", "finally", "for", "function", "if", "implements",
+    "import", "in", "instanceof", "interface", "let", "new", "null", "package", "private", "protected", "public",
+    "return", "static", "super", "switch", "this", "throw", "true", "try", "typeof", "undefined", "var", "void",
+    "while", "with", "yield",
+];
+
+const BUILTINS: &[&str] = &[
+    "len", "first", "last", "rest", "push", "puts", "type", "throw", "assert", "assert_eq", "assert_throws",
+    "gc_stats", "iter", "next", "map", "filter", "take", "collect",
+];
+
+/// Compiles `program` to a JavaScript script that prints what running it
+/// would. An uncaught error is written to stderr and sets the exit code.
+pub fn javascript(program: &ast::ProgramNode) -> Result<String, Diagnostic> {
+    let mut compiler = Compiler {
+        out: String::new(),
+        depth: 1,
+        temps: 0,
+        scopes: Vec::new(),
+        in_function: false,
+        try_depth: 0,
+    };
+    compiler.push_scope(&program.statements, &[], false);
+    for statement in &program.statements {
+        compiler.statement(statement)?;
+    }
+    return Ok(format!("{}\n$rt.main(() => {{\n{}}});\n", RUNTIME, compiler.out));
+}
+
+/// Where the value of a block goes once it's computed.
+#[derive(Clone)]
+enum Target {
+    Return,
+    Assign(String),
+    Discard,
+}
+
+struct Binding {
+    js_name: String,
+    declared: bool,
+}
+
+/// The names one scope of the script defines. They're collected up front,
+/// because a function can refer to a name its scope only defines after it.
+struct Scope {
+    bindings: HashMap<String, Binding>,
+    is_function: bool,
+}
+
+struct Compiler {
+    out: String,
+    depth: usize,
+    temps: usize,
+    scopes: Vec<Scope>,
+    in_function: bool,
+    try_depth: usize,
+}
+
+impl Compiler {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn temp(&mut self, prefix: &str) -> String {
+        self.temps += 1;
+        return format!("${}{}", prefix, self.temps);
+    }
+
+    /// Runs `f` with the statements it emits collected separately, one level
+    /// deeper than the current code.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Compiler) -> Result<T, Diagnostic>) -> Result<(String, T), Diagnostic> {
+        let out = std::mem::take(&mut self.out);
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        let nested = std::mem::replace(&mut self.out, out);
+        return Ok((nested, result?));
+    }
+
+    /// Enters a scope where `names` are bound from the start and the `let`s
+    /// and structs among `statements` are bound when they're reached.
+    fn push_scope(&mut self, statements: &[ast::StatementNode], names: &[&str], is_function: bool) {
+        let mut bindings = HashMap::new();
+        for name in names {
+            bindings.insert(name.to_string(), Binding { js_name: js_name(name), declared: false });
+        }
+        for statement in statements {
+            let name = match statement {
+                ast::StatementNode::Let(stmt) => &stmt.name.value,
+                ast::StatementNode::Struct(stmt) => &stmt.name.value,
+                _ => continue,
+            };
+            if bindings.contains_key(name) {
+                continue;
+            }
+            // Until the `let` runs, the name still means the outer variable,
+            // so the two need different names in JavaScript.
+            let shadowed = self.scopes.iter().filter(|s| s.bindings.contains_key(name)).count();
+            let js_name = match shadowed {
+                0 => js_name(name),
+                n => format!("{}${}", name, n),
+            };
+            bindings.insert(name.clone(), Binding { js_name, declared: false });
+        }
+        self.scopes.push(Scope { bindings, is_function });
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Emits `let` the first time a scope defines `name` and a plain
+    /// assignment after that, returning the statement's left-hand side.
+    fn declare(&mut self, name: &str) -> String {
+        let scope = self.scopes.last_mut().expect("scope stack is empty");
+        let binding = scope.bindings.get_mut(name).expect("name was not collected");
+        if binding.declared {
+            return binding.js_name.clone();
+        }
+        binding.declared = true;
+        return format!("let {}", binding.js_name);
+    }
+
+    /// Finds the variable `name` refers to. A scope's name is visible from
+    /// the point it's defined on, or anywhere in a function nested inside
+    /// it, since the function can only run later.
+    fn resolve(&self, name: &str) -> Option<String> {
+        let mut in_nested_function = false;
+        for scope in self.scopes.iter().rev() {
+            if let Some(binding) = scope.bindings.get(name) {
+                if binding.declared || in_nested_function {
+                    return Some(binding.js_name.clone());
+                }
+            }
+            in_nested_function |= scope.is_function;
+        }
+        return None;
+    }
+
+    fn deliver(&mut self, value: String, target: &Target) {
+        match target {
+            Target::Return => self.line(&format!("return {};", value)),
+            Target::Assign(temp) => self.line(&format!("{} = {};", temp, value)),
+            Target::Discard if is_simple(&value) => {}
+            Target::Discard => self.line(&format!("{};", value)),
+        }
+    }
+
+    fn statement(&mut self, statement: &ast::StatementNode) -> Result<(), Diagnostic> {
+        match statement {
+            ast::StatementNode::Let(stmt) => {
+                let value = self.expression(&stmt.value)?;
+                let name = self.declare(&stmt.name.value);
+                self.line(&format!("{} = {};", name, value));
+            }
+            ast::StatementNode::Return(stmt) => {
+                let tail = self.in_function && self.try_depth == 0;
+                let value = match &stmt.value {
+                    ast::ExpressionNode::Call(call) => self.call(call, tail)?,
+                    value => self.expression(value)?,
+                };
+                self.line(&format!("return {};", value));
+            }
+            ast::StatementNode::Expression(stmt) => self.expression_to(&stmt.expression, &Target::Discard)?,
+            ast::StatementNode::Import(stmt) => {
+                let message = "import is not supported when transpiling to JavaScript".to_string();
+                return Err(Diagnostic::at(message, &stmt.token));
+            }
+            ast::StatementNode::Struct(stmt) => {
+                let fields: Vec<String> = stmt.fields.iter().map(|f| string_literal(&f.value)).collect();
+                let name = self.declare(&stmt.name.value);
+                let value = format!("$rt.struct({}, [{}])", string_literal(&stmt.name.value), fields.join(", "));
+                self.line(&format!("{} = {};", name, value));
+            }
+            ast::StatementNode::Impl(stmt) => {
+                let struct_type = self.identifier(&stmt.name.value);
+                let mut methods = Vec::new();
+                for (name, func) in &stmt.methods {
+                    methods.push(format!("[{}, {}]", string_literal(&name.value), self.function(func)?));
+                }
+                self.line(&format!("$rt.impl({}, [{}]);", struct_type, methods.join(", ")));
+            }
+            ast::StatementNode::Yield(stmt) => {
+                let value = self.expression(&stmt.value)?;
+                match stmt.delegate {
+                    true => self.line(&format!("yield* $rt.iter({});", value)),
+                    false => self.line(&format!("yield {};", value)),
+                }
+            }
+            ast::StatementNode::For(stmt) => {
+                let iterable = self.expression(&stmt.iterable)?;
+                let binding = stmt.binding.value.as_str();
+                self.push_scope(&stmt.body.statements, &[binding], false);
+                let name = self.declare(binding);
+                self.line(&format!("for ({} of $rt.iter({})) {{", name, iterable));
+                let body = self.nested(|c| c.statements(&stmt.body.statements, &Target::Discard));
+                self.pop_scope();
+                self.out.push_str(&body?.0);
+                self.line("}");
+            }
+        }
+        return Ok(());
+    }
+
+    /// Compiles a block in a scope of its own, with `names` already bound.
+    fn block(&mut self, block: &ast::BlockStatement, names: &[&str], target: &Target) -> Result<(), Diagnostic> {
+        self.push_scope(&block.statements, names, false);
+        let result = self.statements(&block.statements, target);
+        self.pop_scope();
+        return result;
+    }
+
+    fn statements(&mut self, statements: &[ast::StatementNode], target: &Target) -> Result<(), Diagnostic> {
+        let Some((last, init)) = statements.split_last() else {
+            self.deliver("null".to_string(), target);
+            return Ok(());
+        };
+        for statement in init {
+            self.statement(statement)?;
+        }
+        match last {
+            ast::StatementNode::Expression(stmt) => self.expression_to(&stmt.expression, target)?,
+            ast::StatementNode::Return(_) => self.statement(last)?,
+            _ => {
+                self.statement(last)?;
+                self.deliver("null".to_string(), target);
+            }
+        }
+        return Ok(());
+    }
+
+    /// Compiles an expression whose value goes straight to `target`, which
+    /// saves a temporary for the ones that compile to statements.
+    fn expression_to(&mut self, expression: &ast::ExpressionNode, target: &Target) -> Result<(), Diagnostic> {
+        match expression {
+            ast::ExpressionNode::If(if_expr) if matches!(target, Target::Discard) || !has_simple_branches(if_expr) => {
+                self.if_statement(if_expr, target)
+            }
+            ast::ExpressionNode::Match(match_expr) => self.match_statement(match_expr, target),
+            ast::ExpressionNode::Try(try_expr) => self.try_statement(try_expr, target),
+            expression => {
+                let value = self.expression(expression)?;
+                self.deliver(value, target);
+                Ok(())
+            }
+        }
+    }
+
+    /// Compiles an expression to JavaScript, emitting any statements it
+    /// needs beforehand.
+    fn expression(&mut self, expression: &ast::ExpressionNode) -> Result<String, Diagnostic> {
+        let value = match expression {
+            ast::ExpressionNode::Identifier(ident) => self.identifier(&ident.value),
+            ast::ExpressionNode::Literal(literal) => literal_value(&literal.value),
+            ast::ExpressionNode::Prefix(prefix) => {
+                let right = self.expression(&prefix.right)?;
+                match prefix.operator {
+                    TokenType::Minus => format!("$rt.neg({})", right),
+                    _ => format!("$rt.not({})", right),
+                }
+            }
+            ast::ExpressionNode::Infix(infix) => {
+                let operands = self.operands(&[infix.left.as_ref(), infix.right.as_ref()])?;
+                let operator = string_literal(&infix.token.literal);
+                format!("$rt.infix({}, {}, {})", operator, operands[0], operands[1])
+            }
+            ast::ExpressionNode::If(if_expr) if has_simple_branches(if_expr) => {
+                let condition = self.expression(&if_expr.condition)?;
+                let consequence = self.branch_value(Some(&if_expr.consequence))?;
+                let alternative = self.branch_value(if_expr.alternative.as_ref())?;
+                format!("($rt.truthy({}) ? {} : {})", condition, consequence, alternative)
+            }
+            ast::ExpressionNode::If(_) | ast::ExpressionNode::Match(_) | ast::ExpressionNode::Try(_) => {
+                let temp = self.temp("t");
+                self.line(&format!("let {};", temp));
+                self.expression_to(expression, &Target::Assign(temp.clone()))?;
+                temp
+            }
+            ast::ExpressionNode::Function(func) => self.function(func)?,
+            ast::ExpressionNode::Call(call) => self.call(call, false)?,
+            ast::ExpressionNode::Array(array) => {
+                let elements: Vec<&ast::ExpressionNode> = array.elements.iter().collect();
+                format!("[{}]", self.operands(&elements)?.join(", "))
+            }
+            ast::ExpressionNode::Hash(hash) => {
+                let nodes: Vec<&ast::ExpressionNode> = hash.pairs.iter().flat_map(|(k, v)| [k, v]).collect();
+                let values = self.operands(&nodes)?;
+                let pairs: Vec<String> = values.chunks(2).map(|pair| format!("[{}, {}]", pair[0], pair[1])).collect();
+                format!("$rt.hash([{}])", pairs.join(", "))
+            }
+            ast::ExpressionNode::Index(index) => {
+                let operands = self.operands(&[index.left.as_ref(), index.index.as_ref()])?;
+                format!("$rt.index({}, {})", operands[0], operands[1])
+            }
+            ast::ExpressionNode::StructLiteral(literal) => {
+                let mut nodes = vec![literal.name.as_ref()];
+                nodes.extend(literal.fields.iter().map(|(_, value)| value));
+                let values = self.operands(&nodes)?;
+                let fields: Vec<String> = literal
+                    .fields
+                    .iter()
+                    .zip(&values[1..])
+                    .map(|((name, _), value)| format!("[{}, {}]", string_literal(&name.value), value))
+                    .collect();
+                format!("$rt.construct({}, [{}])", values[0], fields.join(", "))
+            }
+            ast::ExpressionNode::Field(field) => {
+                let left = self.expression(&field.left)?;
+                format!("$rt.field({}, {})", left, string_literal(&field.field.value))
+            }
+        };
+        return Ok(value);
+    }
+
+    /// Compiles expressions that are evaluated left to right. When a later
+    /// one needs statements, the earlier values are saved in temporaries
+    /// first so they're still computed in order.
+    fn operands(&mut self, expressions: &[&ast::ExpressionNode]) -> Result<Vec<String>, Diagnostic> {
+        let mut compiled = Vec::with_capacity(expressions.len());
+        for expression in expressions {
+            let out = std::mem::take(&mut self.out);
+            let value = self.expression(expression);
+            let statements = std::mem::replace(&mut self.out, out);
+            compiled.push((statements, value?));
+        }
+        let last_with_statements = compiled.iter().rposition(|(statements, _)| !statements.is_empty());
+        let mut values = Vec::with_capacity(compiled.len());
+        for (i, (statements, value)) in compiled.into_iter().enumerate() {
+            self.out.push_str(&statements);
+            match last_with_statements {
+                Some(last) if i < last && !is_simple(&value) => {
+                    let temp = self.temp("t");
+                    self.line(&format!("const {} = {};", temp, value));
+                    values.push(temp);
+                }
+                _ => values.push(value),
+            }
+        }
+        return Ok(values);
+    }
+
+    fn identifier(&self, name: &str) -> String {
+        if let Some(js_name) = self.resolve(name) {
+            return js_name;
+        }
+        if BUILTINS.contains(&name) {
+            return format!("$b.{}", name);
+        }
+        return format!("$rt.notFound({})", string_literal(name));
+    }
+
+    fn function(&mut self, func: &ast::FunctionExpr) -> Result<String, Diagnostic> {
+        let names: Vec<&str> = func.parameters.iter().map(|p| p.value.as_str()).collect();
+        self.push_scope(&func.body.statements, &names, true);
+        // A repeated parameter takes the last argument, as it does when the
+        // interpreter binds them in order.
+        let params: Vec<String> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| match names[i + 1..].contains(name) {
+                true => format!("${}", i),
+                false => {
+                    let scope = self.scopes.last_mut().expect("scope stack is empty");
+                    let binding = scope.bindings.get_mut(*name).expect("name was not collected");
+                    binding.declared = true;
+                    binding.js_name.clone()
+                }
+            })
+            .collect();
+        // A generator's `return` only ends it, so it can't be a tail call,
+        // and the value of its body goes nowhere.
+        let in_function = std::mem::replace(&mut self.in_function, !func.is_generator);
+        let try_depth = std::mem::replace(&mut self.try_depth, 0);
+        let target = match func.is_generator {
+            true => Target::Discard,
+            false => Target::Return,
+        };
+        let result = self.nested(|c| c.statements(&func.body.statements, &target));
+        self.in_function = in_function;
+        self.try_depth = try_depth;
+        self.pop_scope();
+        let (body, _) = result?;
+
+        let display: Vec<String> = names.iter().map(|name| string_literal(name)).collect();
+        let mut result = match func.is_generator {
+            true => format!("$rt.gen([{}], function* ({}) {{\n", display.join(", "), params.join(", ")),
+            false => format!("$rt.fn([{}], ({}) => {{\n", display.join(", "), params.join(", ")),
+        };
+        result.push_str(&body);
+        for _ in 0..self.depth {
+            result.push_str(INDENT);
+        }
+        result.push_str("})");
+        return Ok(result);
+    }
+
+    /// Compiles a call. A tail call is handed back to `$rt.call` to make,
+    /// so that deep tail recursion runs in constant stack space.
+    fn call(&mut self, call: &ast::CallExpr, tail: bool) -> Result<String, Diagnostic> {
+        let (prefix, mut nodes) = match call.function.as_ref() {
+            ast::ExpressionNode::Field(field) => (Some(&field.field.value), vec![field.left.as_ref()]),
+            function => (None, vec![function]),
+        };
+        nodes.extend(call.arguments.iter());
+        let values = self.operands(&nodes)?;
+        let arguments = values[1..].join(", ");
+        let value = match (prefix, tail) {
+            (Some(name), false) => format!("$rt.method({}, {}, [{}])", values[0], string_literal(name), arguments),
+            (Some(name), true) => format!("$rt.tailMethod({}, {}, [{}])", values[0], string_literal(name), arguments),
+            (None, false) => format!("$rt.call({}, [{}])", values[0], arguments),
+            (None, true) => format!("$rt.tail({}, [{}])", values[0], arguments),
+        };
+        return Ok(value);
+    }
+
+    fn branch_value(&mut self, block: Option<&ast::BlockStatement>) -> Result<String, Diagnostic> {
+        let Some(block) = block else {
+            return Ok("null".to_string());
+        };
+        let [ast::StatementNode::Expression(stmt)] = block.statements.as_slice() else {
+            unreachable!("branch is not a single expression");
+        };
+        return self.expression(&stmt.expression);
+    }
+
+    fn if_statement(&mut self, if_expr: &ast::IfExpr, target: &Target) -> Result<(), Diagnostic> {
+        let condition = self.expression(&if_expr.condition)?;
+        self.line(&format!("if ($rt.truthy({})) {{", condition));
+        let (consequence, _) = self.nested(|c| c.block(&if_expr.consequence, &[], target))?;
+        self.out.push_str(&consequence);
+        match &if_expr.alternative {
+            Some(alternative) => {
+                self.line("} else {");
+                let (alternative, _) = self.nested(|c| c.block(alternative, &[], target))?;
+                self.out.push_str(&alternative);
+            }
+            None if !matches!(target, Target::Discard) => {
+                self.line("} else {");
+                let (alternative, _) = self.nested(|c| {
+                    c.deliver("null".to_string(), target);
+                    Ok(())
+                })?;
+                self.out.push_str(&alternative);
+            }
+            None => {}
+        }
+        self.line("}");
+        return Ok(());
+    }
+
+    /// Compiles a `match` to a labeled block that tries each arm in turn and
+    /// breaks out of the block once one has run.
+    fn match_statement(&mut self, match_expr: &ast::MatchExpr, target: &Target) -> Result<(), Diagnostic> {
+        let value = self.expression(&match_expr.value)?;
+        let subject = self.temp("m");
+        self.line(&format!("const {} = {};", subject, value));
+        self.line(&format!("{}: {{", subject));
+        self.depth += 1;
+        for arm in &match_expr.arms {
+            let mut names = Vec::new();
+            pattern_names(&arm.pattern, &mut names);
+            self.push_scope(&arm.body.statements, &names, false);
+            let result = self.match_arm(arm, &subject, target);
+            self.pop_scope();
+            result?;
+        }
+        self.line(&format!("$rt.noMatch({});", subject));
+        self.depth -= 1;
+        self.line("}");
+        return Ok(());
+    }
+
+    fn match_arm(&mut self, arm: &ast::MatchArm, subject: &str, target: &Target) -> Result<(), Diagnostic> {
+        let mut closing = 1;
+        self.line("{");
+        self.depth += 1;
+        match &arm.pattern {
+            ast::Pattern::Wildcard(_) => {}
+            ast::Pattern::Binding(ident) => {
+                let name = self.declare(&ident.value);
+                self.line(&format!("{} = {};", name, subject));
+            }
+            ast::Pattern::Literal(literal) => {
+                self.line(&format!("if ({} === {}) {{", subject, literal_value(&literal.value)));
+                self.depth += 1;
+                closing += 1;
+            }
+            pattern => {
+                let bindings = self.temp("b");
+                self.line(&format!("const {} = $rt.match({}, {});", bindings, pattern_value(pattern), subject));
+                self.line(&format!("if ({} !== null) {{", bindings));
+                self.depth += 1;
+                closing += 1;
+                let mut names = Vec::new();
+                pattern_names(pattern, &mut names);
+                for name in names {
+                    let js_name = self.declare(name);
+                    if js_name.starts_with("let ") {
+                        self.line(&format!("{} = {}.{};", js_name, bindings, name));
+                    }
+                }
+            }
+        }
+        if let Some(guard) = &arm.guard {
+            let guard = self.expression(guard)?;
+            self.line(&format!("if ($rt.truthy({})) {{", guard));
+            self.depth += 1;
+            closing += 1;
+        }
+        self.statements(&arm.body.statements, target)?;
+        self.line(&format!("break {};", subject));
+        for _ in 0..closing {
+            self.depth -= 1;
+            self.line("}");
+        }
+        return Ok(());
+    }
+
+    fn try_statement(&mut self, try_expr: &ast::TryExpr, target: &Target) -> Result<(), Diagnostic> {
+        self.line("try {");
+        self.try_depth += 1;
+        let body = self.nested(|c| c.block(&try_expr.body, &[], target));
+        self.try_depth -= 1;
+        self.out.push_str(&body?.0);
+        let caught = self.temp("e");
+        self.line(&format!("}} catch ({}) {{", caught));
+        let binding = try_expr.binding.value.as_str();
+        self.push_scope(&try_expr.handler.statements, &[binding], false);
+        let handler = self.nested(|c| {
+            let name = c.declare(binding);
+            c.line(&format!("{} = $rt.caught({});", name, caught));
+            c.statements(&try_expr.handler.statements, target)
+        });
+        self.pop_scope();
+        self.out.push_str(&handler?.0);
+        self.line("}");
+        return Ok(());
+    }
+}
+
+/// Whether an `if` can be a conditional expression: both branches are a
+/// single expression that doesn't need any statements.
+fn has_simple_branches(if_expr: &ast::IfExpr) -> bool {
+    let simple = |block: &ast::BlockStatement| match block.statements.as_slice() {
+        [ast::StatementNode::Expression(stmt)] => is_pure(&stmt.expression),
+        _ => false,
+    };
+    return simple(&if_expr.consequence) && if_expr.alternative.as_ref().is_none_or(simple);
+}
+
+/// Whether an expression compiles without emitting statements.
+fn is_pure(expression: &ast::ExpressionNode) -> bool {
+    match expression {
+        ast::ExpressionNode::Identifier(_) | ast::ExpressionNode::Literal(_) | ast::ExpressionNode::Function(_) => true,
+        ast::ExpressionNode::Prefix(prefix) => is_pure(&prefix.right),
+        ast::ExpressionNode::Infix(infix) => is_pure(&infix.left) && is_pure(&infix.right),
+        ast::ExpressionNode::If(if_expr) => is_pure(&if_expr.condition) && has_simple_branches(if_expr),
+        ast::ExpressionNode::Call(call) => is_pure(&call.function) && call.arguments.iter().all(is_pure),
+        ast::ExpressionNode::Array(array) => array.elements.iter().all(is_pure),
+        ast::ExpressionNode::Hash(hash) => hash.pairs.iter().all(|(k, v)| is_pure(k) && is_pure(v)),
+        ast::ExpressionNode::Index(index) => is_pure(&index.left) && is_pure(&index.index),
+        ast::ExpressionNode::Match(_) | ast::ExpressionNode::Try(_) => false,
+        ast::ExpressionNode::StructLiteral(literal) => {
+            is_pure(&literal.name) && literal.fields.iter().all(|(_, value)| is_pure(value))
+        }
+        ast::ExpressionNode::Field(field) => is_pure(&field.left),
+    }
+}
+
+/// Whether compiled JavaScript is a name or literal, which evaluating can
+/// neither fail nor have side effects.
+fn is_simple(value: &str) -> bool {
+    if value.starts_with('"') {
+        return true;
+    }
+    return value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.');
+}
+
+/// The JavaScript name for a script variable. Words JavaScript reserves
+/// get a `$` appended, which no script name can contain.
+fn js_name(name: &str) -> String {
+    match RESERVED.contains(&name) {
+        true => format!("{}$", name),
+        false => name.to_string(),
+    }
+}
+
+fn literal_value(literal: &ast::LiteralEnum) -> String {
+    match literal {
+        ast::LiteralEnum::IntLiteral(i) => format!("{}n", i),
+        ast::LiteralEnum::StringLiteral(s) => string_literal(s),
+        ast::LiteralEnum::Boolean(b) => b.to_string(),
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                result.push_str(&format!("\\u{{{:x}}}", c as u32));
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    return result;
+}
+
+fn pattern_names<'a>(pattern: &'a ast::Pattern, names: &mut Vec<&'a str>) {
+    match pattern {
+        ast::Pattern::Wildcard(_) | ast::Pattern::Literal(_) => {}
+        ast::Pattern::Binding(ident) => names.push(&ident.value),
+        ast::Pattern::Array(array) => {
+            for element in &array.elements {
+                pattern_names(element, names);
+            }
+            if let Some(Some(rest)) = &array.rest {
+                names.push(&rest.value);
+            }
+        }
+        ast::Pattern::Hash(hash) => {
+            for (_, value) in &hash.pairs {
+                pattern_names(value, names);
+            }
+        }
+    }
+}
+
+/// The pattern as the nested arrays `$rt.match` takes.
+fn pattern_value(pattern: &ast::Pattern) -> String {
+    match pattern {
+        ast::Pattern::Wildcard(_) => "[\"_\"]".to_string(),
+        ast::Pattern::Binding(ident) => format!("[\"bind\", {}]", string_literal(&ident.value)),
+        ast::Pattern::Literal(literal) => format!("[\"lit\", {}]", literal_value(&literal.value)),
+        ast::Pattern::Array(array) => {
+            let elements: Vec<String> = array.elements.iter().map(pattern_value).collect();
+            let rest = match &array.rest {
+                None => String::new(),
+                Some(None) => ", null".to_string(),
+                Some(Some(name)) => format!(", {}", string_literal(&name.value)),
+            };
+            format!("[\"arr\", [{}]{}]", elements.join(", "), rest)
+        }
+        ast::Pattern::Hash(hash) => {
+            let pairs: Vec<String> = hash
+                .pairs
+                .iter()
+                .map(|(key, value)| format!("[{}, {}]", literal_value(&key.value), pattern_value(value)))
+                .collect();
+            format!("[\"hash\", [{}]]", pairs.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+fn transpile(source: &str) -> Result<String, Diagnostic> {
+    use crate::{lexer, parser};
+
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    assert!(!p.has_errors(), "{:?} in:\n{}", p.errors(), source);
+    return javascript(&program);
+}
+
+/// Runs a script under Node, returning its stdout and stderr, or `None`
+/// when Node isn't installed.
+#[cfg(test)]
+fn run_node(script: &str) -> Option<(String, String)> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("node")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(script.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    return Some((stdout, stderr));
+}
+
+#[rstest]
+#[case("let x = 1; puts(x + 2 * 3, -x, !x, 10 / 3, -7 / 2)")]
+#[case("puts(9223372036854775807 + 1, -9223372036854775807 - 2, 3037000500 * 3037000500)")]
+#[case("puts(1 < 2, 2 >= 3, 1 == 1, \"a\" != \"b\", true == true, 1 == true, \"x\" != 1)")]
+#[case("puts(\"con\" + \"cat\", len(\"héllo\"), \"quote \\\" and \\\\ and \\t tab\")")]
+#[case("puts([1, \"two\", [true, null]], {\"b\": 1, \"a\": \"x\\n\", 2: [], true: {}}, {})")]
+#[case("let a = [1, 2, 3]; puts(a[0], a[5], a[-1], first(a), last(a), rest(a), push(a, 4), a, rest([]))")]
+#[case("let h = {\"k\": 1}; puts(h[\"k\"], h[\"nope\"], len(h), type(h), type(len), type(fn() {}))")]
+#[case("let x = 1; if (true) { let x = 2; puts(x); }; puts(x)")]
+#[case("let x = 1; let f = fn() { x }; let x = 2; puts(f())")]
+#[case("let f = fn() { g(1) }; let g = fn(n) { n + 1 }; puts(f())")]
+#[case("let x = 1; let y = if (x > 0) { let z = x * 10; z + 1 } else { 0 }; puts(y, if (false) { 1 })")]
+#[case("let f = fn(x) { if (x > 1) { return \"big\"; } \"small\" }; puts(f(2), f(0))")]
+#[case("let adder = fn(a) { fn(b) { a + b } }; let add = adder(2); puts(add(3), adder(1)(1), add)")]
+#[case("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; puts(fib(15))")]
+#[case("let count = fn(n) { if (n == 0) { return \"done\"; } return count(n - 1); }; puts(count(100000))")]
+#[case("let apply = fn(f, x) { f(x) }; puts(apply(fn(x) { x * x }, 7), apply(len, \"abc\"))")]
+#[case("let f = fn(a, a) { a }; puts(f(1, 2))")]
+#[case("let new = 1; let this = fn(class) { class + new }; puts(this(2))")]
+#[case("puts(match [1, 2, 3] { [a, ..rest] if a > 1 => a, [a, ..rest] => rest, _ => 0 })")]
+#[case("let f = fn(v) { match v { 0 => \"zero\", \"s\" => \"string\", true => \"yes\", x => x } }; puts(f(0), f(\"s\"), f(true), f(5))")]
+#[case("let v = {\"name\": \"ann\", \"tags\": [1]}; puts(match v { {\"name\": n, \"tags\": [t]} => n + \"!\", _ => \"?\" })")]
+#[case("puts(match [1] { [] => 0, [..] => 1 }, 1 + match 2 { n if n > 1 => { let m = n * 2; m } _ => 0 })")]
+#[case("puts(match 5 { 1 => 1 })")]
+#[case("let r = try { 1 / 0 } catch (e) { e.message }; puts(r, try { 1 } catch (e) { 2 })")]
+#[case("let r = try { throw(\"boom\") } catch (e) { e }; puts(r, type(r), r[\"message\"])")]
+#[case("let f = fn() { try { throw({\"a\": 1}) } catch (e) { return e.message; } 0 }; puts(f())")]
+#[case("try { throw(\"inner\") } catch (e) { throw(e) }")]
+#[case("struct P { x, y } impl P { fn sum(self) { self.x + self.y } fn new(x) { P { x, y: 0 } } }; puts(P { x: 1, y: 2 }.sum(), P.new(4), P)")]
+#[case("struct E {}; puts(E {}, type(E {}), type(E), E {} == E {})")]
+#[case("struct P { x } impl P { fn get(self) { self.x } }; P { x: 1 }.get")]
+#[case("struct P { x }; P { x: 1, y: 2 }")]
+#[case("struct P { x }; puts(P { x: 1 } == P { x: 1 }, P { x: 1 } != P { x: 2 }); P { x: 1 }.z")]
+#[case("assert(true); assert_eq([1, {\"a\": 2}], [1, {\"a\": 2}]); puts(assert_throws(fn() { 1 / 0 }, \"division by zero\"))")]
+#[case("assert_eq({\"a\": 1}, {\"a\": 2})")]
+#[case("assert_eq(\"one\\ntwo\", \"one\\nthree\")")]
+#[case("assert(1 > 2, \"one is not bigger\")")]
+#[case("assert_throws(fn() { 1 })")]
+#[case("puts(len(1))")]
+#[case("puts(1 + true)")]
+#[case("puts(-true)")]
+#[case("puts([1] + [2])")]
+#[case("puts(missing)")]
+#[case("let f = fn(a) { a }; f(1, 2)")]
+#[case("5(1)")]
+#[case("puts({[1]: 2})")]
+#[case("puts(\"before\"); return 1; puts(\"after\")")]
+#[case("puts(1 == \"1\", -4 == \"-4\", 4 == \"+4\", 1 == \" 1\", true == 1, true == \"1\", false == \"false\", 0 == if (false) { 1 })")]
+#[case("puts([1, [true]] == [\"1\", [1]], {\"a\": 1} == {\"a\": \"1\"}, {1: 1} == {\"1\": 1}, [1] != [2], len == len)")]
+#[case("let a = [1]; let f = fn() { 1 }; puts(a === a, a === [1], 1 === \"1\", \"x\" === \"x\", f === f, puts === puts)")]
+#[case("struct P { x }; let p = P { x: 1 }; puts(p === p, p === P { x: 1 }, p == P { x: true }, P === P)")]
+#[case("let e = try { throw(\"x\") } catch (e) { e }; puts(e === e, e == try { throw(\"x\") } catch (e) { e })")]
+#[case("let from = fn*(n) { yield n; yield* from(n + 1); }; puts(collect(take(map(from(1), fn(x) { x * x }), 4)), from)")]
+#[case("let g = fn*(xs) { for (x in xs) { if (x > 1) { yield x; } }; yield* \"hé\"; return 0; yield 9; }; puts(collect(g([1, 2, 3])), type(g([])))")]
+#[case("let it = iter({\"b\": 1, \"a\": 2}); puts(next(it), next(it), next(it), collect(it))")]
+#[case("let it = iter([1, 2, 3, 4]); puts(collect(take(it, 2)), collect(filter(it, fn(x) { x > 3 })))")]
+#[case("let f = fn(xs) { for (x in xs) { if (x > 1) { return x; } }; 0 }; puts(f([1, 5, 9]), f([]))")]
+#[case("let g = fn*() { try { yield 1; throw(\"boom\"); } catch (e) { yield e.message; } }; puts(collect(g()))")]
+#[case("let g = fn*() { yield 1; throw(\"boom\"); }; let it = g(); next(it); puts(try { next(it) } catch (e) { e.message }, next(it))")]
+#[case("let g = fn*() { yield* it; }; let it = g(); collect(it)")]
+#[case("for (x in 5) { x }")]
+#[case("puts(collect(map(1, len)))")]
+fn test_transpiled_output_matches(#[case] source: &str) {
+    use crate::evaluator::CapturedOutput;
+    use crate::Interpreter;
+
+    let output = CapturedOutput::default();
+    let result = Interpreter::new().with_output(output.clone()).eval_str(source);
+    let expected_error = match result {
+        Ok(_) => String::new(),
+        Err(e) => format!("error: {}\n", e),
+    };
+    let script = transpile(source).unwrap();
+    let Some((stdout, stderr)) = run_node(&script) else {
+        eprintln!("node not found, skipping");
+        return;
+    };
+    assert_eq!(stdout, output.contents(), "stdout of:\n{}", source);
+    assert_eq!(stderr, expected_error, "stderr of:\n{}", source);
+}
+
+#[rstest]
+#[case("let x = 1; x", "    let x = 1n;\n")]
+#[case("let x = 1; if (true) { let x = 2; x }", "        let x$1 = 2n;\n")]
+#[case("let f = fn(n) { return f(n - 1); }", "        return $rt.tail(f, [$rt.infix(\"-\", n, 1n)]);\n")]
+#[case("let f = fn(n) { try { return f(n); } catch (e) { 0 } }", "            return $rt.call(f, [n]);\n")]
+#[case("let y = if (true) { 1 } else { 2 }", "    let y = ($rt.truthy(true) ? 1n : 2n);\n")]
+#[case("let new = fn(this) { this }", "    let new$ = $rt.fn([\"this\"], (this$) => {\n")]
+#[case("puts(1, if (true) { let a = 1; a })", "    $rt.call($b.puts, [1n, $t1]);\n")]
+fn test_javascript(#[case] source: &str, #[case] expected: &str) {
+    let script = transpile(source).unwrap();
+    let program = &script[RUNTIME.len()..];
+    assert!(program.contains(expected), "{:?} not in:\n{}", expected, program);
+}
+
+#[test]
+fn test_javascript_import() {
+    let error = transpile("let a = 1;\nimport \"lib/m\" as m").unwrap_err();
+    assert_eq!(
+        error,
+        Diagnostic::new("import is not supported when transpiling to JavaScript".to_string(), 2, 1)
+    );
+}
@@ -0,0 +1,647 @@
+// Runtime support for programs transpiled to JavaScript. It mirrors the
+// interpreter's semantics: integers are 64 bit and wrap, hashes are ordered
+// by key, and errors carry the same messages.
+const $rt = (() => {
+    "use strict";
+
+    class ErrorValue {
+        constructor(message) {
+            this.message = message;
+        }
+    }
+
+    class Hash {
+        constructor(entries) {
+            // Encoded key -> [key, value].
+            this.entries = entries;
+        }
+    }
+
+    class Func {
+        constructor(params, body, generator = false) {
+            this.params = params;
+            this.body = body;
+            this.generator = generator;
+        }
+    }
+
+    // An iterator, advanced by `step`, which returns what a JavaScript
+    // iterator's `next` does. Like the interpreter's, it's finished for good
+    // once it runs out or throws, and can't be advanced from inside itself.
+    class Iter {
+        constructor(step) {
+            this.step = step;
+            this.running = false;
+            this.finished = false;
+        }
+
+        next() {
+            if (this.running) fail("iterator is already running");
+            if (this.finished) return { done: true, value: null };
+            this.running = true;
+            try {
+                const result = this.step();
+                if (result.done) this.finished = true;
+                return result.done ? { done: true, value: null } : result;
+            } catch (e) {
+                this.finished = true;
+                throw e;
+            } finally {
+                this.running = false;
+            }
+        }
+
+        // A `for ... of` leaving early would close an iterator with a
+        // `return` method, so the one handed out doesn't have one.
+        [Symbol.iterator]() {
+            return { next: () => this.next() };
+        }
+    }
+
+    class Builtin {
+        constructor(name, body) {
+            this.name = name;
+            this.body = body;
+        }
+    }
+
+    class StructType {
+        constructor(name, fields) {
+            this.name = name;
+            this.fields = fields;
+            this.methods = new Map();
+        }
+    }
+
+    class Struct {
+        constructor(type, values) {
+            this.type = type;
+            this.values = values;
+        }
+
+        field(name) {
+            const index = this.type.fields.indexOf(name);
+            return index < 0 ? undefined : this.values[index];
+        }
+    }
+
+    class TailCall {
+        constructor(f, args) {
+            this.f = f;
+            this.args = args;
+        }
+    }
+
+    const fail = (message) => {
+        throw new ErrorValue(message);
+    };
+
+    const typeName = (v) => {
+        switch (typeof v) {
+            case "bigint":
+                return "INTEGER";
+            case "boolean":
+                return "BOOLEAN";
+            case "string":
+                return "STRING";
+        }
+        if (v === null) return "NULL";
+        if (Array.isArray(v)) return "ARRAY";
+        if (v instanceof Hash) return "HASH";
+        if (v instanceof Func) return "FUNCTION";
+        if (v instanceof Builtin) return "BUILTIN";
+        if (v instanceof StructType) return "STRUCT";
+        if (v instanceof Struct) return v.type.name;
+        if (v instanceof ErrorValue) return "ERROR";
+        if (v instanceof Iter) return "ITERATOR";
+        throw new TypeError("not a script value: " + v);
+    };
+
+    // Strings are quoted the way Rust's `{:?}` does it.
+    const quote = (s) => {
+        let result = '"';
+        for (const c of s) {
+            const code = c.codePointAt(0);
+            if (c === '"') result += '\\"';
+            else if (c === "\\") result += "\\\\";
+            else if (c === "\n") result += "\\n";
+            else if (c === "\r") result += "\\r";
+            else if (c === "\t") result += "\\t";
+            else if (c === "\0") result += "\\0";
+            else if (code < 0x20 || (code >= 0x7f && code < 0xa0)) result += "\\u{" + code.toString(16) + "}";
+            else result += c;
+        }
+        return result + '"';
+    };
+
+    const display = (v) => {
+        if (typeof v === "string") return v;
+        if (typeof v === "bigint" || typeof v === "boolean") return String(v);
+        if (v === null) return "null";
+        if (Array.isArray(v)) return "[" + v.map(inspect).join(", ") + "]";
+        if (v instanceof Hash) {
+            return "{" + sortedEntries(v).map(([k, val]) => inspect(k) + ": " + inspect(val)).join(", ") + "}";
+        }
+        if (v instanceof Func) return (v.generator ? "fn*(" : "fn(") + v.params.join(", ") + ") { ... }";
+        if (v instanceof Builtin) return "builtin " + v.name;
+        if (v instanceof StructType) return "struct " + v.name;
+        if (v instanceof Struct) {
+            if (v.values.length === 0) return v.type.name + " {}";
+            const fields = v.type.fields.map((name, i) => name + ": " + inspect(v.values[i]));
+            return v.type.name + " { " + fields.join(", ") + " }";
+        }
+        if (v instanceof ErrorValue) return "error: " + v.message;
+        if (v instanceof Iter) return "<iterator>";
+        throw new TypeError("not a script value: " + v);
+    };
+
+    const inspect = (v) => (typeof v === "string" ? quote(v) : display(v));
+
+    const truthy = (v) => v !== null && v !== false;
+
+    // Hash keys order integers before booleans before strings, like the
+    // interpreter's `HashKey`.
+    const keyRank = (k) => (typeof k === "bigint" ? 0 : typeof k === "boolean" ? 1 : 2);
+
+    const compareKeys = (a, b) => {
+        const rank = keyRank(a) - keyRank(b);
+        if (rank !== 0) return rank;
+        if (typeof a === "string") {
+            // Code point order, which is the byte order of UTF-8.
+            const x = [...a];
+            const y = [...b];
+            for (let i = 0; i < Math.min(x.length, y.length); i++) {
+                const d = x[i].codePointAt(0) - y[i].codePointAt(0);
+                if (d !== 0) return d;
+            }
+            return x.length - y.length;
+        }
+        return a < b ? -1 : a > b ? 1 : 0;
+    };
+
+    const sortedEntries = (hash) => [...hash.entries.values()].sort((x, y) => compareKeys(x[0], y[0]));
+
+    const hashKey = (k) => {
+        switch (typeof k) {
+            case "bigint":
+                return "i" + k;
+            case "boolean":
+                return "b" + k;
+            case "string":
+                return "s" + k;
+        }
+        return null;
+    };
+
+    const hash = (pairs) => {
+        const entries = new Map();
+        for (const [k, v] of pairs) {
+            const key = hashKey(k);
+            if (key === null) fail("unusable as hash key: " + typeName(k));
+            entries.set(key, [k, v]);
+        }
+        return new Hash(entries);
+    };
+
+    // Structural equality, as `assert_eq` sees it.
+    const equal = (a, b) => {
+        if (Array.isArray(a) && Array.isArray(b)) {
+            return a.length === b.length && a.every((x, i) => equal(x, b[i]));
+        }
+        if (a instanceof Hash && b instanceof Hash) {
+            if (a.entries.size !== b.entries.size) return false;
+            for (const [key, [, value]] of a.entries) {
+                const other = b.entries.get(key);
+                if (other === undefined || !equal(value, other[1])) return false;
+            }
+            return true;
+        }
+        if (a instanceof Struct && b instanceof Struct) {
+            return a.type === b.type && a.values.every((x, i) => equal(x, b.values[i]));
+        }
+        if (a instanceof ErrorValue && b instanceof ErrorValue) return a.message === b.message;
+        return a === b;
+    };
+
+    const integerText = /^[+-]?[0-9]+$/;
+
+    // Equality as `==` sees it, converting between integers, strings and
+    // booleans like `Object::loosely_equals`.
+    const looseEqual = (a, b) => {
+        if (typeof a === "boolean" && (typeof b === "bigint" || typeof b === "string")) return looseEqual(a ? 1n : 0n, b);
+        if (typeof b === "boolean" && (typeof a === "bigint" || typeof a === "string")) return looseEqual(a, b ? 1n : 0n);
+        if (typeof a === "bigint" && typeof b === "string") return integerText.test(b) && BigInt(b) === a;
+        if (typeof a === "string" && typeof b === "bigint") return integerText.test(a) && BigInt(a) === b;
+        if (Array.isArray(a) && Array.isArray(b)) {
+            return a.length === b.length && a.every((x, i) => looseEqual(x, b[i]));
+        }
+        if (a instanceof Hash && b instanceof Hash) {
+            if (a.entries.size !== b.entries.size) return false;
+            for (const [key, [, value]] of a.entries) {
+                const other = b.entries.get(key);
+                if (other === undefined || !looseEqual(value, other[1])) return false;
+            }
+            return true;
+        }
+        if (a instanceof Struct && b instanceof Struct) {
+            return a.type === b.type && a.values.every((x, i) => looseEqual(x, b.values[i]));
+        }
+        return equal(a, b);
+    };
+
+    const wrap = (n) => BigInt.asIntN(64, n);
+
+    const operators = {
+        "+": (l, r) => (typeof l === "string" ? l + r : wrap(l + r)),
+        "-": (l, r) => wrap(l - r),
+        "*": (l, r) => wrap(l * r),
+        "/": (l, r) => (r === 0n ? fail("division by zero") : wrap(l / r)),
+        "<": (l, r) => l < r,
+        ">": (l, r) => l > r,
+        "<=": (l, r) => l <= r,
+        ">=": (l, r) => l >= r,
+    };
+
+    const supported = {
+        bigint: ["+", "-", "*", "/", "<", ">", "<=", ">="],
+        string: ["+"],
+        boolean: [],
+    };
+
+    const infix = (op, l, r) => {
+        if (op === "==") return looseEqual(l, r);
+        if (op === "!=") return !looseEqual(l, r);
+        // Arrays, hashes and structs are objects, so this is identity.
+        if (op === "===") return l === r;
+        const lt = typeName(l);
+        const rt = typeName(r);
+        const kind = typeof l;
+        if (kind === typeof r && supported[kind] !== undefined) {
+            if (!supported[kind].includes(op)) fail("unknown operator: " + lt + " " + quote(op) + " " + rt);
+            return operators[op](l, r);
+        }
+        if (lt !== rt) {
+            fail("type mismatch: " + lt + " " + quote(op) + " " + rt);
+        }
+        fail("unknown operator: " + lt + " " + quote(op) + " " + rt);
+    };
+
+    const not = (v) => !truthy(v);
+
+    const neg = (v) => (typeof v === "bigint" ? wrap(-v) : fail("unknown operator: " + quote("-") + typeName(v)));
+
+    const index = (l, i) => {
+        if (Array.isArray(l) && typeof i === "bigint") {
+            return i < 0n || i >= BigInt(l.length) ? null : l[Number(i)];
+        }
+        if (l instanceof Hash) {
+            const key = hashKey(i);
+            if (key === null) fail("unusable as hash key: " + typeName(i));
+            const entry = l.entries.get(key);
+            return entry === undefined ? null : entry[1];
+        }
+        if (l instanceof ErrorValue && typeof i === "string") {
+            if (i === "message") return l.message;
+            if (i === "trace") return [];
+            fail("error has no field " + i);
+        }
+        fail("index operator not supported: " + typeName(l) + "[" + typeName(i) + "]");
+    };
+
+    const field = (l, name) => {
+        if (l instanceof Struct) {
+            const value = l.field(name);
+            if (value !== undefined) return value;
+            if (l.type.methods.has(name)) {
+                fail("method `" + name + "` of " + l.type.name + " must be called, as in `." + name + "()`");
+            }
+            fail(l.type.name + " has no field `" + name + "`");
+        }
+        if (l instanceof StructType) {
+            const method = l.methods.get(name);
+            return method !== undefined ? method : fail("struct " + l.name + " has no method `" + name + "`");
+        }
+        if (l instanceof ErrorValue) return index(l, name);
+        fail(typeName(l) + " has no field `" + name + "`");
+    };
+
+    const fn = (params, body) => new Func(params, body);
+
+    const gen = (params, body) => new Func(params, body, true);
+
+    // Iterates over the elements of an array, the characters of a string or
+    // the keys of a hash. `name` is the builtin it's an argument to, if any.
+    const iter = (v, name) => {
+        if (v instanceof Iter) return v;
+        let values;
+        if (Array.isArray(v)) values = v;
+        else if (typeof v === "string") values = [...v];
+        else if (v instanceof Hash) values = sortedEntries(v).map(([k]) => k);
+        else fail((name ? "argument to `" + name + "`: " : "") + typeName(v) + " is not iterable");
+        let next = 0;
+        return new Iter(() => (next < values.length ? { done: false, value: values[next++] } : { done: true }));
+    };
+
+    // Calls run in a loop so that tail calls, which come back as a
+    // `TailCall`, don't grow the JavaScript stack.
+    const call = (f, args) => {
+        for (;;) {
+            if (f instanceof Builtin) return f.body(...args);
+            if (!(f instanceof Func)) fail("not a function: " + typeName(f));
+            if (f.params.length !== args.length) {
+                fail("wrong number of arguments: want=" + f.params.length + ", got=" + args.length);
+            }
+            if (f.generator) {
+                const generator = f.body(...args);
+                return new Iter(() => generator.next());
+            }
+            const result = f.body(...args);
+            if (!(result instanceof TailCall)) return result;
+            ({ f, args } = result);
+        }
+    };
+
+    const tail = (f, args) => new TailCall(f, args);
+
+    // Finds what `receiver.name(...)` calls. Methods of a struct instance get
+    // the instance as `self`.
+    const resolveMethod = (receiver, name, args) => {
+        if (receiver instanceof Struct && receiver.field(name) === undefined) {
+            const method = receiver.type.methods.get(name);
+            if (method !== undefined) return [method, [receiver, ...args]];
+        }
+        return [field(receiver, name), args];
+    };
+
+    const method = (receiver, name, args) => call(...resolveMethod(receiver, name, args));
+
+    const tailMethod = (receiver, name, args) => tail(...resolveMethod(receiver, name, args));
+
+    const struct = (name, fields) => new StructType(name, fields);
+
+    const construct = (type, pairs) => {
+        if (!(type instanceof StructType)) fail("not a struct: " + typeName(type));
+        const values = new Array(type.fields.length);
+        for (const [name, value] of pairs) {
+            const i = type.fields.indexOf(name);
+            if (i < 0) fail(type.name + " has no field `" + name + "`");
+            values[i] = value;
+        }
+        type.fields.forEach((name, i) => {
+            if (values[i] === undefined) fail("missing field `" + name + "` in " + type.name);
+        });
+        return new Struct(type, values);
+    };
+
+    const impl = (type, methods) => {
+        if (!(type instanceof StructType)) fail("not a struct: " + typeName(type));
+        for (const [name, method] of methods) {
+            if (type.fields.includes(name)) fail(type.name + " already has a field named `" + name + "`");
+            type.methods.set(name, method);
+        }
+        return null;
+    };
+
+    // Patterns come as nested arrays: ["_"], ["bind", name], ["lit", value],
+    // ["arr", patterns, rest] and ["hash", [[key, pattern], ...]], where rest
+    // is undefined, null for a bare `..` or the name `..name` binds.
+    const match = (pattern, value, bindings = {}) => {
+        switch (pattern[0]) {
+            case "_":
+                return bindings;
+            case "bind":
+                bindings[pattern[1]] = value;
+                return bindings;
+            case "lit":
+                return typeof value === typeof pattern[1] && value === pattern[1] ? bindings : null;
+            case "arr": {
+                const [, elements, rest] = pattern;
+                if (!Array.isArray(value)) return null;
+                const fits = rest === undefined ? value.length === elements.length : value.length >= elements.length;
+                if (!fits) return null;
+                for (let i = 0; i < elements.length; i++) {
+                    if (match(elements[i], value[i], bindings) === null) return null;
+                }
+                if (typeof rest === "string") bindings[rest] = value.slice(elements.length);
+                return bindings;
+            }
+            case "hash": {
+                if (!(value instanceof Hash)) return null;
+                for (const [key, p] of pattern[1]) {
+                    const entry = value.entries.get(hashKey(key));
+                    if (entry === undefined || match(p, entry[1], bindings) === null) return null;
+                }
+                return bindings;
+            }
+        }
+    };
+
+    const noMatch = (value) => fail("no match arm matched " + inspect(value));
+
+    const notFound = (name) => fail("identifier not found: " + name);
+
+    // Turns whatever a `catch` caught into a script error value.
+    const caught = (e) => {
+        if (e instanceof ErrorValue) return e;
+        if (e instanceof RangeError) return new ErrorValue("stack overflow: " + e.message);
+        throw e;
+    };
+
+    let print = (text) => console.log(text);
+
+    const expectArgs = (name, args, min, max = min) => {
+        if (args.length < min || args.length > max) {
+            const want = min === max ? String(min) : min + ".." + max;
+            fail("wrong number of arguments to `" + name + "`: want=" + want + ", got=" + args.length);
+        }
+    };
+
+    const arrayArg = (name, v) => (Array.isArray(v) ? v : fail("argument to `" + name + "` must be ARRAY, got " + typeName(v)));
+
+    const diff = (actual, expected) => {
+        if (typeof actual === "string" && typeof expected === "string" && (actual.includes("\n") || expected.includes("\n"))) {
+            const lines = (s) => {
+                const result = s.split("\n");
+                if (result[result.length - 1] === "") result.pop();
+                return result;
+            };
+            const a = lines(actual);
+            const e = lines(expected);
+            let result = "  (- expected, + actual)\n";
+            for (let i = 0; i < Math.max(a.length, e.length); i++) {
+                if (i < a.length && i < e.length && a[i] === e[i]) {
+                    result += "    " + a[i] + "\n";
+                    continue;
+                }
+                if (i < e.length) result += "  - " + e[i] + "\n";
+                if (i < a.length) result += "  + " + a[i] + "\n";
+            }
+            return result;
+        }
+        const a = [...inspect(actual)];
+        const e = [...inspect(expected)];
+        let column = 0;
+        while (column < a.length && column < e.length && a[column] === e[column]) column++;
+        return "  expected: " + e.join("") + "\n  actual:   " + a.join("") + "\n            " + " ".repeat(column) + "^\n";
+    };
+
+    const builtinFunctions = {
+        len(...args) {
+            expectArgs("len", args, 1);
+            const [v] = args;
+            if (typeof v === "string") return BigInt([...v].length);
+            if (Array.isArray(v)) return BigInt(v.length);
+            if (v instanceof Hash) return BigInt(v.entries.size);
+            fail("argument to `len` not supported, got " + typeName(v));
+        },
+        first(...args) {
+            expectArgs("first", args, 1);
+            const v = arrayArg("first", args[0]);
+            return v.length === 0 ? null : v[0];
+        },
+        last(...args) {
+            expectArgs("last", args, 1);
+            const v = arrayArg("last", args[0]);
+            return v.length === 0 ? null : v[v.length - 1];
+        },
+        rest(...args) {
+            expectArgs("rest", args, 1);
+            const v = arrayArg("rest", args[0]);
+            return v.length === 0 ? null : v.slice(1);
+        },
+        push(...args) {
+            expectArgs("push", args, 2);
+            return [...arrayArg("push", args[0]), args[1]];
+        },
+        puts(...args) {
+            for (const arg of args) print(display(arg));
+            return null;
+        },
+        type(...args) {
+            expectArgs("type", args, 1);
+            return typeName(args[0]);
+        },
+        throw(...args) {
+            expectArgs("throw", args, 1);
+            const [v] = args;
+            if (v instanceof ErrorValue) throw v;
+            fail(typeof v === "string" ? v : inspect(v));
+        },
+        assert(...args) {
+            expectArgs("assert", args, 1, 2);
+            if (truthy(args[0])) return null;
+            fail(args.length > 1 ? "assertion failed: " + display(args[1]) : "assertion failed: got " + inspect(args[0]));
+        },
+        assert_eq(...args) {
+            expectArgs("assert_eq", args, 2);
+            if (equal(args[0], args[1])) return null;
+            fail("assertion failed: values are not equal\n" + diff(args[0], args[1]).trimEnd());
+        },
+        assert_throws(...args) {
+            expectArgs("assert_throws", args, 1, 2);
+            let value;
+            try {
+                value = call(args[0], []);
+            } catch (e) {
+                const error = caught(e);
+                if (args.length > 1 && typeof args[1] !== "string") {
+                    fail("argument to `assert_throws` must be STRING, got " + typeName(args[1]));
+                }
+                if (args.length > 1 && args[1] !== error.message) {
+                    fail("assertion failed: expected error " + quote(args[1]) + ", got " + quote(error.message));
+                }
+                return error;
+            }
+            fail("assertion failed: expected an error, got " + inspect(value));
+        },
+        iter(...args) {
+            expectArgs("iter", args, 1);
+            return iter(args[0], "iter");
+        },
+        next(...args) {
+            expectArgs("next", args, 1);
+            if (!(args[0] instanceof Iter)) fail("argument to `next` must be ITERATOR, got " + typeName(args[0]));
+            const { done, value } = args[0].next();
+            return hash([["done", done], ["value", value]]);
+        },
+        map(...args) {
+            expectArgs("map", args, 2);
+            const [source, f] = [iter(args[0], "map"), args[1]];
+            return new Iter(() => {
+                const result = source.next();
+                return result.done ? result : { done: false, value: call(f, [result.value]) };
+            });
+        },
+        filter(...args) {
+            expectArgs("filter", args, 2);
+            const [source, f] = [iter(args[0], "filter"), args[1]];
+            return new Iter(() => {
+                for (;;) {
+                    const result = source.next();
+                    if (result.done || truthy(call(f, [result.value]))) return result;
+                }
+            });
+        },
+        take(...args) {
+            expectArgs("take", args, 2);
+            const source = iter(args[0], "take");
+            if (typeof args[1] !== "bigint") fail("argument to `take` must be INTEGER, got " + typeName(args[1]));
+            let remaining = args[1];
+            return new Iter(() => (remaining-- > 0n ? source.next() : { done: true }));
+        },
+        collect(...args) {
+            expectArgs("collect", args, 1);
+            return [...iter(args[0], "collect")];
+        },
+        gc_stats(...args) {
+            expectArgs("gc_stats", args, 0);
+            // The JavaScript engine collects cycles itself and doesn't say.
+            return hash([["collections", 0n], ["environments", 0n], ["freed", 0n]]);
+        },
+    };
+
+    const builtins = {};
+    for (const [name, body] of Object.entries(builtinFunctions)) builtins[name] = new Builtin(name, body);
+
+    // Runs the program, reporting an uncaught error the way `run` does.
+    const main = (program) => {
+        try {
+            program();
+        } catch (e) {
+            console.error("error: " + caught(e).message);
+            if (typeof process !== "undefined") process.exitCode = 1;
+        }
+    };
+
+    return {
+        builtins,
+        call,
+        caught,
+        construct,
+        display,
+        field,
+        fn,
+        gen,
+        hash,
+        impl,
+        index,
+        infix,
+        inspect,
+        iter,
+        main,
+        match,
+        method,
+        neg,
+        noMatch,
+        not,
+        notFound,
+        struct,
+        tail,
+        tailMethod,
+        truthy,
+        setPrint: (f) => {
+            print = f;
+        },
+    };
+})();
+const $b = $rt.builtins;
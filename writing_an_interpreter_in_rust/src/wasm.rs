@@ -0,0 +1,147 @@
+//! The entry points of the browser playground, exported through
+//! wasm-bindgen when built for `wasm32`. Each takes source text and returns
+//! JSON, so the page doesn't need bindings for the interpreter's types.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use serde_json::json;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::interpreter::{Interpreter, Limits};
+use crate::lexer;
+use crate::module::MemoryLoader;
+use crate::parser;
+use crate::token;
+use rstest::rstest;
+
+/// Limits for scripts typed into the playground, so a runaway loop stops
+/// with an error instead of hanging the tab. The call depth fits the 8MiB
+/// stack `.cargo/config.toml` links the module with.
+const PLAYGROUND_LIMITS: Limits = Limits {
+    max_steps: Some(10_000_000),
+    max_memory: Some(64 << 20),
+    max_call_depth: 100,
+};
+
+/// What `puts` printed, kept to hand back to the page.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Every token of `source`, up to and including the EOF.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn tokenize(source: &str) -> String {
+    let mut l = lexer::Lexer::new(source.to_string());
+    let mut tokens = Vec::new();
+    loop {
+        let tok = l.next_token();
+        let done = tok.token_type == token::TokenType::EOF;
+        tokens.push(tok);
+        if done {
+            break;
+        }
+    }
+    return json!(tokens).to_string();
+}
+
+/// The syntax tree of `source`, along with the parser's errors and warnings.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn parse_to_json(source: &str) -> String {
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    return json!({
+        "program": program,
+        "errors": p.errors(),
+        "warnings": p.warnings(),
+    })
+    .to_string();
+}
+
+/// Runs `source`, returning what it printed and either the inspected value
+/// of its last statement or the rendered error. There's no file system, so
+/// imports fail. JavaScript reserves the name `eval`, so wasm-bindgen
+/// exports this as `_eval`.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn eval(source: &str) -> String {
+    let output = Output::default();
+    let mut interpreter = Interpreter::new()
+        .with_limits(PLAYGROUND_LIMITS)
+        .with_loader(MemoryLoader::default())
+        .with_output(output.clone());
+    let (result, error) = match interpreter.eval_str(source) {
+        Ok(value) => (Some(value.inspect()), None),
+        Err(e) => (None, Some(interpreter.render_error(&e, source))),
+    };
+    let printed = String::from_utf8_lossy(&output.0.borrow()).to_string();
+    return json!({ "output": printed, "result": result, "error": error }).to_string();
+}
+
+#[test]
+fn test_tokenize() {
+    let tokens: serde_json::Value = serde_json::from_str(&tokenize("let x = \"hi\";")).unwrap();
+    assert_eq!(
+        tokens,
+        json!([
+            { "token_type": "let", "literal": "let", "line": 1, "column": 1 },
+            { "token_type": "Identifier", "literal": "x", "line": 1, "column": 5 },
+            { "token_type": "=", "literal": "=", "line": 1, "column": 7 },
+            { "token_type": "String", "literal": "hi", "line": 1, "column": 9 },
+            { "token_type": ";", "literal": ";", "line": 1, "column": 13 },
+            { "token_type": "EOF", "literal": "", "line": 1, "column": 14 },
+        ])
+    );
+}
+
+#[test]
+fn test_parse_to_json() {
+    let tree: serde_json::Value = serde_json::from_str(&parse_to_json("let x: int = -1;")).unwrap();
+    let statement = &tree["program"]["statements"][0];
+    assert_eq!(statement["kind"], "Let");
+    assert_eq!(statement["name"]["value"], "x");
+    assert_eq!(statement["annotation"]["kind"], "Named");
+    assert_eq!(statement["value"]["kind"], "Prefix");
+    assert_eq!(statement["value"]["right"]["value"], 1);
+    assert_eq!(tree["errors"], json!([]));
+
+    let tree: serde_json::Value = serde_json::from_str(&parse_to_json("let = 1;")).unwrap();
+    assert_eq!(tree["errors"][0]["line"], 1);
+    assert_eq!(tree["errors"][0]["column"], 5);
+}
+
+#[rstest]
+#[case("puts(\"hi\"); puts(1 + 2); [1, \"a\"]", json!({ "output": "hi\n3\n", "result": "[1, \"a\"]", "error": null }))]
+#[case("puts(\"before\"); 1 + true", json!({
+    "output": "before\n",
+    "result": null,
+    "error": "error: type mismatch: INTEGER \"+\" BOOLEAN\n --> <main>:1:19\n  |\n1 | puts(\"before\"); 1 + true\n  |                   ^\n  = at <main> (line 1, col 19)\n"
+}))]
+#[case("let x = ;", json!({
+    "output": "",
+    "result": null,
+    "error": "error: no prefix parse function for \";\" found\n --> <main>:1:9\n  |\n1 | let x = ;\n  |         ^\n"
+}))]
+fn test_eval(#[case] source: &str, #[case] expected: serde_json::Value) {
+    let result: serde_json::Value = serde_json::from_str(&eval(source)).unwrap();
+    assert_eq!(result, expected);
+}
+
+#[rstest]
+#[case("let spin = fn() { return spin(); }; spin()", "budget of")]
+#[case("import \"lib\"", "could not read lib.wl: no such module")]
+fn test_eval_is_sandboxed(#[case] source: &str, #[case] message: &str) {
+    let result: serde_json::Value = serde_json::from_str(&eval(source)).unwrap();
+    let error = result["error"].as_str().unwrap();
+    assert!(error.contains(message), "{}", error);
+}